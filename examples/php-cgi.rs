@@ -1,9 +1,4 @@
-extern crate ascii;
-extern crate tiny_http;
-
-use ascii::AsAsciiStr;
-
-/**!
+/*!
 
 A web server that redirects every request to a PHP script.
 
@@ -11,6 +6,11 @@ Usage: php-cgi <php-script-path>
 
 */
 
+extern crate ascii;
+extern crate tiny_http;
+
+use ascii::AsAsciiStr;
+
 fn handle(rq: tiny_http::Request, script: &str) {
     use std::io::Write;
     use std::process::Command;
@@ -28,7 +28,7 @@ fn handle(rq: tiny_http::Request, script: &str) {
         .env("GATEWAY_INTERFACE", "CGI/1.1")
         .env("PATH_INFO", "")
         .env("PATH_TRANSLATED", "")
-        .env("QUERY_STRING", format!("{}", rq.url()))
+        .env("QUERY_STRING", rq.url())
         .env("REMOTE_ADDR", format!("{}", rq.remote_addr().unwrap()))
         .env("REMOTE_HOST", "")
         .env("REMOTE_IDENT", "")
@@ -3,8 +3,9 @@ extern crate sha1;
 extern crate tiny_http;
 
 use std::io::Cursor;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::thread::spawn;
+use std::time::Duration;
 
 use rustc_serialize::base64::{Config, Newline, Standard, ToBase64};
 
@@ -74,30 +75,29 @@ fn main() {
         // we are handling this websocket connection in a new task
         spawn(move || {
             // checking the "Upgrade" header to check that it is a websocket
-            match request
+            if request
                 .headers()
                 .iter()
-                .find(|h| h.field.equiv(&"Upgrade"))
+                .find(|h| h.field.equiv("Upgrade"))
                 .and_then(|hdr| {
                     if hdr.value == "websocket" {
                         Some(hdr)
                     } else {
                         None
                     }
-                }) {
-                None => {
-                    // sending the HTML page
-                    request.respond(home_page(port)).expect("Responded");
-                    return;
-                }
-                _ => (),
-            };
+                })
+                .is_none()
+            {
+                // sending the HTML page
+                request.respond(home_page(port)).expect("Responded");
+                return;
+            }
 
             // getting the value of Sec-WebSocket-Key
             let key = match request
                 .headers()
                 .iter()
-                .find(|h| h.field.equiv(&"Sec-WebSocket-Key"))
+                .find(|h| h.field.equiv("Sec-WebSocket-Key"))
                 .map(|h| h.value.clone())
             {
                 None => {
@@ -126,14 +126,18 @@ fn main() {
             //
             let mut stream = request.upgrade("websocket", response);
 
-            //
+            // closing the connections of clients silent for a minute
+            stream
+                .set_read_timeout(Some(Duration::from_secs(60)))
+                .expect("Set the read timeout");
+
             loop {
                 let mut out = Vec::new();
                 match Read::by_ref(&mut stream).take(1).read_to_end(&mut out) {
                     Ok(n) if n >= 1 => {
                         // "Hello" frame
                         let data = [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f];
-                        stream.write(&data).ok();
+                        stream.write_all(&data).ok();
                         stream.flush().ok();
                     }
                     Ok(_) => panic!("eof ; should never happen"),
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Result as IoResult;
+use std::sync::{Arc, RwLock};
+
+#[cfg(feature = "ssl-rustls")]
+use std::error::Error;
+
+use crate::common::{ContentType, Method};
+#[cfg(feature = "ssl-rustls")]
+use crate::SslConfig;
+use crate::{Request, Response};
+
+/// Path prefix of the HTTP-01 challenges, followed by the token of the challenge.
+pub const ACME_CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+/// ALPN protocol of the TLS-ALPN-01 challenges (RFC 8737).
+#[cfg(feature = "ssl-rustls")]
+const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// Answers the challenges of an ACME certificate authority such as Let's Encrypt, proving that
+/// the server controls the domains it requests a certificate for.
+///
+/// This doesn't talk to the certificate authority: an ACME client orders the certificate and
+/// gives the challenges to answer, which are registered here until the order is validated.
+/// Clones share their challenges, so they can be registered from another thread than the one
+/// handling the requests.
+///
+/// HTTP-01 challenges are `GET` requests for [`ACME_CHALLENGE_PATH`] on port 80, answered by
+/// [`AcmeChallenges::respond`]. TLS-ALPN-01 challenges are TLS handshakes on port 443, answered
+/// by a server built with [`AcmeChallenges::ssl_config`] (requires the `ssl-rustls` feature).
+/// Once issued, the certificate is installed with
+/// [`Server::set_ssl_config`](crate::Server::set_ssl_config).
+///
+/// ```no_run
+/// use tiny_http::{AcmeChallenges, Response, Server};
+///
+/// let challenges = AcmeChallenges::new();
+/// // given by the ACME client
+/// challenges.add_http01("evaGxfADs6pSRb2LAv9IZ", "evaGxfADs6pSRb2LAv9IZ.nP1qzpXGymHBrUEepNY9H");
+///
+/// let server = Server::http("0.0.0.0:80").unwrap();
+/// for request in server.incoming_requests() {
+///     let request = match challenges.respond(request).unwrap() {
+///         Some(request) => request,
+///         None => continue,
+///     };
+///     request.respond(Response::from_string("hello")).unwrap();
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct AcmeChallenges {
+    // key authorizations by token
+    http01: Arc<RwLock<HashMap<String, String>>>,
+
+    // challenge certificates by domain, in lower case
+    #[cfg(feature = "ssl-rustls")]
+    tls_alpn01: Arc<RwLock<HashMap<String, Arc<rustls::sign::CertifiedKey>>>>,
+}
+
+impl AcmeChallenges {
+    /// Builds an empty set of challenges.
+    pub fn new() -> AcmeChallenges {
+        AcmeChallenges::default()
+    }
+
+    /// Registers an HTTP-01 challenge, answered with the key authorization.
+    pub fn add_http01(&self, token: &str, key_authorization: &str) {
+        self.http01
+            .write()
+            .unwrap()
+            .insert(token.to_owned(), key_authorization.to_owned());
+    }
+
+    /// Removes an HTTP-01 challenge, once it's validated.
+    pub fn remove_http01(&self, token: &str) {
+        self.http01.write().unwrap().remove(token);
+    }
+
+    /// Returns the key authorization to answer a request for this URL with, `None` if it isn't
+    /// the URL of a registered HTTP-01 challenge.
+    pub fn key_authorization(&self, url: &str) -> Option<String> {
+        let path = url.split('?').next().unwrap_or(url);
+        let token = path.strip_prefix(ACME_CHALLENGE_PATH)?;
+        self.http01.read().unwrap().get(token).cloned()
+    }
+
+    /// Answers the request if it's for an HTTP-01 challenge, with `404 Not Found` if its token
+    /// isn't registered. Returns the request back otherwise, to be handled by the caller.
+    pub fn respond(&self, request: Request) -> IoResult<Option<Request>> {
+        let is_challenge = matches!(request.method(), Method::Get | Method::Head)
+            && request.url().starts_with(ACME_CHALLENGE_PATH);
+        if !is_challenge {
+            return Ok(Some(request));
+        }
+
+        match self.key_authorization(request.url()) {
+            Some(key_authorization) => {
+                let response = Response::from_string(key_authorization)
+                    .with_header(ContentType::ApplicationOctetStream);
+                request.respond(response)?;
+            }
+            None => request.respond(Response::empty(404))?,
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "ssl-rustls")]
+impl AcmeChallenges {
+    /// Registers a TLS-ALPN-01 challenge for `domain`, answered with this self-signed
+    /// certificate carrying the `acmeIdentifier` extension, as generated by the ACME client.
+    ///
+    /// Both are in PEM format. Fails if they can't be parsed.
+    pub fn add_tls_alpn01(
+        &self,
+        domain: &str,
+        certificate: &[u8],
+        private_key: &[u8],
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let key = certified_key(certificate, private_key)?;
+        self.tls_alpn01
+            .write()
+            .unwrap()
+            .insert(domain.to_ascii_lowercase(), key);
+        Ok(())
+    }
+
+    /// Removes a TLS-ALPN-01 challenge, once it's validated.
+    pub fn remove_tls_alpn01(&self, domain: &str) {
+        self.tls_alpn01
+            .write()
+            .unwrap()
+            .remove(&domain.to_ascii_lowercase());
+    }
+
+    /// Builds an SSL configuration serving `certificate` and `private_key` (in PEM format), and
+    /// the challenge certificates to the certificate authority negotiating the `acme-tls/1`
+    /// protocol.
+    ///
+    /// The challenges registered later are answered too. Before the first certificate is issued,
+    /// `certificate` can be a temporary self-signed one.
+    pub fn ssl_config(
+        &self,
+        certificate: &[u8],
+        private_key: &[u8],
+    ) -> Result<SslConfig, Box<dyn Error + Send + Sync + 'static>> {
+        let resolver = ChallengeResolver {
+            default: certified_key(certificate, private_key)?,
+            challenges: self.tls_alpn01.clone(),
+        };
+
+        let mut config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(resolver));
+        // rustls aborts the handshakes of the clients offering none of these
+        config.alpn_protocols = vec![
+            b"http/1.1".to_vec(),
+            b"http/1.0".to_vec(),
+            ACME_TLS_ALPN.to_vec(),
+        ];
+
+        Ok(SslConfig::from_rustls_config(Arc::new(config)))
+    }
+}
+
+impl fmt::Debug for AcmeChallenges {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = formatter.debug_struct("AcmeChallenges");
+        debug.field("http01", &self.http01.read().unwrap().keys());
+        #[cfg(feature = "ssl-rustls")]
+        debug.field("tls_alpn01", &self.tls_alpn01.read().unwrap().keys());
+        debug.finish()
+    }
+}
+
+#[cfg(feature = "ssl-rustls")]
+fn certified_key(
+    certificate: &[u8],
+    private_key: &[u8],
+) -> Result<Arc<rustls::sign::CertifiedKey>, Box<dyn Error + Send + Sync + 'static>> {
+    let (certificate_chain, private_key) = crate::ssl::rustls::parse_pem(certificate, private_key)?;
+    let signing_key = rustls::sign::any_supported_type(&private_key)?;
+    Ok(Arc::new(rustls::sign::CertifiedKey::new(
+        certificate_chain,
+        signing_key,
+    )))
+}
+
+/// Picks the challenge certificate of the requested domain during the handshakes negotiating
+/// `acme-tls/1`, and the regular certificate otherwise.
+#[cfg(feature = "ssl-rustls")]
+struct ChallengeResolver {
+    default: Arc<rustls::sign::CertifiedKey>,
+    challenges: Arc<RwLock<HashMap<String, Arc<rustls::sign::CertifiedKey>>>>,
+}
+
+#[cfg(feature = "ssl-rustls")]
+impl rustls::server::ResolvesServerCert for ChallengeResolver {
+    fn resolve(
+        &self,
+        client_hello: rustls::server::ClientHello<'_>,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        let is_challenge = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|p| p == ACME_TLS_ALPN));
+        if !is_challenge {
+            return Some(self.default.clone());
+        }
+
+        let domain = client_hello.server_name()?.to_ascii_lowercase();
+        self.challenges.read().unwrap().get(&domain).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AcmeChallenges;
+    use crate::TestRequest;
+
+    #[test]
+    fn key_authorization() {
+        let challenges = AcmeChallenges::new();
+        challenges.add_http01("token", "token.thumbprint");
+
+        assert_eq!(
+            challenges
+                .key_authorization("/.well-known/acme-challenge/token?x=1")
+                .as_deref(),
+            Some("token.thumbprint")
+        );
+        assert_eq!(
+            challenges.key_authorization("/.well-known/acme-challenge/other"),
+            None
+        );
+        assert_eq!(challenges.key_authorization("/token"), None);
+
+        challenges.clone().remove_http01("token");
+        assert_eq!(
+            challenges.key_authorization("/.well-known/acme-challenge/token"),
+            None
+        );
+    }
+
+    #[test]
+    fn respond() {
+        let challenges = AcmeChallenges::new();
+        challenges.add_http01("token", "token.thumbprint");
+
+        let request = TestRequest::new().with_path("/.well-known/acme-challenge/token");
+        assert!(challenges.respond(request.into()).unwrap().is_none());
+
+        let request = TestRequest::new().with_path("/index.html");
+        let request = challenges.respond(request.into()).unwrap().unwrap();
+        assert_eq!(request.url(), "/index.html");
+    }
+
+    #[cfg(feature = "ssl-rustls")]
+    #[test]
+    fn ssl_config() {
+        let certificate = include_bytes!("../examples/ssl-cert.pem");
+        let private_key = include_bytes!("../examples/ssl-key.pem");
+
+        let challenges = AcmeChallenges::new();
+        challenges
+            .add_tls_alpn01("Example.com", certificate, private_key)
+            .unwrap();
+        assert!(challenges
+            .add_tls_alpn01("example.com", b"", private_key)
+            .is_err());
+
+        let config = challenges.ssl_config(certificate, private_key).unwrap();
+        assert!(config.rustls_config.is_some());
+    }
+}
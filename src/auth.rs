@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{Read, Result as IoResult};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::common::{Header, StatusCode};
+use crate::{PreDecision, PreHandler, Request, RequestHead, Response};
+
+/// Name of the header holding the API key, or the identifier of the key signing the request.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Name of the header holding the HMAC signature of the request.
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+/// Source of the keys accepted by a [`RequestAuth`].
+pub trait KeyStore: Send + Sync {
+    /// Returns the secret of the key `key_id`, or `None` if there is no such key.
+    fn secret(&self, key_id: &str) -> Option<Vec<u8>>;
+}
+
+impl KeyStore for HashMap<String, Vec<u8>> {
+    fn secret(&self, key_id: &str) -> Option<Vec<u8>> {
+        self.get(key_id).cloned()
+    }
+}
+
+impl<F> KeyStore for F
+where
+    F: Fn(&str) -> Option<Vec<u8>> + Send + Sync,
+{
+    fn secret(&self, key_id: &str) -> Option<Vec<u8>> {
+        self(key_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    ApiKey,
+    Hmac,
+}
+
+/// Authorization of the requests of an internal API, by API key or by HMAC signature.
+///
+/// With [`RequestAuth::api_key`], the `X-Api-Key` header holds the identifier of a key of the
+/// store and its secret, separated by a colon, e.g. `X-Api-Key: deploy-bot:secret`. The secret
+/// is compared in constant time.
+///
+/// With [`RequestAuth::hmac`], the `X-Api-Key` header holds the identifier of a key of the store,
+/// and the `X-Signature` header the hex-encoded HMAC-SHA256 of the following lines, joined with
+/// `\n`, computed with the secret of the key:
+///
+/// - the method, e.g. `POST`,
+/// - the URL, i.e. the path and the query,
+/// - the value of the `Date` header, which must be within the allowed skew of the server's time,
+/// - the hex-encoded SHA-256 of the body.
+///
+/// Requests without credentials, or with a stale date, are rejected with `401 Unauthorized`,
+/// and those with an unknown key or an invalid signature with `403 Forbidden`.
+///
+/// ```no_run
+/// use std::collections::HashMap;
+/// use tiny_http::{RequestAuth, Response, Server};
+///
+/// let mut keys = HashMap::new();
+/// keys.insert("deploy-bot".to_owned(), b"secret".to_vec());
+/// let auth = RequestAuth::hmac(keys);
+///
+/// let server = Server::http("0.0.0.0:0").unwrap();
+/// for request in server.incoming_requests() {
+///     auth.respond(request, |_, key_id| {
+///         Response::from_string(format!("hello {}", key_id))
+///     }).unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RequestAuth {
+    store: Arc<dyn KeyStore>,
+    scheme: Scheme,
+    max_skew: Duration,
+    max_body: usize,
+}
+
+impl RequestAuth {
+    /// Authorizes the requests whose `X-Api-Key` header is a key of `store`.
+    pub fn api_key<S: KeyStore + 'static>(store: S) -> RequestAuth {
+        RequestAuth::new(store, Scheme::ApiKey)
+    }
+
+    /// Authorizes the requests signed with the secret of a key of `store`.
+    pub fn hmac<S: KeyStore + 'static>(store: S) -> RequestAuth {
+        RequestAuth::new(store, Scheme::Hmac)
+    }
+
+    fn new<S: KeyStore + 'static>(store: S, scheme: Scheme) -> RequestAuth {
+        RequestAuth {
+            store: Arc::new(store),
+            scheme,
+            max_skew: Duration::from_secs(300),
+            max_body: 1024 * 1024,
+        }
+    }
+
+    /// Changes how far the `Date` of a signed request can be from the time of the server,
+    /// 5 minutes by default.
+    pub fn with_max_skew(mut self, max_skew: Duration) -> RequestAuth {
+        self.max_skew = max_skew;
+        self
+    }
+
+    /// Changes the largest body of a signed request, read in memory to be hashed, 1 MiB by
+    /// default. Larger requests are rejected with `413 Payload Too Large`.
+    pub fn with_max_body(mut self, max_body: usize) -> RequestAuth {
+        self.max_body = max_body;
+        self
+    }
+
+    /// Checks the credentials of the request, and returns the identifier of its key, or the
+    /// status code to reject it with.
+    ///
+    /// The body of a signed request is read to be hashed, and can then be read again from
+    /// [`Request::as_reader`].
+    pub fn authorize(&self, request: &mut Request) -> Result<String, StatusCode> {
+        let (key_id, secret) = self.key(request.headers())?;
+        let key_id = key_id.to_owned();
+        if self.scheme == Scheme::ApiKey {
+            return Ok(key_id);
+        }
+
+        let now = request.clock().system_time();
+        let date = self.date(request.headers(), now)?.to_owned();
+        let signature = header_value(request.headers(), SIGNATURE_HEADER)
+            .and_then(hex_decode)
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let mut body = Vec::new();
+        request
+            .as_reader()
+            .take((self.max_body as u64).saturating_add(1))
+            .read_to_end(&mut body)
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        if body.len() > self.max_body {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&secret).unwrap();
+        mac.update(request.method().as_str().as_bytes());
+        mac.update(b"\n");
+        mac.update(request.url().as_bytes());
+        mac.update(b"\n");
+        mac.update(date.as_bytes());
+        mac.update(b"\n");
+        mac.update(hex_encode(&Sha256::digest(&body)).as_bytes());
+
+        let valid = mac.verify_slice(&signature).is_ok();
+        request.prepend_body(body);
+        if valid {
+            Ok(key_id)
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+
+    /// Answers the request with the response of `handler`, called with the request and the
+    /// identifier of its key, or with an empty response if it isn't authorized.
+    pub fn respond<F, R>(&self, mut request: Request, handler: F) -> IoResult<()>
+    where
+        F: FnOnce(&mut Request, &str) -> Response<R>,
+        R: Read,
+    {
+        match self.authorize(&mut request) {
+            Ok(key_id) => {
+                let response = handler(&mut request, &key_id);
+                request.respond(response)
+            }
+            Err(status_code) => request.respond(self.rejection(status_code)),
+        }
+    }
+
+    /// Checks the head of the request, for use in a [`PreHandler`].
+    ///
+    /// API keys are fully checked. For signed requests, only the key and the date can be checked
+    /// before the body is read, and the signature must still be verified with
+    /// [`RequestAuth::authorize`] or [`RequestAuth::respond`].
+    pub fn check(&self, head: &RequestHead<'_>) -> PreDecision {
+        let result = self.key(head.headers()).and_then(|_| match self.scheme {
+            Scheme::ApiKey => Ok(()),
+            Scheme::Hmac => self
+                .date(head.headers(), head.clock.system_time())
+                .map(|_| ()),
+        });
+        match result {
+            Ok(()) => PreDecision::Accept,
+            Err(status_code) => PreDecision::Reject(status_code),
+        }
+    }
+
+    /// Builds a [`PreHandler`] calling [`RequestAuth::check`].
+    pub fn pre_handler(self) -> PreHandler {
+        PreHandler::new(move |head| self.check(head))
+    }
+
+    fn rejection(&self, status_code: StatusCode) -> Response<std::io::Empty> {
+        let response = Response::empty(status_code);
+        if status_code != StatusCode::UNAUTHORIZED {
+            return response;
+        }
+        let challenge = match self.scheme {
+            Scheme::ApiKey => "ApiKey",
+            Scheme::Hmac => "HMAC-SHA256",
+        };
+        response.with_header(Header::from_bytes(&b"WWW-Authenticate"[..], challenge).unwrap())
+    }
+
+    // the identifier and the secret of the key of the request, whose secret is checked for an
+    // API key
+    fn key<'a>(&self, headers: &'a [Header]) -> Result<(&'a str, Vec<u8>), StatusCode> {
+        let value = header_value(headers, API_KEY_HEADER).ok_or(StatusCode::UNAUTHORIZED)?;
+        let (key_id, presented) = match self.scheme {
+            Scheme::ApiKey => {
+                let (key_id, presented) = value.split_once(':').ok_or(StatusCode::UNAUTHORIZED)?;
+                (key_id, Some(presented))
+            }
+            Scheme::Hmac => (value, None),
+        };
+
+        let secret = self.store.secret(key_id).ok_or(StatusCode::FORBIDDEN)?;
+        match presented {
+            Some(presented) if !constant_time_eq(presented.as_bytes(), &secret) => {
+                Err(StatusCode::FORBIDDEN)
+            }
+            _ => Ok((key_id, secret)),
+        }
+    }
+
+    // the `Date` header, if it is close enough to `now`
+    fn date<'a>(&self, headers: &'a [Header], now: SystemTime) -> Result<&'a str, StatusCode> {
+        let value = header_value(headers, "Date").ok_or(StatusCode::UNAUTHORIZED)?;
+        let date = httpdate::parse_http_date(value).map_err(|_| StatusCode::UNAUTHORIZED)?;
+        let skew = match now.duration_since(date) {
+            Ok(skew) => skew,
+            Err(err) => err.duration(),
+        };
+        if skew <= self.max_skew {
+            Ok(value)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+impl std::fmt::Debug for RequestAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestAuth")
+            .field("scheme", &self.scheme)
+            .field("max_skew", &self.max_skew)
+            .field("max_body", &self.max_body)
+            .finish()
+    }
+}
+
+fn header_value<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+// compares every byte whatever the first difference, not to reveal how much of a secret was
+// guessed right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hex_encode, RequestAuth};
+    use crate::{
+        Clock, HTTPVersion, Header, Method, PreDecision, Request, RequestHead, StatusCode,
+        TestRequest,
+    };
+    use hmac::{Hmac, Mac};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant, SystemTime};
+
+    fn keys() -> HashMap<String, Vec<u8>> {
+        let mut keys = HashMap::new();
+        keys.insert("bot".to_owned(), b"secret".to_vec());
+        keys
+    }
+
+    fn header(field: &str, value: &str) -> Header {
+        Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+    }
+
+    fn signed(key: &[u8], date: SystemTime, body: &'static str) -> TestRequest {
+        let date = httpdate::fmt_http_date(date);
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        let hash = hex_encode(&Sha256::digest(body.as_bytes()));
+        mac.update(format!("POST\n/deploy\n{}\n{}", date, hash).as_bytes());
+
+        TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/deploy")
+            .with_body(body)
+            .with_header(header("X-Api-Key", "bot"))
+            .with_header(header("Date", &date))
+            .with_header(header(
+                "X-Signature",
+                &hex_encode(&mac.finalize().into_bytes()),
+            ))
+    }
+
+    #[test]
+    fn api_keys() {
+        let auth = RequestAuth::api_key(keys());
+
+        let authorize = |value: Option<&str>| {
+            let mut request = TestRequest::new();
+            if let Some(value) = value {
+                request = request.with_header(header("X-Api-Key", value));
+            }
+            auth.authorize(&mut request.into())
+        };
+        assert_eq!(authorize(Some("bot:secret")), Ok("bot".to_owned()));
+        assert_eq!(authorize(Some("bot:secreT")), Err(StatusCode::FORBIDDEN));
+        assert_eq!(authorize(Some("bot:secret2")), Err(StatusCode::FORBIDDEN));
+        assert_eq!(authorize(Some("other:secret")), Err(StatusCode::FORBIDDEN));
+        assert_eq!(authorize(Some("bot")), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(authorize(None), Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn check_uses_the_clock() {
+        #[derive(Debug)]
+        struct FixedClock(SystemTime);
+
+        impl Clock for FixedClock {
+            fn now(&self) -> Instant {
+                Instant::now()
+            }
+
+            fn system_time(&self) -> SystemTime {
+                self.0
+            }
+        }
+
+        let auth = RequestAuth::hmac(keys()).with_max_skew(Duration::from_secs(60));
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let headers = [
+            header("X-Api-Key", "bot"),
+            header("Date", &httpdate::fmt_http_date(date)),
+        ];
+        let check = |now| {
+            auth.check(&RequestHead {
+                secure: false,
+                method: &Method::Post,
+                path: "/deploy",
+                http_version: &HTTPVersion(1, 1),
+                headers: &headers,
+                remote_addr: None,
+                clock: &FixedClock(now),
+            })
+        };
+        assert_eq!(check(date), PreDecision::Accept);
+        assert_eq!(
+            check(SystemTime::now()),
+            PreDecision::Reject(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn hmac_signatures() {
+        let auth = RequestAuth::hmac(keys()).with_max_skew(Duration::from_secs(60));
+        let now = SystemTime::now();
+
+        let mut request: Request = signed(b"secret", now, "v1.2").into();
+        assert_eq!(auth.authorize(&mut request), Ok("bot".to_owned()));
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "v1.2");
+
+        let mut request: Request = signed(b"wrong", now, "v1.2").into();
+        assert_eq!(auth.authorize(&mut request), Err(StatusCode::FORBIDDEN));
+
+        let stale = now - Duration::from_secs(120);
+        let mut request: Request = signed(b"secret", stale, "v1.2").into();
+        assert_eq!(auth.authorize(&mut request), Err(StatusCode::UNAUTHORIZED));
+
+        let auth = auth.with_max_body(2);
+        let mut request: Request = signed(b"secret", now, "v1.2").into();
+        assert_eq!(
+            auth.authorize(&mut request),
+            Err(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+    }
+}
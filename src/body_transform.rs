@@ -0,0 +1,122 @@
+use std::fmt;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::common::StatusCode;
+
+/// Function wrapping the body of every response before it is sent, e.g. to rewrite HTML on the
+/// fly, count bytes or add a watermark.
+///
+/// Set in [`ServerConfig::body_transform`](crate::ServerConfig::body_transform), it is given
+/// the status code, `Content-Type` and length of the body in a [`BodyInfo`], and returns the
+/// [`ResponseBody`] to send, either as it is or wrapped. The transfer encoding is chosen
+/// afterwards from the length of the returned body: a body whose length changed in a way that
+/// isn't known in advance is sent chunked, or delimited by the end of the connection with
+/// HTTP/1.0 clients.
+///
+/// It applies to the responses of the handlers as well as those the server sends by itself,
+/// like errors, but not to the `101 Switching Protocols` responses of upgrades.
+///
+/// ```
+/// use std::io::Read;
+/// use tiny_http::BodyTransform;
+///
+/// // appends a comment to the HTML pages
+/// let transform = BodyTransform::new(|info, body| {
+///     let is_html = info
+///         .content_type
+///         .map_or(false, |value| value.starts_with("text/html"));
+///     if !is_html {
+///         return body;
+///     }
+///
+///     let comment = "<!-- served by tiny-http -->";
+///     let length = info.length.map(|length| length + comment.len());
+///     body.wrap_with_length(length, move |reader| reader.chain(comment.as_bytes()))
+/// });
+/// ```
+#[derive(Clone)]
+pub struct BodyTransform(Arc<TransformFn>);
+
+type TransformFn =
+    dyn for<'r> Fn(&BodyInfo<'_>, ResponseBody<'r>) -> ResponseBody<'r> + Send + Sync + 'static;
+
+impl BodyTransform {
+    /// Builds a transform from a function.
+    pub fn new<F>(f: F) -> BodyTransform
+    where
+        F: for<'r> Fn(&BodyInfo<'_>, ResponseBody<'r>) -> ResponseBody<'r> + Send + Sync + 'static,
+    {
+        BodyTransform(Arc::new(f))
+    }
+
+    pub(crate) fn call<'r>(&self, info: &BodyInfo<'_>, body: ResponseBody<'r>) -> ResponseBody<'r> {
+        (self.0)(info, body)
+    }
+}
+
+impl fmt::Debug for BodyTransform {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("BodyTransform")
+    }
+}
+
+/// What is known of the body of a response before it is sent, passed to a [`BodyTransform`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyInfo<'a> {
+    /// Status code of the response.
+    pub status_code: StatusCode,
+
+    /// Value of the `Content-Type` header of the response, if any.
+    pub content_type: Option<&'a str>,
+
+    /// Length of the body, if known in advance.
+    pub length: Option<usize>,
+}
+
+/// Body of a response, passed to a [`BodyTransform`] to be wrapped.
+pub struct ResponseBody<'r> {
+    reader: Box<dyn Read + 'r>,
+    length: Option<usize>,
+}
+
+impl<'r> ResponseBody<'r> {
+    pub(crate) fn new(reader: Box<dyn Read + 'r>, length: Option<usize>) -> Self {
+        ResponseBody { reader, length }
+    }
+
+    /// Wraps the reader with one producing a body whose length isn't known in advance.
+    pub fn wrap<F, T>(self, f: F) -> ResponseBody<'r>
+    where
+        F: FnOnce(Box<dyn Read + 'r>) -> T,
+        T: Read + 'r,
+    {
+        self.wrap_with_length(None, f)
+    }
+
+    /// Wraps the reader with one producing a body of `length` bytes, or of unknown length if
+    /// `None`. Passing [`BodyInfo::length`] keeps the length of the body.
+    pub fn wrap_with_length<F, T>(self, length: Option<usize>, f: F) -> ResponseBody<'r>
+    where
+        F: FnOnce(Box<dyn Read + 'r>) -> T,
+        T: Read + 'r,
+    {
+        ResponseBody {
+            reader: Box::new(f(self.reader)),
+            length,
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (Box<dyn Read + 'r>, Option<usize>) {
+        (self.reader, self.length)
+    }
+}
+
+impl fmt::Debug for ResponseBody<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ResponseBody")
+            .field("length", &self.length)
+            .finish()
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Result as IoResult};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::common::{Header, Method, StatusCode};
+use crate::{Request, Response};
+
+/// In-memory cache of small responses, keyed by the `Host` header and the URL of the request
+/// (path and query).
+///
+/// [`ResponseCache::respond`] wraps the handler of a request: a fresh response stored for the
+/// same host and URL is replayed with an `Age` header, otherwise the handler is called and its
+/// response is stored if it can be cached. A response is stored when:
+///
+/// - the request is a `GET` without `Authorization` header,
+/// - the status code is cacheable by default (e.g. `200`, `301` or `404`),
+/// - the response has a `Cache-Control` header with a `max-age` or `s-maxage`, and neither
+///   `no-store`, `no-cache` nor `private`,
+/// - the response has a `Content-Length` and neither `Set-Cookie` nor `Vary` header.
+///
+/// `HEAD` requests are answered from the stored `GET` responses. When the total size of the
+/// stored responses exceeds the capacity, the least recently used ones are evicted.
+///
+/// ```no_run
+/// use tiny_http::{Header, Response, ResponseCache, Server};
+///
+/// let server = Server::http("0.0.0.0:0").unwrap();
+/// let cache = ResponseCache::new(16 * 1024 * 1024);
+///
+/// for request in server.incoming_requests() {
+///     cache.respond(request, |request| {
+///         Response::from_string(format!("Hello from {}", request.url()))
+///             .with_header("Cache-Control: max-age=60".parse::<Header>().unwrap())
+///     }).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ResponseCache {
+    max_bytes: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+
+    // total size of the entries
+    size: usize,
+
+    // incremented on every use, to find the least recently used entry
+    tick: u64,
+}
+
+/// Host, in lowercase and empty if the request has none, and URL of a stored response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    host: String,
+    url: String,
+}
+
+impl Key {
+    fn new(request: &Request) -> Key {
+        let host = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Host"))
+            .map_or_else(String::new, |h| h.value.as_str().to_ascii_lowercase());
+        Key {
+            host,
+            url: request.url().to_owned(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    status_code: StatusCode,
+    headers: Vec<Header>,
+    body: Vec<u8>,
+    stored_at: Instant,
+    max_age: Duration,
+    last_used: u64,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|h| h.field.as_str().len() + h.value.len() + 4)
+            .sum();
+        headers + self.body.len()
+    }
+}
+
+impl ResponseCache {
+    /// Builds an empty cache storing at most `max_bytes` of headers and bodies.
+    pub fn new(max_bytes: usize) -> ResponseCache {
+        ResponseCache {
+            max_bytes,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Answers the request with a stored response if there is a fresh one, or with the response
+    /// of `handler`, which is stored if it can be cached.
+    pub fn respond<F, R>(&self, request: Request, handler: F) -> IoResult<()>
+    where
+        F: FnOnce(&Request) -> Response<R>,
+        R: Read,
+    {
+        if let Some(response) = self.get(&request) {
+            return request.respond(response);
+        }
+
+        let response = handler(&request);
+        let max_age = match self.max_age(&request, &response) {
+            Some(max_age) => max_age,
+            None => return request.respond(response),
+        };
+
+        let status_code = response.status_code();
+        let headers = response.headers().to_vec();
+        let length = response.data_length().unwrap_or(0);
+
+        let mut body = Vec::with_capacity(length);
+        response
+            .into_reader()
+            .take(length as u64)
+            .read_to_end(&mut body)?;
+
+        if body.len() == length {
+            self.insert(
+                Key::new(&request),
+                Entry {
+                    status_code,
+                    headers: headers.clone(),
+                    body: body.clone(),
+                    stored_at: request.clock().now(),
+                    max_age,
+                    last_used: 0,
+                },
+            );
+        }
+
+        let length = body.len();
+        request.respond(Response::new(
+            status_code,
+            headers,
+            Cursor::new(body),
+            Some(length),
+            None,
+        ))
+    }
+
+    /// Removes the responses stored for this URL, whatever their host. Returns false if there
+    /// were none.
+    pub fn invalidate(&self, url: &str) -> bool {
+        self.remove_where(|key| key.url == url) > 0
+    }
+
+    /// Removes the responses stored for the URLs starting with `prefix`, e.g. a directory,
+    /// whatever their host. Returns the number of removed responses.
+    pub fn invalidate_prefix(&self, prefix: &str) -> usize {
+        self.remove_where(|key| key.url.starts_with(prefix))
+    }
+
+    fn remove_where<F>(&self, f: F) -> usize
+    where
+        F: Fn(&Key) -> bool,
+    {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.map.len();
+        let mut removed_size = 0;
+        entries.map.retain(|key, entry| {
+            let keep = !f(key);
+            if !keep {
+                removed_size += entry.size();
+            }
+            keep
+        });
+        entries.size -= removed_size;
+        before - entries.map.len()
+    }
+
+    /// Removes all the stored responses.
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.map.clear();
+        entries.size = 0;
+    }
+
+    /// Returns the number of stored responses, including the expired ones not evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    /// Returns true if no response is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the total size of the stored responses.
+    pub fn size(&self) -> usize {
+        self.entries.lock().unwrap().size
+    }
+
+    fn get(&self, request: &Request) -> Option<Response<Cursor<Vec<u8>>>> {
+        match request.method() {
+            Method::Get | Method::Head => (),
+            _ => return None,
+        }
+        if has_directive(request.headers(), "no-cache") {
+            return None;
+        }
+
+        let now = request.clock().now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+
+        let key = Key::new(request);
+        let entry = entries.map.get_mut(&key)?;
+        let age = now.saturating_duration_since(entry.stored_at);
+        if age >= entry.max_age {
+            let size = entry.size();
+            entries.map.remove(&key);
+            entries.size -= size;
+            return None;
+        }
+        entry.last_used = tick;
+
+        let mut response = Response::new(
+            entry.status_code,
+            entry.headers.clone(),
+            Cursor::new(entry.body.clone()),
+            Some(entry.body.len()),
+            None,
+        );
+        let age = Header::from_bytes(&b"Age"[..], age.as_secs().to_string()).unwrap();
+        response.add_header(age);
+        Some(response)
+    }
+
+    fn insert(&self, key: Key, mut entry: Entry) {
+        let size = entry.size();
+        if size > self.max_bytes {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        entry.last_used = entries.tick;
+
+        if let Some(previous) = entries.map.insert(key, entry) {
+            entries.size -= previous.size();
+        }
+        entries.size += size;
+
+        while entries.size > self.max_bytes {
+            let oldest = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            let entry = entries.map.remove(&oldest).unwrap();
+            entries.size -= entry.size();
+        }
+    }
+
+    /// Returns how long the response can be replayed, or `None` if it can't be stored.
+    fn max_age<R: Read>(&self, request: &Request, response: &Response<R>) -> Option<Duration> {
+        if *request.method() != Method::Get
+            || request
+                .headers()
+                .iter()
+                .any(|h| h.field.equiv("Authorization"))
+        {
+            return None;
+        }
+
+        match response.status_code().0 {
+            200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501 => (),
+            _ => return None,
+        }
+
+        match response.data_length() {
+            Some(length) if length <= self.max_bytes => (),
+            _ => return None,
+        }
+
+        let headers = response.headers();
+        if headers
+            .iter()
+            .any(|h| h.field.equiv("Set-Cookie") || h.field.equiv("Vary"))
+        {
+            return None;
+        }
+
+        max_age(headers).filter(|max_age| *max_age > Duration::from_secs(0))
+    }
+}
+
+/// Returns the lifetime given by the `Cache-Control` headers of a response.
+fn max_age(headers: &[Header]) -> Option<Duration> {
+    let mut max_age = None;
+    let mut s_maxage = None;
+
+    for directive in directives(headers) {
+        let mut elems = directive.splitn(2, '=');
+        let name = elems.next().unwrap_or("");
+        let value = elems.next().map(|v| v.trim_matches('"'));
+
+        if name.eq_ignore_ascii_case("no-store")
+            || name.eq_ignore_ascii_case("no-cache")
+            || name.eq_ignore_ascii_case("private")
+        {
+            return None;
+        } else if name.eq_ignore_ascii_case("max-age") {
+            max_age = value.and_then(|v| v.parse().ok());
+        } else if name.eq_ignore_ascii_case("s-maxage") {
+            s_maxage = value.and_then(|v| v.parse().ok());
+        }
+    }
+
+    s_maxage.or(max_age).map(Duration::from_secs)
+}
+
+fn has_directive(headers: &[Header], name: &str) -> bool {
+    directives(headers).any(|directive| directive.eq_ignore_ascii_case(name))
+}
+
+fn directives(headers: &[Header]) -> impl Iterator<Item = &str> {
+    headers
+        .iter()
+        .filter(|h| h.field.equiv("Cache-Control"))
+        .flat_map(|h| h.split_list())
+}
+
+#[cfg(test)]
+mod test {
+    use super::max_age;
+    use crate::Header;
+    use std::time::Duration;
+
+    fn headers(values: &[&str]) -> Vec<Header> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn cache_control() {
+        assert_eq!(max_age(&[]), None);
+        assert_eq!(
+            max_age(&headers(&["Cache-Control: public, max-age=60"])),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            max_age(&headers(&["Cache-Control: max-age=60, s-maxage=\"10\""])),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            max_age(&headers(&[
+                "Cache-Control: max-age=60",
+                "Cache-Control: no-store"
+            ])),
+            None
+        );
+        assert_eq!(max_age(&headers(&["Cache-Control: max-age=x"])), None);
+        assert_eq!(
+            max_age(&headers(&["Cache-Control: private, max-age=60"])),
+            None
+        );
+    }
+}
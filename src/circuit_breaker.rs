@@ -0,0 +1,360 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Read, Result as IoResult};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::common::StatusCode;
+use crate::log;
+use crate::{Clock, Request, Response, ResponseBox, RetryAfter, SystemClock};
+
+/// Circuit breaker protecting the calls to an upstream service, e.g. a backend requests are
+/// forwarded to, so that a dead upstream fails fast instead of making every client wait for
+/// its timeouts.
+///
+/// The breaker is *closed* while the upstream works: the calls go through and their outcomes
+/// are recorded. Once the proportion of failures among the last calls reaches the threshold, the
+/// breaker *opens* and the calls fail immediately. After the open duration, the breaker is
+/// *half-open*: a single trial call goes through, closing the breaker if it succeeds, and
+/// opening it again otherwise.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use tiny_http::{CircuitBreaker, Response, Server};
+///
+/// # fn fetch_from_upstream(url: &str) -> std::io::Result<String> { unimplemented!() }
+/// let server = Server::http("0.0.0.0:0").unwrap();
+/// let breaker = CircuitBreaker::new().with_open_duration(Duration::from_secs(10));
+///
+/// for request in server.incoming_requests() {
+///     breaker.respond(request, |request| {
+///         fetch_from_upstream(request.url()).map(Response::from_string)
+///     }).unwrap();
+/// }
+/// ```
+pub struct CircuitBreaker {
+    failure_threshold: f64,
+    window: usize,
+    open_duration: Duration,
+    clock: Arc<dyn Clock>,
+    fallback: Option<Box<dyn Fn(CircuitState) -> ResponseBox + Send + Sync>>,
+    inner: Mutex<Inner>,
+}
+
+/// State of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The calls go through.
+    Closed,
+    /// The calls fail without being made.
+    Open,
+    /// A trial call decides whether the breaker closes or opens again.
+    HalfOpen,
+}
+
+/// Error of a call through a [`CircuitBreaker`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum CircuitError<E> {
+    /// The breaker is open, or half-open with a trial call in progress, and the call wasn't made.
+    Open,
+    /// The call was made and failed.
+    Failed(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open => f.write_str("circuit open"),
+            CircuitError::Failed(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for CircuitError<E> {}
+
+struct Inner {
+    state: CircuitState,
+    // outcomes of the last calls while closed, `true` for failures
+    outcomes: VecDeque<bool>,
+    opened_at: Instant,
+    trial_in_progress: bool,
+}
+
+impl CircuitBreaker {
+    /// Builds a closed breaker, opening when half of the last 20 calls failed, for 30 seconds.
+    pub fn new() -> CircuitBreaker {
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        CircuitBreaker {
+            failure_threshold: 0.5,
+            window: 20,
+            open_duration: Duration::from_secs(30),
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::new(),
+                opened_at: clock.now(),
+                trial_in_progress: false,
+            }),
+            clock,
+            fallback: None,
+        }
+    }
+
+    /// Changes the proportion of failures, between 0 and 1, opening the breaker.
+    pub fn with_failure_threshold(mut self, threshold: f64) -> CircuitBreaker {
+        self.failure_threshold = threshold;
+        self
+    }
+
+    /// Changes the number of last calls the failures are counted among. The breaker doesn't
+    /// open before that many calls have been made.
+    pub fn with_window(mut self, calls: usize) -> CircuitBreaker {
+        self.window = calls.max(1);
+        self
+    }
+
+    /// Changes how long the breaker stays open before a trial call.
+    pub fn with_open_duration(mut self, duration: Duration) -> CircuitBreaker {
+        self.open_duration = duration;
+        self
+    }
+
+    /// Replaces the clock measuring the open duration, e.g. with a manual clock in tests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> CircuitBreaker {
+        self.clock = clock;
+        self
+    }
+
+    /// Changes the response of [`CircuitBreaker::respond`] when the call isn't made or fails,
+    /// given the state of the breaker. By default, it is an empty `503 Service Unavailable`,
+    /// with a `Retry-After` header while the breaker is open.
+    pub fn with_fallback<F>(mut self, fallback: F) -> CircuitBreaker
+    where
+        F: Fn(CircuitState) -> ResponseBox + Send + Sync + 'static,
+    {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+
+    /// Returns the current state of the breaker.
+    pub fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        inner.state
+    }
+
+    /// Makes the call `f` if the breaker allows it, and records its outcome.
+    pub fn call<T, E, F>(&self, f: F) -> Result<T, CircuitError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            self.refresh(&mut inner);
+            match inner.state {
+                CircuitState::Closed => (),
+                CircuitState::HalfOpen if !inner.trial_in_progress => {
+                    inner.trial_in_progress = true;
+                }
+                _ => return Err(CircuitError::Open),
+            }
+        }
+
+        // a call that panics counts as failed, so that a trial doesn't stay in progress forever
+        let mut unwinding = FailOnUnwind(Some(self));
+        let result = f();
+        unwinding.0 = None;
+        self.record(result.is_err());
+        result.map_err(CircuitError::Failed)
+    }
+
+    /// Answers the request with the response of `handler`, called through the breaker, or
+    /// with the fallback response if the call isn't made or fails.
+    pub fn respond<F, R, E>(&self, mut request: Request, handler: F) -> IoResult<()>
+    where
+        F: FnOnce(&mut Request) -> Result<Response<R>, E>,
+        R: Read,
+    {
+        match self.call(|| handler(&mut request)) {
+            Ok(response) => request.respond(response),
+            Err(_) => {
+                let state = self.state();
+                match self.fallback {
+                    Some(ref fallback) => request.respond(fallback(state)),
+                    None => request.respond(self.default_fallback(state)),
+                }
+            }
+        }
+    }
+
+    fn default_fallback(&self, state: CircuitState) -> Response<std::io::Empty> {
+        let response = Response::empty(StatusCode::SERVICE_UNAVAILABLE);
+        if state != CircuitState::Open {
+            return response;
+        }
+
+        let opened_at = self.inner.lock().unwrap().opened_at;
+        let opened_for = self.clock.now().saturating_duration_since(opened_at);
+        let remaining = self.open_duration.saturating_sub(opened_for);
+        // rounded up, not to invite a retry before the trial call
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+        response.with_header(RetryAfter::Seconds(seconds.max(1)))
+    }
+
+    // moves from open to half-open once the open duration has elapsed
+    fn refresh(&self, inner: &mut Inner) {
+        if inner.state == CircuitState::Open
+            && self.clock.now().saturating_duration_since(inner.opened_at) >= self.open_duration
+        {
+            inner.state = CircuitState::HalfOpen;
+            inner.trial_in_progress = false;
+        }
+    }
+
+    fn record(&self, failed: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::Closed => {
+                inner.outcomes.push_back(failed);
+                if inner.outcomes.len() > self.window {
+                    inner.outcomes.pop_front();
+                }
+                let failures = inner.outcomes.iter().filter(|&&failed| failed).count();
+                if inner.outcomes.len() == self.window
+                    && failures as f64 >= self.failure_threshold * self.window as f64
+                {
+                    self.open(&mut inner);
+                }
+            }
+            CircuitState::HalfOpen if failed => self.open(&mut inner),
+            CircuitState::HalfOpen => {
+                inner.state = CircuitState::Closed;
+                inner.outcomes.clear();
+            }
+            // a call made before the breaker opened
+            CircuitState::Open => (),
+        }
+    }
+
+    fn open(&self, inner: &mut Inner) {
+        log::debug!("Circuit breaker opened for {:?}", self.open_duration);
+        inner.state = CircuitState::Open;
+        inner.opened_at = self.clock.now();
+        inner.outcomes.clear();
+        inner.trial_in_progress = false;
+    }
+}
+
+/// Records a failed call when dropped while the call is unwinding.
+struct FailOnUnwind<'a>(Option<&'a CircuitBreaker>);
+
+impl Drop for FailOnUnwind<'_> {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.0 {
+            breaker.record(true);
+        }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> CircuitBreaker {
+        CircuitBreaker::new()
+    }
+}
+
+impl fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("failure_threshold", &self.failure_threshold)
+            .field("window", &self.window)
+            .field("open_duration", &self.open_duration)
+            .field("state", &self.inner.lock().unwrap().state)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CircuitBreaker, CircuitError, CircuitState};
+    use crate::Clock;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, SystemTime};
+
+    #[derive(Debug)]
+    struct ManualClock(Mutex<Instant>);
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+
+        fn system_time(&self) -> SystemTime {
+            SystemTime::now()
+        }
+    }
+
+    #[test]
+    fn opens_and_recovers() {
+        let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+        let breaker = CircuitBreaker::new()
+            .with_window(4)
+            .with_failure_threshold(0.5)
+            .with_open_duration(Duration::from_secs(10))
+            .with_clock(clock.clone());
+
+        assert_eq!(breaker.call(|| Ok::<_, ()>(1)), Ok(1));
+        assert_eq!(
+            breaker.call(|| Err::<(), _>("down")),
+            Err(CircuitError::Failed("down"))
+        );
+        assert_eq!(breaker.call(|| Ok::<_, ()>(2)), Ok(2));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.call(|| Err::<(), _>("down")).is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let mut called = false;
+        let result = breaker.call(|| {
+            called = true;
+            Ok::<_, ()>(())
+        });
+        assert_eq!(result, Err(CircuitError::Open));
+        assert!(!called);
+
+        // a failed trial opens the breaker again
+        *clock.0.lock().unwrap() += Duration::from_secs(10);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.call(|| Err::<(), _>("down")).is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // only one trial at a time, and a successful one closes the breaker
+        *clock.0.lock().unwrap() += Duration::from_secs(10);
+        let result = breaker.call(|| {
+            assert_eq!(breaker.call(|| Ok::<_, ()>(())), Err(CircuitError::Open));
+            Ok::<_, ()>(3)
+        });
+        assert_eq!(result, Ok(3));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn panicking_trial() {
+        let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+        let breaker = CircuitBreaker::new()
+            .with_window(1)
+            .with_open_duration(Duration::from_secs(10))
+            .with_clock(clock.clone());
+        assert!(breaker.call(|| Err::<(), _>("down")).is_err());
+
+        // a panicking trial opens the breaker again, instead of blocking the next trials
+        *clock.0.lock().unwrap() += Duration::from_secs(10);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            breaker.call(|| -> Result<(), ()> { panic!("trial") })
+        }));
+        assert!(result.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        *clock.0.lock().unwrap() += Duration::from_secs(10);
+        assert_eq!(breaker.call(|| Ok::<_, ()>(1)), Ok(1));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
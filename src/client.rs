@@ -1,16 +1,34 @@
 use ascii::AsciiString;
 
+use std::fmt;
 use std::io::Error as IoError;
 use std::io::Result as IoResult;
-use std::io::{BufReader, BufWriter, ErrorKind, Read};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
 
 use std::net::SocketAddr;
 use std::str::FromStr;
-
-use crate::common::{HTTPVersion, Method};
-use crate::util::RefinedTcpStream;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use crate::common::{HTTPVersion, Header, Method, StatusCode};
+use crate::error_renderer::{error_response, ErrorRenderer};
+#[cfg(feature = "otel")]
+use crate::otel::Telemetry;
+use crate::ssl::TlsInfo;
+use crate::state::SharedState;
+#[cfg(feature = "status-page")]
+use crate::status_page::StatusPage;
+use crate::trusted_proxy::{read_proxy_header, ProxyHeader};
+use crate::util::refined_tcp_stream::Stream;
+use crate::util::{ConnectionCounter, ConnectionGuard, IdleReaper, IdleWatch};
+use crate::util::{MemoryBudget, RefinedTcpStream};
 use crate::util::{SequentialReader, SequentialReaderBuilder, SequentialWriterBuilder};
-use crate::Request;
+use crate::{
+    BodyTransform, Clock, LimitsConfig, PipelineWait, PipelineWaitHandler, PreDecision, PreHandler,
+    Request, RequestHead, Response, ResponseDecorator, ServerState, TrustedProxies,
+    UnansweredPolicy,
+};
 
 /// A ClientConnection is an object that will store a socket to a client
 /// and return Request objects.
@@ -34,15 +52,247 @@ pub struct ClientConnection {
 
     // true if the connection goes through SSL
     secure: bool,
+
+    // handle to the socket, shared with the requests for e.g. shutting down upgraded streams
+    socket: Option<Arc<Stream>>,
+
+    // parameters of the TLS handshake, read once the first request head is received since
+    // some implementations only complete the handshake when the stream is first read
+    tls_info: Option<Arc<TlsInfo>>,
+
+    settings: Arc<ConnectionSettings>,
+
+    // number of error responses sent on this connection, shared with the requests
+    error_count: Arc<AtomicUsize>,
+
+    // requests read from this connection and not answered yet
+    pipeline: Arc<PipelineCounter>,
+
+    // `Some` if idle connections are closed, see `SocketConfig::keep_alive_idle_timeout`
+    idle: Option<Arc<IdleWatch>>,
+
+    // when the connection was accepted, for `LimitsConfig::connection_max_lifetime`
+    opened_at: Instant,
+
+    // see `Request::connection_id`
+    id: u64,
+
+    // number of requests read from the connection, see `Request::connection_sequence`
+    requests_read: usize,
+
+    // true if the peer is one of `ServerConfig::trusted_proxies`
+    trusted_proxy: bool,
+
+    // true until the PROXY protocol header sent by a trusted proxy has been read
+    proxy_header_pending: bool,
+
+    // counts the connection as open until it's dropped
+    _open_guard: ConnectionGuard,
+}
+
+/// Settings of the server shared by all the connections of a listener.
+#[derive(Clone)]
+pub(crate) struct ConnectionSettings {
+    pub(crate) limits: LimitsConfig,
+
+    // called before reading the body of each request
+    pub(crate) pre_handler: Option<PreHandler>,
+
+    pub(crate) clock: Arc<dyn Clock>,
+
+    // `SocketConfig::write_buffer_size`
+    pub(crate) write_buffer_size: usize,
+
+    // `ServerConfig::health_check_path`
+    pub(crate) health_check_path: Option<String>,
+
+    // `ServerConfig::pipeline_wait_handler`
+    pub(crate) pipeline_wait_handler: Option<PipelineWaitHandler>,
+
+    // `ServerConfig::unanswered`
+    pub(crate) unanswered: Option<UnansweredPolicy>,
+
+    // `ServerConfig::error_renderer`
+    pub(crate) error_renderer: Option<ErrorRenderer>,
+
+    // `ServerConfig::response_decorator`
+    pub(crate) response_decorator: Option<ResponseDecorator>,
+
+    // `ServerConfig::body_transform`
+    pub(crate) body_transform: Option<BodyTransform>,
+
+    // `ServerConfig::auto_etag`
+    pub(crate) auto_etag: bool,
+
+    // `ServerConfig::emit_default_headers`
+    pub(crate) emit_default_headers: bool,
+
+    // `ServerConfig::chunked_threshold`
+    pub(crate) chunked_threshold: Option<usize>,
+
+    // `ServerConfig::normalize_absolute_form`
+    pub(crate) normalize_absolute_form: bool,
+
+    // `ServerConfig::trusted_proxies`
+    pub(crate) trusted_proxies: Option<TrustedProxies>,
+
+    // memory of the bodies read in advance, see `LimitsConfig::buffered_bodies_max`
+    pub(crate) body_budget: Arc<MemoryBudget>,
+
+    // connections currently open, see `Server::num_connections`
+    pub(crate) connections: Arc<ConnectionCounter>,
+
+    // `Some` if `SocketConfig::keep_alive_idle_timeout` is set
+    pub(crate) idle_reaper: Option<Arc<IdleReaper>>,
+
+    // built from `ServerConfig::otel`
+    #[cfg(feature = "otel")]
+    pub(crate) telemetry: Option<Arc<Telemetry>>,
+
+    // built from `ServerConfig::status_page`
+    #[cfg(feature = "status-page")]
+    pub(crate) status_page: Option<Arc<StatusPage>>,
+
+    // lifecycle state of the server, reported by the health check
+    pub(crate) state: Arc<SharedState>,
+}
+
+impl ConnectionSettings {
+    /// Prepares a response sent by the connection itself: leaves out its default headers if
+    /// `ServerConfig::emit_default_headers` is false, and applies
+    /// `ServerConfig::response_decorator` and `ServerConfig::body_transform`.
+    fn internal_response<'a, R: Read + 'a>(
+        &self,
+        response: Response<R>,
+    ) -> Response<Box<dyn Read + 'a>> {
+        let response = if self.emit_default_headers {
+            response
+        } else {
+            response.without_default_headers()
+        };
+        let response = match self.response_decorator {
+            Some(ref decorator) => response.decorated(decorator, None),
+            None => response,
+        };
+        match self.body_transform {
+            Some(ref transform) => response.transformed(transform),
+            None => response.map_reader(|reader| Box::new(reader) as Box<dyn Read + 'a>),
+        }
+    }
+}
+
+/// Measures the head of a request while it is read, and keeps a copy of it if asked to.
+struct HeadRecorder {
+    // size of the head read so far, including the line terminators
+    size: usize,
+
+    // copy of the head, dropped once it exceeds `max_size`
+    raw: Option<Vec<u8>>,
+
+    max_size: usize,
+}
+
+impl HeadRecorder {
+    fn new(retain: Option<usize>) -> HeadRecorder {
+        HeadRecorder {
+            size: 0,
+            raw: retain.map(|_| Vec::new()),
+            max_size: retain.unwrap_or(0),
+        }
+    }
+
+    /// Records a line returned by `read_next_line`, which always ended with CRLF.
+    fn record(&mut self, line: &AsciiString) {
+        self.size += line.len() + 2;
+
+        if self.size > self.max_size {
+            self.raw = None;
+        } else if let Some(ref mut raw) = self.raw {
+            raw.extend_from_slice(line.as_bytes());
+            raw.extend_from_slice(b"\r\n");
+        }
+    }
+}
+
+/// Number of requests of a connection that haven't been answered yet.
+#[derive(Default)]
+pub(crate) struct PipelineCounter {
+    pending: Mutex<usize>,
+    condvar: Condvar,
+}
+
+impl PipelineCounter {
+    /// Counts a new request, which is pending until the guard is dropped.
+    fn register(self: &Arc<Self>) -> PipelineGuard {
+        *self.pending.lock().unwrap() += 1;
+        PipelineGuard(self.clone())
+    }
+
+    /// Returns the number of requests not answered yet.
+    pub(crate) fn pending(&self) -> usize {
+        *self.pending.lock().unwrap()
+    }
+
+    /// Blocks until less than `max` requests are pending.
+    fn wait_below(&self, max: usize) {
+        let mut pending = self.pending.lock().unwrap();
+        while *pending >= max {
+            pending = self.condvar.wait(pending).unwrap();
+        }
+    }
+}
+
+/// Keeps a request counted as pending.
+pub(crate) struct PipelineGuard(Arc<PipelineCounter>);
+
+impl PipelineGuard {
+    pub(crate) fn pending(&self) -> usize {
+        *self.0.pending.lock().unwrap()
+    }
+}
+
+impl Drop for PipelineGuard {
+    fn drop(&mut self) {
+        *self.0.pending.lock().unwrap() -= 1;
+        self.0.condvar.notify_all();
+    }
+}
+
+/// Identifier of the next connection accepted by the process, see `Request::connection_id`.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Returns a new identifier of connection.
+pub(crate) fn next_connection_id() -> u64 {
+    NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Maximum number of bytes of an oversized request head read and thrown away before closing the
+/// connection, see `ClientConnection::drain_head`.
+const HEAD_DRAIN_MAX: usize = 64 * 1024;
+
+/// Error of a line of the head longer than allowed.
+#[derive(Debug)]
+struct LineTooLong;
+
+impl fmt::Display for LineTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Line of the request head too long")
+    }
 }
 
+impl std::error::Error for LineTooLong {}
+
 /// Error that can happen when reading a request.
 #[derive(Debug)]
 enum ReadError {
     WrongRequestLine,
     WrongHeader(HTTPVersion),
+    /// a header line is longer than `header_line_len`, the rest of the head is unread
+    HeaderTooLarge(HTTPVersion),
     /// the client sent an unrecognized `Expect` header
     ExpectationFailed(HTTPVersion),
+    /// the request was already answered, e.g. rejected by the pre-handler
+    Rejected,
     ReadIoError(IoError),
 }
 
@@ -51,21 +301,86 @@ impl ClientConnection {
     pub fn new(
         write_socket: RefinedTcpStream,
         mut read_socket: RefinedTcpStream,
+        settings: Arc<ConnectionSettings>,
     ) -> ClientConnection {
         let remote_addr = read_socket.peer_addr();
         let secure = read_socket.secure();
+        let socket = read_socket.try_clone_stream().ok().map(Arc::new);
 
         let mut source = SequentialReaderBuilder::new(BufReader::with_capacity(1024, read_socket));
         let first_header = source.next().unwrap();
 
+        let mut sink = SequentialWriterBuilder::new(BufWriter::with_capacity(
+            settings.write_buffer_size,
+            write_socket,
+        ));
+        if let Some(ref handler) = settings.pipeline_wait_handler {
+            let handler = handler.clone();
+            let remote_addr = remote_addr.as_ref().ok().and_then(|addr| *addr);
+            sink = sink.with_wait_observer(
+                settings.clock.clone(),
+                Arc::new(move |duration| {
+                    handler.call(&PipelineWait {
+                        remote_addr,
+                        duration,
+                    })
+                }),
+            );
+        }
+
+        let peer_ip = remote_addr
+            .as_ref()
+            .ok()
+            .and_then(|addr| *addr)
+            .map(|addr| addr.ip());
+        let (trusted_proxy, proxy_header_pending) = match (&settings.trusted_proxies, peer_ip) {
+            (Some(proxies), Some(ip)) => {
+                (proxies.is_trusted(&ip), proxies.expects_proxy_protocol(&ip))
+            }
+            _ => (false, false),
+        };
+
+        let opened_at = settings.clock.now();
+        let pipeline = Arc::new(PipelineCounter::default());
+        let idle = match (&settings.idle_reaper, &socket) {
+            (Some(reaper), Some(socket)) => Some(reaper.watch(pipeline.clone(), socket.clone())),
+            _ => None,
+        };
+
         ClientConnection {
             source,
-            sink: SequentialWriterBuilder::new(BufWriter::with_capacity(1024, write_socket)),
+            sink,
             remote_addr,
             next_header_source: first_header,
             no_more_requests: false,
             secure,
+            socket,
+            tls_info: None,
+            error_count: Arc::new(AtomicUsize::new(0)),
+            pipeline,
+            idle,
+            opened_at,
+            id: next_connection_id(),
+            requests_read: 0,
+            trusted_proxy,
+            proxy_header_pending,
+            _open_guard: settings.connections.open(),
+            settings,
+        }
+    }
+
+    /// Applies the PROXY protocol header read before the TLS handshake of the connection.
+    pub(crate) fn with_proxy_header(mut self, header: ProxyHeader) -> ClientConnection {
+        self.proxy_header_pending = false;
+        self.apply_proxy_header(header);
+        self
+    }
+
+    fn apply_proxy_header(&mut self, header: ProxyHeader) {
+        if let Some(source) = header.source {
+            self.remote_addr = Ok(Some(source));
         }
+        self.secure = self.secure || header.ssl;
     }
 
     /// true if the connection is HTTPS
@@ -73,15 +388,60 @@ impl ClientConnection {
         self.secure
     }
 
+    /// Returns true if the connection is open for longer than `connection_max_lifetime`.
+    fn is_expired(&self) -> bool {
+        match self.settings.limits.connection_max_lifetime {
+            Some(lifetime) => {
+                let now = self.settings.clock.now();
+                now.saturating_duration_since(self.opened_at) >= lifetime.as_duration()
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if the request must be answered by the health check.
+    fn is_health_check(&self, rq: &Request) -> bool {
+        let health_check_path = match self.settings.health_check_path {
+            Some(ref path) => path,
+            None => return false,
+        };
+
+        let path = rq.url().split('?').next().unwrap_or("");
+        path == health_check_path && (*rq.method() == Method::Get || *rq.method() == Method::Head)
+    }
+
+    /// Returns the status page if the request must be answered by it.
+    #[cfg(feature = "status-page")]
+    fn status_page_for(&self, rq: &Request) -> Option<&StatusPage> {
+        let status_page = self.settings.status_page.as_ref()?;
+        let path = rq.url().split('?').next().unwrap_or("");
+        if path == crate::STATUS_PAGE_PATH
+            && (*rq.method() == Method::Get || *rq.method() == Method::Head)
+            && status_page.is_allowed(rq.remote_addr())
+        {
+            Some(status_page)
+        } else {
+            None
+        }
+    }
+
     /// Reads the next line from self.next_header_source.
     ///
     /// Reads until `CRLF` is reached. The next read will start
     ///  at the first byte of the new line.
-    fn read_next_line(&mut self) -> IoResult<AsciiString> {
+    ///
+    /// With `strict_parsing`, a line can't contain a bare `LF` or a bare `CR`, and fails with
+    ///  `InvalidData` otherwise. A line longer than `max_len` fails with a
+    ///  `LineTooLong` error, the rest of the line being left unread.
+    fn read_next_line(&mut self, max_len: Option<usize>) -> IoResult<AsciiString> {
+        let strict = self.settings.limits.strict_parsing;
+        let max_len = max_len.unwrap_or(usize::MAX);
         let mut buf = Vec::new();
         let mut prev_byte_was_cr = false;
 
         loop {
+            // `next_header_source` is buffered underneath
+            #[allow(clippy::unbuffered_bytes)]
             let byte = self.next_header_source.by_ref().bytes().next();
 
             let byte = match byte {
@@ -95,19 +455,105 @@ impl ClientConnection {
                     .map_err(|_| IoError::new(ErrorKind::InvalidInput, "Header is not in ASCII"));
             }
 
+            if byte == b'\n' && strict {
+                return Err(IoError::new(ErrorKind::InvalidData, "Line with a bare LF"));
+            }
+
+            if prev_byte_was_cr && strict {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "Line containing a bare CR",
+                ));
+            }
+
             prev_byte_was_cr = byte == b'\r';
 
             buf.push(byte);
+            // the `CR` of the line ending doesn't count
+            if buf.len() > max_len && !(buf.len() == max_len + 1 && prev_byte_was_cr) {
+                return Err(IoError::other(LineTooLong));
+            }
+        }
+    }
+
+    /// Reads and throws away the rest of a request head after a line too long, up to the empty
+    ///  line ending it or `HEAD_DRAIN_MAX` bytes, so that closing the connection with unread
+    ///  data doesn't reset it before the client has received the response.
+    fn drain_head(&mut self) {
+        // number of `LF` seen in a row, ignoring the `CR` before them
+        let mut line_feeds = 0;
+
+        #[allow(clippy::unbuffered_bytes)]
+        for byte in self
+            .next_header_source
+            .by_ref()
+            .bytes()
+            .take(HEAD_DRAIN_MAX)
+        {
+            match byte {
+                Ok(b'\n') => line_feeds += 1,
+                Ok(b'\r') => (),
+                Ok(_) => line_feeds = 0,
+                Err(_) => return,
+            }
+            if line_feeds == 2 {
+                return;
+            }
+        }
+    }
+
+    /// Turns an error reading a line of the head into a `ReadError`, answering with
+    /// `400 Bad Request` the lines rejected by `strict_parsing`, and with
+    /// `431 Request Header Fields Too Large` the header lines longer than `header_line_len`.
+    fn line_error(&self, err: IoError, malformed: ReadError) -> ReadError {
+        if err.get_ref().is_some_and(|err| err.is::<LineTooLong>()) {
+            if let ReadError::WrongHeader(version) = malformed {
+                return ReadError::HeaderTooLarge(version);
+            }
+        }
+
+        if self.settings.limits.strict_parsing && err.kind() == ErrorKind::InvalidData {
+            malformed
+        } else {
+            ReadError::ReadIoError(err)
         }
     }
 
     /// Reads a request from the stream.
     /// Blocks until the header has been read.
     fn read(&mut self) -> Result<Request, ReadError> {
-        let (method, path, version, headers) = {
-            // reading the request line
+        if self.proxy_header_pending {
+            self.proxy_header_pending = false;
+            let header =
+                read_proxy_header(&mut self.next_header_source).map_err(ReadError::ReadIoError)?;
+            self.apply_proxy_header(header);
+        }
+
+        let received_at;
+        let mut head = HeadRecorder::new(self.settings.limits.retain_raw_headers);
+        let (method, mut path, version, headers) = {
+            // reading the request line, after the empty lines allowed by RFC9112 #2.2
             let (method, path, version) = {
-                let line = self.read_next_line().map_err(ReadError::ReadIoError)?;
+                let empty_lines_max = self.settings.limits.leading_empty_lines_max.unwrap_or(4);
+                if let Some(ref idle) = self.idle {
+                    idle.set_waiting(true);
+                }
+                let mut empty_lines = 0;
+                let line = loop {
+                    let line = self.read_next_line(None);
+                    match line {
+                        Ok(ref line) if line.is_empty() && empty_lines < empty_lines_max => {
+                            empty_lines += 1
+                        }
+                        _ => break line,
+                    }
+                };
+                if let Some(ref idle) = self.idle {
+                    idle.set_waiting(false);
+                }
+                let line = line.map_err(|err| self.line_error(err, ReadError::WrongRequestLine))?;
+                received_at = self.settings.clock.now();
+                head.record(&line);
 
                 parse_request_line(
                     line.as_str().trim(), // TODO: remove this conversion
@@ -116,9 +562,13 @@ impl ClientConnection {
 
             // getting all headers
             let headers = {
-                let mut headers = Vec::new();
+                let mut headers: Vec<Header> = Vec::new();
                 loop {
-                    let line = self.read_next_line().map_err(ReadError::ReadIoError)?;
+                    let line_max = self.settings.limits.header_line_len;
+                    let line = self.read_next_line(line_max).map_err(|err| {
+                        self.line_error(err, ReadError::WrongHeader(version.clone()))
+                    })?;
+                    head.record(&line);
 
                     if line.is_empty() {
                         break;
@@ -135,17 +585,114 @@ impl ClientConnection {
 
             (method, path, version, headers)
         };
+        let headers_received_at = self.settings.clock.now();
+
+        if self.settings.limits.strict_parsing {
+            let hosts = headers.iter().filter(|h| h.field.equiv("Host")).count();
+            if hosts > 1 || (hosts == 0 && version >= (1, 1)) {
+                return Err(ReadError::WrongHeader(version));
+            }
+        }
+
+        // rewriting the absolute-form target of a proxy-style request (RFC9112 #3.2.2)
+        let mut raw_path = None;
+        if self.settings.normalize_absolute_form {
+            if let Some((authority, origin)) = crate::url::split_absolute_form(&path) {
+                let host = headers.iter().find(|h| h.field.equiv("Host"));
+                let host_mismatch =
+                    host.is_some_and(|h| !h.value.as_str().eq_ignore_ascii_case(authority));
+                if authority.contains('@') || host_mismatch {
+                    return Err(ReadError::WrongHeader(version));
+                }
+                raw_path = Some(std::mem::replace(&mut path, origin));
+            }
+        }
+
+        if self.secure && self.tls_info.is_none() {
+            self.tls_info = self
+                .socket
+                .as_ref()
+                .and_then(|socket| socket.tls_info())
+                .map(Arc::new);
+        }
 
         // building the writer for the request
         let writer = self.sink.next().unwrap();
 
+        // answering the HTTP versions that this server doesn't speak, the body of the request
+        // can't be found
+        if version > (1, 1) {
+            let response = match self.settings.error_renderer {
+                Some(ref renderer) => {
+                    renderer.render(StatusCode::HTTP_VERSION_NOT_SUPPORTED, &headers)
+                }
+                None => Response::from_string(
+                    "This server only supports HTTP versions 1.0 and 1.1".to_owned(),
+                )
+                .with_status_code(505)
+                .boxed(),
+            }
+            .with_connection_close();
+            self.settings
+                .internal_response(response)
+                .raw_print_with_clock(
+                    writer,
+                    HTTPVersion(1, 1),
+                    &[],
+                    false,
+                    None,
+                    &*self.settings.clock,
+                )
+                .ok();
+            self.error_count.fetch_add(1, Ordering::AcqRel);
+            return Err(ReadError::Rejected);
+        }
+
+        // trusting the proxies to tell whether the client used HTTPS
+        let secure = match self.settings.trusted_proxies {
+            Some(ref proxies) if self.trusted_proxy => {
+                proxies.forwarded_secure(&headers).unwrap_or(self.secure)
+            }
+            _ => self.secure,
+        };
+
+        // giving the pre-handler a chance to reject the request before its body is read
+        if let Some(ref pre_handler) = self.settings.pre_handler {
+            let head = RequestHead {
+                secure,
+                method: &method,
+                path: &path,
+                http_version: &version,
+                headers: &headers,
+                remote_addr: self.remote_addr.as_ref().unwrap().as_ref(),
+                clock: &*self.settings.clock,
+            };
+
+            if let PreDecision::Reject(status_code) = pre_handler.call(&head) {
+                let renderer = self.settings.error_renderer.as_ref();
+                let mut response =
+                    error_response(renderer, status_code, &headers).with_connection_close();
+                if let Some(retry_after) = self.settings.limits.retry_after {
+                    if status_code == 429 || status_code == 503 {
+                        response.add_header(retry_after);
+                    }
+                }
+                self.settings
+                    .internal_response(response)
+                    .raw_print_with_clock(writer, version, &[], false, None, &*self.settings.clock)
+                    .ok();
+                return Err(ReadError::Rejected);
+            }
+        }
+
         // follow-up for next potential request
         let mut data_source = self.source.next().unwrap();
         std::mem::swap(&mut self.next_header_source, &mut data_source);
 
         // building the next reader
+        self.requests_read += 1;
         let request = crate::request::new_request(
-            self.secure,
+            secure,
             method,
             path,
             version.clone(),
@@ -153,6 +700,8 @@ impl ClientConnection {
             *self.remote_addr.as_ref().unwrap(),
             data_source,
             writer,
+            &self.settings.limits,
+            &self.settings.body_budget,
         )
         .map_err(|e| {
             use crate::request;
@@ -161,8 +710,34 @@ impl ClientConnection {
                 request::RequestCreationError::ExpectationFailed => {
                     ReadError::ExpectationFailed(version)
                 }
+                request::RequestCreationError::InvalidContentLength
+                | request::RequestCreationError::InvalidTransferEncoding => {
+                    ReadError::WrongHeader(version)
+                }
             }
-        })?;
+        })?
+        .with_head(head.size, head.raw)
+        .with_clock(self.settings.clock.clone())
+        .with_write_buffer_size(self.settings.write_buffer_size)
+        .with_timing(received_at, headers_received_at)
+        .with_connection_id(self.id)
+        .with_connection_stats(self.requests_read, self.opened_at)
+        .with_socket(self.socket.clone())
+        .with_tls_info(self.tls_info.clone())
+        .with_error_count(self.error_count.clone())
+        .with_pipeline(self.pipeline.register())
+        .with_unanswered_policy(self.settings.unanswered.clone())
+        .with_error_renderer(self.settings.error_renderer.clone())
+        .with_response_decorator(self.settings.response_decorator.clone())
+        .with_body_transform(self.settings.body_transform.clone())
+        .with_auto_etag(self.settings.auto_etag)
+        .with_default_headers(self.settings.emit_default_headers)
+        .with_chunked_threshold(self.settings.chunked_threshold)
+        .with_raw_url(raw_path);
+        #[cfg(feature = "otel")]
+        let request = request.with_telemetry(self.settings.telemetry.as_ref());
+        #[cfg(feature = "status-page")]
+        let request = request.with_status_page(self.settings.status_page.clone());
 
         // return the request
         Ok(request)
@@ -175,7 +750,7 @@ impl Iterator for ClientConnection {
     /// Blocks until the next Request is available.
     /// Returns None when no new Requests will come from the client.
     fn next(&mut self) -> Option<Request> {
-        use crate::{Response, StatusCode};
+        use crate::Response;
 
         // the client sent a "connection: close" header in this previous request
         //  or is using HTTP 1.0, meaning that no new request will come
@@ -184,12 +759,26 @@ impl Iterator for ClientConnection {
         }
 
         loop {
+            // waiting for the handlers to catch up with a pipelining client
+            if let Some(max) = self.settings.limits.pipeline_max {
+                self.pipeline.wait_below(max.max(1));
+            }
+
             let rq = match self.read() {
                 Err(ReadError::WrongRequestLine) => {
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode(400));
-                    response
-                        .raw_print(writer, HTTPVersion(1, 1), &[], false, None)
+                    let renderer = self.settings.error_renderer.as_ref();
+                    let response = error_response(renderer, StatusCode::BAD_REQUEST, &[]);
+                    self.settings
+                        .internal_response(response)
+                        .raw_print_with_clock(
+                            writer,
+                            HTTPVersion(1, 1),
+                            &[],
+                            false,
+                            None,
+                            &*self.settings.clock,
+                        )
                         .ok();
                     return None; // we don't know where the next request would start,
                                  // se we have to close
@@ -197,45 +786,87 @@ impl Iterator for ClientConnection {
 
                 Err(ReadError::WrongHeader(ver)) => {
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode(400));
-                    response.raw_print(writer, ver, &[], false, None).ok();
+                    let renderer = self.settings.error_renderer.as_ref();
+                    let response = error_response(renderer, StatusCode::BAD_REQUEST, &[]);
+                    self.settings
+                        .internal_response(response)
+                        .raw_print_with_clock(writer, ver, &[], false, None, &*self.settings.clock)
+                        .ok();
                     return None; // we don't know where the next request would start,
                                  // se we have to close
                 }
 
-                Err(ReadError::ReadIoError(ref err)) if err.kind() == ErrorKind::TimedOut => {
+                Err(ReadError::HeaderTooLarge(ver)) => {
+                    let mut writer = self.sink.next().unwrap();
+                    let renderer = self.settings.error_renderer.as_ref();
+                    let status = StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE;
+                    let response = error_response(renderer, status, &[]).with_connection_close();
+                    self.settings
+                        .internal_response(response)
+                        .raw_print_with_clock(
+                            &mut writer,
+                            ver,
+                            &[],
+                            false,
+                            None,
+                            &*self.settings.clock,
+                        )
+                        .ok();
+                    writer.flush().ok();
+                    self.drain_head();
+                    return None; // the rest of the head is unknown, so we have to close
+                }
+
+                // blocking sockets report an expired read timeout as `WouldBlock` on some
+                // platforms
+                Err(ReadError::ReadIoError(ref err))
+                    if err.kind() == ErrorKind::TimedOut || err.kind() == ErrorKind::WouldBlock =>
+                {
                     // request timeout
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode(408));
-                    response
-                        .raw_print(writer, HTTPVersion(1, 1), &[], false, None)
+                    let renderer = self.settings.error_renderer.as_ref();
+                    let response = error_response(renderer, StatusCode::REQUEST_TIMEOUT, &[]);
+                    self.settings
+                        .internal_response(response)
+                        .raw_print_with_clock(
+                            writer,
+                            HTTPVersion(1, 1),
+                            &[],
+                            false,
+                            None,
+                            &*self.settings.clock,
+                        )
                         .ok();
                     return None; // closing the connection
                 }
 
                 Err(ReadError::ExpectationFailed(ver)) => {
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode(417));
-                    response.raw_print(writer, ver, &[], true, None).ok();
+                    let renderer = self.settings.error_renderer.as_ref();
+                    let response = error_response(renderer, StatusCode::EXPECTATION_FAILED, &[]);
+                    self.settings
+                        .internal_response(response)
+                        .raw_print_with_clock(writer, ver, &[], true, None, &*self.settings.clock)
+                        .ok();
                     return None; // TODO: should be recoverable, but needs handling in case of body
                 }
 
+                Err(ReadError::Rejected) => return None,
+
                 Err(ReadError::ReadIoError(_)) => return None,
 
                 Ok(rq) => rq,
             };
 
-            // checking HTTP version
-            if *rq.http_version() > (1, 1) {
-                let writer = self.sink.next().unwrap();
-                let response = Response::from_string(
-                    "This server only supports HTTP versions 1.0 and 1.1".to_owned(),
-                )
-                .with_status_code(StatusCode(505));
-                response
-                    .raw_print(writer, HTTPVersion(1, 1), &[], false, None)
-                    .ok();
-                continue;
+            // closing connections of clients which keep sending bad requests
+            if let Some(max) = self.settings.limits.connection_errors_max {
+                if self.error_count.load(Ordering::Acquire) >= max {
+                    let renderer = self.settings.error_renderer.as_ref();
+                    let response = error_response(renderer, StatusCode::BAD_REQUEST, rq.headers())
+                        .with_connection_close();
+                    rq.respond(response).ok();
+                    return None;
+                }
             }
 
             // updating the status of the connection
@@ -259,6 +890,50 @@ impl Iterator for ClientConnection {
                 _ => (),
             };
 
+            // retiring connections open for too long
+            let rq = if self.is_expired() {
+                self.no_more_requests = true;
+                rq.with_connection_close()
+            } else {
+                rq
+            };
+
+            // answering health probes without going through the queue
+            if self.is_health_check(&rq) {
+                let response = match self.settings.state.get() {
+                    ServerState::Ready => Response::from_string("ok"),
+                    state => {
+                        let mut response =
+                            Response::from_string(state.as_str()).with_status_code(503);
+                        if let Some(retry_after) = self.settings.limits.retry_after {
+                            response.add_header(retry_after);
+                        }
+                        response
+                    }
+                };
+                rq.respond(response).ok();
+
+                if self.no_more_requests {
+                    return None;
+                }
+                continue;
+            }
+
+            #[cfg(feature = "status-page")]
+            if let Some(status_page) = self.status_page_for(&rq) {
+                let response = status_page.render(
+                    self.settings.state.get(),
+                    self.settings.clock.now(),
+                    rq.headers(),
+                );
+                rq.respond(response).ok();
+
+                if self.no_more_requests {
+                    return None;
+                }
+                continue;
+            }
+
             // returning the request
             return Some(rq);
         }
@@ -0,0 +1,34 @@
+use std::fmt::Debug;
+use std::time::{Instant, SystemTime};
+
+/// Source of the current time used by the server.
+///
+/// The server reads the time for the `Date` header of responses and for all its timing
+/// measurements, such as [`Request::received_at`](crate::Request::received_at). Replacing the
+/// clock through [`ServerConfig::clock`](crate::ServerConfig::clock) makes these deterministic
+/// in tests.
+///
+/// Socket timeouts are handled by the operating system and don't use the clock.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current instant of a monotonic clock, see [`Instant::now`].
+    fn now(&self) -> Instant;
+
+    /// Returns the current wall-clock time, see [`SystemTime::now`].
+    fn system_time(&self) -> SystemTime;
+}
+
+/// The clock of the operating system, used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline]
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::util;
 use ascii::{AsciiStr, AsciiString, FromAsciiError};
 use std::cmp::Ordering;
 use std::fmt::{self, Display, Formatter};
@@ -7,78 +8,161 @@ use std::str::FromStr;
 #[derive(Eq, PartialEq, Copy, Clone, Debug, Ord, PartialOrd)]
 pub struct StatusCode(pub u16);
 
+// Generates the methods of `StatusCode` that depend on the table of standard status codes, so
+// that the status lines can be built at compile time.
+macro_rules! status_codes {
+    ($($code:literal $name:ident => $phrase:literal,)*) => {
+        impl StatusCode {
+            $(
+                #[doc = concat!("`", stringify!($code), " ", $phrase, "`")]
+                pub const $name: StatusCode = StatusCode($code);
+            )*
+
+            /// Returns the default reason phrase for this status code.
+            /// For example the status code 404 corresponds to "Not Found".
+            pub fn default_reason_phrase(&self) -> &'static str {
+                match self.0 {
+                    $($code => $phrase,)*
+                    _ => "Unknown",
+                }
+            }
+
+            /// Returns the status line with the default reason phrase, including the final
+            /// CRLF, for standard status codes and HTTP 1.0 and 1.1.
+            pub(crate) fn status_line(&self, http_version: &HTTPVersion) -> Option<&'static [u8]> {
+                match (http_version.0, http_version.1, self.0) {
+                    $(
+                        (1, 1, $code) => Some(
+                            concat!("HTTP/1.1 ", stringify!($code), " ", $phrase, "\r\n")
+                                .as_bytes(),
+                        ),
+                        (1, 0, $code) => Some(
+                            concat!("HTTP/1.0 ", stringify!($code), " ", $phrase, "\r\n")
+                                .as_bytes(),
+                        ),
+                    )*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    100 CONTINUE => "Continue",
+    101 SWITCHING_PROTOCOLS => "Switching Protocols",
+    102 PROCESSING => "Processing",
+    103 EARLY_HINTS => "Early Hints",
+
+    200 OK => "OK",
+    201 CREATED => "Created",
+    202 ACCEPTED => "Accepted",
+    203 NON_AUTHORITATIVE_INFORMATION => "Non-Authoritative Information",
+    204 NO_CONTENT => "No Content",
+    205 RESET_CONTENT => "Reset Content",
+    206 PARTIAL_CONTENT => "Partial Content",
+    207 MULTI_STATUS => "Multi-Status",
+    208 ALREADY_REPORTED => "Already Reported",
+    226 IM_USED => "IM Used",
+
+    300 MULTIPLE_CHOICES => "Multiple Choices",
+    301 MOVED_PERMANENTLY => "Moved Permanently",
+    302 FOUND => "Found",
+    303 SEE_OTHER => "See Other",
+    304 NOT_MODIFIED => "Not Modified",
+    305 USE_PROXY => "Use Proxy",
+    307 TEMPORARY_REDIRECT => "Temporary Redirect",
+    308 PERMANENT_REDIRECT => "Permanent Redirect",
+
+    400 BAD_REQUEST => "Bad Request",
+    401 UNAUTHORIZED => "Unauthorized",
+    402 PAYMENT_REQUIRED => "Payment Required",
+    403 FORBIDDEN => "Forbidden",
+    404 NOT_FOUND => "Not Found",
+    405 METHOD_NOT_ALLOWED => "Method Not Allowed",
+    406 NOT_ACCEPTABLE => "Not Acceptable",
+    407 PROXY_AUTHENTICATION_REQUIRED => "Proxy Authentication Required",
+    408 REQUEST_TIMEOUT => "Request Timeout",
+    409 CONFLICT => "Conflict",
+    410 GONE => "Gone",
+    411 LENGTH_REQUIRED => "Length Required",
+    412 PRECONDITION_FAILED => "Precondition Failed",
+    413 PAYLOAD_TOO_LARGE => "Payload Too Large",
+    414 URI_TOO_LONG => "URI Too Long",
+    415 UNSUPPORTED_MEDIA_TYPE => "Unsupported Media Type",
+    416 RANGE_NOT_SATISFIABLE => "Range Not Satisfiable",
+    417 EXPECTATION_FAILED => "Expectation Failed",
+    421 MISDIRECTED_REQUEST => "Misdirected Request",
+    422 UNPROCESSABLE_ENTITY => "Unprocessable Entity",
+    423 LOCKED => "Locked",
+    424 FAILED_DEPENDENCY => "Failed Dependency",
+    426 UPGRADE_REQUIRED => "Upgrade Required",
+    428 PRECONDITION_REQUIRED => "Precondition Required",
+    429 TOO_MANY_REQUESTS => "Too Many Requests",
+    431 REQUEST_HEADER_FIELDS_TOO_LARGE => "Request Header Fields Too Large",
+    451 UNAVAILABLE_FOR_LEGAL_REASONS => "Unavailable For Legal Reasons",
+
+    500 INTERNAL_SERVER_ERROR => "Internal Server Error",
+    501 NOT_IMPLEMENTED => "Not Implemented",
+    502 BAD_GATEWAY => "Bad Gateway",
+    503 SERVICE_UNAVAILABLE => "Service Unavailable",
+    504 GATEWAY_TIMEOUT => "Gateway Timeout",
+    505 HTTP_VERSION_NOT_SUPPORTED => "HTTP Version Not Supported",
+    506 VARIANT_ALSO_NEGOTIATES => "Variant Also Negotiates",
+    507 INSUFFICIENT_STORAGE => "Insufficient Storage",
+    508 LOOP_DETECTED => "Loop Detected",
+    510 NOT_EXTENDED => "Not Extended",
+    511 NETWORK_AUTHENTICATION_REQUIRED => "Network Authentication Required",
+}
+
 impl StatusCode {
-    /// Returns the default reason phrase for this status code.
-    /// For example the status code 404 corresponds to "Not Found".
-    pub fn default_reason_phrase(&self) -> &'static str {
-        match self.0 {
-            100 => "Continue",
-            101 => "Switching Protocols",
-            102 => "Processing",
-            103 => "Early Hints",
-
-            200 => "OK",
-            201 => "Created",
-            202 => "Accepted",
-            203 => "Non-Authoritative Information",
-            204 => "No Content",
-            205 => "Reset Content",
-            206 => "Partial Content",
-            207 => "Multi-Status",
-            208 => "Already Reported",
-            226 => "IM Used",
-
-            300 => "Multiple Choices",
-            301 => "Moved Permanently",
-            302 => "Found",
-            303 => "See Other",
-            304 => "Not Modified",
-            305 => "Use Proxy",
-            307 => "Temporary Redirect",
-            308 => "Permanent Redirect",
-
-            400 => "Bad Request",
-            401 => "Unauthorized",
-            402 => "Payment Required",
-            403 => "Forbidden",
-            404 => "Not Found",
-            405 => "Method Not Allowed",
-            406 => "Not Acceptable",
-            407 => "Proxy Authentication Required",
-            408 => "Request Timeout",
-            409 => "Conflict",
-            410 => "Gone",
-            411 => "Length Required",
-            412 => "Precondition Failed",
-            413 => "Payload Too Large",
-            414 => "URI Too Long",
-            415 => "Unsupported Media Type",
-            416 => "Range Not Satisfiable",
-            417 => "Expectation Failed",
-            421 => "Misdirected Request",
-            422 => "Unprocessable Entity",
-            423 => "Locked",
-            424 => "Failed Dependency",
-            426 => "Upgrade Required",
-            428 => "Precondition Required",
-            429 => "Too Many Requests",
-            431 => "Request Header Fields Too Large",
-            451 => "Unavailable For Legal Reasons",
-
-            500 => "Internal Server Error",
-            501 => "Not Implemented",
-            502 => "Bad Gateway",
-            503 => "Service Unavailable",
-            504 => "Gateway Timeout",
-            505 => "HTTP Version Not Supported",
-            506 => "Variant Also Negotiates",
-            507 => "Insufficient Storage",
-            508 => "Loop Detected",
-            510 => "Not Extended",
-            511 => "Network Authentication Required",
-            _ => "Unknown",
+    /// Returns the status code if it's in the range of valid codes, `100..=599`.
+    ///
+    /// Unlike the `From` conversions, this doesn't truncate or accept meaningless codes.
+    ///
+    /// ```
+    /// use tiny_http::StatusCode;
+    ///
+    /// assert_eq!(StatusCode::try_new(404), Some(StatusCode::NOT_FOUND));
+    /// assert_eq!(StatusCode::try_new(600), None);
+    /// ```
+    pub const fn try_new(code: u16) -> Option<StatusCode> {
+        if code >= 100 && code <= 599 {
+            Some(StatusCode(code))
+        } else {
+            None
         }
     }
+
+    /// Returns true for the `1xx` status codes.
+    #[inline]
+    pub const fn is_informational(&self) -> bool {
+        self.0 >= 100 && self.0 < 200
+    }
+
+    /// Returns true for the `2xx` status codes.
+    #[inline]
+    pub const fn is_success(&self) -> bool {
+        self.0 >= 200 && self.0 < 300
+    }
+
+    /// Returns true for the `3xx` status codes.
+    #[inline]
+    pub const fn is_redirection(&self) -> bool {
+        self.0 >= 300 && self.0 < 400
+    }
+
+    /// Returns true for the `4xx` status codes.
+    #[inline]
+    pub const fn is_client_error(&self) -> bool {
+        self.0 >= 400 && self.0 < 500
+    }
+
+    /// Returns true for the `5xx` status codes.
+    #[inline]
+    pub const fn is_server_error(&self) -> bool {
+        self.0 >= 500 && self.0 < 600
+    }
 }
 
 impl From<i8> for StatusCode {
@@ -176,6 +260,22 @@ impl Header {
             value,
         })
     }
+
+    /// Splits a value made of a comma-separated list into its elements, e.g. the directives of
+    /// `Cache-Control` or the elements of `Forwarded`.
+    ///
+    /// Commas inside quoted strings don't separate elements.
+    ///
+    /// ```
+    /// let header: tiny_http::Header = r#"Cache-Control: no-cache="Set-Cookie, Age", max-age=0"#
+    ///     .parse()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(header.split_list(), [r#"no-cache="Set-Cookie, Age""#, "max-age=0"]);
+    /// ```
+    pub fn split_list(&self) -> Vec<&str> {
+        util::split_header_list(self.value.as_str())
+    }
 }
 
 impl FromStr for Header {
@@ -200,6 +300,42 @@ impl Display for Header {
     }
 }
 
+/// Common values of the `Content-Type` header.
+///
+/// ```
+/// let header: tiny_http::Header = tiny_http::ContentType::TextHtmlUtf8.into();
+/// assert_eq!(header.to_string(), "Content-Type: text/html; charset=UTF-8");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentType {
+    /// `text/html; charset=UTF-8`
+    TextHtmlUtf8,
+    /// `text/plain; charset=UTF-8`
+    TextPlainUtf8,
+    /// `application/json`
+    ApplicationJson,
+    /// `application/octet-stream`
+    ApplicationOctetStream,
+}
+
+impl ContentType {
+    /// Returns the value of the header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentType::TextHtmlUtf8 => "text/html; charset=UTF-8",
+            ContentType::TextPlainUtf8 => "text/plain; charset=UTF-8",
+            ContentType::ApplicationJson => "application/json",
+            ContentType::ApplicationOctetStream => "application/octet-stream",
+        }
+    }
+}
+
+impl From<ContentType> for Header {
+    fn from(content_type: ContentType) -> Header {
+        Header::from_bytes(&b"Content-Type"[..], content_type.as_str()).unwrap()
+    }
+}
+
 /// Field of a header (eg. `Content-Type`, `Content-Length`, etc.)
 ///
 /// Comparison between two `HeaderField`s ignores case.
@@ -403,12 +539,43 @@ mod test {
     fn test_parse_header() {
         let header: Header = "Content-Type: text/html".parse().unwrap();
 
-        assert!(header.field.equiv(&"content-type"));
+        assert!(header.field.equiv("content-type"));
         assert!(header.value.as_str() == "text/html");
 
         assert!("hello world".parse::<Header>().is_err());
     }
 
+    #[test]
+    fn test_status_line() {
+        use super::{HTTPVersion, StatusCode};
+
+        assert_eq!(
+            StatusCode(404).status_line(&HTTPVersion(1, 1)),
+            Some(&b"HTTP/1.1 404 Not Found\r\n"[..])
+        );
+        assert_eq!(
+            StatusCode(200).status_line(&HTTPVersion(1, 0)),
+            Some(&b"HTTP/1.0 200 OK\r\n"[..])
+        );
+        assert_eq!(StatusCode(299).status_line(&HTTPVersion(1, 1)), None);
+        assert_eq!(StatusCode(200).status_line(&HTTPVersion(2, 0)), None);
+    }
+
+    #[test]
+    fn test_status_classes() {
+        use super::StatusCode;
+
+        assert_eq!(StatusCode::OK, StatusCode(200));
+        assert_eq!(StatusCode::try_new(99), None);
+        assert_eq!(StatusCode::try_new(599), Some(StatusCode(599)));
+        assert!(StatusCode::CONTINUE.is_informational());
+        assert!(StatusCode::NO_CONTENT.is_success());
+        assert!(StatusCode::SEE_OTHER.is_redirection());
+        assert!(StatusCode::NOT_FOUND.is_client_error());
+        assert!(StatusCode::BAD_GATEWAY.is_server_error());
+        assert!(!StatusCode(600).is_server_error());
+    }
+
     #[test]
     fn formats_date_correctly() {
         let http_date = HttpDate::from(SystemTime::UNIX_EPOCH + Duration::from_secs(420895020));
@@ -420,7 +587,7 @@ mod test {
     fn test_parse_header_with_doublecolon() {
         let header: Header = "Time: 20: 34".parse().unwrap();
 
-        assert!(header.field.equiv(&"time"));
+        assert!(header.field.equiv("time"));
         assert!(header.value.as_str() == "20: 34");
     }
 
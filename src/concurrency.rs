@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::io::{Read, Result as IoResult};
+use std::sync::{Arc, Mutex};
+
+use crate::common::StatusCode;
+use crate::{Request, Response, RetryAfter};
+
+/// Bound on the number of requests handled at the same time, overall or per route, protecting
+/// resources such as a pool of database connections.
+///
+/// [`ConcurrencyLimit::respond`] wraps the handler of a request: if the limit of the server or
+/// the limit of the [route](Request::route) of the request is reached, the request is answered
+/// at once with `503 Service Unavailable` and a `Retry-After` header, instead of waiting for a
+/// handler to finish.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::thread;
+/// use tiny_http::{ConcurrencyLimit, Response, Server};
+///
+/// let server = Server::http("0.0.0.0:0").unwrap();
+/// let limit = Arc::new(
+///     ConcurrencyLimit::new()
+///         .with_global_limit(64)
+///         .with_route_limit("/reports/:id", 4),
+/// );
+///
+/// for mut request in server.incoming_requests() {
+///     let limit = limit.clone();
+///     thread::spawn(move || {
+///         if request.url().starts_with("/reports/") {
+///             request.set_route("/reports/:id");
+///         }
+///         limit.respond(request, |_| Response::from_string("done")).unwrap();
+///     });
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ConcurrencyLimit {
+    global_limit: Option<usize>,
+    route_limits: HashMap<String, usize>,
+    retry_after: RetryAfter,
+    in_flight: Arc<Mutex<InFlight>>,
+}
+
+#[derive(Debug, Default)]
+struct InFlight {
+    total: usize,
+    per_route: HashMap<String, usize>,
+}
+
+/// Slot of a request counted by a [`ConcurrencyLimit`], given back when dropped.
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    in_flight: Arc<Mutex<InFlight>>,
+    route: Option<String>,
+}
+
+impl ConcurrencyLimit {
+    /// Builds a limit without any bound, until some are added.
+    pub fn new() -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            global_limit: None,
+            route_limits: HashMap::new(),
+            retry_after: RetryAfter::Seconds(1),
+            in_flight: Arc::new(Mutex::new(InFlight::default())),
+        }
+    }
+
+    /// Bounds the number of requests handled at the same time by all the routes together.
+    pub fn with_global_limit(mut self, limit: usize) -> ConcurrencyLimit {
+        self.global_limit = Some(limit);
+        self
+    }
+
+    /// Bounds the number of requests of the route `pattern` handled at the same time.
+    pub fn with_route_limit<S: Into<String>>(
+        mut self,
+        pattern: S,
+        limit: usize,
+    ) -> ConcurrencyLimit {
+        self.route_limits.insert(pattern.into(), limit);
+        self
+    }
+
+    /// Changes the `Retry-After` header of the rejected requests, 1 second by default.
+    pub fn with_retry_after(mut self, retry_after: RetryAfter) -> ConcurrencyLimit {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Counts a request of `route` as in flight until the returned permit is dropped, or returns
+    /// `None` if a limit is reached.
+    pub fn try_acquire(&self, route: Option<&str>) -> Option<ConcurrencyPermit> {
+        let route_limit = route.and_then(|route| self.route_limits.get(route));
+
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if self
+            .global_limit
+            .is_some_and(|limit| in_flight.total >= limit)
+        {
+            return None;
+        }
+        let route = match (route, route_limit) {
+            (Some(route), Some(&limit)) => {
+                let count = in_flight.per_route.get(route).copied().unwrap_or(0);
+                if count >= limit {
+                    return None;
+                }
+                in_flight.per_route.insert(route.to_owned(), count + 1);
+                Some(route.to_owned())
+            }
+            _ => None,
+        };
+        in_flight.total += 1;
+
+        Some(ConcurrencyPermit {
+            in_flight: self.in_flight.clone(),
+            route,
+        })
+    }
+
+    /// Returns the number of requests in flight, overall and for `route`.
+    pub fn in_flight(&self, route: &str) -> (usize, usize) {
+        let in_flight = self.in_flight.lock().unwrap();
+        let route_count = in_flight.per_route.get(route).copied().unwrap_or(0);
+        (in_flight.total, route_count)
+    }
+
+    /// Answers the request with the response of `handler` if a permit can be acquired for its
+    /// route, or with `503 Service Unavailable` otherwise.
+    pub fn respond<F, R>(&self, mut request: Request, handler: F) -> IoResult<()>
+    where
+        F: FnOnce(&mut Request) -> Response<R>,
+        R: Read,
+    {
+        match self.try_acquire(request.route()) {
+            Some(_permit) => {
+                let response = handler(&mut request);
+                request.respond(response)
+            }
+            None => {
+                let response =
+                    Response::empty(StatusCode::SERVICE_UNAVAILABLE).with_header(self.retry_after);
+                request.respond(response)
+            }
+        }
+    }
+}
+
+impl Default for ConcurrencyLimit {
+    fn default() -> ConcurrencyLimit {
+        ConcurrencyLimit::new()
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        in_flight.total -= 1;
+        if let Some(ref route) = self.route {
+            if let Some(count) = in_flight.per_route.get_mut(route) {
+                *count -= 1;
+                if *count == 0 {
+                    in_flight.per_route.remove(route);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConcurrencyLimit;
+
+    #[test]
+    fn global_and_route_limits() {
+        let limit = ConcurrencyLimit::new()
+            .with_global_limit(3)
+            .with_route_limit("/slow", 1);
+
+        let slow = limit.try_acquire(Some("/slow")).unwrap();
+        assert!(limit.try_acquire(Some("/slow")).is_none());
+        let other = limit.try_acquire(Some("/fast")).unwrap();
+        let unrouted = limit.try_acquire(None).unwrap();
+        assert_eq!(limit.in_flight("/slow"), (3, 1));
+        assert!(limit.try_acquire(Some("/fast")).is_none());
+
+        drop(slow);
+        assert_eq!(limit.in_flight("/slow"), (2, 0));
+        let slow = limit.try_acquire(Some("/slow")).unwrap();
+        assert!(limit.try_acquire(None).is_none());
+
+        drop((slow, other, unrouted));
+        assert_eq!(limit.in_flight("/slow"), (0, 0));
+    }
+}
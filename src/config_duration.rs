@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A duration of the configuration, like
+/// [`SocketConfig::read_timeout`](crate::SocketConfig::read_timeout), always written with its
+/// unit so that seconds can't be mistaken for milliseconds.
+///
+/// Built from a [`Duration`], with [`ConfigDuration::from_secs`] and
+/// [`ConfigDuration::from_millis`], or parsed from a string like `30s`, `5m`, `250ms` or
+/// `1h30m`, e.g. read from a configuration file. The units are `ms`, `s`, `m`, `h` and `d`.
+///
+/// [`ServerConfig::validate`](crate::ServerConfig::validate) checks that each duration of the
+/// configuration is within the bounds that make sense for its field, reporting for example a
+/// read timeout of 30 milliseconds that was meant to be 30 seconds.
+///
+/// ```
+/// use std::time::Duration;
+/// use tiny_http::ConfigDuration;
+///
+/// let timeout: ConfigDuration = "1m30s".parse().unwrap();
+/// assert_eq!(Duration::from(timeout), Duration::from_secs(90));
+/// assert_eq!(timeout.to_string(), "1m30s");
+///
+/// // the unit is required
+/// assert!("30".parse::<ConfigDuration>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConfigDuration(Duration);
+
+/// Units of the durations, from the largest, with their length in milliseconds.
+const UNITS: [(&str, u64); 5] = [
+    ("d", 24 * 60 * 60 * 1000),
+    ("h", 60 * 60 * 1000),
+    ("m", 60 * 1000),
+    ("s", 1000),
+    ("ms", 1),
+];
+
+impl ConfigDuration {
+    /// Builds a duration of `secs` seconds.
+    pub const fn from_secs(secs: u64) -> ConfigDuration {
+        ConfigDuration(Duration::from_secs(secs))
+    }
+
+    /// Builds a duration of `millis` milliseconds.
+    pub const fn from_millis(millis: u64) -> ConfigDuration {
+        ConfigDuration(Duration::from_millis(millis))
+    }
+
+    /// Returns the duration as a [`Duration`].
+    pub const fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl From<Duration> for ConfigDuration {
+    fn from(duration: Duration) -> ConfigDuration {
+        ConfigDuration(duration)
+    }
+}
+
+impl From<ConfigDuration> for Duration {
+    fn from(duration: ConfigDuration) -> Duration {
+        duration.0
+    }
+}
+
+impl FromStr for ConfigDuration {
+    type Err = ConfigDurationError;
+
+    fn from_str(s: &str) -> Result<ConfigDuration, ConfigDurationError> {
+        if s.is_empty() {
+            return Err(ConfigDurationError::Empty);
+        }
+
+        let mut millis: u64 = 0;
+        let mut rest = s;
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or(ConfigDurationError::MissingUnit)?;
+            if digits == 0 {
+                return Err(ConfigDurationError::InvalidNumber);
+            }
+            let value: u64 = rest[..digits]
+                .parse()
+                .map_err(|_| ConfigDurationError::Overflow)?;
+            rest = &rest[digits..];
+
+            let unit_len = rest
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let unit = UNITS
+                .iter()
+                .find(|(name, _)| *name == &rest[..unit_len])
+                .ok_or(ConfigDurationError::UnknownUnit)?;
+            rest = &rest[unit_len..];
+
+            millis = value
+                .checked_mul(unit.1)
+                .and_then(|value| millis.checked_add(value))
+                .ok_or(ConfigDurationError::Overflow)?;
+        }
+
+        Ok(ConfigDuration::from_millis(millis))
+    }
+}
+
+impl Display for ConfigDuration {
+    /// Writes the duration with the largest units first, e.g. `1h30m`, rounded down to the
+    /// millisecond.
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let mut millis = self
+            .0
+            .as_secs()
+            .saturating_mul(1000)
+            .saturating_add(u64::from(self.0.subsec_millis()));
+        if millis == 0 {
+            return formatter.write_str("0s");
+        }
+
+        for (name, length) in UNITS.iter() {
+            if millis >= *length {
+                write!(formatter, "{}{}", millis / length, name)?;
+                millis %= length;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error parsing a [`ConfigDuration`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigDurationError {
+    /// The string is empty.
+    Empty,
+    /// A unit isn't preceded by a number.
+    InvalidNumber,
+    /// A number isn't followed by a unit.
+    MissingUnit,
+    /// A unit isn't one of `ms`, `s`, `m`, `h` and `d`.
+    UnknownUnit,
+    /// The duration is too long.
+    Overflow,
+}
+
+impl Display for ConfigDurationError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            ConfigDurationError::Empty => "the duration is empty",
+            ConfigDurationError::InvalidNumber => "a unit isn't preceded by a number",
+            ConfigDurationError::MissingUnit => "a number isn't followed by a unit",
+            ConfigDurationError::UnknownUnit => "a unit isn't one of ms, s, m, h and d",
+            ConfigDurationError::Overflow => "the duration is too long",
+        };
+        formatter.write_str(msg)
+    }
+}
+
+impl Error for ConfigDurationError {}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfigDuration, ConfigDurationError};
+    use std::time::Duration;
+
+    #[test]
+    fn parse() {
+        let parse = |s: &str| s.parse::<ConfigDuration>().map(Duration::from);
+        assert_eq!(parse("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse("2d"), Ok(Duration::from_secs(172_800)));
+        assert_eq!(parse("0s"), Ok(Duration::from_secs(0)));
+
+        assert_eq!(parse(""), Err(ConfigDurationError::Empty));
+        assert_eq!(parse("30"), Err(ConfigDurationError::MissingUnit));
+        assert_eq!(parse("1m30"), Err(ConfigDurationError::MissingUnit));
+        assert_eq!(parse("s"), Err(ConfigDurationError::InvalidNumber));
+        assert_eq!(parse("1.5s"), Err(ConfigDurationError::UnknownUnit));
+        assert_eq!(parse("30 s"), Err(ConfigDurationError::UnknownUnit));
+        assert_eq!(parse("10y"), Err(ConfigDurationError::UnknownUnit));
+        assert_eq!(
+            parse("99999999999999999999s"),
+            Err(ConfigDurationError::Overflow)
+        );
+        assert_eq!(
+            parse("9999999999999999d"),
+            Err(ConfigDurationError::Overflow)
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(ConfigDuration::from_secs(0).to_string(), "0s");
+        assert_eq!(ConfigDuration::from_millis(250).to_string(), "250ms");
+        assert_eq!(ConfigDuration::from_secs(5400).to_string(), "1h30m");
+        assert_eq!(
+            ConfigDuration::from_millis(90_500).to_string(),
+            "1m30s500ms"
+        );
+        assert_eq!(
+            ConfigDuration::from(Duration::from_micros(1500)).to_string(),
+            "1ms"
+        );
+    }
+}
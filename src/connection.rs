@@ -1,24 +1,43 @@
 //! Abstractions of Tcp and Unix socket types
+//!
+//! The rest of the crate only reaches the sockets through [`Listener`], [`Connection`] and
+//! [`ListenAddr`], which keeps the parsing and the serializing of the messages independent of
+//! `std::net`.
 
+use crate::SocketConfig;
 #[cfg(unix)]
 use std::os::unix::net as unix_net;
 use std::{
+    fmt,
+    io::{ErrorKind, Read, Write},
     net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
     path::PathBuf,
+    sync::Arc,
+    time::Duration,
 };
+#[cfg(unix)]
+use std::{os::unix::fs::MetadataExt, path::Path};
 
-/// Unified listener. Either a [`TcpListener`] or [`std::os::unix::net::UnixListener`]
+/// Unified listener. Either a [`TcpListener`], a [`std::os::unix::net::UnixListener`] or a
+/// custom [`Acceptor`].
 pub enum Listener {
     Tcp(TcpListener),
     #[cfg(unix)]
     Unix(unix_net::UnixListener),
+    Custom(Arc<dyn Acceptor>),
 }
 impl Listener {
+    /// Wraps a custom acceptor, to be passed to [`Server::from_listener`](crate::Server::from_listener).
+    pub fn custom<A: Acceptor>(acceptor: A) -> Self {
+        Self::Custom(Arc::new(acceptor))
+    }
+
     pub(crate) fn local_addr(&self) -> std::io::Result<ListenAddr> {
         match self {
             Self::Tcp(l) => l.local_addr().map(ListenAddr::from),
             #[cfg(unix)]
             Self::Unix(l) => l.local_addr().map(ListenAddr::from),
+            Self::Custom(l) => l.local_addr(),
         }
     }
 
@@ -29,9 +48,187 @@ impl Listener {
                 .map(|(conn, addr)| (Connection::from(conn), Some(addr))),
             #[cfg(unix)]
             Self::Unix(l) => l.accept().map(|(conn, _)| (Connection::from(conn), None)),
+            Self::Custom(l) => l
+                .accept()
+                .map(|(conn, addr)| (Connection::Custom(conn, addr), addr)),
         }
     }
 }
+
+/// Source of the connections of a server, for transports other than TCP and Unix sockets, or to
+/// accept connections in an unusual way.
+///
+/// The server calls [`accept`](Acceptor::accept) in a loop from its accept thread, and
+/// [`unblock`](Acceptor::unblock) from another thread when it is dropped. An error returned by
+/// `accept` while the server is running stops the server.
+///
+/// ```no_run
+/// use std::net::{SocketAddr, TcpListener};
+/// use tiny_http::{AcceptedStream, Acceptor, ListenAddr, Listener, Server};
+///
+/// /// Accepts TCP connections from the loopback interface only.
+/// struct LocalOnly(TcpListener);
+///
+/// impl Acceptor for LocalOnly {
+///     fn accept(&self) -> std::io::Result<(Box<dyn AcceptedStream>, Option<SocketAddr>)> {
+///         loop {
+///             let (stream, addr) = self.0.accept()?;
+///             if addr.ip().is_loopback() {
+///                 return Ok((Box::new(stream), Some(addr)));
+///             }
+///         }
+///     }
+///
+///     fn local_addr(&self) -> std::io::Result<ListenAddr> {
+///         self.0.local_addr().map(ListenAddr::from)
+///     }
+/// }
+///
+/// let listener = TcpListener::bind("0.0.0.0:8000").unwrap();
+/// let server = Server::from_listener(Listener::custom(LocalOnly(listener)), None).unwrap();
+/// ```
+pub trait Acceptor: Send + Sync + 'static {
+    /// Accepts a new connection, with the address of the peer if it has one, waiting for it if
+    /// needed.
+    ///
+    /// The returned stream must be in blocking mode.
+    fn accept(&self) -> std::io::Result<(Box<dyn AcceptedStream>, Option<SocketAddr>)>;
+
+    /// Returns the address the acceptor listens to, reported by
+    /// [`Server::server_addr`](crate::Server::server_addr).
+    fn local_addr(&self) -> std::io::Result<ListenAddr>;
+
+    /// Wakes up the thread waiting in [`accept`](Acceptor::accept), which may then return a
+    /// connection or an error.
+    ///
+    /// The default connects to the address returned by [`local_addr`](Acceptor::local_addr),
+    /// which works for TCP and Unix sockets.
+    fn unblock(&self) -> std::io::Result<()> {
+        self.local_addr()?.connect().map(drop)
+    }
+}
+
+/// Connection returned by an [`Acceptor`].
+///
+/// Only reading, writing and cloning are required. The other methods default to no-ops, with
+/// which the timeouts of the [`SocketConfig`] aren't applied.
+pub trait AcceptedStream: Read + Write + Send + Sync + 'static {
+    /// Returns another handle to the same connection, used to write the responses while the
+    /// requests are read.
+    fn try_clone(&self) -> std::io::Result<Box<dyn AcceptedStream>>;
+
+    /// Shuts down the reading half, the writing half or both halves of the connection.
+    fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
+        let _ = how;
+        Ok(())
+    }
+
+    /// Sets the timeout of the reads, `None` waiting forever.
+    fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        let _ = dur;
+        Ok(())
+    }
+
+    /// Sets the timeout of the writes, `None` waiting forever.
+    fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        let _ = dur;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for dyn AcceptedStream {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("AcceptedStream")
+    }
+}
+
+impl Acceptor for TcpListener {
+    fn accept(&self) -> std::io::Result<(Box<dyn AcceptedStream>, Option<SocketAddr>)> {
+        let (stream, addr) = TcpListener::accept(self)?;
+        // inherited from the listener on some platforms
+        stream.set_nonblocking(false)?;
+        Ok((Box::new(stream), Some(addr)))
+    }
+
+    fn local_addr(&self) -> std::io::Result<ListenAddr> {
+        TcpListener::local_addr(self).map(ListenAddr::from)
+    }
+}
+
+impl AcceptedStream for TcpStream {
+    fn try_clone(&self) -> std::io::Result<Box<dyn AcceptedStream>> {
+        TcpStream::try_clone(self).map(|s| Box::new(s) as Box<dyn AcceptedStream>)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        TcpStream::set_read_timeout(self, dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        TcpStream::set_write_timeout(self, dur)
+    }
+}
+
+#[cfg(unix)]
+impl Acceptor for unix_net::UnixListener {
+    fn accept(&self) -> std::io::Result<(Box<dyn AcceptedStream>, Option<SocketAddr>)> {
+        let (stream, _) = unix_net::UnixListener::accept(self)?;
+        stream.set_nonblocking(false)?;
+        Ok((Box::new(stream), None))
+    }
+
+    fn local_addr(&self) -> std::io::Result<ListenAddr> {
+        unix_net::UnixListener::local_addr(self).map(ListenAddr::from)
+    }
+
+    fn unblock(&self) -> std::io::Result<()> {
+        unblock_unix(self, || true)
+    }
+}
+
+/// Wakes up the thread accepting the connections of a Unix listener.
+///
+/// Shutting the listener down does it on Linux, even if its socket file was bound again by
+/// another listener. Elsewhere, the listener is connected to through its socket file, if
+/// `is_owned` tells that the file is still the listener's.
+#[cfg(unix)]
+pub(crate) fn unblock_unix<F>(listener: &unix_net::UnixListener, is_owned: F) -> std::io::Result<()>
+where
+    F: FnOnce() -> bool,
+{
+    match socket2::SockRef::from(listener).shutdown(Shutdown::Both) {
+        Ok(()) => Ok(()),
+        Err(_) if is_owned() => listener
+            .local_addr()
+            .map(ListenAddr::from)?
+            .connect()
+            .map(drop),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(unix)]
+impl AcceptedStream for unix_net::UnixStream {
+    fn try_clone(&self) -> std::io::Result<Box<dyn AcceptedStream>> {
+        unix_net::UnixStream::try_clone(self).map(|s| Box::new(s) as Box<dyn AcceptedStream>)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
+        unix_net::UnixStream::shutdown(self, how)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        unix_net::UnixStream::set_read_timeout(self, dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        unix_net::UnixStream::set_write_timeout(self, dur)
+    }
+}
 impl From<TcpListener> for Listener {
     fn from(s: TcpListener) -> Self {
         Self::Tcp(s)
@@ -44,46 +241,57 @@ impl From<unix_net::UnixListener> for Listener {
     }
 }
 
-/// Unified connection. Either a [`TcpStream`] or [`std::os::unix::net::UnixStream`].
+/// Unified connection. Either a [`TcpStream`], a [`std::os::unix::net::UnixStream`] or the
+/// stream of a custom [`Acceptor`].
+///
+/// The sockets are shared by the clones of a connection rather than duplicated, so that the
+/// halves of a connection and the handles kept to shut it down don't each hold a descriptor.
 #[derive(Debug)]
 pub(crate) enum Connection {
-    Tcp(TcpStream),
+    Tcp(Arc<TcpStream>),
     #[cfg(unix)]
-    Unix(unix_net::UnixStream),
+    Unix(Arc<unix_net::UnixStream>),
+    // with the address of the peer returned by the acceptor
+    Custom(Box<dyn AcceptedStream>, Option<SocketAddr>),
 }
 impl std::io::Read for Connection {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
-            Self::Tcp(s) => s.read(buf),
+            Self::Tcp(s) => (&**s).read(buf),
             #[cfg(unix)]
-            Self::Unix(s) => s.read(buf),
+            Self::Unix(s) => (&**s).read(buf),
+            Self::Custom(s, _) => s.read(buf),
         }
     }
 }
 impl std::io::Write for Connection {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
-            Self::Tcp(s) => s.write(buf),
+            Self::Tcp(s) => (&**s).write(buf),
             #[cfg(unix)]
-            Self::Unix(s) => s.write(buf),
+            Self::Unix(s) => (&**s).write(buf),
+            Self::Custom(s, _) => s.write(buf),
         }
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
-            Self::Tcp(s) => s.flush(),
+            Self::Tcp(s) => (&**s).flush(),
             #[cfg(unix)]
-            Self::Unix(s) => s.flush(),
+            Self::Unix(s) => (&**s).flush(),
+            Self::Custom(s, _) => s.flush(),
         }
     }
 }
 impl Connection {
-    /// Gets the peer's address. Some for TCP, None for Unix sockets.
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
+    /// Gets the peer's address. Some for TCP, None for Unix sockets, and the address returned by
+    /// the acceptor for custom streams.
+    pub(crate) fn peer_addr(&self) -> std::io::Result<Option<SocketAddr>> {
         match self {
             Self::Tcp(s) => s.peer_addr().map(Some),
             #[cfg(unix)]
             Self::Unix(_) => Ok(None),
+            Self::Custom(_, addr) => Ok(*addr),
         }
     }
 
@@ -92,26 +300,218 @@ impl Connection {
             Self::Tcp(s) => s.shutdown(how),
             #[cfg(unix)]
             Self::Unix(s) => s.shutdown(how),
+            Self::Custom(s, _) => s.shutdown(how),
+        }
+    }
+
+    pub(crate) fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.set_read_timeout(dur),
+            #[cfg(unix)]
+            Self::Unix(s) => s.set_read_timeout(dur),
+            Self::Custom(s, _) => s.set_read_timeout(dur),
+        }
+    }
+
+    pub(crate) fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.set_write_timeout(dur),
+            #[cfg(unix)]
+            Self::Unix(s) => s.set_write_timeout(dur),
+            Self::Custom(s, _) => s.set_write_timeout(dur),
+        }
+    }
+
+    /// Sets `TCP_NODELAY`. This is a no-op for Unix sockets and custom streams.
+    pub(crate) fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.set_nodelay(nodelay),
+            #[cfg(unix)]
+            Self::Unix(_) => Ok(()),
+            Self::Custom(..) => Ok(()),
+        }
+    }
+
+    /// Returns false if the peer closed or reset the connection, without waiting. Data not read
+    /// yet counts as connected.
+    ///
+    /// The socket is peeked with `MSG_DONTWAIT`, which doesn't change its options, so that
+    /// concurrent reads and writes aren't disturbed. Custom streams, and any socket on other
+    /// platforms than Unix, can't be polled this way, and are always reported connected.
+    pub(crate) fn is_peer_connected(&self) -> bool {
+        match self {
+            #[cfg(unix)]
+            Self::Tcp(s) => peek_connected(socket2::SockRef::from(&**s)),
+            #[cfg(not(unix))]
+            Self::Tcp(_) => true,
+            #[cfg(unix)]
+            Self::Unix(s) => peek_connected(socket2::SockRef::from(&**s)),
+            Self::Custom(..) => true,
+        }
+    }
+
+    /// Applies the options of the [`SocketConfig`].
+    pub(crate) fn apply_config(&self, config: &SocketConfig) -> std::io::Result<()> {
+        self.set_read_timeout(config.read_timeout.map(Duration::from))?;
+        self.set_write_timeout(config.write_timeout.map(Duration::from))?;
+        if config.no_delay {
+            self.set_nodelay(true)?;
+        }
+        let keepalive = tcp_keepalive(config).map_err(|option| {
+            std::io::Error::new(
+                ErrorKind::Unsupported,
+                format!("`{}` can't be set on this platform", option),
+            )
+        })?;
+        if let Some(keepalive) = keepalive {
+            self.set_tcp_keepalive(&keepalive)?;
         }
+        Ok(())
     }
 
+    /// Enables TCP keep-alive probes. This is a no-op for Unix sockets and custom streams.
+    fn set_tcp_keepalive(&self, keepalive: &socket2::TcpKeepalive) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(s) => socket2::SockRef::from(&**s).set_tcp_keepalive(keepalive),
+            #[cfg(unix)]
+            Self::Unix(_) => Ok(()),
+            Self::Custom(..) => Ok(()),
+        }
+    }
+
+    /// Returns another handle to the connection, sharing the socket except for custom streams.
     pub(crate) fn try_clone(&self) -> std::io::Result<Self> {
         match self {
-            Self::Tcp(s) => s.try_clone().map(Self::from),
+            Self::Tcp(s) => Ok(Self::Tcp(s.clone())),
             #[cfg(unix)]
-            Self::Unix(s) => s.try_clone().map(Self::from),
+            Self::Unix(s) => Ok(Self::Unix(s.clone())),
+            Self::Custom(s, addr) => s.try_clone().map(|s| Self::Custom(s, *addr)),
         }
     }
 }
+
+/// Builds the TCP keep-alive parameters of the [`SocketConfig`], or `None` if it sets none.
+/// Fails with the name of the first option that can't be set on this platform.
+pub(crate) fn tcp_keepalive(
+    config: &SocketConfig,
+) -> Result<Option<socket2::TcpKeepalive>, &'static str> {
+    if config.tcp_keepalive_time.is_none()
+        && config.tcp_keepalive_interval.is_none()
+        && config.tcp_keepalive_retries.is_none()
+    {
+        return Ok(None);
+    }
+
+    let mut keepalive = socket2::TcpKeepalive::new();
+    if let Some(time) = config.tcp_keepalive_time {
+        // silently ignored by socket2 there
+        if cfg!(any(target_os = "openbsd", target_os = "haiku")) {
+            return Err("tcp_keepalive_time");
+        }
+        keepalive = keepalive.with_time(time.into());
+    }
+    if let Some(interval) = config.tcp_keepalive_interval {
+        #[cfg(any(
+            target_os = "android",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "illumos",
+            target_os = "ios",
+            target_os = "visionos",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "windows",
+            target_os = "cygwin",
+        ))]
+        {
+            keepalive = keepalive.with_interval(interval.into());
+        }
+        #[cfg(not(any(
+            target_os = "android",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "illumos",
+            target_os = "ios",
+            target_os = "visionos",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "windows",
+            target_os = "cygwin",
+        )))]
+        {
+            let _ = interval;
+            return Err("tcp_keepalive_interval");
+        }
+    }
+    if let Some(retries) = config.tcp_keepalive_retries {
+        #[cfg(any(
+            target_os = "android",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "illumos",
+            target_os = "ios",
+            target_os = "visionos",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "cygwin",
+        ))]
+        {
+            keepalive = keepalive.with_retries(retries);
+        }
+        #[cfg(not(any(
+            target_os = "android",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "illumos",
+            target_os = "ios",
+            target_os = "visionos",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "cygwin",
+        )))]
+        {
+            let _ = retries;
+            return Err("tcp_keepalive_retries");
+        }
+    }
+    Ok(Some(keepalive))
+}
+
+/// Peeks a byte of the socket without blocking, see [`Connection::is_peer_connected`].
+#[cfg(unix)]
+fn peek_connected(socket: socket2::SockRef<'_>) -> bool {
+    let mut buf = [std::mem::MaybeUninit::<u8>::uninit()];
+    match socket.recv_with_flags(&mut buf, libc::MSG_PEEK | libc::MSG_DONTWAIT) {
+        Ok(0) => false,
+        Ok(_) => true,
+        Err(err) => matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted),
+    }
+}
 impl From<TcpStream> for Connection {
     fn from(s: TcpStream) -> Self {
-        Self::Tcp(s)
+        Self::Tcp(Arc::new(s))
     }
 }
 #[cfg(unix)]
 impl From<unix_net::UnixStream> for Connection {
     fn from(s: unix_net::UnixStream) -> Self {
-        Self::Unix(s)
+        Self::Unix(Arc::new(s))
     }
 }
 
@@ -121,6 +521,12 @@ pub enum ConfigListenAddr {
     #[cfg(unix)]
     // TODO: use SocketAddr when bind_addr is stabilized
     Unix(std::path::PathBuf),
+    /// A VM socket, to serve the host from a virtual machine or the other way round.
+    #[cfg(all(feature = "vsock", target_os = "linux"))]
+    Vsock {
+        cid: u32,
+        port: u32,
+    },
 }
 impl ConfigListenAddr {
     pub fn from_socket_addrs<A: ToSocketAddrs>(addrs: A) -> std::io::Result<Self> {
@@ -132,21 +538,98 @@ impl ConfigListenAddr {
         Self::Unix(path.into())
     }
 
+    /// Listens to a port of a VM socket. `cid` is the context identifier the connections are
+    /// accepted on, usually [`VMADDR_CID_ANY`](ConfigListenAddr::VMADDR_CID_ANY).
+    #[cfg(all(feature = "vsock", target_os = "linux"))]
+    pub fn vsock(cid: u32, port: u32) -> Self {
+        Self::Vsock { cid, port }
+    }
+
+    /// Context identifier accepting the connections to any of the identifiers of the machine.
+    #[cfg(all(feature = "vsock", target_os = "linux"))]
+    pub const VMADDR_CID_ANY: u32 = u32::MAX;
+
     pub(crate) fn bind(&self) -> std::io::Result<Listener> {
         match self {
             Self::IP(a) => TcpListener::bind(a.as_slice()).map(Listener::from),
             #[cfg(unix)]
             Self::Unix(a) => unix_net::UnixListener::bind(a).map(Listener::from),
+            #[cfg(all(feature = "vsock", target_os = "linux"))]
+            Self::Vsock { cid, port } => VsockListener::bind(*cid, *port).map(Listener::custom),
+        }
+    }
+}
+
+/// Listener of a VM socket, accepted through the [`Acceptor`] interface.
+#[cfg(all(feature = "vsock", target_os = "linux"))]
+struct VsockListener(socket2::Socket);
+
+#[cfg(all(feature = "vsock", target_os = "linux"))]
+impl VsockListener {
+    fn bind(cid: u32, port: u32) -> std::io::Result<Self> {
+        use socket2::{Domain, SockAddr, Socket, Type};
+
+        let socket = Socket::new(Domain::VSOCK, Type::STREAM, None)?;
+        socket.bind(&SockAddr::vsock(cid, port))?;
+        socket.listen(128)?;
+        Ok(VsockListener(socket))
+    }
+}
+
+#[cfg(all(feature = "vsock", target_os = "linux"))]
+impl Acceptor for VsockListener {
+    fn accept(&self) -> std::io::Result<(Box<dyn AcceptedStream>, Option<SocketAddr>)> {
+        let (stream, _) = self.0.accept()?;
+        stream.set_nonblocking(false)?;
+        Ok((Box::new(stream), None))
+    }
+
+    fn local_addr(&self) -> std::io::Result<ListenAddr> {
+        let addr = self.0.local_addr()?;
+        match addr.as_vsock_address() {
+            Some((cid, port)) => Ok(ListenAddr::Vsock { cid, port }),
+            None => Err(ErrorKind::InvalidData.into()),
         }
     }
+
+    // accept fails with `EINVAL` once the listening socket is shut down
+    fn unblock(&self) -> std::io::Result<()> {
+        self.0.shutdown(Shutdown::Both)
+    }
 }
 
-/// Unified listen socket address. Either a [`SocketAddr`] or [`std::os::unix::net::SocketAddr`].
+#[cfg(all(feature = "vsock", target_os = "linux"))]
+impl AcceptedStream for socket2::Socket {
+    fn try_clone(&self) -> std::io::Result<Box<dyn AcceptedStream>> {
+        socket2::Socket::try_clone(self).map(|s| Box::new(s) as Box<dyn AcceptedStream>)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
+        socket2::Socket::shutdown(self, how)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        socket2::Socket::set_read_timeout(self, dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        socket2::Socket::set_write_timeout(self, dur)
+    }
+}
+
+/// Unified listen socket address. Either a [`SocketAddr`], a [`std::os::unix::net::SocketAddr`]
+/// or the address of a VM socket.
 #[derive(Debug, Clone)]
 pub enum ListenAddr {
     IP(SocketAddr),
     #[cfg(unix)]
     Unix(unix_net::SocketAddr),
+    /// Context identifier and port of a VM socket.
+    #[cfg(all(feature = "vsock", target_os = "linux"))]
+    Vsock {
+        cid: u32,
+        port: u32,
+    },
 }
 impl ListenAddr {
     pub fn to_ip(self) -> Option<SocketAddr> {
@@ -154,6 +637,8 @@ impl ListenAddr {
             Self::IP(s) => Some(s),
             #[cfg(unix)]
             Self::Unix(_) => None,
+            #[cfg(all(feature = "vsock", target_os = "linux"))]
+            Self::Vsock { .. } => None,
         }
     }
 
@@ -165,12 +650,83 @@ impl ListenAddr {
         match self {
             Self::IP(_) => None,
             Self::Unix(s) => Some(s),
+            #[cfg(all(feature = "vsock", target_os = "linux"))]
+            Self::Vsock { .. } => None,
         }
     }
     #[cfg(not(unix))]
     pub fn to_unix(self) -> Option<SocketAddr> {
         None
     }
+
+    /// Connects to the address, to wake up the thread accepting the connections of a listener.
+    pub(crate) fn connect(&self) -> std::io::Result<Connection> {
+        match self {
+            Self::IP(addr) => TcpStream::connect(addr).map(Connection::from),
+            #[cfg(unix)]
+            Self::Unix(addr) => {
+                // TODO: use connect_addr when its stabilized.
+                let path = addr.as_pathname().ok_or_else(|| {
+                    std::io::Error::new(ErrorKind::InvalidInput, "Unix socket without a path")
+                })?;
+                unix_net::UnixStream::connect(path).map(Connection::from)
+            }
+            // VM sockets are woken up by shutting their listener down
+            #[cfg(all(feature = "vsock", target_os = "linux"))]
+            Self::Vsock { .. } => Err(ErrorKind::Unsupported.into()),
+        }
+    }
+
+    /// Gets the path of the Unix socket, if it is bound to one.
+    ///
+    /// Returns `None` for unnamed and abstract Unix sockets, and for the other addresses.
+    #[cfg(unix)]
+    pub fn unix_path(&self) -> Option<&Path> {
+        match self {
+            Self::Unix(s) => s.as_pathname(),
+            _ => None,
+        }
+    }
+}
+
+/// Socket file of a Unix listener, removed when the server is dropped.
+///
+/// The device and inode of the file are recorded when the server starts, so that a path bound
+/// again by another listener in the meantime, e.g. by a restarted server, is left untouched.
+#[cfg(unix)]
+pub(crate) struct UnixSocketFile {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+}
+
+#[cfg(unix)]
+impl UnixSocketFile {
+    /// Records the socket file the listener is bound to, or `None` if it has no path.
+    pub(crate) fn of(addr: &ListenAddr) -> Option<Self> {
+        let path = addr.unix_path()?;
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        Some(UnixSocketFile {
+            path: path.to_path_buf(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        })
+    }
+
+    /// Returns `true` if the path still leads to the recorded socket file.
+    pub(crate) fn is_owned(&self) -> bool {
+        match std::fs::symlink_metadata(&self.path) {
+            Ok(metadata) => metadata.dev() == self.dev && metadata.ino() == self.ino,
+            Err(_) => false,
+        }
+    }
+
+    /// Removes the socket file, unless the path has been bound again by another listener.
+    pub(crate) fn remove(&self) {
+        if self.is_owned() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
 }
 impl From<SocketAddr> for ListenAddr {
     fn from(s: SocketAddr) -> Self {
@@ -189,6 +745,55 @@ impl std::fmt::Display for ListenAddr {
             Self::IP(s) => s.fmt(f),
             #[cfg(unix)]
             Self::Unix(s) => std::fmt::Debug::fmt(s, f),
+            #[cfg(all(feature = "vsock", target_os = "linux"))]
+            Self::Vsock { cid, port } => write!(f, "vsock:{}:{}", cid, port),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Connection;
+    use crate::{ConfigDuration, SocketConfig};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn clones_share_the_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let connection = Connection::from(stream);
+        match (&connection, &connection.try_clone().unwrap()) {
+            (Connection::Tcp(a), Connection::Tcp(b)) => assert!(Arc::ptr_eq(a, b)),
+            _ => unreachable!(),
         }
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn tcp_keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let connection = Connection::from(stream);
+        connection
+            .apply_config(&SocketConfig {
+                tcp_keepalive_time: Some(ConfigDuration::from_secs(60)),
+                tcp_keepalive_interval: Some(ConfigDuration::from_secs(5)),
+                tcp_keepalive_retries: Some(3),
+                ..SocketConfig::default()
+            })
+            .unwrap();
+
+        let socket = match &connection {
+            Connection::Tcp(s) => socket2::SockRef::from(&**s),
+            _ => unreachable!(),
+        };
+        assert!(socket.keepalive().unwrap());
+        assert_eq!(socket.keepalive_time().unwrap(), Duration::from_secs(60));
+        assert_eq!(socket.keepalive_interval().unwrap(), Duration::from_secs(5));
+        assert_eq!(socket.keepalive_retries().unwrap(), 3);
+    }
 }
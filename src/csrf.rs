@@ -0,0 +1,241 @@
+use std::fmt::Write as _;
+use std::io::Result as IoResult;
+
+use crate::common::{Header, Method, StatusCode};
+use crate::{PreDecision, PreHandler, Request, RequestHead};
+
+/// Name of the cookie holding the token, unless changed with [`Csrf::with_cookie_name`].
+pub const DEFAULT_CSRF_COOKIE: &str = "csrf_token";
+
+/// Name of the header holding the token, unless changed with [`Csrf::with_header_name`].
+pub const DEFAULT_CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Protection against cross-site request forgery using double-submit tokens.
+///
+/// The server gives the client a random token in a cookie. Requests with an unsafe method
+/// (anything but `GET`, `HEAD`, `OPTIONS` and `TRACE`) must send the same token again, either in
+/// a header or in a form field. Another site can make the browser send the cookie, but can't
+/// read it to submit it a second time.
+///
+/// Requests carrying the token in a header can be checked before their body is read, with
+/// [`Csrf::pre_handler`]. Forms are checked by the handler with [`Csrf::verify`] once the field
+/// has been extracted from the body.
+///
+/// ```
+/// # fn handle(request: tiny_http::Request) -> std::io::Result<()> {
+/// use tiny_http::{Csrf, Response};
+///
+/// let csrf = Csrf::new();
+/// let (token, set_cookie) = match csrf.token(&request) {
+///     Some(token) => (token.to_owned(), None),
+///     None => {
+///         let token = Csrf::generate_token()?;
+///         let set_cookie = csrf.cookie_header(&token);
+///         (token, Some(set_cookie))
+///     }
+/// };
+///
+/// let form = format!(r#"<input type="hidden" name="csrf_token" value="{}">"#, token);
+/// let mut response = Response::from_string(form);
+/// if let Some(set_cookie) = set_cookie {
+///     response.add_header(set_cookie);
+/// }
+/// request.respond(response)
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Csrf {
+    cookie_name: String,
+    header_name: String,
+    secure_cookie: bool,
+}
+
+impl Csrf {
+    /// Builds the protection with the default cookie and header names.
+    pub fn new() -> Csrf {
+        Csrf {
+            cookie_name: DEFAULT_CSRF_COOKIE.to_owned(),
+            header_name: DEFAULT_CSRF_HEADER.to_owned(),
+            secure_cookie: false,
+        }
+    }
+
+    /// Changes the name of the cookie holding the token.
+    pub fn with_cookie_name<S: Into<String>>(mut self, name: S) -> Csrf {
+        self.cookie_name = name.into();
+        self
+    }
+
+    /// Changes the name of the header holding the token in unsafe requests.
+    pub fn with_header_name<S: Into<String>>(mut self, name: S) -> Csrf {
+        self.header_name = name.into();
+        self
+    }
+
+    /// Adds the `Secure` attribute to the cookie, for sites only served over HTTPS.
+    pub fn with_secure_cookie(mut self, secure: bool) -> Csrf {
+        self.secure_cookie = secure;
+        self
+    }
+
+    /// Generates a new random token.
+    pub fn generate_token() -> IoResult<String> {
+        let mut bytes = [0; 32];
+        getrandom::getrandom(&mut bytes)?;
+
+        let mut token = String::with_capacity(bytes.len() * 2);
+        for byte in bytes.iter() {
+            write!(token, "{:02x}", byte).unwrap();
+        }
+        Ok(token)
+    }
+
+    /// Returns the `Set-Cookie` header giving the token to the client.
+    pub fn cookie_header(&self, token: &str) -> Header {
+        let mut value = format!("{}={}; Path=/; SameSite=Strict", self.cookie_name, token);
+        if self.secure_cookie {
+            value.push_str("; Secure");
+        }
+
+        Header::from_bytes(&b"Set-Cookie"[..], value.as_bytes()).unwrap()
+    }
+
+    /// Returns the token the client got in its cookie, if any.
+    pub fn token<'a>(&self, request: &'a Request) -> Option<&'a str> {
+        cookie_value(request.headers(), &self.cookie_name)
+    }
+
+    /// Returns true if the request is allowed: its method is safe, or the token of its cookie
+    /// was submitted again, either in the header or as `submitted` (e.g. a form field).
+    pub fn verify(&self, request: &Request, submitted: Option<&str>) -> bool {
+        self.verify_parts(request.method(), request.headers(), submitted)
+    }
+
+    /// Checks the token of the header, for use in a [`PreHandler`]. Rejected requests get a
+    /// `403 Forbidden`.
+    ///
+    /// As the body isn't available yet, forms must be checked with [`Csrf::verify`] instead.
+    pub fn check(&self, head: &RequestHead<'_>) -> PreDecision {
+        if self.verify_parts(head.method(), head.headers(), None) {
+            PreDecision::Accept
+        } else {
+            PreDecision::Reject(StatusCode::FORBIDDEN)
+        }
+    }
+
+    /// Builds a [`PreHandler`] calling [`Csrf::check`].
+    pub fn pre_handler(self) -> PreHandler {
+        PreHandler::new(move |head| self.check(head))
+    }
+
+    fn verify_parts<'a>(
+        &self,
+        method: &Method,
+        headers: &'a [Header],
+        submitted: Option<&'a str>,
+    ) -> bool {
+        match method {
+            Method::Get | Method::Head | Method::Options | Method::Trace => return true,
+            _ => (),
+        }
+
+        let expected = match cookie_value(headers, &self.cookie_name) {
+            Some(token) if !token.is_empty() => token,
+            _ => return false,
+        };
+
+        let submitted = submitted.or_else(|| {
+            headers
+                .iter()
+                .find(|h| {
+                    h.field
+                        .as_str()
+                        .as_str()
+                        .eq_ignore_ascii_case(&self.header_name)
+                })
+                .map(|h| h.value.as_str())
+        });
+
+        match submitted {
+            Some(submitted) => constant_time_eq(expected.as_bytes(), submitted.as_bytes()),
+            None => false,
+        }
+    }
+}
+
+impl Default for Csrf {
+    fn default() -> Csrf {
+        Csrf::new()
+    }
+}
+
+impl Request {
+    /// Returns the CSRF token of the request's cookie with the default name, for embedding it in
+    /// forms. See [`Csrf`].
+    pub fn csrf_token(&self) -> Option<&str> {
+        cookie_value(self.headers(), DEFAULT_CSRF_COOKIE)
+    }
+}
+
+/// Returns the value of a cookie of the `Cookie` headers.
+fn cookie_value<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .filter(|h| h.field.equiv("Cookie"))
+        .flat_map(|h| h.value.as_str().split(';'))
+        .filter_map(|cookie| {
+            let mut elems = cookie.trim().splitn(2, '=');
+            match (elems.next(), elems.next()) {
+                (Some(n), Some(v)) if n == name => Some(v.trim_matches('"')),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// Compares the tokens without leaking the position of the first difference through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::Csrf;
+    use crate::{Header, Method, TestRequest};
+
+    fn request(method: Method, headers: &[&str]) -> crate::Request {
+        let mut request = TestRequest::new().with_method(method);
+        for header in headers {
+            request = request.with_header(header.parse::<Header>().unwrap());
+        }
+        request.into()
+    }
+
+    #[test]
+    fn generate_token() {
+        let a = Csrf::generate_token().unwrap();
+        let b = Csrf::generate_token().unwrap();
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn verify() {
+        let csrf = Csrf::new();
+
+        assert!(csrf.verify(&request(Method::Get, &[]), None));
+        assert!(!csrf.verify(&request(Method::Post, &[]), None));
+
+        let cookie = "Cookie: theme=dark; csrf_token=abc";
+        let rq = request(Method::Post, &[cookie, "X-CSRF-Token: abc"]);
+        assert!(csrf.verify(&rq, None));
+        assert_eq!(rq.csrf_token(), Some("abc"));
+
+        let rq = request(Method::Post, &[cookie, "X-CSRF-Token: abd"]);
+        assert!(!csrf.verify(&rq, None));
+
+        let rq = request(Method::Delete, &[cookie]);
+        assert!(csrf.verify(&rq, Some("abc")));
+        assert!(!csrf.verify(&rq, Some("")));
+    }
+}
@@ -0,0 +1,178 @@
+use std::io::Read;
+use std::time::SystemTime;
+
+use httpdate::HttpDate;
+
+use crate::common::Header;
+use crate::Response;
+
+impl Header {
+    /// Builds a `Deprecation` header, telling that the resource is deprecated since `date`, or
+    /// simply deprecated if `None`.
+    ///
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    /// let header = tiny_http::Header::deprecation(Some(date));
+    /// assert_eq!(header.to_string(), "Deprecation: Tue, 14 Nov 2023 22:13:20 GMT");
+    /// ```
+    pub fn deprecation(date: Option<SystemTime>) -> Header {
+        let value = match date {
+            Some(date) => HttpDate::from(date).to_string(),
+            None => "true".to_owned(),
+        };
+        Header::from_bytes(&b"Deprecation"[..], value).unwrap()
+    }
+
+    /// Builds a `Sunset` header (RFC 8594), telling when the resource will stop responding.
+    pub fn sunset(date: SystemTime) -> Header {
+        Header::from_bytes(&b"Sunset"[..], HttpDate::from(date).to_string()).unwrap()
+    }
+
+    /// Builds a `Warning` header (RFC 7234), e.g. with the code `299` for a persistent warning.
+    ///
+    /// Quotes and backslashes of `text` are escaped. Returns an error if `agent` or `text`
+    /// contain non-ASCII characters or line breaks.
+    ///
+    /// ```
+    /// let header = tiny_http::Header::warning(299, "-", r#"use "/v2" instead"#).unwrap();
+    /// assert_eq!(header.to_string(), r#"Warning: 299 - "use \"/v2\" instead""#);
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn warning(code: u16, agent: &str, text: &str) -> Result<Header, ()> {
+        if agent.is_empty() || agent.contains(char::is_whitespace) || text.contains(['\r', '\n']) {
+            return Err(());
+        }
+
+        let text = text.replace('\\', "\\\\").replace('"', "\\\"");
+        let value = format!("{:03} {} \"{}\"", code, agent, text);
+        Header::from_bytes(&b"Warning"[..], value)
+    }
+}
+
+/// Marks parts of an API as deprecated, by adding `Deprecation`, `Sunset` and `Warning` headers
+/// to the responses for the URLs starting with configured prefixes.
+///
+/// When several prefixes match a URL, only the headers of the longest one are added.
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use tiny_http::{DeprecationPolicy, Response};
+///
+/// let sunset = SystemTime::UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+/// let policy = DeprecationPolicy::new()
+///     .deprecate("/v1/", None, Some(sunset))
+///     .with_warning("/v1/", "use /v2/ instead");
+///
+/// let response = policy.apply("/v1/users", Response::from_string("[]"));
+/// assert_eq!(response.headers().len(), 4);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DeprecationPolicy {
+    rules: Vec<(String, Vec<Header>)>,
+}
+
+impl DeprecationPolicy {
+    /// Builds a policy without any deprecated URL.
+    pub fn new() -> DeprecationPolicy {
+        DeprecationPolicy::default()
+    }
+
+    /// Deprecates the URLs starting with `prefix` since `deprecated_since` (or without date),
+    /// and announces their removal at `sunset` if any.
+    pub fn deprecate(
+        mut self,
+        prefix: &str,
+        deprecated_since: Option<SystemTime>,
+        sunset: Option<SystemTime>,
+    ) -> DeprecationPolicy {
+        let headers = self.rule(prefix);
+        headers.push(Header::deprecation(deprecated_since));
+        if let Some(sunset) = sunset {
+            headers.push(Header::sunset(sunset));
+        }
+        self
+    }
+
+    /// Adds a `299` warning with this text to the responses for the URLs starting with
+    /// `prefix`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the text contains non-ASCII characters or line breaks.
+    pub fn with_warning(mut self, prefix: &str, text: &str) -> DeprecationPolicy {
+        let warning = Header::warning(299, "-", text).expect("invalid warning text");
+        self.rule(prefix).push(warning);
+        self
+    }
+
+    /// Returns the headers to add to the response for this URL, the ones of the longest
+    /// matching prefix.
+    pub fn headers(&self, url: &str) -> &[Header] {
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, headers)| &headers[..])
+            .unwrap_or(&[])
+    }
+
+    /// Adds the headers for this URL to the response.
+    pub fn apply<R: Read>(&self, url: &str, mut response: Response<R>) -> Response<R> {
+        for header in self.headers(url).iter() {
+            response.add_header(header.clone());
+        }
+        response
+    }
+
+    fn rule(&mut self, prefix: &str) -> &mut Vec<Header> {
+        match self.rules.iter().position(|(p, _)| p == prefix) {
+            Some(index) => &mut self.rules[index].1,
+            None => {
+                self.rules.push((prefix.to_owned(), Vec::new()));
+                &mut self.rules.last_mut().unwrap().1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeprecationPolicy;
+    use crate::Header;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn warning() {
+        assert!(Header::warning(299, "-", "a\r\nb").is_err());
+        assert!(Header::warning(299, "", "a").is_err());
+        assert!(Header::warning(299, "-", "é").is_err());
+        assert_eq!(
+            Header::warning(199, "example.com", r"a\b")
+                .unwrap()
+                .to_string(),
+            r#"Warning: 199 example.com "a\\b""#
+        );
+    }
+
+    #[test]
+    fn policy() {
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let policy = DeprecationPolicy::new()
+            .deprecate("/v1/", Some(date), None)
+            .deprecate("/v1/legacy/", None, Some(date));
+
+        let headers: Vec<_> = policy
+            .headers("/v1/legacy/a")
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        assert_eq!(
+            headers,
+            ["Deprecation: true", "Sunset: Tue, 14 Nov 2023 22:13:20 GMT"]
+        );
+        assert_eq!(policy.headers("/v1/a").len(), 1);
+        assert!(policy.headers("/v2/a").is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt::Write;
+
+use crate::common::Header;
+
+impl Header {
+    /// Builds a `Content-Disposition` header telling the client to download the body as a file
+    /// named `filename`.
+    ///
+    /// Filenames that aren't printable ASCII are also sent in the `filename*` parameter, encoded
+    /// as described in RFC 5987, while `filename` gets an ASCII fallback with these characters
+    /// replaced by `_`.
+    ///
+    /// ```
+    /// let header = tiny_http::Header::attachment("résumé 2024.pdf");
+    /// assert_eq!(
+    ///     header.to_string(),
+    ///     "Content-Disposition: attachment; filename=\"r_sum_ 2024.pdf\"; \
+    ///      filename*=UTF-8''r%C3%A9sum%C3%A9%202024.pdf"
+    /// );
+    /// ```
+    pub fn attachment(filename: &str) -> Header {
+        disposition("attachment", filename)
+    }
+
+    /// Builds a `Content-Disposition` header telling the client to display the body, and to use
+    /// `filename` if it saves it, encoded as for [`Header::attachment`].
+    pub fn inline(filename: &str) -> Header {
+        disposition("inline", filename)
+    }
+}
+
+fn disposition(kind: &str, filename: &str) -> Header {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => c,
+            _ => '_',
+        })
+        .collect();
+    let fallback = fallback.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut value = format!("{}; filename=\"{}\"", kind, fallback);
+
+    if filename.chars().any(|c| !matches!(c, ' '..='~')) {
+        value.push_str("; filename*=UTF-8''");
+        for byte in filename.bytes() {
+            // attr-char of RFC 5987, sent as is
+            if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+                value.push(byte as char);
+            } else {
+                let _ = write!(value, "%{:02X}", byte);
+            }
+        }
+    }
+
+    Header::from_bytes(&b"Content-Disposition"[..], value).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Header;
+
+    #[test]
+    fn ascii_filenames() {
+        assert_eq!(
+            Header::attachment("report.csv").to_string(),
+            "Content-Disposition: attachment; filename=\"report.csv\""
+        );
+        assert_eq!(
+            Header::inline(r#"a "quoted" \name"#).to_string(),
+            r#"Content-Disposition: inline; filename="a \"quoted\" \\name""#
+        );
+    }
+
+    #[test]
+    fn encoded_filenames() {
+        assert_eq!(
+            Header::attachment("日本.txt").to_string(),
+            "Content-Disposition: attachment; filename=\"__.txt\"; \
+             filename*=UTF-8''%E6%97%A5%E6%9C%AC.txt"
+        );
+        // line breaks can't end the header
+        assert_eq!(
+            Header::attachment("a\r\nb").to_string(),
+            "Content-Disposition: attachment; filename=\"a__b\"; filename*=UTF-8''a%0D%0Ab"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use crate::common::{ContentType, Header, StatusCode};
+use crate::util;
+use crate::{Response, ResponseBox};
+
+/// Function building the responses the server sends by itself, given their status code and the
+/// headers of the request, which are empty if they couldn't be read.
+///
+/// Set in [`ServerConfig::error_renderer`](crate::ServerConfig::error_renderer), it is used for
+/// malformed requests (`400`), read timeouts (`408`), bodies over the limits (`413`), failed
+/// expectations (`417`), unsupported HTTP versions (`505`), requests rejected by the
+/// pre-handler, and requests answered according to their
+/// [`UnansweredPolicy`](crate::UnansweredPolicy).
+///
+/// ```
+/// use tiny_http::{ErrorFormat, ErrorRenderer};
+///
+/// // plain text errors for every client, whatever its `Accept` header
+/// let renderer = ErrorRenderer::new(|status, _| ErrorFormat::Text.render(status).boxed());
+///
+/// // or negotiated
+/// let renderer = ErrorRenderer::negotiated();
+/// ```
+#[derive(Clone)]
+pub struct ErrorRenderer(Arc<RenderFn>);
+
+type RenderFn = dyn Fn(StatusCode, &[Header]) -> ResponseBox + Send + Sync + 'static;
+
+impl ErrorRenderer {
+    /// Builds a renderer from a function.
+    pub fn new<F>(f: F) -> ErrorRenderer
+    where
+        F: Fn(StatusCode, &[Header]) -> ResponseBox + Send + Sync + 'static,
+    {
+        ErrorRenderer(Arc::new(f))
+    }
+
+    /// Builds a renderer choosing the format of the body from the `Accept` header of the
+    /// request, see [`ErrorFormat::negotiate`], so that API clients get `problem+json`
+    /// bodies and browsers get HTML pages.
+    pub fn negotiated() -> ErrorRenderer {
+        ErrorRenderer::new(|status, headers| ErrorFormat::negotiate(headers).render(status).boxed())
+    }
+
+    pub(crate) fn render(&self, status: StatusCode, request_headers: &[Header]) -> ResponseBox {
+        (self.0)(status, request_headers)
+    }
+}
+
+impl fmt::Debug for ErrorRenderer {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ErrorRenderer")
+    }
+}
+
+/// Format of the body of an error response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `text/plain`, e.g. `404 Not Found`.
+    Text,
+    /// A minimal `text/html` page.
+    Html,
+    /// `application/problem+json`, as described in RFC 9457.
+    Json,
+}
+
+impl ErrorFormat {
+    /// Chooses the format preferred by the `Accept` header among the `headers` of a request:
+    /// JSON for `application/json`, `application/problem+json` or `application/*`, HTML for
+    /// `text/html` or `application/xhtml+xml`, and plain text for `text/*`, `*/*`, unknown
+    /// types or without `Accept` header.
+    ///
+    /// ```
+    /// use tiny_http::{ErrorFormat, Header};
+    ///
+    /// let accept: Header = "Accept: text/html;q=0.8, application/json".parse().unwrap();
+    /// assert_eq!(ErrorFormat::negotiate(&[accept]), ErrorFormat::Json);
+    /// assert_eq!(ErrorFormat::negotiate(&[]), ErrorFormat::Text);
+    /// ```
+    pub fn negotiate(headers: &[Header]) -> ErrorFormat {
+        let accept = match headers.iter().find(|h| h.field.equiv("Accept")) {
+            Some(header) => header.value.as_str(),
+            None => return ErrorFormat::Text,
+        };
+
+        let mut ranges = util::parse_header_value(accept);
+        // stable, so that the first of the ranges of the same quality wins
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        ranges
+            .iter()
+            .filter(|(_, quality)| *quality > 0.0)
+            .find_map(|(range, _)| {
+                let range = range.to_ascii_lowercase();
+                match range.as_str() {
+                    "application/json" | "application/problem+json" | "application/*" => {
+                        Some(ErrorFormat::Json)
+                    }
+                    "text/html" | "application/xhtml+xml" => Some(ErrorFormat::Html),
+                    "text/plain" | "text/*" | "*/*" => Some(ErrorFormat::Text),
+                    _ => None,
+                }
+            })
+            .unwrap_or(ErrorFormat::Text)
+    }
+
+    /// Builds the response with this status code and a body in this format.
+    pub fn render(self, status: StatusCode) -> Response<Cursor<Vec<u8>>> {
+        let reason = status.default_reason_phrase();
+        let (body, content_type) = match self {
+            ErrorFormat::Text => (
+                format!("{} {}\n", status.0, reason),
+                ContentType::TextPlainUtf8.into(),
+            ),
+            ErrorFormat::Html => (
+                format!(
+                    "<!DOCTYPE html>\n<html><head><title>{0} {1}</title></head>\
+                     <body><h1>{0} {1}</h1></body></html>\n",
+                    status.0, reason
+                ),
+                ContentType::TextHtmlUtf8.into(),
+            ),
+            ErrorFormat::Json => (
+                format!(
+                    "{{\"type\":\"about:blank\",\"title\":\"{}\",\"status\":{}}}",
+                    reason, status.0
+                ),
+                Header::from_bytes(&b"Content-Type"[..], &b"application/problem+json"[..]).unwrap(),
+            ),
+        };
+
+        Response::from_data(body)
+            .with_status_code(status)
+            .with_header(content_type)
+    }
+}
+
+/// Returns the response the server sends by itself with this status code, empty without
+/// renderer.
+pub(crate) fn error_response(
+    renderer: Option<&ErrorRenderer>,
+    status: StatusCode,
+    request_headers: &[Header],
+) -> ResponseBox {
+    match renderer {
+        Some(renderer) => renderer.render(status, request_headers),
+        None => Response::empty(status).boxed(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ErrorFormat;
+    use crate::{Header, StatusCode};
+    use std::io::Read;
+
+    fn negotiate(accept: &str) -> ErrorFormat {
+        let header = Header::from_bytes(&b"Accept"[..], accept).unwrap();
+        ErrorFormat::negotiate(&[header])
+    }
+
+    #[test]
+    fn negotiation() {
+        assert_eq!(negotiate("application/json"), ErrorFormat::Json);
+        assert_eq!(negotiate("application/problem+json"), ErrorFormat::Json);
+        assert_eq!(
+            negotiate("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+            ErrorFormat::Html
+        );
+        assert_eq!(
+            negotiate("text/html;q=0.5, application/json"),
+            ErrorFormat::Json
+        );
+        assert_eq!(negotiate("application/json;q=0, */*"), ErrorFormat::Text);
+        assert_eq!(negotiate("image/png"), ErrorFormat::Text);
+        assert_eq!(negotiate("*/*"), ErrorFormat::Text);
+    }
+
+    #[test]
+    fn render() {
+        let body = |format: ErrorFormat| {
+            let response = format.render(StatusCode::NOT_FOUND);
+            assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+            let content_type = response.headers()[0].value.to_string();
+            let mut body = String::new();
+            response.into_reader().read_to_string(&mut body).unwrap();
+            (content_type, body)
+        };
+
+        assert_eq!(
+            body(ErrorFormat::Text),
+            (
+                "text/plain; charset=UTF-8".to_owned(),
+                "404 Not Found\n".to_owned()
+            )
+        );
+        assert_eq!(
+            body(ErrorFormat::Json),
+            (
+                "application/problem+json".to_owned(),
+                r#"{"type":"about:blank","title":"Not Found","status":404}"#.to_owned()
+            )
+        );
+        assert!(body(ErrorFormat::Html).1.contains("<h1>404 Not Found</h1>"));
+    }
+}
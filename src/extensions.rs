@@ -0,0 +1,93 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Values attached to a request while it is handled, at most one per type, e.g. the claims of
+/// a token checked by a middleware for the handler.
+///
+/// ```
+/// # let mut request: tiny_http::Request = tiny_http::TestRequest::new().into();
+/// struct UserId(u64);
+///
+/// request.extensions_mut().insert(UserId(42));
+/// assert_eq!(request.extensions().get::<UserId>().map(|id| id.0), Some(42));
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Builds an empty map.
+    pub fn new() -> Extensions {
+        Extensions::default()
+    }
+
+    /// Inserts a value, and returns the previous value of the same type if there was one.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns the value of type `T`, if there is one.
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of type `T`, if there is one.
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Removes the value of type `T` and returns it, if there was one.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if there is no value.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Extensions;
+
+    #[test]
+    fn one_value_per_type() {
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.insert(1u32), None);
+        assert_eq!(extensions.insert("name"), None);
+        assert_eq!(extensions.insert(2u32), Some(1));
+        assert_eq!(extensions.len(), 2);
+
+        *extensions.get_mut::<u32>().unwrap() += 1;
+        assert_eq!(extensions.get::<u32>(), Some(&3));
+        assert_eq!(extensions.remove::<&str>(), Some("name"));
+        assert_eq!(extensions.get::<&str>(), None);
+        assert_eq!(extensions.get::<u64>(), None);
+    }
+}
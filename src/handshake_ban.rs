@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Instant;
+
+use crate::ConfigDuration;
+
+/// Temporary ban of the addresses failing too many TLS handshakes, set in
+/// [`LimitsConfig::tls_handshake_failures`](crate::LimitsConfig::tls_handshake_failures).
+///
+/// Once an address has failed `failures` handshakes within `window`, the connections from it
+/// are closed right after being accepted, without attempting a handshake, for `duration`. This
+/// saves the CPU spent on scanners probing the server with invalid or unsupported handshakes.
+///
+/// IPv6 addresses are counted by their /64 prefix, which is usually what a single client can
+/// pick addresses from. At most 65536 addresses are tracked: once the limit is reached, the
+/// failures of addresses that aren't banned are forgotten.
+///
+/// ```
+/// use tiny_http::{ConfigDuration, HandshakeBan, LimitsConfig};
+///
+/// let limits = LimitsConfig {
+///     tls_handshake_failures: Some(HandshakeBan {
+///         failures: 10,
+///         window: ConfigDuration::from_secs(60),
+///         duration: ConfigDuration::from_secs(600),
+///     }),
+///     ..LimitsConfig::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeBan {
+    /// Number of failed handshakes that bans an address.
+    pub failures: usize,
+
+    /// Period in which the failures are counted, starting at the first one.
+    pub window: ConfigDuration,
+
+    /// How long an address stays banned.
+    pub duration: ConfigDuration,
+}
+
+/// Number of addresses tracked above which those with nothing left to track are forgotten.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Maximum number of addresses tracked.
+const MAX_ADDRS: usize = 65536;
+
+/// Failed handshakes and bans of the addresses connecting to a listener.
+pub(crate) struct HandshakeFailures {
+    config: HandshakeBan,
+    addrs: HashMap<IpAddr, Failures>,
+
+    // number of addresses tracked above which they are pruned, so that the addresses still
+    // tracked aren't all checked again on every failure
+    prune_at: usize,
+}
+
+struct Failures {
+    // start of the current window
+    since: Instant,
+
+    // number of failures in the current window
+    count: usize,
+
+    // `Some` once the address is banned, until `duration` has passed
+    banned_since: Option<Instant>,
+}
+
+impl HandshakeFailures {
+    pub(crate) fn new(config: HandshakeBan) -> HandshakeFailures {
+        HandshakeFailures {
+            config,
+            addrs: HashMap::new(),
+            prune_at: PRUNE_THRESHOLD,
+        }
+    }
+
+    /// Returns true if handshakes from this address are refused.
+    pub(crate) fn is_banned(&mut self, ip: &IpAddr, now: Instant) -> bool {
+        let key = key(*ip);
+        let banned_since = match self.addrs.get(&key) {
+            Some(failures) => failures.banned_since,
+            None => return false,
+        };
+
+        match banned_since {
+            Some(since)
+                if now.saturating_duration_since(since) < self.config.duration.as_duration() =>
+            {
+                true
+            }
+            Some(_) => {
+                self.addrs.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Counts a failed handshake, and returns true if the address is banned because of it.
+    #[cfg_attr(
+        not(any(
+            feature = "ssl-openssl",
+            feature = "ssl-rustls",
+            feature = "ssl-native-tls"
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn failed(&mut self, ip: IpAddr, now: Instant) -> bool {
+        let key = key(ip);
+        if self.addrs.len() >= self.prune_at && !self.addrs.contains_key(&key) {
+            self.prune(now);
+            if self.addrs.len() >= MAX_ADDRS {
+                self.addrs
+                    .retain(|_, failures| failures.banned_since.is_some());
+            }
+            if self.addrs.len() >= MAX_ADDRS {
+                // every address tracked is banned
+                return false;
+            }
+            self.prune_at = (self.addrs.len() * 2).clamp(PRUNE_THRESHOLD, MAX_ADDRS);
+        }
+
+        let config = self.config;
+        let failures = self.addrs.entry(key).or_insert(Failures {
+            since: now,
+            count: 0,
+            banned_since: None,
+        });
+        if now.saturating_duration_since(failures.since) >= config.window.as_duration() {
+            failures.since = now;
+            failures.count = 0;
+        }
+
+        failures.count += 1;
+        if failures.count >= config.failures {
+            failures.banned_since = Some(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forgets the addresses whose ban and window are over.
+    fn prune(&mut self, now: Instant) {
+        let window = self.config.window.as_duration();
+        let duration = self.config.duration.as_duration();
+        self.addrs
+            .retain(|_, failures| match failures.banned_since {
+                Some(since) => now.saturating_duration_since(since) < duration,
+                None => now.saturating_duration_since(failures.since) < window,
+            });
+    }
+}
+
+/// Address under which the failures of `ip` are counted: IPv6 addresses are truncated to their
+/// /64 prefix, except those mapping an IPv4 address.
+fn key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ip) => match ip.segments() {
+            [0, 0, 0, 0, 0, 0xffff, ..] => ip.to_ipv4().map_or(IpAddr::V6(ip), IpAddr::V4),
+            [a, b, c, d, ..] => IpAddr::V6(Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HandshakeBan, HandshakeFailures, MAX_ADDRS, PRUNE_THRESHOLD};
+    use crate::ConfigDuration;
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant};
+
+    fn failures() -> HandshakeFailures {
+        HandshakeFailures::new(HandshakeBan {
+            failures: 3,
+            window: ConfigDuration::from_secs(10),
+            duration: ConfigDuration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn ban_after_failures() {
+        let mut failures = failures();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(!failures.failed(ip, start));
+        assert!(!failures.failed(ip, start + Duration::from_secs(1)));
+        assert!(!failures.is_banned(&ip, start + Duration::from_secs(2)));
+        assert!(failures.failed(ip, start + Duration::from_secs(2)));
+        assert!(failures.is_banned(&ip, start + Duration::from_secs(3)));
+        assert!(!failures.is_banned(&other, start + Duration::from_secs(3)));
+
+        // lifted once the ban is over, with a new budget
+        assert!(failures.is_banned(&ip, start + Duration::from_secs(61)));
+        assert!(!failures.is_banned(&ip, start + Duration::from_secs(62)));
+        assert!(!failures.failed(ip, start + Duration::from_secs(62)));
+    }
+
+    #[test]
+    fn window() {
+        let mut failures = failures();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(!failures.failed(ip, start));
+        assert!(!failures.failed(ip, start + Duration::from_secs(5)));
+        // the first two failures are forgotten
+        assert!(!failures.failed(ip, start + Duration::from_secs(10)));
+        assert!(!failures.failed(ip, start + Duration::from_secs(11)));
+        assert!(failures.failed(ip, start + Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn prune() {
+        let mut failures = failures();
+        let banned: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            failures.failed(banned, start);
+        }
+        for i in 0..PRUNE_THRESHOLD as u32 - 1 {
+            failures.failed(IpAddr::from(((i + 1) << 8).to_be_bytes()), start);
+        }
+
+        failures.failed(
+            "192.168.0.1".parse().unwrap(),
+            start + Duration::from_secs(30),
+        );
+        assert_eq!(failures.addrs.len(), 2);
+        assert!(failures.is_banned(&banned, start + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn ipv6_prefix() {
+        let mut failures = failures();
+        let start = Instant::now();
+        for ip in &["2001:db8::1", "2001:db8::2", "2001:db8::ffff:1"] {
+            failures.failed(ip.parse().unwrap(), start);
+        }
+        assert!(failures.is_banned(&"2001:db8::3".parse().unwrap(), start));
+        assert!(!failures.is_banned(&"2001:db8:0:1::1".parse().unwrap(), start));
+
+        // mapped IPv4 addresses are counted apart
+        for _ in 0..2 {
+            failures.failed("::ffff:10.0.0.1".parse().unwrap(), start);
+        }
+        assert!(failures.failed("10.0.0.1".parse().unwrap(), start));
+        assert!(!failures.is_banned(&"::ffff:10.0.0.2".parse().unwrap(), start));
+    }
+
+    #[test]
+    fn endless_ban() {
+        let mut failures = HandshakeFailures::new(HandshakeBan {
+            failures: 1,
+            window: ConfigDuration::from_secs(10),
+            duration: ConfigDuration::from_secs(u64::MAX),
+        });
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+        assert!(failures.failed(ip, start));
+        assert!(failures.is_banned(&ip, start + Duration::from_secs(1_000_000)));
+    }
+
+    #[test]
+    fn bounded() {
+        let mut failures = failures();
+        let banned: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            failures.failed(banned, start);
+        }
+        for i in 0..MAX_ADDRS as u32 {
+            failures.failed(IpAddr::from(((i + 1) << 8).to_be_bytes()), start);
+            assert!(failures.addrs.len() <= MAX_ADDRS);
+        }
+        assert!(failures.is_banned(&banned, start));
+    }
+}
@@ -0,0 +1,778 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{self, Error as IoError, ErrorKind, Read, Result as IoResult};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use bytes::{Buf, Bytes};
+use h3::server::RequestStream;
+use quinn::crypto::rustls::QuicServerConfig;
+use quinn::rustls;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+use crate::client::next_connection_id;
+use crate::common::{HTTPVersion, Header, Method, StatusCode};
+use crate::error_renderer::{error_response, ErrorRenderer};
+use crate::log;
+#[cfg(feature = "otel")]
+use crate::otel::Telemetry;
+use crate::request::{new_request, RequestCreationError};
+use crate::response::ResponseStats;
+use crate::util::{ConnectionCounter, FrameWriter, MemoryBudget, MessagesQueue, TaskPool};
+use crate::{
+    BodyTransform, Clock, ConfigListenAddr, IpFilter, LimitsConfig, ListenAddr, ListenerHandle,
+    Message, PreDecision, PreHandler, RequestHead, Response, ResponseDecorator, Server,
+    ServerConfig, ServerState, SharedState, SslConfig, SystemClock, TlsInfo, UnansweredPolicy,
+};
+
+/// How often the accept loop checks whether the server was dropped.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pieces of body received ahead of the reader of the request.
+const BODY_CHANNEL_BOUND: usize = 4;
+
+type SendStream = RequestStream<h3_quinn::SendStream<Bytes>, Bytes>;
+
+/// State shared by the tasks of an HTTP/3 server.
+struct Context {
+    runtime: Arc<Runtime>,
+    close: Arc<AtomicBool>,
+    messages: Arc<MessagesQueue<Message>>,
+    ip_filter: Arc<RwLock<Option<IpFilter>>>,
+    tasks_pool: TaskPool,
+    limits: LimitsConfig,
+    body_budget: Arc<MemoryBudget>,
+    connections: Arc<ConnectionCounter>,
+    pre_handler: Option<PreHandler>,
+    clock: Arc<dyn Clock>,
+    unanswered: Option<UnansweredPolicy>,
+    error_renderer: Option<ErrorRenderer>,
+    response_decorator: Option<ResponseDecorator>,
+    body_transform: Option<BodyTransform>,
+    auto_etag: bool,
+    emit_default_headers: bool,
+    chunked_threshold: Option<usize>,
+    #[cfg(feature = "otel")]
+    telemetry: Option<Arc<Telemetry>>,
+}
+
+impl Server {
+    /// Builds an experimental HTTP/3 server, receiving QUIC connections on a UDP socket.
+    ///
+    /// Its requests are received and answered like the ones of the other servers. `config.ssl`
+    /// is required, as HTTP/3 always uses TLS 1.3, and only the first address of `config.addr`
+    /// is listened to. The limits, the IP filter, the pre-handler, the clock, the pool, the policy
+    /// for unanswered requests, the error renderer, the response decorator, the body transform,
+    /// the automatic `ETag`s, the default headers and the OpenTelemetry configuration apply; the
+    /// socket options, the pinning of threads, the health check, the status page and the
+    /// pipelining options don't, and neither does `SslConfig::rustls_config`.
+    ///
+    /// [`Request::upgrade`](crate::Request::upgrade),
+    /// [`Request::into_writer`](crate::Request::into_writer) and
+    /// [`Request::into_duplex`](crate::Request::into_duplex) aren't supported, and
+    /// [`Server::set_ssl_config`] fails. Clients usually discover an HTTP/3 server through the
+    /// `Alt-Svc` header of an HTTPS server listening to the same port, e.g. `Alt-Svc: h3=":443"`.
+    ///
+    /// Requires the `http3` feature, whose dependencies need a more recent compiler than
+    /// tiny-http itself.
+    pub fn http3(config: ServerConfig) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
+        let ServerConfig {
+            addr,
+            ssl,
+            limits,
+            ip_filter,
+            pre_handler,
+            clock,
+            pool,
+            unanswered,
+            error_renderer,
+            response_decorator,
+            body_transform,
+            auto_etag,
+            emit_default_headers,
+            chunked_threshold,
+            #[cfg(feature = "otel")]
+            otel,
+            ..
+        } = config;
+
+        let addr = match addr {
+            ConfigListenAddr::IP(addrs) => {
+                addrs.into_iter().next().ok_or("No address to listen to")?
+            }
+            #[cfg(unix)]
+            ConfigListenAddr::Unix(_) => return Err("HTTP/3 requires an IP address".into()),
+            #[cfg(all(feature = "vsock", target_os = "linux"))]
+            ConfigListenAddr::Vsock { .. } => return Err("HTTP/3 requires an IP address".into()),
+        };
+        let ssl = ssl.ok_or("HTTP/3 requires an SSL configuration")?;
+        let tls = QuicServerConfig::try_from(tls_config(&ssl)?)?;
+
+        let runtime = Arc::new(
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(2)
+                .thread_name("tiny-http-h3")
+                .enable_all()
+                .build()?,
+        );
+        let endpoint = {
+            let _guard = runtime.enter();
+            quinn::Endpoint::server(quinn::ServerConfig::with_crypto(Arc::new(tls)), addr)?
+        };
+        let local_addr = endpoint.local_addr()?;
+
+        let close = Arc::new(AtomicBool::new(false));
+        let messages = MessagesQueue::with_capacity(8);
+        let ip_filter = Arc::new(RwLock::new(ip_filter));
+        let connections = ConnectionCounter::new();
+        let context = Arc::new(Context {
+            runtime: runtime.clone(),
+            close: close.clone(),
+            messages: messages.clone(),
+            ip_filter: ip_filter.clone(),
+            tasks_pool: TaskPool::new(pool, None),
+            body_budget: MemoryBudget::new(limits.buffered_bodies_max.unwrap_or(usize::MAX)),
+            connections: connections.clone(),
+            limits,
+            pre_handler,
+            clock: clock.unwrap_or_else(|| Arc::new(SystemClock)),
+            unanswered,
+            error_renderer,
+            response_decorator,
+            body_transform,
+            auto_etag,
+            emit_default_headers,
+            chunked_threshold,
+            #[cfg(feature = "otel")]
+            telemetry: otel.map(|config| Arc::new(Telemetry::new(config))),
+        });
+
+        thread::Builder::new()
+            .name("tiny-http-h3-accept".to_owned())
+            .spawn(move || runtime.block_on(accept_loop(endpoint, context)))?;
+
+        Ok(Server {
+            close,
+            messages,
+            listeners: vec![ListenerHandle {
+                addr: ListenAddr::IP(local_addr),
+                waker: None,
+                #[cfg(unix)]
+                socket_file: None,
+            }],
+            ip_filter,
+            ssl: Arc::new(RwLock::new(None)),
+            pinning: None,
+            state: Arc::new(SharedState::new(ServerState::Ready)),
+            connections,
+        })
+    }
+}
+
+/// Builds the TLS 1.3 configuration of QUIC, negotiating the `h3` protocol.
+fn tls_config(
+    config: &SslConfig,
+) -> Result<rustls::ServerConfig, Box<dyn Error + Send + Sync + 'static>> {
+    use rustls::pki_types::{
+        CertificateDer, PrivateKeyDer, PrivatePkcs1KeyDer, PrivatePkcs8KeyDer,
+    };
+
+    let certificates: Vec<CertificateDer<'static>> =
+        rustls_pemfile::certs(&mut config.certificate.as_slice())?
+            .into_iter()
+            .map(CertificateDer::from)
+            .collect();
+    if certificates.is_empty() {
+        return Err("Couldn't extract certificate chain from config.".into());
+    }
+
+    let mut private_key = config.private_key.as_slice();
+    let private_key = match rustls_pemfile::pkcs8_private_keys(&mut private_key)?.pop() {
+        Some(key) => PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key)),
+        None => {
+            let mut private_key = config.private_key.as_slice();
+            match rustls_pemfile::rsa_private_keys(&mut private_key)?.pop() {
+                Some(key) => PrivateKeyDer::Pkcs1(PrivatePkcs1KeyDer::from(key)),
+                None => return Err("Couldn't extract private key from config.".into()),
+            }
+        }
+    };
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_no_client_auth()
+        .with_single_cert(certificates, private_key)?;
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+    Ok(tls)
+}
+
+async fn accept_loop(endpoint: quinn::Endpoint, context: Arc<Context>) {
+    while !context.close.load(Ordering::Relaxed) {
+        let incoming = match tokio::time::timeout(ACCEPT_POLL_INTERVAL, endpoint.accept()).await {
+            Ok(Some(incoming)) => incoming,
+            Ok(None) => break,
+            Err(_) => continue,
+        };
+
+        let remote_ip = incoming.remote_address().ip();
+        let allowed = match *context.ip_filter.read().unwrap() {
+            Some(ref filter) => filter.is_allowed(&remote_ip),
+            None => true,
+        };
+        if !allowed {
+            log::debug!("Refused QUIC connection from {}", remote_ip);
+            incoming.refuse();
+            continue;
+        }
+
+        tokio::spawn(handle_connection(incoming, context.clone()));
+    }
+
+    endpoint.close(0u32.into(), b"");
+}
+
+async fn handle_connection(incoming: quinn::Incoming, context: Arc<Context>) {
+    let connection = match incoming.await {
+        Ok(connection) => connection,
+        Err(err) => {
+            log::debug!("QUIC handshake failed: {}", err);
+            return;
+        }
+    };
+    let remote_addr = connection.remote_address();
+    let connection_id = next_connection_id();
+    let _open_guard = context.connections.open();
+
+    let mut connection = match h3::server::Connection::<_, Bytes>::new(h3_quinn::Connection::new(
+        connection,
+    ))
+    .await
+    {
+        Ok(connection) => connection,
+        Err(err) => {
+            log::debug!("HTTP/3 connection failed: {}", err);
+            return;
+        }
+    };
+
+    loop {
+        let resolver = match connection.accept().await {
+            Ok(Some(resolver)) => resolver,
+            Ok(None) => break,
+            Err(err) => {
+                log::debug!("HTTP/3 connection closed: {}", err);
+                break;
+            }
+        };
+        match resolver.resolve_request().await {
+            Ok((head, stream)) => dispatch(head, stream, remote_addr, connection_id, &context),
+            Err(err) => log::debug!("Invalid HTTP/3 request: {}", err),
+        }
+    }
+}
+
+/// Streams the body of the request in the background, and builds the request on the pool, as
+/// the body reader and the writer of the response block.
+fn dispatch(
+    head: http::Request<()>,
+    stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+    remote_addr: SocketAddr,
+    connection_id: u64,
+    context: &Arc<Context>,
+) {
+    let (send, mut recv) = stream.split();
+    let (sender, receiver) = mpsc::channel(BODY_CHANNEL_BOUND);
+
+    tokio::spawn(async move {
+        loop {
+            let data = match recv.recv_data().await {
+                Ok(Some(mut data)) => Ok(data.copy_to_bytes(data.remaining())),
+                Ok(None) => break,
+                Err(err) => Err(IoError::new(ErrorKind::ConnectionAborted, err.to_string())),
+            };
+            let failed = data.is_err();
+            if sender.send(data).await.is_err() {
+                // the request was answered without reading its whole body
+                recv.stop_sending(h3::error::Code::H3_NO_ERROR);
+                break;
+            }
+            if failed {
+                break;
+            }
+        }
+    });
+
+    let body = BodyReader {
+        receiver,
+        data: Bytes::new(),
+    };
+    let writer = H3FrameWriter {
+        runtime: context.runtime.clone(),
+        stream: send,
+    };
+
+    let mut task = Some((head.into_parts().0, body, writer));
+    let inside_context = context.clone();
+    context.tasks_pool.spawn(Box::new(move || {
+        if let Some((head, body, writer)) = task.take() {
+            handle_request(
+                head,
+                body,
+                writer,
+                remote_addr,
+                connection_id,
+                &inside_context,
+            );
+        }
+    }));
+}
+
+fn handle_request(
+    head: http::request::Parts,
+    body: BodyReader,
+    mut writer: H3FrameWriter,
+    remote_addr: SocketAddr,
+    connection_id: u64,
+    context: &Context,
+) {
+    let method: Method = head.method.as_str().parse().unwrap();
+    let path = head
+        .uri
+        .path_and_query()
+        .map_or("/", |path| path.as_str())
+        .to_owned();
+    let version = HTTPVersion(3, 0);
+
+    // the `:authority` pseudo-header replaces `Host`
+    let mut headers = Vec::with_capacity(head.headers.len() + 1);
+    if let Some(authority) = head.uri.authority() {
+        if !head.headers.contains_key(http::header::HOST) {
+            headers.push(Header::from_bytes(&b"Host"[..], authority.as_str()).unwrap());
+        }
+    }
+    for (name, value) in head.headers.iter() {
+        match Header::from_bytes(name.as_str(), value.as_bytes()) {
+            Ok(header) => headers.push(header),
+            Err(()) => {
+                let renderer = context.error_renderer.as_ref();
+                respond_error(
+                    &mut writer,
+                    error_response(renderer, StatusCode::BAD_REQUEST, &headers),
+                    context,
+                );
+                return;
+            }
+        }
+    }
+
+    if let Some(ref pre_handler) = context.pre_handler {
+        let request_head = RequestHead {
+            secure: true,
+            method: &method,
+            path: &path,
+            http_version: &version,
+            headers: &headers,
+            remote_addr: Some(&remote_addr),
+            clock: &*context.clock,
+        };
+
+        if let PreDecision::Reject(status_code) = pre_handler.call(&request_head) {
+            let renderer = context.error_renderer.as_ref();
+            let mut response = error_response(renderer, status_code, &headers);
+            if let Some(retry_after) = context.limits.retry_after {
+                if status_code == 429 || status_code == 503 {
+                    response.add_header(retry_after);
+                }
+            }
+            respond_error(&mut writer, response, context);
+            return;
+        }
+    }
+
+    // without `Content-Length`, the body lasts until the end of the stream
+    let has_length = headers
+        .iter()
+        .any(|header| header.field.equiv("Content-Length"));
+    // the headers are moved into the request, but the error renderer negotiates with them
+    let error_headers = context.error_renderer.as_ref().map(|_| headers.clone());
+    let error_headers = error_headers.as_deref().unwrap_or(&[]);
+    let create = |source: Box<dyn Read + Send>| {
+        new_request(
+            true,
+            method,
+            path,
+            version,
+            headers,
+            Some(remote_addr),
+            source,
+            io::sink(),
+            &context.limits,
+            &context.body_budget,
+        )
+    };
+    let request = if has_length {
+        create(Box::new(body))
+    } else {
+        create(Box::new(io::empty())).map(|request| {
+            request.with_data_reader(Box::new(body), context.limits.chunked_body_max_size)
+        })
+    };
+
+    let request = match request {
+        Ok(request) => request,
+        Err(RequestCreationError::ExpectationFailed) => {
+            let renderer = context.error_renderer.as_ref();
+            respond_error(
+                &mut writer,
+                error_response(renderer, StatusCode::EXPECTATION_FAILED, error_headers),
+                context,
+            );
+            return;
+        }
+        Err(RequestCreationError::InvalidContentLength)
+        | Err(RequestCreationError::InvalidTransferEncoding) => {
+            let renderer = context.error_renderer.as_ref();
+            respond_error(
+                &mut writer,
+                error_response(renderer, StatusCode::BAD_REQUEST, error_headers),
+                context,
+            );
+            return;
+        }
+        Err(RequestCreationError::CreationIoError(err)) => {
+            log::debug!("Error while reading an HTTP/3 request: {}", err);
+            return;
+        }
+    };
+
+    let tls_info = TlsInfo {
+        protocol_version: Some("TLSv1.3".to_owned()),
+        cipher_suite: None,
+    };
+    let request = request
+        .with_clock(context.clock.clone())
+        .with_connection_id(connection_id)
+        .with_tls_info(Some(Arc::new(tls_info)))
+        .with_frame_writer(Box::new(writer))
+        .with_unanswered_policy(context.unanswered.clone())
+        .with_error_renderer(context.error_renderer.clone())
+        .with_response_decorator(context.response_decorator.clone())
+        .with_body_transform(context.body_transform.clone())
+        .with_auto_etag(context.auto_etag)
+        .with_default_headers(context.emit_default_headers)
+        .with_chunked_threshold(context.chunked_threshold);
+    #[cfg(feature = "otel")]
+    let request = request.with_telemetry(context.telemetry.as_ref());
+    context.messages.push(request.into());
+}
+
+fn respond_error<'a, R: Read + 'a>(
+    writer: &mut H3FrameWriter,
+    response: Response<R>,
+    context: &Context,
+) {
+    let response = if context.emit_default_headers {
+        response
+    } else {
+        response.without_default_headers()
+    };
+    let response = match context.response_decorator {
+        Some(ref decorator) => response.decorated(decorator, None),
+        None => response,
+    };
+    let response = match context.body_transform {
+        Some(ref transform) => response.transformed(transform),
+        None => response.map_reader(|reader| Box::new(reader) as Box<dyn Read + 'a>),
+    };
+    response
+        .print_frames(
+            writer,
+            false,
+            &*context.clock,
+            0,
+            &mut ResponseStats::default(),
+        )
+        .ok();
+}
+
+/// Reads the body received by the background task of the request.
+struct BodyReader {
+    receiver: mpsc::Receiver<IoResult<Bytes>>,
+    // rest of the last piece received
+    data: Bytes,
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while !self.data.has_remaining() {
+            match self.receiver.blocking_recv() {
+                Some(Ok(data)) => self.data = data,
+                Some(Err(err)) => return Err(err),
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.data.len());
+        self.data.copy_to_slice(&mut buf[..len]);
+        Ok(len)
+    }
+}
+
+/// Sends the response as HTTP/3 frames, blocking on the runtime of the server.
+struct H3FrameWriter {
+    runtime: Arc<Runtime>,
+    stream: SendStream,
+}
+
+impl FrameWriter for H3FrameWriter {
+    fn write_head(&mut self, status_code: StatusCode, headers: &[Header]) -> IoResult<()> {
+        let mut response = http::Response::builder().status(status_code.0);
+        for header in headers {
+            response = response.header(header.field.as_str().as_str(), header.value.as_str());
+        }
+        let response = response
+            .body(())
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+
+        self.runtime
+            .block_on(self.stream.send_response(response))
+            .map_err(stream_error)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> IoResult<()> {
+        self.runtime
+            .block_on(self.stream.send_data(Bytes::copy_from_slice(data)))
+            .map_err(stream_error)
+    }
+
+    fn finish(&mut self) -> IoResult<()> {
+        self.runtime
+            .block_on(self.stream.finish())
+            .map_err(stream_error)
+    }
+}
+
+// the stream or the connection is gone, like a closed socket
+fn stream_error(err: h3::error::StreamError) -> IoError {
+    IoError::new(ErrorKind::BrokenPipe, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use std::future::Future;
+    use std::io::Read;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::thread;
+
+    use bytes::{Buf, Bytes};
+    use h3::client::SendRequest;
+    use h3_quinn::OpenStreams;
+    use quinn::crypto::rustls::QuicClientConfig;
+    use quinn::rustls;
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::crypto::CryptoProvider;
+    use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+    use rustls::{DigitallySignedStruct, SignatureScheme};
+    use tokio::sync::mpsc;
+
+    use super::{tls_config, BodyReader};
+    use crate::{
+        ConfigListenAddr, ErrorRenderer, LimitsConfig, Response, Server, ServerConfig, SslConfig,
+    };
+
+    /// Trusts the self-signed certificate of the examples.
+    #[derive(Debug)]
+    struct AnyCertificate(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for AnyCertificate {
+        fn verify_server_cert(
+            &self,
+            _: &CertificateDer<'_>,
+            _: &[CertificateDer<'_>],
+            _: &ServerName<'_>,
+            _: &[u8],
+            _: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            let algorithms = &self.0.signature_verification_algorithms;
+            rustls::crypto::verify_tls12_signature(message, cert, dss, algorithms)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            let algorithms = &self.0.signature_verification_algorithms;
+            rustls::crypto::verify_tls13_signature(message, cert, dss, algorithms)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    fn ssl_config() -> SslConfig {
+        SslConfig::new(
+            include_bytes!("../examples/ssl-cert.pem").to_vec(),
+            include_bytes!("../examples/ssl-key.pem").to_vec(),
+        )
+    }
+
+    #[test]
+    fn tls_config_negotiates_h3() {
+        let tls = tls_config(&ssl_config()).unwrap();
+        assert_eq!(tls.alpn_protocols, vec![b"h3".to_vec()]);
+
+        let config = SslConfig::new(Vec::new(), Vec::new());
+        assert!(tls_config(&config).is_err());
+    }
+
+    #[test]
+    fn body_reader() {
+        let (sender, receiver) = mpsc::channel(4);
+        sender.try_send(Ok(Bytes::from_static(b"hello "))).unwrap();
+        sender.try_send(Ok(Bytes::new())).unwrap();
+        sender.try_send(Ok(Bytes::from_static(b"world"))).unwrap();
+        drop(sender);
+
+        let mut reader = BodyReader {
+            receiver,
+            data: Bytes::new(),
+        };
+        let mut body = String::new();
+        reader.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "hello world");
+    }
+
+    fn server(limits: LimitsConfig, error_renderer: Option<ErrorRenderer>) -> Server {
+        Server::http3(ServerConfig {
+            ssl: Some(ssl_config()),
+            limits,
+            error_renderer,
+            ..ServerConfig::new(ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap())
+        })
+        .unwrap()
+    }
+
+    /// Runs `f` with an HTTP/3 client connected to `addr`.
+    fn with_client<F, T>(addr: SocketAddr, f: F)
+    where
+        F: FnOnce(SendRequest<OpenStreams, Bytes>) -> T,
+        T: Future<Output = ()>,
+    {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let mut tls = rustls::ClientConfig::builder_with_provider(provider.clone())
+                .with_protocol_versions(&[&rustls::version::TLS13])
+                .unwrap()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+                .with_no_client_auth();
+            tls.alpn_protocols = vec![b"h3".to_vec()];
+            let quic = QuicClientConfig::try_from(tls).unwrap();
+
+            let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+            endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(quic)));
+            let connection = endpoint.connect(addr, "localhost").unwrap().await.unwrap();
+            let (mut driver, send_request) = h3::client::new(h3_quinn::Connection::new(connection))
+                .await
+                .unwrap();
+            tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+            f(send_request).await
+        });
+    }
+
+    #[test]
+    fn body_without_length_is_limited() {
+        let limits = LimitsConfig {
+            chunked_body_max_size: Some(4),
+            ..LimitsConfig::default()
+        };
+        let server = server(limits, None);
+        let addr = server.server_addr().to_ip().unwrap();
+
+        thread::spawn(move || {
+            // echoes the body, or lets the request be answered by its drop
+            for mut request in server.incoming_requests() {
+                let mut body = Vec::new();
+                if request.as_reader().read_to_end(&mut body).is_ok() {
+                    request.respond(Response::from_data(body)).unwrap();
+                }
+            }
+        });
+
+        with_client(addr, |mut send_request| async move {
+            for (body, status, echoed) in [
+                (&b"abcd"[..], 200, &b"abcd"[..]),
+                (&b"hello world"[..], 413, &b""[..]),
+            ] {
+                let request = http::Request::post("https://localhost/").body(()).unwrap();
+                let mut stream = send_request.send_request(request).await.unwrap();
+                stream.send_data(Bytes::from_static(body)).await.unwrap();
+                stream.finish().await.unwrap();
+
+                let response = stream.recv_response().await.unwrap();
+                assert_eq!(response.status(), status);
+                if status == 200 {
+                    let mut data = Vec::new();
+                    while let Some(mut chunk) = stream.recv_data().await.unwrap() {
+                        data.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+                    }
+                    assert_eq!(data, echoed);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn errors_negotiated_with_the_request_headers() {
+        let server = server(LimitsConfig::default(), Some(ErrorRenderer::negotiated()));
+        let addr = server.server_addr().to_ip().unwrap();
+
+        with_client(addr, |mut send_request| async move {
+            for (name, value, status) in [("expect", "teapot", 417), ("content-length", "x", 400)] {
+                let request = http::Request::get("https://localhost/")
+                    .header("accept", "application/json")
+                    .header(name, value)
+                    .body(())
+                    .unwrap();
+                let mut stream = send_request.send_request(request).await.unwrap();
+                stream.finish().await.unwrap();
+
+                let response = stream.recv_response().await.unwrap();
+                assert_eq!(response.status(), status);
+                assert_eq!(
+                    response.headers()["content-type"],
+                    "application/problem+json"
+                );
+            }
+        });
+        drop(server);
+    }
+}
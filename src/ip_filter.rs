@@ -0,0 +1,193 @@
+use std::fmt::{self, Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+
+/// A range of IP addresses in CIDR notation, like `192.168.0.0/16` or `fe80::/10`.
+///
+/// An address without prefix length, like `10.1.2.3`, is a range containing only this address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// Builds a range from an address and a prefix length.
+    ///
+    /// Returns `None` if the prefix length is larger than the address (32 bits for IPv4,
+    /// 128 bits for IPv6).
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<IpCidr> {
+        if prefix_len > max_prefix_len(&addr) {
+            return None;
+        }
+
+        Some(IpCidr { addr, prefix_len })
+    }
+
+    /// Returns true if the address is part of the range.
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) are matched as IPv4 addresses.
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        match (self.addr, normalize(*addr)) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = mask(self.prefix_len, 32) as u32;
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = mask(self.prefix_len, 128);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<IpCidr, ()> {
+        let mut elems = s.trim().splitn(2, '/');
+
+        let addr: IpAddr = elems.next().ok_or(())?.parse().map_err(|_| ())?;
+        let prefix_len = match elems.next() {
+            Some(len) => len.parse().map_err(|_| ())?,
+            None => max_prefix_len(&addr),
+        };
+
+        IpCidr::new(addr, prefix_len).ok_or(())
+    }
+}
+
+impl Display for IpCidr {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(formatter, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Allow and deny lists of IP addresses, checked right after a connection has been accepted.
+///
+/// Connections from denied addresses are closed before anything is read from them, including
+/// the TLS handshake. An address is denied if it matches an entry of the deny list, or if the
+/// allow list isn't empty and the address doesn't match any of its entries.
+///
+/// Connections without IP address, like Unix sockets, are never filtered.
+///
+/// ```
+/// let filter = tiny_http::IpFilter::new()
+///     .allow("10.0.0.0/8".parse().unwrap())
+///     .deny("10.0.13.0/24".parse().unwrap());
+///
+/// assert!(filter.is_allowed(&"10.1.2.3".parse().unwrap()));
+/// assert!(!filter.is_allowed(&"10.0.13.37".parse().unwrap()));
+/// assert!(!filter.is_allowed(&"192.168.1.1".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<IpCidr>,
+    deny: Vec<IpCidr>,
+}
+
+impl IpFilter {
+    /// Builds a filter allowing every address.
+    pub fn new() -> IpFilter {
+        IpFilter::default()
+    }
+
+    /// Adds a range to the allow list.
+    pub fn allow(mut self, range: IpCidr) -> IpFilter {
+        self.allow.push(range);
+        self
+    }
+
+    /// Adds a range to the deny list.
+    pub fn deny(mut self, range: IpCidr) -> IpFilter {
+        self.deny.push(range);
+        self
+    }
+
+    /// Returns true if connections from this address are accepted.
+    pub fn is_allowed(&self, addr: &IpAddr) -> bool {
+        if self.deny.iter().any(|range| range.contains(addr)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|range| range.contains(addr))
+    }
+}
+
+fn max_prefix_len(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+// mask of the `prefix_len` upper bits of an address of `bits` bits
+fn mask(prefix_len: u8, bits: u32) -> u128 {
+    if prefix_len == 0 {
+        0
+    } else {
+        (!0u128 << (bits - u32::from(prefix_len))) & (!0u128 >> (128 - bits))
+    }
+}
+
+fn normalize(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, hi, lo] => {
+                IpAddr::V4(Ipv4Addr::from((u32::from(hi) << 16) | u32::from(lo)))
+            }
+            _ => IpAddr::V6(v6),
+        },
+        addr => addr,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IpCidr, IpFilter};
+    use std::net::IpAddr;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_cidr() {
+        assert!("10.0.0.0/8".parse::<IpCidr>().is_ok());
+        assert!("10.0.0.1".parse::<IpCidr>().is_ok());
+        assert!("::1/128".parse::<IpCidr>().is_ok());
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("10.0.0.0/".parse::<IpCidr>().is_err());
+        assert!("localhost/8".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let range: IpCidr = "192.168.0.0/16".parse().unwrap();
+        assert!(range.contains(&ip("192.168.42.1")));
+        assert!(range.contains(&ip("::ffff:192.168.42.1")));
+        assert!(!range.contains(&ip("192.169.0.1")));
+        assert!(!range.contains(&ip("::1")));
+
+        let range: IpCidr = "fe80::/10".parse().unwrap();
+        assert!(range.contains(&ip("fe80::1")));
+        assert!(!range.contains(&ip("fec0::1")));
+
+        let range: IpCidr = "0.0.0.0/0".parse().unwrap();
+        assert!(range.contains(&ip("8.8.8.8")));
+
+        let range: IpCidr = "127.0.0.1".parse().unwrap();
+        assert!(range.contains(&ip("127.0.0.1")));
+        assert!(!range.contains(&ip("127.0.0.2")));
+    }
+
+    #[test]
+    fn filter() {
+        assert!(IpFilter::new().is_allowed(&ip("1.2.3.4")));
+
+        let filter = IpFilter::new().deny("1.2.3.0/24".parse().unwrap());
+        assert!(!filter.is_allowed(&ip("1.2.3.4")));
+        assert!(filter.is_allowed(&ip("1.2.4.4")));
+    }
+}
@@ -0,0 +1,735 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Result as IoResult};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ring::{hmac, signature};
+
+use crate::common::{Header, StatusCode};
+use crate::{Request, Response};
+
+/// Validation of the JSON Web Tokens (RFC 7519) sent in `Authorization: Bearer` headers.
+///
+/// A token is accepted if it is signed with the configured key and algorithm, `HS256` or
+/// `RS256`, and if its `exp` and `nbf` claims, when present, include the current time give or
+/// take the leeway. The issuer and the audience are also checked if they have been configured.
+///
+/// The claims of an accepted token are added to the [extensions](Request::extensions) of the
+/// request, and rejected requests are answered with `401 Unauthorized` and a `WWW-Authenticate`
+/// header describing the error (RFC 6750).
+///
+/// ```no_run
+/// use tiny_http::{Claims, JwtAuth, Response, Server};
+///
+/// let auth = JwtAuth::hs256(b"secret").with_issuer("https://auth.example.com");
+///
+/// let server = Server::http("0.0.0.0:0").unwrap();
+/// for request in server.incoming_requests() {
+///     auth.respond(request, |request| {
+///         let claims = request.extensions().get::<Claims>().unwrap();
+///         Response::from_string(format!("hello {}", claims.subject().unwrap_or("you")))
+///     }).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct JwtAuth {
+    key: Key,
+    leeway: Duration,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Key {
+    Hs256(hmac::Key),
+    // DER-encoded `RSAPublicKey`
+    Rs256(Vec<u8>),
+}
+
+impl Key {
+    fn algorithm(&self) -> &'static str {
+        match self {
+            Key::Hs256(_) => "HS256",
+            Key::Rs256(_) => "RS256",
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Key::Hs256(key) => hmac::verify(key, message, signature).is_ok(),
+            Key::Rs256(public_key) => signature::UnparsedPublicKey::new(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                public_key,
+            )
+            .verify(message, signature)
+            .is_ok(),
+        }
+    }
+}
+
+/// Reason for rejecting a token, see [`JwtAuth::authorize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtError {
+    /// The request has no `Authorization: Bearer` header.
+    Missing,
+    /// The token isn't made of three base64url parts, its header or claims aren't JSON
+    /// objects, or its `exp` or `nbf` claim isn't a representable time.
+    Malformed,
+    /// The token isn't signed with the algorithm of the key.
+    UnsupportedAlgorithm,
+    /// The signature doesn't match.
+    InvalidSignature,
+    /// The `exp` claim is in the past.
+    Expired,
+    /// The `nbf` claim is in the future.
+    NotYetValid,
+    /// The `iss` claim isn't the expected issuer.
+    InvalidIssuer,
+    /// The `aud` claim doesn't include the expected audience.
+    InvalidAudience,
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JwtError::Missing => "missing bearer token",
+            JwtError::Malformed => "malformed token",
+            JwtError::UnsupportedAlgorithm => "unsupported signing algorithm",
+            JwtError::InvalidSignature => "invalid signature",
+            JwtError::Expired => "token expired",
+            JwtError::NotYetValid => "token not yet valid",
+            JwtError::InvalidIssuer => "invalid issuer",
+            JwtError::InvalidAudience => "invalid audience",
+        })
+    }
+}
+
+impl Error for JwtError {}
+
+impl JwtError {
+    /// Returns the `WWW-Authenticate` header sent with the `401 Unauthorized` response.
+    pub fn challenge(&self) -> Header {
+        let value = match self {
+            JwtError::Missing => "Bearer".to_owned(),
+            _ => format!(
+                "Bearer error=\"invalid_token\", error_description=\"{}\"",
+                self
+            ),
+        };
+        Header::from_bytes(&b"WWW-Authenticate"[..], value).unwrap()
+    }
+}
+
+impl JwtAuth {
+    /// Accepts the tokens signed with HMAC-SHA256 and `secret`.
+    pub fn hs256(secret: &[u8]) -> JwtAuth {
+        JwtAuth::new(Key::Hs256(hmac::Key::new(hmac::HMAC_SHA256, secret)))
+    }
+
+    /// Accepts the tokens signed with RSASSA-PKCS1-v1_5 and SHA-256, verified with the DER
+    /// encoding of an `RSAPublicKey` (PKCS#1) of at least 2048 bits.
+    pub fn rs256(public_key: &[u8]) -> JwtAuth {
+        JwtAuth::new(Key::Rs256(public_key.to_vec()))
+    }
+
+    fn new(key: Key) -> JwtAuth {
+        JwtAuth {
+            key,
+            leeway: Duration::from_secs(60),
+            issuer: None,
+            audience: None,
+        }
+    }
+
+    /// Changes the tolerance for the clock differences when checking `exp` and `nbf`,
+    /// 1 minute by default.
+    pub fn with_leeway(mut self, leeway: Duration) -> JwtAuth {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Only accepts the tokens whose `iss` claim is `issuer`.
+    pub fn with_issuer<S: Into<String>>(mut self, issuer: S) -> JwtAuth {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Only accepts the tokens whose `aud` claim is or includes `audience`.
+    pub fn with_audience<S: Into<String>>(mut self, audience: S) -> JwtAuth {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Validates the bearer token of the request, and adds its [`Claims`] to the extensions of
+    /// the request.
+    pub fn authorize(&self, request: &mut Request) -> Result<(), JwtError> {
+        let token = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .and_then(|h| bearer_token(h.value.as_str()))
+            .ok_or(JwtError::Missing)?;
+        let claims = self.validate(token, request.clock().system_time())?;
+        request.extensions_mut().insert(claims);
+        Ok(())
+    }
+
+    /// Answers the request with the response of `handler` if its token is valid, or with an
+    /// empty `401 Unauthorized` response.
+    pub fn respond<F, R>(&self, mut request: Request, handler: F) -> IoResult<()>
+    where
+        F: FnOnce(&mut Request) -> Response<R>,
+        R: Read,
+    {
+        match self.authorize(&mut request) {
+            Ok(()) => {
+                let response = handler(&mut request);
+                request.respond(response)
+            }
+            Err(err) => {
+                let response =
+                    Response::empty(StatusCode::UNAUTHORIZED).with_header(err.challenge());
+                request.respond(response)
+            }
+        }
+    }
+
+    /// Validates a token, and returns its claims.
+    pub fn validate(&self, token: &str, now: SystemTime) -> Result<Claims, JwtError> {
+        let mut parts = token.split('.');
+        let (header, payload, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(h), Some(p), Some(s)) if parts.next().is_none() => (h, p, s),
+            _ => return Err(JwtError::Malformed),
+        };
+
+        let header = decode_object(header)?;
+        match header.iter().find(|(name, _)| name == "alg") {
+            Some((_, ClaimValue::String(alg))) if alg == self.key.algorithm() => (),
+            _ => return Err(JwtError::UnsupportedAlgorithm),
+        }
+        let signature = base64url_decode(signature).ok_or(JwtError::Malformed)?;
+        let signed = &token[..header_len(token)];
+        if !self.key.verify(signed.as_bytes(), &signature) {
+            return Err(JwtError::InvalidSignature);
+        }
+
+        let claims = Claims(decode_object(payload)?);
+        // times beyond what `SystemTime` can represent reject the token rather than overflow
+        if let Some(expires_at) = claims.time("exp")? {
+            let expires_at = expires_at
+                .checked_add(self.leeway)
+                .ok_or(JwtError::Malformed)?;
+            if now > expires_at {
+                return Err(JwtError::Expired);
+            }
+        }
+        if let Some(not_before) = claims.time("nbf")? {
+            let latest = now.checked_add(self.leeway).ok_or(JwtError::Malformed)?;
+            if latest < not_before {
+                return Err(JwtError::NotYetValid);
+            }
+        }
+        if let Some(ref issuer) = self.issuer {
+            if claims.issuer() != Some(issuer.as_str()) {
+                return Err(JwtError::InvalidIssuer);
+            }
+        }
+        if let Some(ref audience) = self.audience {
+            let included = match claims.get("aud") {
+                Some(ClaimValue::String(aud)) => aud == audience,
+                Some(ClaimValue::Array(auds)) => auds
+                    .iter()
+                    .any(|aud| matches!(aud, ClaimValue::String(aud) if aud == audience)),
+                _ => false,
+            };
+            if !included {
+                return Err(JwtError::InvalidAudience);
+            }
+        }
+        Ok(claims)
+    }
+}
+
+/// Claims of a validated JSON Web Token, added to the [extensions](Request::extensions) of the
+/// request by [`JwtAuth`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims(Vec<(String, ClaimValue)>);
+
+/// Value of a claim, as decoded from JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClaimValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<ClaimValue>),
+    Object(Vec<(String, ClaimValue)>),
+}
+
+impl Claims {
+    /// Returns the value of the claim `name`, if present.
+    pub fn get(&self, name: &str) -> Option<&ClaimValue> {
+        self.0
+            .iter()
+            .find(|(claim, _)| claim == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the subject of the token, the `sub` claim.
+    pub fn subject(&self) -> Option<&str> {
+        self.string("sub")
+    }
+
+    /// Returns the issuer of the token, the `iss` claim.
+    pub fn issuer(&self) -> Option<&str> {
+        self.string("iss")
+    }
+
+    /// Returns the expiration time of the token, the `exp` claim.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.time("exp").ok().flatten()
+    }
+
+    /// Iterates over the names and values of all the claims.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ClaimValue)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(ClaimValue::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    // a NumericDate claim, seconds since the epoch
+    fn time(&self, name: &str) -> Result<Option<SystemTime>, JwtError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(ClaimValue::Number(secs)) if *secs >= 0.0 && secs.is_finite() => UNIX_EPOCH
+                .checked_add(Duration::from_secs(*secs as u64))
+                .map(Some)
+                .ok_or(JwtError::Malformed),
+            Some(_) => Err(JwtError::Malformed),
+        }
+    }
+}
+
+fn bearer_token(value: &str) -> Option<&str> {
+    let mut parts = value.trim().splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("Bearer") => Some(token.trim()),
+        _ => None,
+    }
+}
+
+// length of the signed part of the token, up to the second dot
+fn header_len(token: &str) -> usize {
+    token.rfind('.').unwrap_or(0)
+}
+
+fn decode_object(part: &str) -> Result<Vec<(String, ClaimValue)>, JwtError> {
+    let json = base64url_decode(part).ok_or(JwtError::Malformed)?;
+    let json = String::from_utf8(json).map_err(|_| JwtError::Malformed)?;
+    let mut parser = JsonParser {
+        input: json.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    match parser.value() {
+        Some(ClaimValue::Object(members)) if parser.at_end() => Ok(members),
+        _ => Err(JwtError::Malformed),
+    }
+}
+
+// accepts both the base64url alphabet and the standard one, with or without padding
+fn base64url_decode(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in input.bytes().take_while(|&b| b != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'-' | b'+' => 62,
+            b'_' | b'/' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Some(output)
+}
+
+// deepest nesting of arrays and objects accepted in a token
+const MAX_JSON_DEPTH: usize = 32;
+
+/// Parser of the JSON objects of a token, returning `None` on syntax errors.
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos == self.input.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, literal: &[u8]) -> Option<()> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn value(&mut self) -> Option<ClaimValue> {
+        match self.peek()? {
+            b'n' => self.expect(b"null").map(|_| ClaimValue::Null),
+            b't' => self.expect(b"true").map(|_| ClaimValue::Bool(true)),
+            b'f' => self.expect(b"false").map(|_| ClaimValue::Bool(false)),
+            b'"' => self.string().map(ClaimValue::String),
+            b'[' => self.nested(|parser| {
+                let mut items = Vec::new();
+                if parser.peek()? == b']' {
+                    parser.pos += 1;
+                    return Some(ClaimValue::Array(items));
+                }
+                loop {
+                    items.push(parser.value()?);
+                    match parser.peek()? {
+                        b',' => parser.pos += 1,
+                        b']' => {
+                            parser.pos += 1;
+                            return Some(ClaimValue::Array(items));
+                        }
+                        _ => return None,
+                    }
+                }
+            }),
+            b'{' => self.nested(|parser| {
+                let mut members = Vec::new();
+                if parser.peek()? == b'}' {
+                    parser.pos += 1;
+                    return Some(ClaimValue::Object(members));
+                }
+                loop {
+                    if parser.peek()? != b'"' {
+                        return None;
+                    }
+                    let name = parser.string()?;
+                    if parser.peek()? != b':' {
+                        return None;
+                    }
+                    parser.pos += 1;
+                    members.push((name, parser.value()?));
+                    match parser.peek()? {
+                        b',' => parser.pos += 1,
+                        b'}' => {
+                            parser.pos += 1;
+                            return Some(ClaimValue::Object(members));
+                        }
+                        _ => return None,
+                    }
+                }
+            }),
+            _ => self.number(),
+        }
+    }
+
+    // parses an array or an object, after its opening bracket
+    fn nested<F>(&mut self, f: F) -> Option<ClaimValue>
+    where
+        F: FnOnce(&mut Self) -> Option<ClaimValue>,
+    {
+        if self.depth == MAX_JSON_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        self.pos += 1;
+        let value = f(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn number(&mut self) -> Option<ClaimValue> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+        let number = std::str::from_utf8(&self.input[start..self.pos]).ok()?;
+        number.parse().ok().map(ClaimValue::Number)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(&byte) = self.input.get(self.pos) {
+                if byte == b'"' || byte == b'\\' || byte < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            string.push_str(std::str::from_utf8(&self.input[start..self.pos]).ok()?);
+
+            match *self.input.get(self.pos)? {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(string);
+                }
+                b'\\' => {
+                    let escaped = *self.input.get(self.pos + 1)?;
+                    self.pos += 2;
+                    string.push(match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return None,
+                    });
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    // the code point of a `\u` escape, combining surrogate pairs
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high);
+        }
+        self.expect(b"\\u")?;
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.input.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{base64url_decode, decode_object, ClaimValue, JwtAuth, JwtError};
+    use crate::{Claims, Header, Request, TestRequest};
+    use ring::signature::{self, KeyPair};
+    use ring::{hmac, rand};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn base64url(data: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let mut encoded = String::new();
+        for chunk in data.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+            for i in 0..=chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            }
+        }
+        encoded
+    }
+
+    fn encode(alg: &str, claims: &str, sign: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let header = format!(r#"{{"alg":"{}","typ":"JWT"}}"#, alg);
+        let signed = format!(
+            "{}.{}",
+            base64url(header.as_bytes()),
+            base64url(claims.as_bytes())
+        );
+        format!("{}.{}", signed, base64url(&sign(signed.as_bytes())))
+    }
+
+    fn hs256(claims: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        encode("HS256", claims, |data| {
+            hmac::sign(&key, data).as_ref().to_vec()
+        })
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn hs256_tokens() {
+        let auth = JwtAuth::hs256(b"secret")
+            .with_issuer("me")
+            .with_audience("api");
+        let claims = r#"{"sub":"alice","iss":"me","aud":["web","api"],"exp":2000,"nbf":1000}"#;
+        let token = hs256(claims);
+
+        let validated = auth.validate(&token, at(1500)).unwrap();
+        assert_eq!(validated.subject(), Some("alice"));
+        assert_eq!(validated.expires_at(), Some(at(2000)));
+        assert_eq!(auth.validate(&token, at(2100)), Err(JwtError::Expired));
+        assert_eq!(auth.validate(&token, at(900)), Err(JwtError::NotYetValid));
+        assert!(auth.validate(&token, at(2059)).is_ok());
+
+        let wrong_key = JwtAuth::hs256(b"other");
+        assert_eq!(
+            wrong_key.validate(&token, at(1500)),
+            Err(JwtError::InvalidSignature)
+        );
+        let tampered = token.replacen('.', ".e30.", 1);
+        assert_eq!(auth.validate(&tampered, at(1500)), Err(JwtError::Malformed));
+
+        let other_issuer = hs256(r#"{"iss":"you","aud":"api"}"#);
+        assert_eq!(
+            auth.validate(&other_issuer, at(0)),
+            Err(JwtError::InvalidIssuer)
+        );
+        let other_audience = hs256(r#"{"iss":"me","aud":"web"}"#);
+        assert_eq!(
+            auth.validate(&other_audience, at(0)),
+            Err(JwtError::InvalidAudience)
+        );
+
+        for claims in &[r#"{"exp":1e300}"#, r#"{"nbf":1e19}"#, r#"{"exp":-1}"#] {
+            assert_eq!(
+                auth.validate(&hs256(claims), at(0)),
+                Err(JwtError::Malformed)
+            );
+        }
+        let lenient = JwtAuth::hs256(b"secret").with_leeway(Duration::MAX);
+        assert_eq!(
+            lenient.validate(&hs256(r#"{"exp":2000}"#), at(0)),
+            Err(JwtError::Malformed)
+        );
+
+        let unsigned = encode("none", "{}", |_| Vec::new());
+        assert_eq!(
+            auth.validate(&unsigned, at(0)),
+            Err(JwtError::UnsupportedAlgorithm)
+        );
+    }
+
+    #[test]
+    fn rs256_tokens() {
+        let pem = include_str!("../examples/ssl-key.pem");
+        let der: String = pem
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        let key_pair = signature::RsaKeyPair::from_pkcs8(&base64url_decode(&der).unwrap()).unwrap();
+
+        let token = encode("RS256", r#"{"sub":"bob"}"#, |data| {
+            let mut signature = vec![0; key_pair.public().modulus_len()];
+            key_pair
+                .sign(
+                    &signature::RSA_PKCS1_SHA256,
+                    &rand::SystemRandom::new(),
+                    data,
+                    &mut signature,
+                )
+                .unwrap();
+            signature
+        });
+
+        let auth = JwtAuth::rs256(key_pair.public_key().as_ref());
+        let claims = auth.validate(&token, SystemTime::now()).unwrap();
+        assert_eq!(claims.subject(), Some("bob"));
+
+        // an HS256 token signed with the public key isn't accepted in its place
+        let public_key = key_pair.public_key().as_ref().to_vec();
+        let forged = token_with_secret(&public_key);
+        assert_eq!(
+            auth.validate(&forged, SystemTime::now()),
+            Err(JwtError::UnsupportedAlgorithm)
+        );
+    }
+
+    fn token_with_secret(secret: &[u8]) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        encode("HS256", r#"{"sub":"mallory"}"#, |data| {
+            hmac::sign(&key, data).as_ref().to_vec()
+        })
+    }
+
+    #[test]
+    fn claims_in_extensions() {
+        let auth = JwtAuth::hs256(b"secret");
+        let bearer = format!("Bearer {}", hs256(r#"{"sub":"alice","admin":true}"#));
+        let mut request: Request = TestRequest::new()
+            .with_header(Header::from_bytes(&b"Authorization"[..], bearer).unwrap())
+            .into();
+        auth.authorize(&mut request).unwrap();
+
+        let claims = request.extensions().get::<Claims>().unwrap();
+        assert_eq!(claims.get("admin"), Some(&ClaimValue::Bool(true)));
+
+        let mut request: Request = TestRequest::new().into();
+        let err = auth.authorize(&mut request).unwrap_err();
+        assert_eq!(err, JwtError::Missing);
+        assert_eq!(err.challenge().to_string(), "WWW-Authenticate: Bearer");
+        assert_eq!(
+            JwtError::Expired.challenge().to_string(),
+            r#"WWW-Authenticate: Bearer error="invalid_token", error_description="token expired""#
+        );
+    }
+
+    #[test]
+    fn json_objects() {
+        let object = |json: &str| decode_object(&base64url(json.as_bytes()));
+
+        assert_eq!(
+            object(r#" {"a": [1, -2.5e1, null], "b": {"c": "é😀\n"}} "#),
+            Ok(vec![
+                (
+                    "a".to_owned(),
+                    ClaimValue::Array(vec![
+                        ClaimValue::Number(1.0),
+                        ClaimValue::Number(-25.0),
+                        ClaimValue::Null
+                    ])
+                ),
+                (
+                    "b".to_owned(),
+                    ClaimValue::Object(vec![(
+                        "c".to_owned(),
+                        ClaimValue::String("é😀\n".to_owned())
+                    )])
+                ),
+            ])
+        );
+        assert_eq!(object("[]"), Err(JwtError::Malformed));
+        assert_eq!(object(r#"{"a":1,}"#), Err(JwtError::Malformed));
+        assert_eq!(object(r#"{"a":1} x"#), Err(JwtError::Malformed));
+        let nested = format!(r#"{{"a":{}1{}}}"#, "[".repeat(40), "]".repeat(40));
+        assert_eq!(object(&nested), Err(JwtError::Malformed));
+    }
+}
@@ -99,36 +99,127 @@
 use zeroize::Zeroizing;
 
 use std::error::Error;
+use std::fmt;
 use std::io::Error as IoError;
-use std::io::ErrorKind as IoErrorKind;
 use std::io::Result as IoResult;
-use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::net::{Shutdown, ToSocketAddrs};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::Duration;
 
-use client::ClientConnection;
-use connection::Connection;
-use util::MessagesQueue;
+use client::{ClientConnection, ConnectionSettings};
+use handshake_ban::HandshakeFailures;
+use state::SharedState;
+use util::{CorePinning, MessagesQueue};
 
-pub use common::{HTTPVersion, Header, HeaderField, Method, StatusCode};
-pub use connection::{ConfigListenAddr, ListenAddr, Listener};
-pub use request::{ReadWrite, Request};
-pub use response::{Response, ResponseBox};
-pub use test::TestRequest;
+#[cfg(feature = "acme")]
+pub use acme::{AcmeChallenges, ACME_CHALLENGE_PATH};
+#[cfg(feature = "auth")]
+pub use auth::{KeyStore, RequestAuth, API_KEY_HEADER, SIGNATURE_HEADER};
+pub use body_transform::{BodyInfo, BodyTransform, ResponseBody};
+pub use cache::ResponseCache;
+pub use circuit_breaker::{CircuitBreaker, CircuitError, CircuitState};
+pub use clock::{Clock, SystemClock};
+pub use common::{ContentType, HTTPVersion, Header, HeaderField, Method, StatusCode};
+pub use concurrency::{ConcurrencyLimit, ConcurrencyPermit};
+pub use config_duration::{ConfigDuration, ConfigDurationError};
+pub use connection::{AcceptedStream, Acceptor, ConfigListenAddr, ListenAddr, Listener};
+#[cfg(feature = "csrf")]
+pub use csrf::{Csrf, DEFAULT_CSRF_COOKIE, DEFAULT_CSRF_HEADER};
+pub use deprecation::DeprecationPolicy;
+pub use error_renderer::{ErrorFormat, ErrorRenderer};
+pub use extensions::Extensions;
+pub use handshake_ban::HandshakeBan;
+pub use ip_filter::{IpCidr, IpFilter};
+#[cfg(feature = "jwt")]
+pub use jwt::{ClaimValue, Claims, JwtAuth, JwtError};
+pub use list_headers::Link;
+#[cfg(feature = "memmap")]
+pub use memmap2::Mmap;
+#[cfg(feature = "otel")]
+pub use otel::OtelConfig;
+pub use parking::Parking;
+pub use pipeline::{PipelineWait, PipelineWaitHandler};
+pub use pre_handler::{PreDecision, PreHandler, RequestHead};
+pub use range::{ContentRange, RangeError, RangeParseError};
+pub use recorder::{Exchange, RecordedRequest, RecordedResponse, Recorder};
+pub use redirect::{CanonicalPolicy, CanonicalRedirect, TrailingSlash};
+pub use request::{DuplexReader, DuplexWriter, ReadWrite, Request, RespondError, UpgradedStream};
+#[cfg(feature = "memmap")]
+pub use response::SharedMmap;
+pub use response::{Response, ResponseBox, ResponseStats, RetryAfter, TransferEncoding};
+pub use response_decorator::{ResponseDecorator, ResponseHeaders};
+#[cfg(feature = "ssl-openssl")]
+pub use ssl::openssl::OpenSslOptions;
+pub use ssl::TlsInfo;
+pub use state::ServerState;
+#[cfg(feature = "status-page")]
+pub use status_page::STATUS_PAGE_PATH;
+pub use test::{FaultInjector, TestRequest};
+pub use trace::TraceContext;
+pub use trusted_proxy::TrustedProxies;
+pub use unanswered::{UnansweredHandler, UnansweredPolicy};
+pub use url::{ParsedUrl, UrlError};
+pub use util::{PinnedThread, RenderReader};
+pub use validation::{ConfigError, ConfigProblem};
+pub use worker::StatefulRequestHandler;
 
+#[cfg(feature = "acme")]
+mod acme;
+#[cfg(feature = "auth")]
+mod auth;
+mod body_transform;
+mod cache;
+mod circuit_breaker;
 mod client;
+mod clock;
 mod common;
+mod concurrency;
+mod config_duration;
 mod connection;
+#[cfg(feature = "csrf")]
+mod csrf;
+mod deprecation;
+mod disposition;
+mod error_renderer;
+mod extensions;
+mod handshake_ban;
+#[cfg(feature = "http3")]
+mod http3;
+mod ip_filter;
+#[cfg(feature = "jwt")]
+mod jwt;
+mod list_headers;
 mod log;
+#[cfg(feature = "otel")]
+mod otel;
+mod parking;
+mod pipeline;
+mod pre_handler;
+pub mod prelude;
+mod range;
+mod recorder;
+mod redirect;
 mod request;
 mod response;
+mod response_decorator;
 mod ssl;
+mod state;
+#[cfg(feature = "status-page")]
+mod status_page;
+#[cfg(feature = "strict-responses")]
+mod strict_responses;
 mod test;
+mod trace;
+mod trusted_proxy;
+mod unanswered;
+mod url;
 mod util;
+mod validation;
+mod worker;
 
 /// The main class of this library.
 ///
@@ -143,10 +234,31 @@ pub struct Server {
     // queue for messages received by child threads
     messages: Arc<MessagesQueue<Message>>,
 
-    // result of TcpListener::local_addr()
-    listening_addr: ListenAddr,
+    // listeners of `ServerConfig::addr` and of `ServerConfig::listeners`, in this order
+    listeners: Vec<ListenerHandle>,
+
+    // filter checked by the accept thread, can be replaced while the server is running
+    ip_filter: Arc<RwLock<Option<IpFilter>>>,
+
+    // context of the TLS handshakes done by the accept thread, `None` for plain HTTP
+    ssl: Arc<RwLock<Option<Arc<SslContext>>>>,
+
+    // `Some` if the threads are pinned to cores
+    pinning: Option<Arc<CorePinning>>,
+
+    // reported by the health check, shared with the connections
+    state: Arc<SharedState>,
+
+    // connections currently open on all the listeners
+    connections: Arc<util::ConnectionCounter>,
 }
 
+/// Time a proxy has to send its PROXY protocol header before the TLS handshake, which the
+/// accept thread waits for.
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(1);
+
+// the queue mostly carries requests, boxing them would only add an allocation
+#[allow(clippy::large_enum_variant)]
 enum Message {
     Error(IoError),
     NewRequest(Request),
@@ -166,6 +278,7 @@ impl From<Request> for Message {
 
 // this trait is to make sure that Server implements Share and Send
 #[doc(hidden)]
+#[allow(dead_code)]
 trait MustBeShareDummy: Sync + Send {}
 #[doc(hidden)]
 impl MustBeShareDummy for Server {}
@@ -175,22 +288,556 @@ pub struct IncomingRequests<'a> {
 }
 
 /// Represents the parameters required to create a server.
+///
+/// Built with [`ServerConfig::new`], whose defaults the other fields can override.
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     /// The addresses to try to listen to.
     pub addr: ConfigListenAddr,
 
+    /// Additional listeners, whose connections are handled like those of `addr` and whose
+    /// requests are received by the same [`Server::recv`]. Each one can override the SSL, socket
+    /// and limits configuration, e.g. for an internal Unix socket without limits next to a
+    /// public TCP port with strict ones.
+    pub listeners: Vec<ListenerSpec>,
+
     /// If `Some`, then the server will use SSL to encode the communications.
     pub ssl: Option<SslConfig>,
+
+    /// Options applied to the socket of every accepted connection.
+    pub socket: SocketConfig,
+
+    /// Limits protecting the server against misbehaving clients.
+    pub limits: LimitsConfig,
+
+    /// If `Some`, connections from addresses denied by the filter are closed right after being
+    /// accepted. The filter can be replaced later with [`Server::set_ip_filter`].
+    pub ip_filter: Option<IpFilter>,
+
+    /// If `Some`, the TLS-terminating proxies whose requests are secure if they tell so, see
+    /// [`TrustedProxies`].
+    pub trusted_proxies: Option<TrustedProxies>,
+
+    /// If `Some`, called with the head of every request before its body is read, and able to
+    /// reject the request.
+    pub pre_handler: Option<PreHandler>,
+
+    /// If `Some`, replaces the clock of the operating system for the `Date` header of responses
+    /// and the timing of requests. Mostly useful for tests.
+    pub clock: Option<Arc<dyn Clock>>,
+
+    /// Configuration of the threads handling the connections.
+    pub pool: PoolConfig,
+
+    /// If `Some`, `GET` and `HEAD` requests for this path (e.g. `/healthz`) are answered
+    /// directly by the thread reading the connection, without going through [`Server::recv`],
+    /// so that health probes succeed even when the handlers are saturated.
+    ///
+    /// The answer is `200 OK` with the body `ok` when the server is [`ServerState::Ready`], or
+    /// `503 Service Unavailable` with the name of the state as body, e.g. `draining` after
+    /// [`Server::set_draining`], and the [`LimitsConfig::retry_after`] header. The query string
+    /// is ignored.
+    pub health_check_path: Option<String>,
+
+    /// If `Some`, called when the response to a pipelined request has to wait for the
+    /// response to an earlier request of the same connection.
+    pub pipeline_wait_handler: Option<PipelineWaitHandler>,
+
+    /// What happens to the requests dropped without being answered. If `None`, they are
+    /// answered with `500 Internal Server Error`.
+    pub unanswered: Option<UnansweredPolicy>,
+
+    /// If `Some`, builds the responses the server sends by itself, e.g. to malformed requests,
+    /// to requests rejected by the pre-handler or to unanswered requests, for example with
+    /// bodies negotiated from the `Accept` header with [`ErrorRenderer::negotiated`]. If
+    /// `None`, these responses are empty.
+    pub error_renderer: Option<ErrorRenderer>,
+
+    /// If `Some`, edits the headers of every response right before it is sent, including the
+    /// responses the server sends by itself, see [`ResponseDecorator`].
+    pub response_decorator: Option<ResponseDecorator>,
+
+    /// If `Some`, wraps the body of every response before it is sent, including the responses
+    /// the server sends by itself, see [`BodyTransform`].
+    pub body_transform: Option<BodyTransform>,
+
+    /// If true, `200` responses to `GET` and `HEAD` requests whose body is smaller than their
+    /// [chunked threshold](Response::chunked_threshold) get a weak `ETag` computed from the
+    /// body, unless they already have one, and are turned into `304 Not Modified` responses
+    /// when the tag matches the `If-None-Match` header of the request.
+    ///
+    /// The body is still produced for every request, but not sent again to clients that have
+    /// it already.
+    pub auto_etag: bool,
+
+    /// If false, the responses don't get the `Date` and `Server` headers added by default, see
+    /// [`Response::without_default_headers`].
+    pub emit_default_headers: bool,
+
+    /// If `Some`, the [chunked threshold](Response::with_chunked_threshold) of the responses
+    /// that don't set their own, e.g. a large value to send responses with a `Content-Length`
+    /// rather than chunked. If `None`, the default of 32768 bytes applies.
+    pub chunked_threshold: Option<usize>,
+
+    /// If true, the absolute-form targets of proxy-style requests (`GET http://host/path`) are
+    /// rewritten to their origin form (`/path`) as RFC 9112 #3.2.2 requires, so that
+    /// [`Request::url`] is always a path. The target as received is still returned by
+    /// [`Request::raw_url`].
+    ///
+    /// Requests whose `Host` header doesn't match the authority of the target, or whose target
+    /// contains credentials, are answered with `400 Bad Request`.
+    pub normalize_absolute_form: bool,
+
+    /// If true, [`Server::new`] doesn't fail when [`ServerConfig::validate`] finds problems in
+    /// the configuration, e.g. when a zero limit is intended.
+    pub skip_validation: bool,
+
+    /// If `Some`, spans and metrics of the requests are exported to OpenTelemetry.
+    #[cfg(feature = "otel")]
+    pub otel: Option<OtelConfig>,
+
+    /// If `Some`, `GET` and `HEAD` requests for [`STATUS_PAGE_PATH`] from the addresses allowed
+    /// by the filter are answered directly by the thread reading the connection, like the
+    /// health check, with live statistics of the server: open connections, requests waiting for
+    /// [`Server::recv`], busy and idle threads, number of responses by status code, and the
+    /// main settings of this configuration.
+    ///
+    /// The page is JSON, or HTML for clients preferring it like browsers. Requests for it from
+    /// other addresses are handled normally. Requires the `status-page` feature.
+    #[cfg(feature = "status-page")]
+    pub status_page: Option<IpFilter>,
+}
+
+impl ServerConfig {
+    /// Builds a configuration listening on `addr`, without SSL, with the default socket, limits
+    /// and pool configurations, and with every optional feature disabled.
+    ///
+    /// The other fields are meant to be set with the struct update syntax, which keeps compiling
+    /// when fields are added to the configuration or enabled by features:
+    ///
+    /// ```no_run
+    /// # use tiny_http::{ConfigListenAddr, Server, ServerConfig};
+    /// let server = Server::new(ServerConfig {
+    ///     auto_etag: true,
+    ///     ..ServerConfig::new(ConfigListenAddr::from_socket_addrs("0.0.0.0:8000").unwrap())
+    /// });
+    /// ```
+    pub fn new(addr: ConfigListenAddr) -> ServerConfig {
+        ServerConfig {
+            addr,
+            listeners: Vec::new(),
+            ssl: None,
+            socket: SocketConfig::default(),
+            limits: LimitsConfig::default(),
+            ip_filter: None,
+            trusted_proxies: None,
+            pre_handler: None,
+            clock: None,
+            pool: PoolConfig::default(),
+            health_check_path: None,
+            pipeline_wait_handler: None,
+            unanswered: None,
+            error_renderer: None,
+            response_decorator: None,
+            body_transform: None,
+            auto_etag: false,
+            emit_default_headers: true,
+            chunked_threshold: None,
+            normalize_absolute_form: false,
+            skip_validation: false,
+            #[cfg(feature = "otel")]
+            otel: None,
+            #[cfg(feature = "status-page")]
+            status_page: None,
+        }
+    }
 }
 
 /// Configuration of the server for SSL.
-#[derive(Debug, Clone)]
+///
+/// Some fields only exist with some SSL features, so the configuration is better built with
+/// [`SslConfig::new`] and the struct update syntax than with a struct literal.
+#[derive(Clone)]
 pub struct SslConfig {
     /// Contains the public certificate to send to clients.
     pub certificate: Vec<u8>,
     /// Contains the ultra-secret private key used to decode communications.
     pub private_key: Vec<u8>,
+    /// If `Some`, used as is instead of a configuration built from `certificate` and
+    /// `private_key`, which are then ignored. This gives access to the features of Rustls that
+    /// tiny-http doesn't configure, e.g. OCSP stapling, session tickets or client
+    /// authentication.
+    ///
+    /// The configuration must come from the version of Rustls used by tiny-http (0.20).
+    #[cfg(feature = "ssl-rustls")]
+    pub rustls_config: Option<Arc<rustls::ServerConfig>>,
+    /// Options of OpenSSL which tiny-http leaves to their defaults otherwise, e.g. OCSP
+    /// stapling and session resumption.
+    #[cfg(feature = "ssl-openssl")]
+    pub openssl: OpenSslOptions,
+}
+
+impl SslConfig {
+    /// Builds a configuration from a certificate chain and a private key in PEM format.
+    pub fn new(certificate: Vec<u8>, private_key: Vec<u8>) -> SslConfig {
+        SslConfig {
+            certificate,
+            private_key,
+            #[cfg(feature = "ssl-rustls")]
+            rustls_config: None,
+            #[cfg(feature = "ssl-openssl")]
+            openssl: OpenSslOptions::default(),
+        }
+    }
+
+    /// Builds a configuration from a Rustls configuration, see `rustls_config`.
+    #[cfg(feature = "ssl-rustls")]
+    pub fn from_rustls_config(config: Arc<rustls::ServerConfig>) -> SslConfig {
+        SslConfig {
+            rustls_config: Some(config),
+            ..SslConfig::new(Vec::new(), Vec::new())
+        }
+    }
+}
+
+impl fmt::Debug for SslConfig {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = formatter.debug_struct("SslConfig");
+        debug
+            .field("certificate", &self.certificate)
+            .field("private_key", &self.private_key);
+        #[cfg(feature = "ssl-rustls")]
+        debug.field(
+            "rustls_config",
+            &self.rustls_config.as_ref().map(|_| "rustls::ServerConfig"),
+        );
+        #[cfg(feature = "ssl-openssl")]
+        debug.field("openssl", &self.openssl);
+        debug.finish()
+    }
+}
+
+/// An additional listener of the server, see [`ServerConfig::listeners`].
+///
+/// ```
+/// use tiny_http::{ConfigListenAddr, LimitsConfig, ListenerOverrides, ListenerSpec};
+///
+/// // an internal Unix socket without the limits of the public listener
+/// let internal = ListenerSpec {
+///     addr: ConfigListenAddr::unix_from_path("/run/app/internal.sock"),
+///     overrides: ListenerOverrides {
+///         limits: Some(LimitsConfig::default()),
+///         ..ListenerOverrides::default()
+///     },
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct ListenerSpec {
+    /// The addresses to try to listen to.
+    pub addr: ConfigListenAddr,
+
+    /// Configuration replacing that of the [`ServerConfig`] for this listener.
+    pub overrides: ListenerOverrides,
+}
+
+/// Configuration of a [`ListenerSpec`] replacing that of the [`ServerConfig`], which is kept
+/// for the fields left to `None`.
+#[derive(Debug, Clone, Default)]
+pub struct ListenerOverrides {
+    /// Replaces [`ServerConfig::ssl`], `Some(None)` accepting plain HTTP connections on a server
+    /// using SSL. [`Server::set_ssl_config`] doesn't affect listeners with this override.
+    pub ssl: Option<Option<SslConfig>>,
+
+    /// Replaces [`ServerConfig::socket`].
+    pub socket: Option<SocketConfig>,
+
+    /// Replaces [`ServerConfig::limits`]. Its `buffered_bodies_max` is then shared by the
+    /// connections of this listener only.
+    pub limits: Option<LimitsConfig>,
+}
+
+/// Options applied to the socket of every accepted connection.
+///
+/// All fields are available on every platform and build. Options which can't be applied on
+/// the platform make [`Server::new`] fail instead of being silently ignored.
+#[derive(Debug, Clone, Default)]
+pub struct SocketConfig {
+    /// Timeout for reading from the client. A client that doesn't send the next request
+    /// within this time gets a `408 Request Timeout` and the connection is closed.
+    ///
+    /// `None`, the default, waits forever.
+    pub read_timeout: Option<ConfigDuration>,
+
+    /// Time after which a connection waiting for its next request, with all its requests
+    /// answered, is closed without a response.
+    ///
+    /// Unlike `read_timeout`, this doesn't apply while a request is being handled, and the
+    /// connections are checked by a background thread, so idle keep-alive connections give
+    /// their thread and socket back even if `read_timeout` is long or `None`. `None`, the
+    /// default, keeps them open until the client closes them.
+    pub keep_alive_idle_timeout: Option<ConfigDuration>,
+
+    /// Timeout for writing to the client. `None`, the default, waits forever.
+    pub write_timeout: Option<ConfigDuration>,
+
+    /// Sets `TCP_NODELAY`, disabling Nagle's algorithm. Ignored for Unix sockets.
+    pub no_delay: bool,
+
+    /// Idle time of the connection before TCP keep-alive probes are sent. Setting any of the `tcp_keepalive_*` options enables the probes, the
+    /// others keep the defaults of the system. Ignored for Unix sockets.
+    ///
+    /// Not supported on OpenBSD and Haiku.
+    pub tcp_keepalive_time: Option<ConfigDuration>,
+
+    /// Interval between TCP keep-alive probes. Setting any of the `tcp_keepalive_*` options enables the probes, the
+    /// others keep the defaults of the system. Ignored for Unix sockets.
+    ///
+    /// Not supported on OpenBSD, Solaris and Haiku, among others.
+    pub tcp_keepalive_interval: Option<ConfigDuration>,
+
+    /// Number of unanswered TCP keep-alive probes before the connection is dropped. Setting any of the `tcp_keepalive_*` options enables the probes, the
+    /// others keep the defaults of the system. Ignored for Unix sockets.
+    ///
+    /// Not supported on Windows, OpenBSD, Solaris and Haiku, among others.
+    pub tcp_keepalive_retries: Option<u32>,
+
+    /// Capacity of the buffer in front of the socket for writing responses. `None`, the
+    /// default, uses 1024 bytes.
+    ///
+    /// Bodies larger than the buffer bypass it and are written to the socket in blocks of at
+    /// least this size, so a larger buffer mostly helps with many small responses.
+    pub write_buffer_size: Option<usize>,
+}
+
+/// Configuration of the pool of threads handling the connections.
+///
+/// Every connection occupies a thread for as long as it is open.
+///
+/// The threads started with the server are named `tiny-http-worker-N`, the ones started on
+/// demand `tiny-http-task-N`. The thread accepting the connections is named `tiny-http-accept`.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Number of threads kept alive even when they are idle. Defaults to 4.
+    pub min_threads: usize,
+
+    /// Maximum number of threads. Once reached, new connections wait until a thread becomes
+    /// available. `None`, the default, doesn't limit the number of threads.
+    pub max_threads: Option<usize>,
+
+    /// Time after which an idle thread above `min_threads` dies. Defaults to 5 seconds.
+    pub idle_timeout: ConfigDuration,
+
+    /// Maximum number of idle threads dying within each `idle_timeout`, so that the pool shrinks
+    /// gradually after a burst of connections. `None`, the default, lets all idle threads die.
+    pub shrink_step: Option<usize>,
+
+    /// Stack size of the threads in bytes. `None`, the default, uses the default of the standard
+    /// library.
+    pub stack_size: Option<usize>,
+
+    /// Pins the threads of the pool and the thread accepting the connections to the CPU cores,
+    /// in turn. The assignment is returned by [`Server::pinned_threads`].
+    ///
+    /// Requires the `cpu-affinity` feature, [`Server::new`] fails if it is enabled otherwise.
+    pub pin_worker_threads: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> PoolConfig {
+        PoolConfig {
+            min_threads: 4,
+            max_threads: None,
+            idle_timeout: ConfigDuration::from_secs(5),
+            shrink_step: None,
+            stack_size: None,
+            pin_worker_threads: false,
+        }
+    }
+}
+
+/// Limits protecting the server against misbehaving clients.
+///
+/// By default, nothing is limited.
+#[derive(Debug, Clone, Default)]
+pub struct LimitsConfig {
+    /// Maximum size of a single chunk of a `Transfer-Encoding: chunked` request body.
+    ///
+    /// Reading a larger chunk from [`Request::as_reader`] fails, the request is answered with
+    /// `413 Payload Too Large` if the handler doesn't respond itself, and the connection is
+    /// closed.
+    pub chunk_max_size: Option<usize>,
+
+    /// Maximum size of a decoded `Transfer-Encoding: chunked` request body.
+    ///
+    /// Exceeding it is handled like exceeding `chunk_max_size`. It also limits the HTTP/3
+    /// request bodies without `Content-Length`, which last until the end of their stream.
+    pub chunked_body_max_size: Option<usize>,
+
+    /// Maximum number of error responses on a single connection.
+    ///
+    /// Responses with a `4xx` status code, whether sent by the handler or by tiny-http, and
+    /// protocol errors such as an unsupported HTTP version count as errors. Once the limit is
+    /// reached, the next request on the connection is answered with `400 Bad Request` and the
+    /// connection is closed.
+    pub connection_errors_max: Option<usize>,
+
+    /// Time budget of a request, starting when its request line is received.
+    ///
+    /// tiny-http doesn't interrupt handlers, the budget is only reported by
+    /// [`Request::deadline`] and [`Request::remaining_time`] so that handlers can plan their work.
+    pub request_timeout: Option<ConfigDuration>,
+
+    /// If `Some`, the request line and headers of each request are kept exactly as received, as
+    /// long as they aren't larger than this number of bytes. See [`Request::raw_head`] and
+    /// [`Request::raw_headers`].
+    pub retain_raw_headers: Option<usize>,
+
+    /// If `Some`, added as a `Retry-After` header to the `429 Too Many Requests` and
+    /// `503 Service Unavailable` responses generated by tiny-http, e.g. when the pre-handler
+    /// rejects a request with one of these status codes or when the server isn't ready to
+    /// answer a health check.
+    pub retry_after: Option<RetryAfter>,
+
+    /// Maximum number of requests of a single connection handed to the handlers and not
+    /// answered yet.
+    ///
+    /// Once reached, tiny-http stops reading from the connection until a response is sent, so
+    /// that a client pipelining its requests can't make the server hold an unbounded number of
+    /// them. Responses are always sent in the order of the requests. See
+    /// [`Request::pending_requests`].
+    pub pipeline_max: Option<usize>,
+
+    /// Maximum size of the unread rest of a body that [`Request::respond`] reads and throws
+    /// away, so that the next request of the connection can be read. If the rest is larger, the
+    /// connection is closed instead. `None`, the default, uses 64 KiB.
+    ///
+    /// See [`Request::discard_body`].
+    pub discard_body_max: Option<usize>,
+
+    /// Discards the unread rest of the body before writing the response instead of after.
+    ///
+    /// This delays the response until the client has sent its whole body, but a response to a
+    /// request whose body can't be discarded carries `Connection: close`, as it should, instead
+    /// of the connection being closed without notice.
+    pub discard_body_before_response: bool,
+
+    /// Maximum size of a response body of unknown length kept in memory to send it to an
+    /// HTTP/1.0 client, which can't receive chunks and needs its length. A larger body is
+    /// written to a temporary file instead, only readable by the user of the process. `None`,
+    /// the default, uses 1 MiB.
+    ///
+    /// The temporary files of all the responses together are limited to 1 GiB, sending a
+    /// response that would exceed it fails.
+    pub response_buffer_max: Option<usize>,
+
+    /// Maximum total size of the headers of a response, counting the line of each header set by
+    /// the handler. A response with larger headers, e.g. thousands of `Set-Cookie` headers
+    /// added by a buggy handler, isn't sent: [`Request::respond`] logs an error and fails with
+    /// `InvalidData`, and the request is answered according to the
+    /// [`ServerConfig::unanswered`] policy. `None`, the default, sets no limit.
+    pub response_headers_max: Option<usize>,
+
+    /// Maximum total size of the small request bodies, up to 1 KiB, that tiny-http reads into
+    /// memory in advance, over all the connections. Once reached, e.g. by many clients
+    /// pipelining requests, the handlers read the next bodies from the sockets, as they do for
+    /// larger bodies. `None`, the default, sets no limit.
+    pub buffered_bodies_max: Option<usize>,
+
+    /// Maximum time a connection is kept open, starting when it's accepted.
+    ///
+    /// The response to the first request read after this time carries `Connection: close`, and
+    /// the connection is closed once it's sent, so that clients reconnect, e.g. to another
+    /// instance during a rolling restart. Connections idle past this time are only closed by
+    /// [`SocketConfig::keep_alive_idle_timeout`].
+    pub connection_max_lifetime: Option<ConfigDuration>,
+
+    /// Maximum number of empty lines skipped before a request line, as sent by some old clients
+    /// after a request body. More are answered with `400 Bad Request`. `None`, the default,
+    /// skips up to 4.
+    pub leading_empty_lines_max: Option<usize>,
+
+    /// Maximum length of a header line, without its line ending. A longer line is answered with
+    /// `431 Request Header Fields Too Large` and the connection is closed, after reading and
+    /// throwing away up to 64 KiB of the rest of the request head so that the client receives
+    /// the response instead of a connection reset. `None`, the default, sets no limit.
+    pub header_line_len: Option<usize>,
+
+    /// If `Some`, the addresses failing too many TLS handshakes are temporarily refused new
+    /// handshakes, see [`HandshakeBan`]. Only applies to HTTPS listeners. `None`, the default,
+    /// attempts every handshake.
+    pub tls_handshake_failures: Option<HandshakeBan>,
+
+    /// Rejects with `400 Bad Request` the requests that RFC 9112 allows a server to accept
+    /// leniently, but that another server in the chain could interpret differently.
+    ///
+    /// These are lines containing a bare `LF` or a bare `CR`, HTTP/1.1 requests without a
+    /// single `Host` header. `Content-Length` values that aren't only digits, and
+    /// `Transfer-Encoding` headers whose last coding isn't `chunked` or that come with a
+    /// `Content-Length` are always rejected.
+    pub strict_parsing: bool,
+}
+
+impl SocketConfig {
+    /// Capacity of the write buffer, see `write_buffer_size`.
+    fn write_buffer_size(&self) -> usize {
+        self.write_buffer_size.unwrap_or(1024)
+    }
+
+    /// Returns an error if an option is set that can't be applied to the sockets.
+    fn check_supported(&self) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        match connection::tcp_keepalive(self) {
+            Ok(_) => Ok(()),
+            Err(option) => Err(format!("`{}` can't be set on this platform", option).into()),
+        }
+    }
+}
+
+#[cfg(any(
+    all(feature = "ssl-openssl", feature = "ssl-rustls"),
+    all(feature = "ssl-openssl", feature = "ssl-native-tls"),
+    all(feature = "ssl-native-tls", feature = "ssl-rustls"),
+))]
+compile_error!(
+    "Only one feature from 'ssl-openssl', 'ssl-rustls', 'ssl-native-tls' can be enabled at the same time"
+);
+#[cfg(not(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+)))]
+type SslContext = ();
+#[cfg(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+))]
+type SslContext = crate::ssl::SslContextImpl;
+
+/// Builds the context of the TLS handshakes from the configuration.
+fn ssl_context(config: SslConfig) -> Result<SslContext, Box<dyn Error + Send + Sync + 'static>> {
+    match config {
+        #[cfg(feature = "ssl-rustls")]
+        SslConfig {
+            rustls_config: Some(rustls_config),
+            ..
+        } => Ok(SslContext::from_config(rustls_config)),
+        #[cfg(feature = "ssl-openssl")]
+        config => SslContext::from_pem_with_options(
+            config.certificate,
+            Zeroizing::new(config.private_key),
+            &config.openssl,
+        ),
+        #[cfg(any(feature = "ssl-rustls", feature = "ssl-native-tls"))]
+        config => SslContext::from_pem(config.certificate, Zeroizing::new(config.private_key)),
+        #[cfg(not(any(
+            feature = "ssl-openssl",
+            feature = "ssl-rustls",
+            feature = "ssl-native-tls"
+        )))]
+        _ => Err(
+            "Building a server with SSL requires enabling the `ssl` feature in tiny-http".into(),
+        ),
+    }
 }
 
 impl Server {
@@ -200,10 +847,9 @@ impl Server {
     where
         A: ToSocketAddrs,
     {
-        Server::new(ServerConfig {
-            addr: ConfigListenAddr::from_socket_addrs(addr)?,
-            ssl: None,
-        })
+        Server::new(ServerConfig::new(ConfigListenAddr::from_socket_addrs(
+            addr,
+        )?))
     }
 
     /// Shortcut for an HTTPS server on a specific address.
@@ -221,8 +867,8 @@ impl Server {
         A: ToSocketAddrs,
     {
         Server::new(ServerConfig {
-            addr: ConfigListenAddr::from_socket_addrs(addr)?,
             ssl: Some(config),
+            ..ServerConfig::new(ConfigListenAddr::from_socket_addrs(addr)?)
         })
     }
 
@@ -232,164 +878,226 @@ impl Server {
     pub fn http_unix(
         path: &std::path::Path,
     ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
-        Server::new(ServerConfig {
-            addr: ConfigListenAddr::unix_from_path(path),
-            ssl: None,
-        })
+        Server::new(ServerConfig::new(ConfigListenAddr::unix_from_path(path)))
     }
 
     /// Builds a new server that listens on the specified address.
+    ///
+    /// Fails with a [`ConfigError`] if [`ServerConfig::validate`] finds problems in the
+    /// configuration, unless [`ServerConfig::skip_validation`] is set.
     pub fn new(config: ServerConfig) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
+        config.check()?;
+        config.socket.check_supported()?;
+        for spec in &config.listeners {
+            if let Some(socket) = &spec.overrides.socket {
+                socket.check_supported()?;
+            }
+        }
         let listener = config.addr.bind()?;
-        Self::from_listener(listener, config.ssl)
+        Self::from_listener_impl(listener, config)
     }
 
     /// Builds a new server using the specified TCP listener.
     ///
     /// This is useful if you've constructed TcpListener using some less usual method
-    /// such as from systemd. For other cases, you probably want the `new()` function.
+    /// such as from systemd, or to accept the connections of another transport with
+    /// [`Listener::custom`]. For other cases, you probably want the `new()` function.
     pub fn from_listener<L: Into<Listener>>(
         listener: L,
         ssl_config: Option<SslConfig>,
     ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
-        let listener = listener.into();
-        // building the "close" variable
-        let close_trigger = Arc::new(AtomicBool::new(false));
+        Self::from_listener_impl(
+            listener.into(),
+            ServerConfig {
+                ssl: ssl_config,
+                // the listener is already bound
+                ..ServerConfig::new(ConfigListenAddr::IP(Vec::new()))
+            },
+        )
+    }
 
-        // building the TcpListener
-        let (server, local_addr) = {
-            let local_addr = listener.local_addr()?;
-            log::debug!("Server listening on {}", local_addr);
-            (listener, local_addr)
+    fn from_listener_impl(
+        listener: Listener,
+        config: ServerConfig,
+    ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
+        // rendered before the configuration is taken apart
+        #[cfg(feature = "status-page")]
+        let status_page = config
+            .status_page
+            .clone()
+            .map(|filter| (filter, status_page::config_json(&config)));
+        let ServerConfig {
+            ssl: ssl_config,
+            socket: socket_config,
+            limits,
+            ip_filter,
+            trusted_proxies,
+            pre_handler,
+            clock,
+            pool: pool_config,
+            health_check_path,
+            pipeline_wait_handler,
+            unanswered,
+            error_renderer,
+            response_decorator,
+            body_transform,
+            auto_etag,
+            emit_default_headers,
+            chunked_threshold,
+            normalize_absolute_form,
+            listeners: listener_specs,
+            #[cfg(feature = "otel")]
+            otel,
+            ..
+        } = config;
+        let state = Arc::new(SharedState::new(ServerState::Ready));
+        let clock = clock.unwrap_or_else(|| Arc::new(SystemClock));
+        let idle_reaper = match socket_config.keep_alive_idle_timeout {
+            Some(timeout) => Some(util::IdleReaper::start(timeout.into(), clock.clone())?),
+            None => None,
         };
+        let body_budget = util::MemoryBudget::new(limits.buffered_bodies_max.unwrap_or(usize::MAX));
 
-        // building the SSL capabilities
-        #[cfg(any(
-            all(feature = "ssl-openssl", feature = "ssl-rustls"),
-            all(feature = "ssl-openssl", feature = "ssl-native-tls"),
-            all(feature = "ssl-native-tls", feature = "ssl-rustls"),
-        ))]
-        compile_error!(
-            "Only one feature from 'ssl-openssl', 'ssl-rustls', 'ssl-native-tls' can be enabled at the same time"
-        );
-        #[cfg(not(any(
-            feature = "ssl-openssl",
-            feature = "ssl-rustls",
-            feature = "ssl-native-tls"
-        )))]
-        type SslContext = ();
-        #[cfg(any(
-            feature = "ssl-openssl",
-            feature = "ssl-rustls",
-            feature = "ssl-native-tls"
-        ))]
-        type SslContext = crate::ssl::SslContextImpl;
-        let ssl: Option<SslContext> = {
-            match ssl_config {
-                #[cfg(any(
-                    feature = "ssl-openssl",
-                    feature = "ssl-rustls",
-                    feature = "ssl-native-tls"
-                ))]
-                Some(config) => Some(SslContext::from_pem(
-                    config.certificate,
-                    Zeroizing::new(config.private_key),
-                )?),
-                #[cfg(not(any(
-                    feature = "ssl-openssl",
-                    feature = "ssl-rustls",
-                    feature = "ssl-native-tls"
-                )))]
-                Some(_) => return Err(
-                    "Building a server with SSL requires enabling the `ssl` feature in tiny-http"
-                        .into(),
-                ),
-                None => None,
+        let pinning = if pool_config.pin_worker_threads {
+            match CorePinning::new() {
+                Some(pinning) => Some(Arc::new(pinning)),
+                None if cfg!(feature = "cpu-affinity") => {
+                    return Err("Failed to list the CPU cores to pin threads to".into())
+                }
+                None => return Err("Pinning threads requires the `cpu-affinity` feature".into()),
             }
+        } else {
+            None
         };
 
-        // creating a task where server.accept() is continuously called
-        // and ClientConnection objects are pushed in the messages queue
         let messages = MessagesQueue::with_capacity(8);
+        let connections = util::ConnectionCounter::new();
 
-        let inside_close_trigger = close_trigger.clone();
-        let inside_messages = messages.clone();
-        thread::spawn(move || {
-            // a tasks pool is used to dispatch the connections into threads
-            let tasks_pool = util::TaskPool::new();
+        // a tasks pool is used to dispatch the connections of all the listeners into threads
+        let tasks_pool = Arc::new(util::TaskPool::new(pool_config, pinning.clone()));
 
-            log::debug!("Running accept thread");
-            while !inside_close_trigger.load(Relaxed) {
-                let new_client = match server.accept() {
-                    Ok((sock, _)) => {
-                        use util::RefinedTcpStream;
-                        let (read_closable, write_closable) = match ssl {
-                            None => RefinedTcpStream::new(sock),
-                            #[cfg(any(
-                                feature = "ssl-openssl",
-                                feature = "ssl-rustls",
-                                feature = "ssl-native-tls"
-                            ))]
-                            Some(ref ssl) => {
-                                // trying to apply SSL over the connection
-                                // if an error occurs, we just close the socket and resume listening
-                                let sock = match ssl.accept(sock) {
-                                    Ok(s) => s,
-                                    Err(_) => continue,
-                                };
+        #[cfg(feature = "status-page")]
+        let status_page = status_page.map(|(filter, config)| {
+            Arc::new(status_page::StatusPage::new(
+                filter,
+                config,
+                connections.clone(),
+                messages.clone(),
+                tasks_pool.monitor(),
+                clock.now(),
+            ))
+        });
+        let settings = Arc::new(ConnectionSettings {
+            limits,
+            pre_handler,
+            clock,
+            write_buffer_size: socket_config.write_buffer_size(),
+            health_check_path,
+            pipeline_wait_handler,
+            unanswered,
+            error_renderer,
+            response_decorator,
+            body_transform,
+            auto_etag,
+            emit_default_headers,
+            chunked_threshold,
+            normalize_absolute_form,
+            trusted_proxies,
+            idle_reaper,
+            body_budget,
+            connections: connections.clone(),
+            #[cfg(feature = "otel")]
+            telemetry: otel.map(|config| Arc::new(otel::Telemetry::new(config))),
+            #[cfg(feature = "status-page")]
+            status_page,
+            state: state.clone(),
+        });
+        let ip_filter = Arc::new(RwLock::new(ip_filter));
 
-                                RefinedTcpStream::new(sock)
-                            }
-                            #[cfg(not(any(
-                                feature = "ssl-openssl",
-                                feature = "ssl-rustls",
-                                feature = "ssl-native-tls"
-                            )))]
-                            Some(ref _ssl) => unreachable!(),
-                        };
+        // building the "close" variable
+        let close_trigger = Arc::new(AtomicBool::new(false));
 
-                        Ok(ClientConnection::new(write_closable, read_closable))
-                    }
-                    Err(e) => Err(e),
-                };
+        // building the SSL capabilities
+        let ssl = match ssl_config {
+            Some(config) => Some(Arc::new(ssl_context(config)?)),
+            None => None,
+        };
+        let ssl = Arc::new(RwLock::new(ssl));
 
-                match new_client {
-                    Ok(client) => {
-                        let messages = inside_messages.clone();
-                        let mut client = Some(client);
-                        tasks_pool.spawn(Box::new(move || {
-                            if let Some(client) = client.take() {
-                                // Synchronization is needed for HTTPS requests to avoid a deadlock
-                                if client.secure() {
-                                    let (sender, receiver) = mpsc::channel();
-                                    for rq in client {
-                                        messages.push(rq.with_notify_sender(sender.clone()).into());
-                                        receiver.recv().unwrap();
-                                    }
-                                } else {
-                                    for rq in client {
-                                        messages.push(rq.into());
-                                    }
-                                }
-                            }
-                        }));
-                    }
+        // binding the additional listeners, with their own settings if they override some
+        let mut accept_loops = vec![AcceptLoop {
+            listener,
+            socket_config: socket_config.clone(),
+            ssl: ssl.clone(),
+            settings: settings.clone(),
+        }];
+        for spec in listener_specs {
+            let ListenerOverrides {
+                ssl: ssl_override,
+                socket: socket_override,
+                limits: limits_override,
+            } = spec.overrides;
+            if let Some(ref socket) = socket_override {
+                socket.check_supported()?;
+            }
 
-                    Err(e) => {
-                        log::error!("Error accepting new client: {}", e);
-                        inside_messages.push(e.into());
-                        break;
-                    }
+            let mut listener_settings = (*settings).clone();
+            let listener_socket_config = match socket_override {
+                Some(socket) => {
+                    listener_settings.write_buffer_size = socket.write_buffer_size();
+                    listener_settings.idle_reaper = match socket.keep_alive_idle_timeout {
+                        Some(timeout) => Some(util::IdleReaper::start(
+                            timeout.into(),
+                            listener_settings.clock.clone(),
+                        )?),
+                        None => None,
+                    };
+                    socket
                 }
+                None => socket_config.clone(),
+            };
+            if let Some(limits) = limits_override {
+                listener_settings.body_budget =
+                    util::MemoryBudget::new(limits.buffered_bodies_max.unwrap_or(usize::MAX));
+                listener_settings.limits = limits;
             }
-            log::debug!("Terminating accept thread");
-        });
+            let listener_ssl = match ssl_override {
+                Some(Some(config)) => Arc::new(RwLock::new(Some(Arc::new(ssl_context(config)?)))),
+                Some(None) => Arc::new(RwLock::new(None)),
+                None => ssl.clone(),
+            };
+
+            accept_loops.push(AcceptLoop {
+                listener: spec.addr.bind()?,
+                socket_config: listener_socket_config,
+                ssl: listener_ssl,
+                settings: Arc::new(listener_settings),
+            });
+        }
+
+        // creating a task where server.accept() is continuously called for each listener
+        // and ClientConnection objects are pushed in the messages queue
+        let mut listeners = Vec::with_capacity(accept_loops.len());
+        for accept_loop in accept_loops {
+            listeners.push(accept_loop.spawn(
+                &close_trigger,
+                &messages,
+                &ip_filter,
+                &pinning,
+                &tasks_pool,
+            )?);
+        }
 
         // result
         Ok(Server {
             messages,
             close: close_trigger,
-            listening_addr: local_addr,
+            listeners,
+            ip_filter,
+            ssl,
+            pinning,
+            state,
+            connections,
         })
     }
 
@@ -404,13 +1112,100 @@ impl Server {
     /// Returns the address the server is listening to.
     #[inline]
     pub fn server_addr(&self) -> ListenAddr {
-        self.listening_addr.clone()
+        self.listeners[0].addr.clone()
+    }
+
+    /// Returns the addresses of all the listeners of the server, the one of
+    /// [`ServerConfig::addr`] followed by those of [`ServerConfig::listeners`].
+    pub fn server_addrs(&self) -> Vec<ListenAddr> {
+        self.listeners
+            .iter()
+            .map(|listener| listener.addr.clone())
+            .collect()
+    }
+
+    /// Replaces the IP filter of the server. `None` accepts connections from every address.
+    ///
+    /// Only connections accepted after this call are affected.
+    pub fn set_ip_filter(&self, filter: Option<IpFilter>) {
+        *self.ip_filter.write().unwrap() = filter;
+    }
+
+    /// Replaces the SSL configuration of an HTTPS server, e.g. with a renewed certificate.
+    ///
+    /// Only connections accepted after this call are affected, and not those of the listeners
+    /// overriding the SSL configuration. Fails, keeping the previous configuration, if the new
+    /// one is invalid or if the server doesn't use SSL.
+    pub fn set_ssl_config(
+        &self,
+        config: SslConfig,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let context = Arc::new(ssl_context(config)?);
+        let mut ssl = self.ssl.write().unwrap();
+        if ssl.is_none() {
+            return Err("The server doesn't use SSL".into());
+        }
+        *ssl = Some(context);
+        Ok(())
+    }
+
+    /// Returns the lifecycle state of the server.
+    pub fn state(&self) -> ServerState {
+        self.state.get()
+    }
+
+    /// Moves the server from [`ServerState::Starting`] to [`ServerState::Ready`], or back with
+    /// `false`, e.g. while caches are warmed up. Has no effect in other states.
+    ///
+    /// Requests are handled normally in both states, only the health check is affected.
+    pub fn set_ready(&self, ready: bool) {
+        if ready {
+            self.state
+                .transition(&[ServerState::Starting], ServerState::Ready);
+        } else {
+            self.state
+                .transition(&[ServerState::Ready], ServerState::Starting);
+        }
+    }
+
+    /// Moves the server to [`ServerState::Draining`], so that the health check answers
+    /// `503 Service Unavailable` and load balancers stop sending new connections before the
+    /// server is shut down. `false` moves it back to [`ServerState::Ready`].
+    ///
+    /// Other requests are still handled normally.
+    pub fn set_draining(&self, draining: bool) {
+        if draining {
+            self.state.transition(
+                &[ServerState::Starting, ServerState::Ready],
+                ServerState::Draining,
+            );
+        } else {
+            self.state
+                .transition(&[ServerState::Draining], ServerState::Ready);
+        }
+    }
+
+    /// Returns true if the server is in the [`ServerState::Draining`] state.
+    pub fn is_draining(&self) -> bool {
+        self.state() == ServerState::Draining
+    }
+
+    /// Returns the threads of the server currently pinned to a CPU core.
+    ///
+    /// Empty unless [`PoolConfig::pin_worker_threads`] is enabled.
+    pub fn pinned_threads(&self) -> Vec<PinnedThread> {
+        match self.pinning {
+            Some(ref pinning) => pinning.pinned_threads(),
+            None => Vec::new(),
+        }
     }
 
     /// Returns the number of clients currently connected to the server.
+    ///
+    /// A connection counts until it's closed, or until it's handed over to the handler of a
+    /// protocol upgrade.
     pub fn num_connections(&self) -> usize {
-        unimplemented!()
-        //self.requests_receiver.lock().len()
+        self.connections.count()
     }
 
     /// Blocks until an HTTP request has been submitted and returns it.
@@ -418,7 +1213,7 @@ impl Server {
         match self.messages.pop() {
             Some(Message::Error(err)) => Err(err),
             Some(Message::NewRequest(rq)) => Ok(rq),
-            None => Err(IoError::new(IoErrorKind::Other, "thread unblocked")),
+            None => Err(IoError::other("thread unblocked")),
         }
     }
 
@@ -457,26 +1252,269 @@ impl Iterator for IncomingRequests<'_> {
 
 impl Drop for Server {
     fn drop(&mut self) {
+        self.state.set(ServerState::Stopped);
         self.close.store(true, Relaxed);
-        // Connect briefly to ourselves to unblock the accept thread
-        let maybe_stream = match &self.listening_addr {
-            ListenAddr::IP(addr) => TcpStream::connect(addr).map(Connection::from),
+        for listener in &self.listeners {
+            listener.unblock();
+        }
+    }
+}
+
+/// Accept loop of one of the listeners of a server, before its thread is spawned.
+struct AcceptLoop {
+    listener: Listener,
+    socket_config: SocketConfig,
+    ssl: Arc<RwLock<Option<Arc<SslContext>>>>,
+    settings: Arc<ConnectionSettings>,
+}
+
+/// Listener of a running server, kept to unblock its accept thread when the server is dropped.
+struct ListenerHandle {
+    // result of TcpListener::local_addr()
+    addr: ListenAddr,
+
+    // how the accept thread is woken up, connecting to `addr` if `None`
+    waker: Option<ListenerWaker>,
+
+    // socket file of a Unix listener, removed on drop if it is still ours
+    #[cfg(unix)]
+    socket_file: Option<connection::UnixSocketFile>,
+}
+
+impl AcceptLoop {
+    /// Spawns the thread accepting the connections of the listener and pushing their requests
+    /// in the messages queue.
+    fn spawn(
+        self,
+        close_trigger: &Arc<AtomicBool>,
+        messages: &Arc<MessagesQueue<Message>>,
+        ip_filter: &Arc<RwLock<Option<IpFilter>>>,
+        pinning: &Option<Arc<CorePinning>>,
+        tasks_pool: &Arc<util::TaskPool>,
+    ) -> IoResult<ListenerHandle> {
+        let AcceptLoop {
+            listener,
+            socket_config,
+            ssl,
+            settings,
+        } = self;
+
+        // building the TcpListener
+        let local_addr = listener.local_addr()?;
+        log::debug!("Server listening on {}", local_addr);
+
+        // custom acceptors can't be unblocked by connecting to them, and neither can Unix
+        // listeners whose socket file was bound again by another listener
+        let waker = match listener {
+            Listener::Custom(ref acceptor) => Some(ListenerWaker::Custom(acceptor.clone())),
             #[cfg(unix)]
-            ListenAddr::Unix(addr) => {
-                // TODO: use connect_addr when its stabilized.
-                let path = addr.as_pathname().unwrap();
-                std::os::unix::net::UnixStream::connect(path).map(Connection::from)
+            Listener::Unix(ref listener) => Some(ListenerWaker::Unix(listener.try_clone()?)),
+            _ => None,
+        };
+
+        let handle = ListenerHandle {
+            #[cfg(unix)]
+            socket_file: connection::UnixSocketFile::of(&local_addr),
+            addr: local_addr,
+            waker,
+        };
+
+        let close_trigger = close_trigger.clone();
+        let messages = messages.clone();
+        let ip_filter = ip_filter.clone();
+        let pinning = pinning.clone();
+        let tasks_pool = tasks_pool.clone();
+        let accept_thread = thread::Builder::new().name("tiny-http-accept".to_owned());
+        accept_thread.spawn(move || {
+            let _pin_guard = pinning.as_ref().map(|pinning| pinning.pin_current());
+            let mut handshake_failures = settings
+                .limits
+                .tls_handshake_failures
+                .map(HandshakeFailures::new);
+
+            log::debug!("Running accept thread");
+            while !close_trigger.load(Relaxed) {
+                let new_client = match listener.accept() {
+                    Ok((mut sock, peer_addr)) => {
+                        use util::RefinedTcpStream;
+
+                        if let Some(peer_addr) = peer_addr {
+                            let allowed = match *ip_filter.read().unwrap() {
+                                Some(ref filter) => filter.is_allowed(&peer_addr.ip()),
+                                None => true,
+                            };
+                            if !allowed {
+                                log::debug!("Rejecting connection from {}", peer_addr);
+                                continue;
+                            }
+                        }
+
+                        if let Err(err) = sock.apply_config(&socket_config) {
+                            log::error!("Error configuring client socket: {}", err);
+                            continue;
+                        }
+
+                        let context = ssl.read().unwrap().clone();
+                        if let (Some(_), Some(failures), Some(peer_addr)) =
+                            (&context, &mut handshake_failures, peer_addr)
+                        {
+                            if failures.is_banned(&peer_addr.ip(), settings.clock.now()) {
+                                log::debug!("Refusing TLS handshake from {}", peer_addr);
+                                continue;
+                            }
+                        }
+
+                        // a proxy passing TLS through sends its header before the handshake
+                        let proxy_header = match (&context, &settings.trusted_proxies, peer_addr) {
+                            (Some(_), Some(proxies), Some(peer_addr))
+                                if proxies.expects_proxy_protocol(&peer_addr.ip()) =>
+                            {
+                                let header = sock
+                                    .set_read_timeout(Some(PROXY_HEADER_TIMEOUT))
+                                    .and_then(|()| trusted_proxy::read_proxy_header(&mut sock))
+                                    .and_then(|header| {
+                                        let timeout =
+                                            socket_config.read_timeout.map(Duration::from);
+                                        sock.set_read_timeout(timeout).map(|()| header)
+                                    });
+                                match header {
+                                    Ok(header) => Some(header),
+                                    Err(err) => {
+                                        log::debug!(
+                                            "Invalid PROXY protocol header from {}: {}",
+                                            peer_addr,
+                                            err
+                                        );
+                                        continue;
+                                    }
+                                }
+                            }
+                            _ => None,
+                        };
+
+                        let (read_closable, write_closable) = match context {
+                            None => RefinedTcpStream::new(sock),
+                            #[cfg(any(
+                                feature = "ssl-openssl",
+                                feature = "ssl-rustls",
+                                feature = "ssl-native-tls"
+                            ))]
+                            Some(context) => {
+                                // trying to apply SSL over the connection
+                                // if an error occurs, we just close the socket and resume listening
+                                let sock = match context.accept(sock) {
+                                    Ok(s) => s,
+                                    Err(_) => {
+                                        if let (Some(failures), Some(peer_addr)) =
+                                            (&mut handshake_failures, peer_addr)
+                                        {
+                                            let ip = peer_addr.ip();
+                                            if failures.failed(ip, settings.clock.now()) {
+                                                log::error!(
+                                                    "Banning {} after failed TLS handshakes",
+                                                    ip
+                                                );
+                                            }
+                                        }
+                                        continue;
+                                    }
+                                };
+
+                                RefinedTcpStream::new(sock)
+                            }
+                            #[cfg(not(any(
+                                feature = "ssl-openssl",
+                                feature = "ssl-rustls",
+                                feature = "ssl-native-tls"
+                            )))]
+                            Some(_) => unreachable!(),
+                        };
+
+                        let client =
+                            ClientConnection::new(write_closable, read_closable, settings.clone());
+                        Ok(match proxy_header {
+                            Some(header) => client.with_proxy_header(header),
+                            None => client,
+                        })
+                    }
+                    // woken up by `ListenerHandle::unblock`
+                    Err(_) if close_trigger.load(Relaxed) => break,
+                    Err(e) => Err(e),
+                };
+
+                match new_client {
+                    Ok(client) => {
+                        let messages = messages.clone();
+                        let mut client = Some(client);
+                        tasks_pool.spawn(Box::new(move || {
+                            if let Some(client) = client.take() {
+                                // Synchronization is needed for HTTPS requests to avoid a deadlock
+                                if client.secure() {
+                                    let (sender, receiver) = mpsc::channel();
+                                    for rq in client {
+                                        messages.push(rq.with_notify_sender(sender.clone()).into());
+                                        receiver.recv().unwrap();
+                                    }
+                                } else {
+                                    for rq in client {
+                                        messages.push(rq.into());
+                                    }
+                                }
+                            }
+                        }));
+                    }
+
+                    Err(e) => {
+                        log::error!("Error accepting new client: {}", e);
+                        messages.push(e.into());
+                        break;
+                    }
+                }
             }
+            log::debug!("Terminating accept thread");
+        })?;
+
+        Ok(handle)
+    }
+}
+
+/// Handle to a listener which its accept thread can't be woken up from by connecting to its
+/// address.
+enum ListenerWaker {
+    Custom(Arc<dyn Acceptor>),
+    // a clone of the listener, shut down to wake up the accept thread
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+impl ListenerHandle {
+    /// Unblocks the accept thread of the listener, once the server is closed.
+    fn unblock(&self) {
+        let result = match &self.waker {
+            // Connect briefly to ourselves to unblock the accept thread
+            None => self
+                .addr
+                .connect()
+                .map(|stream| drop(stream.shutdown(Shutdown::Both))),
+            Some(ListenerWaker::Custom(acceptor)) => acceptor.unblock(),
+            #[cfg(unix)]
+            Some(ListenerWaker::Unix(listener)) => connection::unblock_unix(listener, || {
+                self.socket_file
+                    .as_ref()
+                    .is_some_and(|file| file.is_owned())
+            }),
         };
-        if let Ok(stream) = maybe_stream {
-            let _ = stream.shutdown(Shutdown::Both);
+        if let Err(err) = result {
+            log::debug!(
+                "Failed to unblock the accept thread of {}: {}",
+                self.addr,
+                err
+            );
         }
 
         #[cfg(unix)]
-        if let ListenAddr::Unix(addr) = &self.listening_addr {
-            if let Some(path) = addr.as_pathname() {
-                let _ = std::fs::remove_file(path);
-            }
+        if let Some(file) = &self.socket_file {
+            file.remove();
         }
     }
 }
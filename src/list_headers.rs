@@ -0,0 +1,220 @@
+use crate::common::{Header, HeaderField, Method};
+
+impl Header {
+    /// Builds an `Allow` header listing `methods`, e.g. for a `405 Method Not Allowed` response.
+    ///
+    /// Each method is listed once, in the order of its first occurrence.
+    ///
+    /// ```
+    /// use tiny_http::{Header, Method};
+    ///
+    /// let header = Header::allow(vec![Method::Get, Method::Head, Method::Get]);
+    /// assert_eq!(header.to_string(), "Allow: GET, HEAD");
+    /// ```
+    pub fn allow<I>(methods: I) -> Header
+    where
+        I: IntoIterator<Item = Method>,
+    {
+        let mut listed: Vec<Method> = Vec::new();
+        for method in methods {
+            if !listed.contains(&method) {
+                listed.push(method);
+            }
+        }
+        let value = join(listed.iter().map(Method::as_str));
+        Header::from_bytes(&b"Allow"[..], value).unwrap()
+    }
+
+    /// Builds a `Vary` header listing the request headers that select the response.
+    ///
+    /// Each field is listed once, ignoring the case, and `*` replaces the whole list.
+    ///
+    /// ```
+    /// use tiny_http::Header;
+    ///
+    /// let fields = ["Accept-Encoding", "Origin", "accept-encoding"];
+    /// let header = Header::vary(fields.iter().map(|f| f.parse().unwrap()));
+    /// assert_eq!(header.to_string(), "Vary: Accept-Encoding, Origin");
+    /// ```
+    pub fn vary<I>(fields: I) -> Header
+    where
+        I: IntoIterator<Item = HeaderField>,
+    {
+        let mut listed: Vec<HeaderField> = Vec::new();
+        for field in fields {
+            if field.equiv("*") {
+                listed = vec![field];
+                break;
+            }
+            if !listed.contains(&field) {
+                listed.push(field);
+            }
+        }
+        let value = join(listed.iter().map(|field| field.as_str().as_str()));
+        Header::from_bytes(&b"Vary"[..], value).unwrap()
+    }
+
+    /// Builds a `Link` header (RFC 8288) from one or more links.
+    ///
+    /// Identical links are listed once. Returns an error if there is no link, if a URI contains
+    /// characters that can't appear in a URI reference, or if a relation type or the name of a
+    /// parameter isn't a token.
+    ///
+    /// ```
+    /// use tiny_http::{Header, Link};
+    ///
+    /// let header = Header::link(vec![
+    ///     Link::new("/items?page=3").rel("next"),
+    ///     Link::new("/style.css").rel("preload").param("as", "style"),
+    /// ])
+    /// .unwrap();
+    /// assert_eq!(
+    ///     header.to_string(),
+    ///     r#"Link: </items?page=3>; rel="next", </style.css>; rel="preload"; as="style""#
+    /// );
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn link<I>(links: I) -> Result<Header, ()>
+    where
+        I: IntoIterator<Item = Link>,
+    {
+        let mut listed: Vec<Link> = Vec::new();
+        for link in links {
+            if !link.is_valid() {
+                return Err(());
+            }
+            if !listed.contains(&link) {
+                listed.push(link);
+            }
+        }
+        if listed.is_empty() {
+            return Err(());
+        }
+
+        let values: Vec<String> = listed.iter().map(Link::to_value).collect();
+        Header::from_bytes(&b"Link"[..], values.join(", "))
+    }
+}
+
+/// Link to a resource related to the response, sent by [`Header::link`].
+///
+/// The relation types are deduplicated ignoring the case, and a parameter set again replaces
+/// the previous value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    uri: String,
+    rels: Vec<String>,
+    params: Vec<(String, String)>,
+}
+
+impl Link {
+    /// Builds a link to `uri`, without relation type.
+    pub fn new<S: Into<String>>(uri: S) -> Link {
+        Link {
+            uri: uri.into(),
+            rels: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Adds a relation type, e.g. `next` or `preload`.
+    pub fn rel<S: Into<String>>(mut self, rel: S) -> Link {
+        let rel = rel.into();
+        if !self.rels.iter().any(|r| r.eq_ignore_ascii_case(&rel)) {
+            self.rels.push(rel);
+        }
+        self
+    }
+
+    /// Sets a target attribute, e.g. `type` or `title`. The value is sent quoted.
+    pub fn param<N, V>(mut self, name: N, value: V) -> Link
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        let (name, value) = (name.into(), value.into());
+        match self
+            .params
+            .iter_mut()
+            .find(|(n, _)| n.eq_ignore_ascii_case(&name))
+        {
+            Some(param) => param.1 = value,
+            None => self.params.push((name, value)),
+        }
+        self
+    }
+
+    fn is_valid(&self) -> bool {
+        let uri_valid = self
+            .uri
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !matches!(b, b'<' | b'>' | b'"'));
+        let value_valid = |v: &str| v.bytes().all(|b| b == b' ' || b.is_ascii_graphic());
+        uri_valid
+            && self.rels.iter().all(|rel| is_token(rel))
+            && self.params.iter().all(|(name, value)| {
+                is_token(name) && !name.eq_ignore_ascii_case("rel") && value_valid(value)
+            })
+    }
+
+    fn to_value(&self) -> String {
+        let mut value = format!("<{}>", self.uri);
+        if !self.rels.is_empty() {
+            value.push_str(&format!("; rel=\"{}\"", self.rels.join(" ")));
+        }
+        for (name, param) in &self.params {
+            let param = param.replace('\\', "\\\\").replace('"', "\\\"");
+            value.push_str(&format!("; {}=\"{}\"", name, param));
+        }
+        value
+    }
+}
+
+fn join<'a, I: Iterator<Item = &'a str>>(items: I) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
+
+// token of RFC 9110, section 5.6.2
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Link;
+    use crate::{Header, Method};
+
+    #[test]
+    fn allow_and_vary() {
+        let header = Header::allow(vec![Method::Options, "PURGE".parse().unwrap()]);
+        assert_eq!(header.to_string(), "Allow: OPTIONS, PURGE");
+
+        let fields = ["Origin", "*", "Accept"];
+        let header = Header::vary(fields.iter().map(|f| f.parse().unwrap()));
+        assert_eq!(header.to_string(), "Vary: *");
+    }
+
+    #[test]
+    fn link_validation() {
+        assert!(Header::link(Vec::new()).is_err());
+        assert!(Header::link(vec![Link::new("/a b")]).is_err());
+        assert!(Header::link(vec![Link::new("/a>")]).is_err());
+        assert!(Header::link(vec![Link::new("/a").rel("no space")]).is_err());
+        assert!(Header::link(vec![Link::new("/a").param("rel", "next")]).is_err());
+        assert!(Header::link(vec![Link::new("/a").param("title", "x\r\ny")]).is_err());
+
+        let link = Link::new("/a")
+            .rel("next")
+            .rel("NEXT")
+            .rel("last")
+            .param("title", "old")
+            .param("Title", r#"say "hi""#);
+        let header = Header::link(vec![link.clone(), link]).unwrap();
+        assert_eq!(
+            header.to_string(),
+            r#"Link: </a>; rel="next last"; title="say \"hi\"""#
+        );
+    }
+}
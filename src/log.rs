@@ -1,17 +1,47 @@
+#[cfg(all(feature = "log", feature = "strict-responses"))]
+pub(crate) use log::warn;
 #[cfg(feature = "log")]
 pub(crate) use log::{debug, error};
 
+// the arguments are type-checked but never evaluated, so that they don't end up unused
 #[cfg(not(feature = "log"))]
 macro_rules! _debug {
-    (target: $target:expr, $($arg:tt)+) => {};
-    ($($arg:tt)+) => {};
+    (target: $target:expr, $($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+    ($($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
 }
 
 #[cfg(not(feature = "log"))]
 macro_rules! _error {
-    (target: $target:expr, $($arg:tt)+) => {};
-    ($($arg:tt)+) => {};
+    (target: $target:expr, $($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+    ($($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
 }
 
+#[cfg(all(not(feature = "log"), feature = "strict-responses"))]
+macro_rules! _warn {
+    ($($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+}
+
+#[cfg(all(not(feature = "log"), feature = "strict-responses"))]
+pub(crate) use _warn as warn;
 #[cfg(not(feature = "log"))]
 pub(crate) use {_debug as debug, _error as error};
@@ -0,0 +1,298 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use opentelemetry::global::{self, BoxedSpan, BoxedTracer};
+use opentelemetry::metrics::{Histogram, Meter, UpDownCounter};
+use opentelemetry::trace::{
+    Span, SpanContext, SpanId, SpanKind, Status, TraceContextExt, TraceFlags, TraceId, Tracer,
+};
+use opentelemetry::{Context, KeyValue};
+
+use crate::common::{HTTPVersion, StatusCode};
+use crate::Request;
+
+/// Name of the instrumentation scope of the spans and metrics.
+const SCOPE: &str = "tiny-http";
+
+/// Exports the requests of a server to OpenTelemetry, following the HTTP semantic conventions:
+/// a server span per request, and the `http.server.request.duration` and
+/// `http.server.active_requests` metrics.
+///
+/// The spans continue the [`TraceContext`](crate::TraceContext) of the requests, joining the
+/// traces of the clients. The route set with [`Request::set_route`] names the span and is an
+/// attribute of the metrics. The exporters (e.g. OTLP) are set up with the OpenTelemetry SDK.
+///
+/// Set in [`ServerConfig::otel`](crate::ServerConfig::otel). Requires the `otel` feature.
+#[derive(Debug, Clone)]
+pub struct OtelConfig {
+    /// Tracer creating the spans of the requests, `None` not to create spans.
+    pub tracer: Option<Arc<BoxedTracer>>,
+
+    /// Meter recording the metrics of the requests, `None` not to record metrics.
+    pub meter: Option<Meter>,
+}
+
+impl OtelConfig {
+    /// Uses the tracer and meter providers installed globally, with
+    /// `opentelemetry::global::set_tracer_provider` and `set_meter_provider`.
+    pub fn global() -> OtelConfig {
+        OtelConfig {
+            tracer: Some(Arc::new(global::tracer(SCOPE))),
+            meter: Some(global::meter(SCOPE)),
+        }
+    }
+}
+
+/// Instruments built from an [`OtelConfig`], shared by the connections.
+pub(crate) struct Telemetry {
+    tracer: Option<Arc<BoxedTracer>>,
+    duration: Option<Histogram<f64>>,
+    active_requests: Option<UpDownCounter<i64>>,
+}
+
+impl Telemetry {
+    pub(crate) fn new(config: OtelConfig) -> Telemetry {
+        let (duration, active_requests) = match config.meter {
+            Some(meter) => {
+                let duration = meter
+                    .f64_histogram("http.server.request.duration")
+                    .with_unit("s")
+                    .with_description("Duration of HTTP server requests.")
+                    .build();
+                let active_requests = meter
+                    .i64_up_down_counter("http.server.active_requests")
+                    .with_unit("{request}")
+                    .with_description("Number of active HTTP server requests.")
+                    .build();
+                (Some(duration), Some(active_requests))
+            }
+            None => (None, None),
+        };
+
+        Telemetry {
+            tracer: config.tracer,
+            duration,
+            active_requests,
+        }
+    }
+
+    /// Starts the span of a request and counts it as active.
+    pub(crate) fn start(self: &Arc<Self>, request: &Request) -> RequestTelemetry {
+        let method = request.method().as_str().to_owned();
+        let scheme = if request.secure() { "https" } else { "http" };
+        let attributes = vec![
+            KeyValue::new("http.request.method", method.clone()),
+            KeyValue::new("url.scheme", scheme),
+            KeyValue::new(
+                "network.protocol.version",
+                protocol_version(request.http_version()),
+            ),
+        ];
+
+        if let Some(ref active_requests) = self.active_requests {
+            active_requests.add(1, &attributes);
+        }
+
+        let span = self.tracer.as_deref().map(|tracer| {
+            let path = request.url().split('?').next().unwrap_or_default();
+            let mut span_attributes = attributes.clone();
+            span_attributes.push(KeyValue::new("url.path", path.to_owned()));
+            if let Some(remote_addr) = request.remote_addr() {
+                span_attributes.push(KeyValue::new(
+                    "client.address",
+                    remote_addr.ip().to_string(),
+                ));
+            }
+
+            let trace_context = request.trace_context();
+            let trace_id = TraceId::from_bytes(trace_context.trace_id());
+            let builder = tracer
+                .span_builder(method.clone())
+                .with_kind(SpanKind::Server)
+                .with_attributes(span_attributes);
+
+            if trace_context.is_remote() {
+                let parent = SpanContext::new(
+                    trace_id,
+                    SpanId::from_bytes(trace_context.parent_id()),
+                    TraceFlags::new(trace_context.trace_flags()),
+                    true,
+                    trace_context
+                        .tracestate()
+                        .and_then(|tracestate| tracestate.parse().ok())
+                        .unwrap_or_default(),
+                );
+                builder.start_with_context(tracer, &Context::new().with_remote_span_context(parent))
+            } else {
+                // same trace id as the generated context, which the application may propagate
+                builder
+                    .with_trace_id(trace_id)
+                    .start_with_context(tracer, &Context::new())
+            }
+        });
+
+        RequestTelemetry {
+            telemetry: self.clone(),
+            method,
+            span,
+            attributes,
+        }
+    }
+}
+
+/// Span and metrics of a request, recorded when it's answered.
+///
+/// If the request isn't answered with a response (e.g. [`Request::into_writer`]), the span ends
+/// without a status code and no duration is recorded.
+pub(crate) struct RequestTelemetry {
+    telemetry: Arc<Telemetry>,
+    method: String,
+    span: Option<BoxedSpan>,
+    // attributes of `http.server.active_requests`
+    attributes: Vec<KeyValue>,
+}
+
+impl RequestTelemetry {
+    pub(crate) fn finish(
+        mut self,
+        status_code: StatusCode,
+        route: Option<&str>,
+        duration: Duration,
+    ) {
+        let mut attributes = self.attributes.clone();
+        attributes.push(KeyValue::new(
+            "http.response.status_code",
+            i64::from(status_code.0),
+        ));
+        if let Some(route) = route {
+            attributes.push(KeyValue::new("http.route", route.to_owned()));
+        }
+
+        if let Some(ref histogram) = self.telemetry.duration {
+            histogram.record(duration.as_secs_f64(), &attributes);
+        }
+
+        if let Some(mut span) = self.span.take() {
+            if let Some(route) = route {
+                span.update_name(format!("{} {}", self.method, route));
+            }
+            span.set_attributes(attributes.into_iter().skip(self.attributes.len()));
+            // client errors are the client's fault, not the server's
+            if status_code.0 >= 500 {
+                span.set_status(Status::error(""));
+            }
+            span.end();
+        }
+    }
+}
+
+impl Drop for RequestTelemetry {
+    fn drop(&mut self) {
+        if let Some(ref active_requests) = self.telemetry.active_requests {
+            active_requests.add(-1, &self.attributes);
+        }
+    }
+}
+
+fn protocol_version(version: &HTTPVersion) -> String {
+    match *version {
+        HTTPVersion(major, 0) if major >= 2 => major.to_string(),
+        HTTPVersion(major, minor) => format!("{}.{}", major, minor),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use opentelemetry::global::BoxedTracer;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::trace::{SpanKind, Status, TracerProvider};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    use super::{OtelConfig, Telemetry};
+    use crate::{Method, Request, StatusCode, TestRequest};
+
+    #[test]
+    fn finish() {
+        let span_exporter = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_simple_exporter(span_exporter.clone())
+            .build();
+        let metric_exporter = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(metric_exporter.clone()).build())
+            .build();
+
+        let telemetry = Arc::new(Telemetry::new(OtelConfig {
+            tracer: Some(Arc::new(BoxedTracer::new(Box::new(
+                tracer_provider.tracer("test"),
+            )))),
+            meter: Some(meter_provider.meter("test")),
+        }));
+        let request: Request = TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/users/42?details")
+            .into();
+        telemetry.start(&request).finish(
+            StatusCode(503),
+            Some("/users/{id}"),
+            Duration::from_millis(250),
+        );
+        meter_provider.force_flush().unwrap();
+
+        let spans = span_exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        assert_eq!(span.name, "POST /users/{id}");
+        assert_eq!(span.span_kind, SpanKind::Server);
+        assert_eq!(span.status, Status::error(""));
+        for attribute in &[
+            KeyValue::new("http.request.method", "POST"),
+            KeyValue::new("url.path", "/users/42"),
+            KeyValue::new("http.response.status_code", 503_i64),
+            KeyValue::new("http.route", "/users/{id}"),
+        ] {
+            assert!(span.attributes.contains(attribute), "{:?}", attribute);
+        }
+
+        let resource_metrics = metric_exporter.get_finished_metrics().unwrap();
+        let metrics: Vec<_> = resource_metrics
+            .iter()
+            .flat_map(|resource| resource.scope_metrics())
+            .flat_map(|scope| scope.metrics())
+            .collect();
+
+        let duration = metrics
+            .iter()
+            .find(|metric| metric.name() == "http.server.request.duration")
+            .unwrap();
+        match duration.data() {
+            AggregatedMetrics::F64(MetricData::Histogram(histogram)) => {
+                let point = histogram.data_points().next().unwrap();
+                assert_eq!(point.count(), 1);
+                assert_eq!(point.sum(), 0.25);
+                let status_code = KeyValue::new("http.response.status_code", 503_i64);
+                assert!(point
+                    .attributes()
+                    .any(|attribute| *attribute == status_code));
+            }
+            data => panic!("unexpected duration {:?}", data),
+        }
+
+        let active_requests = metrics
+            .iter()
+            .find(|metric| metric.name() == "http.server.active_requests")
+            .unwrap();
+        match active_requests.data() {
+            AggregatedMetrics::I64(MetricData::Sum(sum)) => {
+                assert_eq!(sum.data_points().next().unwrap().value(), 0);
+            }
+            data => panic!("unexpected active requests {:?}", data),
+        }
+    }
+}
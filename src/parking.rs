@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{Read, Result as IoResult};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Request, Response};
+
+/// Parking lot of long-polling requests, answered when an event happens or after a timeout.
+///
+/// Handlers [`park`](Parking::park) requests under a key, e.g. the channel a client waits
+/// for, and a producer later answers all the requests of a key at once with
+/// [`wake`](Parking::wake). Requests still parked after their timeout are answered with
+/// `204 No Content`, and so are the requests parked when the lot is dropped.
+///
+/// A thread of the lot answers the timed out requests. The responses of the pipelined requests
+/// of a connection are still sent in order, the next ones waiting for the parked one.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use tiny_http::{Parking, Response, Server};
+///
+/// let server = Server::http("0.0.0.0:0").unwrap();
+/// let parking = Arc::new(Parking::new().unwrap());
+///
+/// let producer = parking.clone();
+/// std::thread::spawn(move || loop {
+///     std::thread::sleep(Duration::from_secs(5));
+///     producer.wake(&"news".to_owned(), Response::from_string("breaking")).unwrap();
+/// });
+///
+/// for request in server.incoming_requests() {
+///     let channel = request.url().trim_start_matches('/').to_owned();
+///     parking.park(request, channel, Duration::from_secs(30));
+/// }
+/// ```
+pub struct Parking<K> {
+    lot: Arc<Lot<K>>,
+}
+
+struct Lot<K> {
+    parked: Mutex<Parked<K>>,
+
+    // notified when a request is parked and when the lot is dropped
+    condvar: Condvar,
+}
+
+struct Parked<K> {
+    requests: HashMap<K, Vec<(Request, Instant)>>,
+
+    // set when the lot is dropped, the timer thread then answers all the requests and stops
+    closed: bool,
+}
+
+impl<K> Parking<K>
+where
+    K: Hash + Eq + Send + 'static,
+{
+    /// Builds an empty parking lot, and starts the thread answering the timed out requests.
+    pub fn new() -> IoResult<Parking<K>> {
+        let lot = Arc::new(Lot {
+            parked: Mutex::new(Parked {
+                requests: HashMap::new(),
+                closed: false,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        let inside_lot = lot.clone();
+        thread::Builder::new()
+            .name("tiny-http-parking".to_owned())
+            .spawn(move || inside_lot.run_timer())?;
+
+        Ok(Parking { lot })
+    }
+
+    /// Parks a request under `key` until [`wake`](Parking::wake) is called with the same key,
+    /// or until `timeout` elapses.
+    pub fn park(&self, request: Request, key: K, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut parked = self.lot.parked.lock().unwrap();
+        parked
+            .requests
+            .entry(key)
+            .or_default()
+            .push((request, deadline));
+        self.lot.condvar.notify_one();
+    }
+
+    /// Answers all the requests parked under `key` with `response`, and returns how many were
+    /// answered successfully.
+    ///
+    /// Fails if the body of the response can't be read, dropping the requests unanswered.
+    pub fn wake<R: Read>(&self, key: &K, response: Response<R>) -> IoResult<usize> {
+        let requests = match self.lot.parked.lock().unwrap().requests.remove(key) {
+            Some(requests) => requests,
+            None => return Ok(0),
+        };
+
+        let results = response.respond_to_all(requests.into_iter().map(|(request, _)| request))?;
+        Ok(results.iter().filter(|result| result.is_ok()).count())
+    }
+
+    /// Returns the number of requests parked.
+    pub fn len(&self) -> usize {
+        let parked = self.lot.parked.lock().unwrap();
+        parked.requests.values().map(Vec::len).sum()
+    }
+
+    /// Returns true if no request is parked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K> Drop for Parking<K> {
+    fn drop(&mut self) {
+        self.lot.parked.lock().unwrap().closed = true;
+        self.lot.condvar.notify_one();
+    }
+}
+
+impl<K> Lot<K>
+where
+    K: Hash + Eq,
+{
+    /// Answers the requests whose timeout elapsed, until the lot is dropped.
+    fn run_timer(&self) {
+        let mut parked = self.parked.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let closed = parked.closed;
+            let mut expired = Vec::new();
+            let mut next_deadline: Option<Instant> = None;
+
+            for requests in parked.requests.values_mut() {
+                let mut index = 0;
+                while index < requests.len() {
+                    let deadline = requests[index].1;
+                    if closed || deadline <= now {
+                        expired.push(requests.swap_remove(index).0);
+                    } else {
+                        next_deadline = Some(next_deadline.map_or(deadline, |d| d.min(deadline)));
+                        index += 1;
+                    }
+                }
+            }
+            parked.requests.retain(|_, requests| !requests.is_empty());
+
+            if !expired.is_empty() {
+                // answering without the lock, the clients may be slow
+                drop(parked);
+                for request in expired {
+                    let _ = request.respond(Response::empty(204));
+                }
+                parked = self.parked.lock().unwrap();
+                continue;
+            }
+            if closed {
+                return;
+            }
+
+            parked = match next_deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(now);
+                    self.condvar.wait_timeout(parked, timeout).unwrap().0
+                }
+                None => self.condvar.wait(parked).unwrap(),
+            };
+        }
+    }
+}
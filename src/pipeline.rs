@@ -0,0 +1,54 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A response that had to wait for the responses to earlier requests of the same connection
+/// before being sent, passed to a [`PipelineWaitHandler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineWait {
+    /// Address of the client, `None` for Unix sockets.
+    pub remote_addr: Option<SocketAddr>,
+
+    /// Time spent waiting for the earlier responses.
+    pub duration: Duration,
+}
+
+/// Function called when a client pipelines its requests and the response to one of them has to
+/// wait for the response to an earlier one, which happens when the earlier request is handled
+/// more slowly.
+///
+/// Frequent or long waits are a sign of head-of-line blocking, which can be avoided by limiting
+/// pipelining with [`LimitsConfig::pipeline_max`](crate::LimitsConfig::pipeline_max).
+///
+/// The function is called by the thread sending the waiting response, right before it is sent.
+///
+/// ```
+/// use tiny_http::PipelineWaitHandler;
+///
+/// let handler = PipelineWaitHandler::new(|wait| {
+///     eprintln!("response to {:?} waited {:?}", wait.remote_addr, wait.duration);
+/// });
+/// ```
+#[derive(Clone)]
+pub struct PipelineWaitHandler(Arc<dyn Fn(&PipelineWait) + Send + Sync + 'static>);
+
+impl PipelineWaitHandler {
+    /// Builds a handler from a function.
+    pub fn new<F>(f: F) -> PipelineWaitHandler
+    where
+        F: Fn(&PipelineWait) + Send + Sync + 'static,
+    {
+        PipelineWaitHandler(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, wait: &PipelineWait) {
+        (self.0)(wait)
+    }
+}
+
+impl fmt::Debug for PipelineWaitHandler {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("PipelineWaitHandler")
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::common::{HTTPVersion, Header, Method, StatusCode};
+use crate::Clock;
+
+/// Request line and headers of a request whose body hasn't been read yet.
+///
+/// Passed to the [`PreHandler`] of the server.
+#[derive(Debug)]
+pub struct RequestHead<'a> {
+    pub(crate) secure: bool,
+    pub(crate) method: &'a Method,
+    pub(crate) path: &'a str,
+    pub(crate) http_version: &'a HTTPVersion,
+    pub(crate) headers: &'a [Header],
+    pub(crate) remote_addr: Option<&'a SocketAddr>,
+    // `ServerConfig::clock`
+    #[cfg_attr(not(feature = "auth"), allow(dead_code))]
+    pub(crate) clock: &'a dyn Clock,
+}
+
+impl<'a> RequestHead<'a> {
+    /// Returns true if the request was made through HTTPS.
+    #[inline]
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Returns the method requested by the client (eg. `GET`, `POST`, etc.).
+    #[inline]
+    pub fn method(&self) -> &Method {
+        self.method
+    }
+
+    /// Returns the resource requested by the client.
+    #[inline]
+    pub fn url(&self) -> &str {
+        self.path
+    }
+
+    /// Returns a list of all headers sent by the client.
+    #[inline]
+    pub fn headers(&self) -> &[Header] {
+        self.headers
+    }
+
+    /// Returns the HTTP version of the request.
+    #[inline]
+    pub fn http_version(&self) -> &HTTPVersion {
+        self.http_version
+    }
+
+    /// Returns the address of the client that sent this request.
+    ///
+    /// See [`Request::remote_addr`](crate::Request::remote_addr).
+    #[inline]
+    pub fn remote_addr(&self) -> Option<&SocketAddr> {
+        self.remote_addr
+    }
+}
+
+/// What to do with a request, as decided by a [`PreHandler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreDecision {
+    /// The request is read and returned by [`Server::recv`](crate::Server::recv) as usual.
+    Accept,
+
+    /// The request is answered with an empty response with this status code and
+    /// `Connection: close`, then the connection is closed without reading the body.
+    Reject(StatusCode),
+}
+
+/// Function called with the head of every request before its body is read.
+///
+/// This allows cheaply shedding requests, for example unauthorized ones or ones with a
+/// `Content-Length` that is too large, before any resources are spent on them.
+///
+/// ```
+/// use tiny_http::{PreDecision, PreHandler, StatusCode};
+///
+/// let pre_handler = PreHandler::new(|head| {
+///     if head.headers().iter().any(|h| h.field.equiv("Authorization")) {
+///         PreDecision::Accept
+///     } else {
+///         PreDecision::Reject(StatusCode::UNAUTHORIZED)
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct PreHandler(Arc<dyn Fn(&RequestHead<'_>) -> PreDecision + Send + Sync + 'static>);
+
+impl PreHandler {
+    /// Builds a pre-handler from a function.
+    pub fn new<F>(f: F) -> PreHandler
+    where
+        F: Fn(&RequestHead<'_>) -> PreDecision + Send + Sync + 'static,
+    {
+        PreHandler(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, head: &RequestHead<'_>) -> PreDecision {
+        (self.0)(head)
+    }
+}
+
+impl fmt::Debug for PreHandler {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("PreHandler")
+    }
+}
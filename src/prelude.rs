@@ -0,0 +1,20 @@
+//! Types used by most request handlers, to be imported all at once.
+//!
+//! ```no_run
+//! use tiny_http::prelude::*;
+//!
+//! let server = Server::http("0.0.0.0:0").unwrap();
+//! for request in server.incoming_requests() {
+//!     let response = match request.method() {
+//!         Method::Get => {
+//!             Response::from_string("hello world").with_header(ContentType::TextPlainUtf8)
+//!         }
+//!         _ => Response::from_string("").with_status_code(StatusCode::METHOD_NOT_ALLOWED),
+//!     };
+//!     let _ = request.respond(response);
+//! }
+//! ```
+
+pub use crate::{
+    ContentType, HTTPVersion, Header, Method, Request, Response, ResponseBox, Server, StatusCode,
+};
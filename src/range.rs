@@ -0,0 +1,486 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read};
+
+use crate::common::{Header, StatusCode};
+use crate::Response;
+
+/// The `Content-Range` header of a request sending part of a resource, e.g. a `PUT` resuming an
+/// interrupted upload.
+///
+/// Returned by [`Request::content_range`](crate::Request::content_range). The body is written
+/// in place with [`Request::write_range_to`](crate::Request::write_range_to), after checking the
+/// range against the current length of the target with [`check`](ContentRange::check).
+///
+/// ```
+/// use tiny_http::{ContentRange, RangeParseError};
+///
+/// let range = ContentRange::parse("bytes 500-999/1234").unwrap();
+///
+/// assert_eq!(range.len(), 500);
+/// assert!(range.check(500).is_ok());
+/// assert!(range.check(400).is_err());
+///
+/// assert_eq!(
+///     ContentRange::parse("bytes 999-500/1234"),
+///     Err(RangeParseError::EmptySet)
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// Offset of the first byte of the body in the resource.
+    pub first: u64,
+
+    /// Offset of the last byte of the body in the resource, inclusive.
+    pub last: u64,
+
+    /// Length of the whole resource, `None` if the client doesn't know it yet (`*`).
+    pub complete_length: Option<u64>,
+}
+
+/// Error returned when the `Content-Range` of a request can't be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeError {
+    /// There are several `Content-Range` headers.
+    Duplicate,
+
+    /// The header isn't a valid range of bytes.
+    Parse(RangeParseError),
+
+    /// The length of the body doesn't match the length of the range.
+    LengthMismatch,
+
+    /// The range starts beyond the end of the target, which would leave a gap, or the complete
+    /// length is shorter than the target.
+    Unsatisfiable,
+}
+
+/// Reason why the value of a `Content-Range` header isn't a valid range of bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeParseError {
+    /// The unit isn't `bytes`.
+    BadUnit,
+
+    /// The value isn't of the form `bytes first-last/length`, e.g. `bytes */1234`.
+    Malformed,
+
+    /// A position or the length isn't a decimal number.
+    InvalidNumber,
+
+    /// A position, the length or the length of the range doesn't fit in 64 bits.
+    Overflow,
+
+    /// The last position is before the first one.
+    EmptySet,
+
+    /// The last position is beyond the complete length.
+    BeyondLength,
+}
+
+impl ContentRange {
+    /// Parses the value of a `Content-Range` header, e.g. `bytes 0-499/1234` or `bytes 0-499/*`.
+    ///
+    /// The `bytes */1234` form of the responses carries no range, and is
+    /// [`Malformed`](RangeParseError::Malformed) here.
+    pub fn parse(value: &str) -> Result<ContentRange, RangeParseError> {
+        let value = value.trim();
+        let (unit, rest) = value.split_at(value.find(' ').ok_or(RangeParseError::Malformed)?);
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return Err(RangeParseError::BadUnit);
+        }
+
+        let rest = rest.trim_start();
+        let (range, complete_length) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], &rest[pos + 1..]),
+            None => return Err(RangeParseError::Malformed),
+        };
+        let (first, last) = match range.find('-') {
+            Some(pos) => (&range[..pos], &range[pos + 1..]),
+            None => return Err(RangeParseError::Malformed),
+        };
+        let first = parse_number(first)?;
+        let last = parse_number(last)?;
+        let complete_length = match complete_length {
+            "*" => None,
+            length => Some(parse_number(length)?),
+        };
+
+        if first > last {
+            return Err(RangeParseError::EmptySet);
+        }
+        // the range would be 2^64 bytes long
+        if first == 0 && last == u64::MAX {
+            return Err(RangeParseError::Overflow);
+        }
+        if complete_length.is_some_and(|length| last >= length) {
+            return Err(RangeParseError::BeyondLength);
+        }
+
+        Ok(ContentRange {
+            first,
+            last,
+            complete_length,
+        })
+    }
+
+    /// Parses the `Content-Range` header of a request, `Ok(None)` if there is none.
+    pub fn from_headers(headers: &[Header]) -> Result<Option<ContentRange>, RangeError> {
+        let mut values = headers.iter().filter(|h| h.field.equiv("Content-Range"));
+        let header = match values.next() {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        if values.next().is_some() {
+            return Err(RangeError::Duplicate);
+        }
+
+        ContentRange::parse(header.value.as_str())
+            .map(Some)
+            .map_err(RangeError::Parse)
+    }
+
+    /// Resolves the value of the `Range` header of a request, e.g. `bytes=500-999` or
+    /// `bytes=-500`, against a resource `complete_length` bytes long.
+    ///
+    /// Returns `Ok(None)` if the whole resource must be sent: the value is invalid, has another
+    /// unit than `bytes`, or asks for several ranges, which aren't supported. Fails with
+    /// [`RangeError::Unsatisfiable`] if the range starts beyond the end of the resource.
+    ///
+    /// ```
+    /// use tiny_http::{ContentRange, RangeError};
+    ///
+    /// let range = ContentRange::from_range("bytes=-500", 1234).unwrap().unwrap();
+    /// assert_eq!(range.to_header().to_string(), "Content-Range: bytes 734-1233/1234");
+    ///
+    /// assert_eq!(ContentRange::from_range("bytes=0-1,5-6", 1234), Ok(None));
+    /// assert_eq!(
+    ///     ContentRange::from_range("bytes=1234-", 1234),
+    ///     Err(RangeError::Unsatisfiable)
+    /// );
+    /// ```
+    pub fn from_range(
+        value: &str,
+        complete_length: u64,
+    ) -> Result<Option<ContentRange>, RangeError> {
+        let value = value.trim();
+        let unit_len = "bytes=".len();
+        if !value
+            .get(..unit_len)
+            .is_some_and(|unit| unit.eq_ignore_ascii_case("bytes="))
+        {
+            return Ok(None);
+        }
+        let spec = value[unit_len..].trim();
+        let (first, last) = match spec.find('-') {
+            Some(pos) if !spec.contains(',') => (spec[..pos].trim(), spec[pos + 1..].trim()),
+            _ => return Ok(None),
+        };
+
+        let (first, last) = match (first, last) {
+            // suffix range, the last bytes of the resource
+            ("", suffix) => match parse_number(suffix) {
+                Ok(0) => return Err(RangeError::Unsatisfiable),
+                Ok(suffix) => (complete_length.saturating_sub(suffix), None),
+                Err(_) => return Ok(None),
+            },
+            (first, "") => match parse_number(first) {
+                Ok(first) => (first, None),
+                Err(_) => return Ok(None),
+            },
+            (first, last) => match (parse_number(first), parse_number(last)) {
+                (Ok(first), Ok(last)) if first <= last => (first, Some(last)),
+                _ => return Ok(None),
+            },
+        };
+        if first >= complete_length {
+            return Err(RangeError::Unsatisfiable);
+        }
+
+        let end = complete_length - 1;
+        Ok(Some(ContentRange {
+            first,
+            last: last.map_or(end, |last| last.min(end)),
+            complete_length: Some(complete_length),
+        }))
+    }
+
+    /// Builds the `Content-Range` header of a response sending this range.
+    pub fn to_header(&self) -> Header {
+        let value = match self.complete_length {
+            Some(length) => format!("bytes {}-{}/{}", self.first, self.last, length),
+            None => format!("bytes {}-{}/*", self.first, self.last),
+        };
+        Header::from_bytes(&b"Content-Range"[..], value).unwrap()
+    }
+
+    /// Returns the number of bytes of the range.
+    ///
+    /// The ranges returned by [`parse`](ContentRange::parse) and
+    /// [`from_range`](ContentRange::from_range) always have a length. A range built with `last`
+    /// before `first` has none and returns 0, and one of 2^64 bytes saturates to `u64::MAX`.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        match self.last.checked_sub(self.first) {
+            Some(len) => len.saturating_add(1),
+            None => 0,
+        }
+    }
+
+    /// Checks that the range can be written into a target currently `target_length` bytes long:
+    /// it must start within the target or right at its end, and the complete length, if known,
+    /// must not be shorter than the target.
+    pub fn check(&self, target_length: u64) -> Result<(), RangeError> {
+        if self.first > target_length
+            || self
+                .complete_length
+                .is_some_and(|length| length < target_length)
+        {
+            return Err(RangeError::Unsatisfiable);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Response<R> {
+    /// Builds a `206 Partial Content` response sending `range`, whose bytes are read from
+    /// `reader`, which must provide exactly [`range.len()`](ContentRange::len) bytes.
+    pub fn partial(range: &ContentRange, reader: R) -> Response<R> {
+        Response::new(
+            StatusCode::PARTIAL_CONTENT,
+            vec![range.to_header()],
+            reader,
+            Some(range.len() as usize),
+            None,
+        )
+    }
+}
+
+impl Response<io::Empty> {
+    /// Builds the `206 Partial Content` response to a `HEAD` request for `range`, with the
+    /// `Content-Range` and `Content-Length` headers of the response to a `GET`, without reading
+    /// the resource.
+    ///
+    /// ```
+    /// # let request: tiny_http::Request = tiny_http::TestRequest::new()
+    /// #     .with_method(tiny_http::Method::Head)
+    /// #     .with_header("Range: bytes=100-".parse().unwrap())
+    /// #     .into();
+    /// use tiny_http::Response;
+    ///
+    /// let complete_length = 1000;
+    /// match request.range(complete_length) {
+    ///     Ok(Some(range)) => request.respond(Response::partial_head(&range)),
+    ///     Ok(None) => request.respond(Response::empty(200)),
+    ///     Err(_) => request.respond(Response::range_not_satisfiable(complete_length)),
+    /// }
+    /// .unwrap();
+    /// ```
+    pub fn partial_head(range: &ContentRange) -> Response<io::Empty> {
+        Response::partial(range, io::empty())
+    }
+
+    /// Builds an empty `416 Range Not Satisfiable` response, with the `Content-Range` header
+    /// telling the length of the resource.
+    pub fn range_not_satisfiable(complete_length: u64) -> Response<io::Empty> {
+        let value = format!("bytes */{}", complete_length);
+        let header = Header::from_bytes(&b"Content-Range"[..], value).unwrap();
+        Response::empty(StatusCode::RANGE_NOT_SATISFIABLE).with_header(header)
+    }
+}
+
+impl RangeError {
+    /// Returns the status code to answer the request with: `416 Range Not Satisfiable` for
+    /// [`Unsatisfiable`](RangeError::Unsatisfiable), `400 Bad Request` otherwise.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RangeError::Unsatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
+            RangeError::Duplicate | RangeError::Parse(_) | RangeError::LengthMismatch => {
+                StatusCode::BAD_REQUEST
+            }
+        }
+    }
+}
+
+impl Display for RangeError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            RangeError::Duplicate => "there are several Content-Range headers",
+            RangeError::Parse(err) => {
+                return write!(formatter, "the Content-Range header is invalid: {}", err)
+            }
+            RangeError::LengthMismatch => "the length of the body doesn't match the range",
+            RangeError::Unsatisfiable => "the range doesn't fit the target",
+        };
+        formatter.write_str(msg)
+    }
+}
+
+impl Error for RangeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RangeError::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RangeParseError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            RangeParseError::BadUnit => "the unit isn't bytes",
+            RangeParseError::Malformed => "the value isn't a range of bytes",
+            RangeParseError::InvalidNumber => "a position isn't a decimal number",
+            RangeParseError::Overflow => "a position is too large",
+            RangeParseError::EmptySet => "the last position is before the first one",
+            RangeParseError::BeyondLength => "the last position is beyond the complete length",
+        };
+        formatter.write_str(msg)
+    }
+}
+
+impl Error for RangeParseError {}
+
+/// Parses a number of decimal digits only, unlike `u64::from_str` which accepts a `+`.
+fn parse_number(input: &str) -> Result<u64, RangeParseError> {
+    if input.is_empty() || !input.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RangeParseError::InvalidNumber);
+    }
+    // only digits, so the only possible error
+    input.parse().map_err(|_| RangeParseError::Overflow)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ContentRange, RangeError, RangeParseError};
+    use crate::{Header, Response, StatusCode};
+    use std::io::Cursor;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            ContentRange::parse("bytes 0-499/1234"),
+            Ok(ContentRange {
+                first: 0,
+                last: 499,
+                complete_length: Some(1234),
+            })
+        );
+        assert_eq!(
+            ContentRange::parse(" Bytes 500-999/* "),
+            Ok(ContentRange {
+                first: 500,
+                last: 999,
+                complete_length: None,
+            })
+        );
+
+        for (invalid, err) in &[
+            ("", RangeParseError::Malformed),
+            ("bytes */1234", RangeParseError::Malformed),
+            ("bytes 0-499", RangeParseError::Malformed),
+            ("bytes 500-499/1234", RangeParseError::EmptySet),
+            ("bytes 0-1234/1234", RangeParseError::BeyondLength),
+            ("bytes +0-499/1234", RangeParseError::InvalidNumber),
+            ("bytes 0-/1234", RangeParseError::InvalidNumber),
+            ("bytes 0-99999999999999999999/*", RangeParseError::Overflow),
+            ("bytes 0-18446744073709551615/*", RangeParseError::Overflow),
+            ("items 0-499/1234", RangeParseError::BadUnit),
+        ] {
+            assert_eq!(ContentRange::parse(invalid), Err(*err), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn from_headers() {
+        let header = Header::from_bytes(&b"content-range"[..], "bytes 0-9/*").unwrap();
+        let headers = [header.clone(), header];
+        assert_eq!(
+            ContentRange::from_headers(&headers[..1]),
+            Ok(ContentRange::parse("bytes 0-9/*").ok())
+        );
+        assert_eq!(ContentRange::from_headers(&[]), Ok(None));
+        assert_eq!(
+            ContentRange::from_headers(&headers),
+            Err(RangeError::Duplicate)
+        );
+
+        let header = Header::from_bytes(&b"Content-Range"[..], "bytes 9-0/*").unwrap();
+        assert_eq!(
+            ContentRange::from_headers(&[header]),
+            Err(RangeError::Parse(RangeParseError::EmptySet))
+        );
+    }
+
+    #[test]
+    fn check() {
+        let range = ContentRange::parse("bytes 100-199/300").unwrap();
+        assert_eq!(range.len(), 100);
+        assert_eq!(range.check(100), Ok(()));
+        assert_eq!(range.check(250), Ok(()));
+        assert_eq!(range.check(99), Err(RangeError::Unsatisfiable));
+        assert_eq!(range.check(301), Err(RangeError::Unsatisfiable));
+
+        let huge = ContentRange::parse("bytes 1-18446744073709551615/*").unwrap();
+        assert_eq!(huge.len(), u64::MAX);
+        let reversed = ContentRange {
+            first: 2,
+            last: 1,
+            complete_length: None,
+        };
+        assert_eq!(reversed.len(), 0);
+        assert_eq!(RangeError::Unsatisfiable.status_code().0, 416);
+    }
+
+    #[test]
+    fn from_range() {
+        let range = |value: &str| {
+            ContentRange::from_range(value, 1000)
+                .map(|range| range.map(|range| (range.first, range.last)))
+        };
+        assert_eq!(range("bytes=0-499"), Ok(Some((0, 499))));
+        assert_eq!(range("Bytes=500-"), Ok(Some((500, 999))));
+        assert_eq!(range("bytes=900-2000"), Ok(Some((900, 999))));
+        assert_eq!(range("bytes=-100"), Ok(Some((900, 999))));
+        assert_eq!(range("bytes=-2000"), Ok(Some((0, 999))));
+        assert_eq!(range("bytes=1000-"), Err(RangeError::Unsatisfiable));
+        assert_eq!(range("bytes=-0"), Err(RangeError::Unsatisfiable));
+        for ignored in &[
+            "",
+            "items=0-1",
+            "bytes=1-0",
+            "bytes=0-1,3-4",
+            "bytes=+1-",
+            "bytes=-",
+            "bytesé=0-1",
+            "é",
+        ] {
+            assert_eq!(range(ignored), Ok(None), "{}", ignored);
+        }
+        assert_eq!(
+            ContentRange::from_range("bytes=0-", 0),
+            Err(RangeError::Unsatisfiable)
+        );
+    }
+
+    #[test]
+    fn head_matches_get() {
+        let range = ContentRange::from_range("bytes=2-5", 10).unwrap().unwrap();
+        let get = Response::partial(&range, Cursor::new(b"2345".to_vec()));
+        let head = Response::partial_head(&range);
+        assert_eq!(get.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(head.status_code(), get.status_code());
+        assert_eq!(head.data_length(), Some(4));
+        let headers = |response: &[Header]| -> Vec<String> {
+            response.iter().map(|h| h.to_string()).collect()
+        };
+        assert_eq!(headers(head.headers()), headers(get.headers()));
+        assert_eq!(head.headers()[0].to_string(), "Content-Range: bytes 2-5/10");
+
+        let response = Response::range_not_satisfiable(10);
+        assert_eq!(
+            response.headers()[0].to_string(),
+            "Content-Range: bytes */10"
+        );
+    }
+}
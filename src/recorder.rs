@@ -0,0 +1,599 @@
+use std::fmt::Write as _;
+use std::io::{self, Cursor, Read, Result as IoResult, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::common::{HTTPVersion, Header, HeaderField, Method, StatusCode};
+use crate::request::{new_request, RequestCreationError};
+use crate::util::MemoryBudget;
+use crate::{LimitsConfig, Request, Response};
+
+/// Records requests and their responses, to inspect the traffic of a server or to replay it
+/// against a handler in tests.
+///
+/// [`Recorder::respond`] wraps the handler of a request: the head of the request and the head of
+/// the response given by the handler are recorded, with at most `max_body` bytes of each body.
+/// The recorded exchanges can then be exported as a
+/// [HAR](http://www.softwareishard.com/blog/har-12-spec/) file, or as raw HTTP/1.1 requests to be
+/// sent again to a server, and [`Recorder::replay`] passes the recorded requests to a handler in
+/// the same process.
+///
+/// Every exchange is kept in memory until [`Recorder::take`] is called.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use tiny_http::{Recorder, Response, Server};
+///
+/// let server = Server::http("0.0.0.0:0").unwrap();
+/// let recorder = Recorder::new(64 * 1024);
+///
+/// for request in server.incoming_requests().take(100) {
+///     recorder.respond(request, |request| {
+///         Response::from_string(format!("Hello from {}", request.url()))
+///     }).unwrap();
+/// }
+/// recorder.write_har(File::create("traffic.har").unwrap()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Recorder {
+    max_body: usize,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+/// A request and its response, recorded by a [`Recorder`].
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    /// Wall-clock time when the handler was called.
+    pub started_at: SystemTime,
+
+    /// Time spent handling the request and sending the response.
+    pub duration: Duration,
+
+    pub request: RecordedRequest,
+
+    pub response: RecordedResponse,
+}
+
+/// Request recorded by a [`Recorder`].
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+
+    /// Path and query of the request.
+    pub url: String,
+
+    pub http_version: HTTPVersion,
+
+    pub headers: Vec<Header>,
+
+    /// Address of the client, `None` for Unix sockets.
+    pub remote_addr: Option<SocketAddr>,
+
+    /// True if the request was made through HTTPS.
+    pub secure: bool,
+
+    /// Start of the body, at most the `max_body` bytes of the recorder.
+    pub body: Vec<u8>,
+
+    /// True if the body is longer than what has been recorded.
+    pub body_truncated: bool,
+}
+
+/// Response recorded by a [`Recorder`], as given by the handler: the headers added by the server
+/// when sending it, e.g. `Date` or `Content-Length`, aren't included.
+#[derive(Debug, Clone)]
+pub struct RecordedResponse {
+    pub status_code: StatusCode,
+
+    pub headers: Vec<Header>,
+
+    /// Start of the body sent, at most the `max_body` bytes of the recorder. Empty if the body
+    /// wasn't sent, e.g. in response to a `HEAD` request.
+    pub body: Vec<u8>,
+
+    /// True if the body sent is longer than what has been recorded.
+    pub body_truncated: bool,
+}
+
+impl Recorder {
+    /// Builds a recorder keeping at most `max_body` bytes of each body.
+    pub fn new(max_body: usize) -> Recorder {
+        Recorder {
+            max_body,
+            exchanges: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answers the request with the response of `handler`, and records both.
+    ///
+    /// The start of the body of the request is read before `handler` is called, which still
+    /// reads the whole body from [`Request::as_reader`].
+    pub fn respond<F, R>(&self, mut request: Request, handler: F) -> IoResult<()>
+    where
+        F: FnOnce(&mut Request) -> Response<R>,
+        R: Read,
+    {
+        let clock = request.clock().clone();
+        let started_at = clock.system_time();
+        let start = clock.now();
+
+        let mut body = Vec::new();
+        request
+            .as_reader()
+            .take((self.max_body as u64).saturating_add(1))
+            .read_to_end(&mut body)?;
+        request.prepend_body(body.clone());
+        let body_truncated = body.len() > self.max_body;
+        body.truncate(self.max_body);
+
+        let recorded_request = RecordedRequest {
+            method: request.method().clone(),
+            url: request.url().to_owned(),
+            http_version: request.http_version().clone(),
+            headers: request.headers().to_vec(),
+            remote_addr: request.remote_addr().copied(),
+            secure: request.secure(),
+            body,
+            body_truncated,
+        };
+
+        let response = handler(&mut request);
+        let status_code = response.status_code();
+        let headers = response.headers().to_vec();
+        let capture = Arc::new(Mutex::new(Capture::default()));
+        let response = response.map_reader(|reader| CapturingReader {
+            inner: reader,
+            capture: capture.clone(),
+            max: self.max_body,
+        });
+        let result = request.respond(response);
+
+        let capture = std::mem::take(&mut *capture.lock().unwrap());
+        self.exchanges.lock().unwrap().push(Exchange {
+            started_at,
+            duration: clock.now().saturating_duration_since(start),
+            request: recorded_request,
+            response: RecordedResponse {
+                status_code,
+                headers,
+                body: capture.body,
+                body_truncated: capture.truncated,
+            },
+        });
+        result
+    }
+
+    /// Returns a copy of the exchanges recorded so far, in the order they were answered.
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().unwrap().clone()
+    }
+
+    /// Removes the exchanges recorded so far and returns them.
+    pub fn take(&self) -> Vec<Exchange> {
+        std::mem::take(&mut *self.exchanges.lock().unwrap())
+    }
+
+    /// Calls `handler` with each recorded request, in the order they were recorded, e.g. to
+    /// load test a handler with real traffic. The responses go nowhere.
+    ///
+    /// Fails without calling `handler` if a recorded request can't be built again, see
+    /// [`RecordedRequest::to_request`].
+    pub fn replay<F>(&self, mut handler: F) -> IoResult<()>
+    where
+        F: FnMut(Request),
+    {
+        let requests = self
+            .exchanges
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|exchange| exchange.request.to_request())
+            .collect::<IoResult<Vec<Request>>>()?;
+        for request in requests {
+            handler(request);
+        }
+        Ok(())
+    }
+
+    /// Writes the recorded exchanges in the HAR 1.2 format.
+    ///
+    /// Bodies that aren't UTF-8 are encoded in base64.
+    pub fn write_har<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        let mut out = String::new();
+        out.push_str("{\"log\":{\"version\":\"1.2\",\"creator\":{\"name\":\"tiny-http\",");
+        let _ = write!(out, "\"version\":\"{}\"}},", env!("CARGO_PKG_VERSION"));
+        out.push_str("\"entries\":[");
+        for (i, exchange) in self.exchanges.lock().unwrap().iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            har_entry(&mut out, exchange);
+        }
+        out.push_str("]}}");
+        writer.write_all(out.as_bytes())
+    }
+
+    /// Writes the recorded requests as raw HTTP/1.1 messages, one after the other, which can be
+    /// sent again to a server on a single connection.
+    ///
+    /// The body of a request is sent with a `Content-Length` header, even if it was chunked, and
+    /// only the recorded part of a truncated body is sent.
+    pub fn write_raw<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        for exchange in self.exchanges.lock().unwrap().iter() {
+            let request = &exchange.request;
+            write!(writer, "{} {} HTTP/1.1\r\n", request.method, request.url)?;
+            for header in request.replay_headers() {
+                write!(writer, "{}: {}\r\n", header.field, header.value)?;
+            }
+            writer.write_all(b"\r\n")?;
+            writer.write_all(&request.body)?;
+        }
+        writer.flush()
+    }
+}
+
+impl RecordedRequest {
+    /// Builds a request with the same head and body, whose response goes nowhere.
+    ///
+    /// Fails with an error of kind `InvalidData` if the head isn't accepted by the server, e.g.
+    /// an `Expect` header it doesn't support, or a `Transfer-Encoding` whose last coding isn't
+    /// `chunked`.
+    pub fn to_request(&self) -> IoResult<Request> {
+        new_request(
+            self.secure,
+            self.method.clone(),
+            self.url.clone(),
+            self.http_version.clone(),
+            self.replay_headers(),
+            self.remote_addr,
+            Cursor::new(self.body.clone()),
+            io::sink(),
+            &LimitsConfig::default(),
+            &MemoryBudget::new(usize::MAX),
+        )
+        .map_err(|err| match err {
+            RequestCreationError::CreationIoError(err) => err,
+            RequestCreationError::ExpectationFailed => {
+                io::Error::new(io::ErrorKind::InvalidData, "Unsupported Expect header")
+            }
+            RequestCreationError::InvalidContentLength
+            | RequestCreationError::InvalidTransferEncoding => {
+                io::Error::new(io::ErrorKind::InvalidData, "Invalid body length")
+            }
+        })
+    }
+
+    /// Headers of the request, with the length of the recorded body.
+    fn replay_headers(&self) -> Vec<Header> {
+        let mut headers: Vec<Header> = self
+            .headers
+            .iter()
+            .filter(|h| !h.field.equiv("Content-Length") && !h.field.equiv("Transfer-Encoding"))
+            .cloned()
+            .collect();
+        if !self.body.is_empty() || self.body_truncated {
+            headers.push(Header {
+                field: "Content-Length".parse::<HeaderField>().unwrap(),
+                value: self.body.len().to_string().parse().unwrap(),
+            });
+        }
+        headers
+    }
+}
+
+/// Start of a body read through a `CapturingReader`.
+#[derive(Debug, Default)]
+struct Capture {
+    body: Vec<u8>,
+    truncated: bool,
+}
+
+/// Reader keeping a copy of the first `max` bytes read.
+struct CapturingReader<R> {
+    inner: R,
+    capture: Arc<Mutex<Capture>>,
+    max: usize,
+}
+
+impl<R: Read> Read for CapturingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let len = self.inner.read(buf)?;
+        let mut capture = self.capture.lock().unwrap();
+        let kept = len.min(self.max - capture.body.len());
+        capture.body.extend_from_slice(&buf[..kept]);
+        capture.truncated |= kept < len;
+        Ok(len)
+    }
+}
+
+fn har_entry(out: &mut String, exchange: &Exchange) {
+    let request = &exchange.request;
+    let response = &exchange.response;
+    let time = exchange.duration.as_secs_f64() * 1000.0;
+
+    out.push_str("{\"startedDateTime\":");
+    json_string(out, &iso8601(exchange.started_at));
+    let _ = write!(out, ",\"time\":{:.3},\"request\":{{\"method\":", time);
+    json_string(out, request.method.as_str());
+    out.push_str(",\"url\":");
+    let host = request
+        .headers
+        .iter()
+        .find(|h| h.field.equiv("Host"))
+        .map(|h| h.value.as_str());
+    match host {
+        Some(host) => {
+            let scheme = if request.secure { "https" } else { "http" };
+            json_string(out, &format!("{}://{}{}", scheme, host, request.url));
+        }
+        None => json_string(out, &request.url),
+    }
+    let _ = write!(
+        out,
+        ",\"httpVersion\":\"HTTP/{}\",\"cookies\":[],\"headers\":",
+        request.http_version
+    );
+    har_headers(out, &request.headers);
+    out.push_str(",\"queryString\":[]");
+    if !request.body.is_empty() {
+        out.push_str(",\"postData\":{\"mimeType\":");
+        json_string(out, content_type(&request.headers));
+        har_text(out, &request.body);
+        out.push('}');
+    }
+    let _ = write!(
+        out,
+        ",\"headersSize\":-1,\"bodySize\":{}}},\"response\":{{\"status\":{},\"statusText\":",
+        request.body.len(),
+        response.status_code.0
+    );
+    json_string(out, response.status_code.default_reason_phrase());
+    let _ = write!(
+        out,
+        ",\"httpVersion\":\"HTTP/{}\",\"cookies\":[],\"headers\":",
+        request.http_version
+    );
+    har_headers(out, &response.headers);
+    let _ = write!(
+        out,
+        ",\"content\":{{\"size\":{},\"mimeType\":",
+        response.body.len()
+    );
+    json_string(out, content_type(&response.headers));
+    har_text(out, &response.body);
+    let _ = write!(
+        out,
+        "}},\"redirectURL\":\"\",\"headersSize\":-1,\"bodySize\":{}}},\"cache\":{{}},",
+        response.body.len()
+    );
+    let _ = write!(
+        out,
+        "\"timings\":{{\"send\":0,\"wait\":{:.3},\"receive\":0}}}}",
+        time
+    );
+}
+
+fn har_headers(out: &mut String, headers: &[Header]) {
+    out.push('[');
+    for (i, header) in headers.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        json_string(out, header.field.as_str().as_str());
+        out.push_str(",\"value\":");
+        json_string(out, header.value.as_str());
+        out.push('}');
+    }
+    out.push(']');
+}
+
+// the `text` of a body, and its `encoding` if it isn't UTF-8
+fn har_text(out: &mut String, body: &[u8]) {
+    out.push_str(",\"text\":");
+    match std::str::from_utf8(body) {
+        Ok(text) => json_string(out, text),
+        Err(_) => {
+            json_string(out, &base64(body));
+            out.push_str(",\"encoding\":\"base64\"");
+        }
+    }
+}
+
+fn content_type(headers: &[Header]) -> &str {
+    headers
+        .iter()
+        .find(|h| h.field.equiv("Content-Type"))
+        .map_or("", |h| h.value.as_str())
+}
+
+pub(crate) fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// e.g. `2024-05-01T12:30:00.000Z`
+fn iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // civil date from the number of days since 1970-01-01, by Howard Hinnant
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{base64, iso8601, json_string, Recorder};
+    use crate::{Header, Method, Response, TestRequest};
+    use std::io::ErrorKind;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn records_bounded_bodies() {
+        let recorder = Recorder::new(4);
+        let request = TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/upload")
+            .with_body("0123456789");
+
+        recorder
+            .respond(request.into(), |request| {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                assert_eq!(body, "0123456789");
+                Response::from_string("ok")
+            })
+            .unwrap();
+
+        let exchanges = recorder.exchanges();
+        assert_eq!(exchanges.len(), 1);
+        let exchange = &exchanges[0];
+        assert_eq!(exchange.request.url, "/upload");
+        assert_eq!(exchange.request.body, b"0123");
+        assert!(exchange.request.body_truncated);
+        assert_eq!(exchange.response.body, b"ok");
+        assert!(!exchange.response.body_truncated);
+
+        let mut raw = Vec::new();
+        recorder.write_raw(&mut raw).unwrap();
+        assert_eq!(
+            String::from_utf8(raw).unwrap(),
+            "POST /upload HTTP/1.1\r\nContent-Length: 4\r\n\r\n0123"
+        );
+
+        let mut replayed = Vec::new();
+        recorder
+            .replay(|mut request| {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                replayed.push((request.url().to_owned(), body));
+            })
+            .unwrap();
+        assert_eq!(replayed, vec![("/upload".to_owned(), "0123".to_owned())]);
+
+        assert_eq!(recorder.take().len(), 1);
+        assert!(recorder.exchanges().is_empty());
+    }
+
+    #[test]
+    fn unlimited_bodies() {
+        let recorder = Recorder::new(usize::MAX);
+        let request = TestRequest::new()
+            .with_method(Method::Post)
+            .with_body("0123456789");
+        recorder
+            .respond(request.into(), |_| Response::empty(204))
+            .unwrap();
+
+        let exchanges = recorder.exchanges();
+        assert_eq!(exchanges[0].request.body, b"0123456789");
+        assert!(!exchanges[0].request.body_truncated);
+    }
+
+    #[test]
+    fn request_not_replayable() {
+        let recorder = Recorder::new(1024);
+        recorder
+            .respond(TestRequest::new().into(), |_| Response::empty(204))
+            .unwrap();
+
+        let mut exchanges = recorder.take();
+        exchanges[0].request.headers = vec!["Expect: teapot".parse::<Header>().unwrap()];
+        let err = exchanges[0].request.to_request().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn har_format() {
+        let recorder = Recorder::new(1024);
+        recorder
+            .respond(TestRequest::new().into(), |_| {
+                Response::from_data(vec![0xff, 0x00])
+            })
+            .unwrap();
+
+        let mut har = Vec::new();
+        recorder.write_har(&mut har).unwrap();
+        let har = String::from_utf8(har).unwrap();
+        assert!(har.starts_with("{\"log\":{\"version\":\"1.2\""));
+        assert!(har.contains("\"method\":\"GET\",\"url\":\"/\""));
+        assert!(har.contains("\"status\":200,\"statusText\":\"OK\""));
+        assert!(har.contains("\"text\":\"/wA=\",\"encoding\":\"base64\""));
+        assert!(har.ends_with("}]}}"));
+    }
+
+    #[test]
+    fn encodings() {
+        let mut s = String::new();
+        json_string(&mut s, "a\"b\\c\n\u{1}");
+        assert_eq!(s, r#""a\"b\\c\n\u0001""#);
+
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+
+        assert_eq!(iso8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            iso8601(UNIX_EPOCH + Duration::from_millis(1_709_210_096_789)),
+            "2024-02-29T12:34:56.789Z"
+        );
+    }
+}
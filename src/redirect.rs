@@ -0,0 +1,518 @@
+use std::io::{self, Read, Result as IoResult};
+
+use crate::common::{Header, Method, StatusCode};
+use crate::{Request, Response};
+
+impl Response<io::Empty> {
+    /// Builds an empty redirection to `location`, which can't inject headers into the response.
+    ///
+    /// Returns an error if `status` isn't a `3xx` status code, or if `location` is empty or
+    /// contains anything else than the characters allowed in a URI, such as CR, LF or spaces,
+    /// or a `%` not followed by two hexadecimal digits. Characters outside this set must be
+    /// percent-encoded by the caller.
+    ///
+    /// ```
+    /// use tiny_http::{Response, StatusCode};
+    ///
+    /// let response = Response::redirect(StatusCode::SEE_OTHER, "/orders/42").unwrap();
+    /// assert_eq!(response.headers()[0].to_string(), "Location: /orders/42");
+    ///
+    /// assert!(Response::redirect(302, "/\r\nSet-Cookie: a=b").is_err());
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn redirect<S>(status: S, location: &str) -> Result<Response<io::Empty>, ()>
+    where
+        S: Into<StatusCode>,
+    {
+        let status = status.into();
+        if !status.is_redirection() || !is_uri_reference(location) {
+            return Err(());
+        }
+
+        let header = Header::from_bytes(&b"Location"[..], location).map_err(|_| ())?;
+        Ok(Response::empty(status).with_header(header))
+    }
+
+    /// Builds an empty redirection as [`Response::redirect`] does, with `location` resolved
+    /// against the URL of `request` into an absolute URL, using its `Host` header and `https` if
+    /// the request was made through HTTPS.
+    ///
+    /// Returns an error if the request has no valid `Host` header.
+    ///
+    /// ```
+    /// # let request: tiny_http::Request = tiny_http::TestRequest::new()
+    /// #     .with_path("/docs/guide/intro")
+    /// #     .with_header("Host: example.com".parse().unwrap())
+    /// #     .into();
+    /// use tiny_http::Response;
+    ///
+    /// // for a request of `/docs/guide/intro` with `Host: example.com`
+    /// let response = Response::redirect_absolute(&request, 301, "../api").unwrap();
+    /// assert_eq!(
+    ///     response.headers()[0].to_string(),
+    ///     "Location: http://example.com/docs/api"
+    /// );
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn redirect_absolute<S>(
+        request: &Request,
+        status: S,
+        location: &str,
+    ) -> Result<Response<io::Empty>, ()>
+    where
+        S: Into<StatusCode>,
+    {
+        if !is_uri_reference(location) {
+            return Err(());
+        }
+        let location = resolve(request, location).ok_or(())?;
+        Response::redirect(status, &location)
+    }
+}
+
+/// What [`CanonicalRedirect`] does with the slash at the end of the paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Paths are left as they are.
+    Keep,
+    /// Paths get a trailing slash, except the ones whose last segment has an extension, such as
+    /// `/style.css`.
+    Add,
+    /// Trailing slashes are removed, except for `/`.
+    Remove,
+}
+
+/// Canonicalization rules applied by a [`CanonicalRedirect`] to a part of the URLs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanonicalPolicy {
+    trailing_slash: TrailingSlash,
+    lowercase_host: bool,
+    https: bool,
+}
+
+impl CanonicalPolicy {
+    /// Builds a policy leaving the URLs as they are, until rules are added.
+    pub fn new() -> CanonicalPolicy {
+        CanonicalPolicy {
+            trailing_slash: TrailingSlash::Keep,
+            lowercase_host: false,
+            https: false,
+        }
+    }
+
+    /// Adds or removes the trailing slashes of the paths.
+    pub fn with_trailing_slash(mut self, trailing_slash: TrailingSlash) -> CanonicalPolicy {
+        self.trailing_slash = trailing_slash;
+        self
+    }
+
+    /// Redirects the requests whose `Host` header has uppercase letters to the lowercase host.
+    pub fn with_lowercase_host(mut self) -> CanonicalPolicy {
+        self.lowercase_host = true;
+        self
+    }
+
+    /// Redirects the requests not made through HTTPS to the same URL with `https`. The port of
+    /// the `Host` header, if any, is kept.
+    pub fn with_https(mut self) -> CanonicalPolicy {
+        self.https = true;
+        self
+    }
+}
+
+impl Default for CanonicalPolicy {
+    fn default() -> CanonicalPolicy {
+        CanonicalPolicy::new()
+    }
+}
+
+/// Redirects the requests to the canonical form of their URL, following policies configured
+/// for the URLs starting with given prefixes.
+///
+/// When several prefixes match a URL, only the policy of the longest one is applied. The
+/// redirections are `301 Moved Permanently` for `GET` and `HEAD` requests, and
+/// `308 Permanent Redirect` for the other methods, which must be repeated with their body.
+///
+/// ```
+/// use tiny_http::{CanonicalPolicy, CanonicalRedirect, TestRequest, TrailingSlash};
+///
+/// let canonical = CanonicalRedirect::new()
+///     .mount("/", CanonicalPolicy::new().with_https().with_lowercase_host())
+///     .mount(
+///         "/docs/",
+///         CanonicalPolicy::new().with_trailing_slash(TrailingSlash::Add),
+///     );
+///
+/// let request = TestRequest::new()
+///     .with_path("/docs/intro?v=2")
+///     .with_https()
+///     .with_header("Host: example.com".parse().unwrap())
+///     .into();
+/// assert_eq!(
+///     canonical.canonical_url(&request).as_deref(),
+///     Some("/docs/intro/?v=2")
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CanonicalRedirect {
+    mounts: Vec<(String, CanonicalPolicy)>,
+}
+
+impl CanonicalRedirect {
+    /// Builds a middleware without any policy, until some are mounted.
+    pub fn new() -> CanonicalRedirect {
+        CanonicalRedirect::default()
+    }
+
+    /// Applies `policy` to the URLs starting with `prefix`, replacing the previous policy of
+    /// this prefix if any.
+    pub fn mount(mut self, prefix: &str, policy: CanonicalPolicy) -> CanonicalRedirect {
+        match self.mounts.iter_mut().find(|(p, _)| p == prefix) {
+            Some(mount) => mount.1 = policy,
+            None => self.mounts.push((prefix.to_owned(), policy)),
+        }
+        self
+    }
+
+    /// Returns the policy of the URL, the one of the longest matching prefix.
+    pub fn policy(&self, url: &str) -> Option<&CanonicalPolicy> {
+        self.mounts
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, policy)| policy)
+    }
+
+    /// Returns the URL the request must be redirected to, or `None` if its URL is already
+    /// canonical. The URL is absolute if the scheme or the host change. The leading slashes of
+    /// the path are collapsed into one, so that the URL always stays on the same host.
+    ///
+    /// Requests without a valid `Host` header are only redirected for their path.
+    pub fn canonical_url(&self, request: &Request) -> Option<String> {
+        let target = target(request);
+        let (path, query) = match target.find('?') {
+            Some(pos) => target.split_at(pos),
+            None => (target.as_str(), ""),
+        };
+        let policy = self.policy(path)?;
+
+        let canonical_path = match policy.trailing_slash {
+            TrailingSlash::Add if !path.ends_with('/') => {
+                let last_segment = &path[path.rfind('/').map_or(0, |pos| pos + 1)..];
+                if last_segment.contains('.') {
+                    path.to_owned()
+                } else {
+                    format!("{}/", path)
+                }
+            }
+            TrailingSlash::Remove if path.len() > 1 && path.ends_with('/') => {
+                let trimmed = path.trim_end_matches('/');
+                if trimmed.is_empty() {
+                    "/".to_owned()
+                } else {
+                    trimmed.to_owned()
+                }
+            }
+            _ => path.to_owned(),
+        };
+        // `//host` would be taken by browsers for a protocol-relative URL to another host
+        let canonical_path = if canonical_path.starts_with("//") {
+            format!("/{}", canonical_path.trim_start_matches('/'))
+        } else {
+            canonical_path
+        };
+
+        let host = host(request);
+        let upgrade = policy.https && !request.secure();
+        let lowercase = policy.lowercase_host
+            && host.is_some_and(|host| host.bytes().any(|b| b.is_ascii_uppercase()));
+
+        match host {
+            Some(host) if upgrade || lowercase => {
+                let scheme = if upgrade { "https" } else { scheme(request) };
+                let host = if policy.lowercase_host {
+                    host.to_ascii_lowercase()
+                } else {
+                    host.to_owned()
+                };
+                Some(format!("{}://{}{}{}", scheme, host, canonical_path, query))
+            }
+            _ if canonical_path != path => Some(format!("{}{}", canonical_path, query)),
+            _ => None,
+        }
+    }
+
+    /// Returns the redirection of the request to the canonical form of its URL, or `None` if
+    /// its URL is already canonical.
+    pub fn redirect(&self, request: &Request) -> Option<Response<io::Empty>> {
+        let location = self.canonical_url(request)?;
+        let status = match request.method() {
+            Method::Get | Method::Head => StatusCode::MOVED_PERMANENTLY,
+            _ => StatusCode::PERMANENT_REDIRECT,
+        };
+        Response::redirect(status, &location).ok()
+    }
+
+    /// Answers the request with its redirection if its URL isn't canonical, or with the
+    /// response of `handler` otherwise.
+    pub fn respond<F, R>(&self, mut request: Request, handler: F) -> IoResult<()>
+    where
+        F: FnOnce(&mut Request) -> Response<R>,
+        R: Read,
+    {
+        match self.redirect(&request) {
+            Some(response) => request.respond(response),
+            None => {
+                let response = handler(&mut request);
+                request.respond(response)
+            }
+        }
+    }
+}
+
+/// Returns true if `location` is a non-empty string of the characters allowed in a URI
+/// reference by RFC 3986, with well-formed percent-encoded bytes.
+pub(crate) fn is_uri_reference(location: &str) -> bool {
+    let bytes = location.as_bytes();
+    !bytes.is_empty()
+        && bytes.iter().enumerate().all(|(i, &byte)| match byte {
+            b'%' => bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|hex| hex.iter().all(|byte| byte.is_ascii_hexdigit())),
+            _ => byte.is_ascii_alphanumeric() || b"-._~:/?#[]@!$&'()*+,;=".contains(&byte),
+        })
+}
+
+/// Returns the `Host` header of the request, or `None` if there is none or if it isn't a valid
+/// `host[:port]`.
+fn host(request: &Request) -> Option<&str> {
+    let host = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Host"))?
+        .value
+        .as_str();
+    let valid = !host.is_empty()
+        && host
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-._:[]".contains(&byte));
+    if valid {
+        Some(host)
+    } else {
+        None
+    }
+}
+
+/// Returns the URL of the request in origin form, `/path?query`.
+fn target(request: &Request) -> String {
+    match crate::url::split_absolute_form(request.url()) {
+        Some((_, origin_form)) => origin_form,
+        None => request.url().to_owned(),
+    }
+}
+
+fn scheme(request: &Request) -> &'static str {
+    if request.secure() {
+        "https"
+    } else {
+        "http"
+    }
+}
+
+// resolution of a URI reference against the URL of the request, RFC 3986 section 5.2
+fn resolve(request: &Request, location: &str) -> Option<String> {
+    if has_scheme(location) {
+        return Some(location.to_owned());
+    }
+    if location.starts_with("//") {
+        return Some(format!("{}:{}", scheme(request), location));
+    }
+
+    let origin = format!("{}://{}", scheme(request), host(request)?);
+    let target = target(request);
+    let (base_path, base_query) = match target.find('?') {
+        Some(pos) => target.split_at(pos),
+        None => (target.as_str(), ""),
+    };
+
+    let (path, rest) = match location.find(['?', '#']) {
+        Some(pos) => location.split_at(pos),
+        None => (location, ""),
+    };
+    let path = if path.is_empty() {
+        // only a query or a fragment, which keeps the path, and the query for a fragment
+        return Some(if rest.starts_with('#') {
+            format!("{}{}{}{}", origin, base_path, base_query, rest)
+        } else {
+            format!("{}{}{}", origin, base_path, rest)
+        });
+    } else if path.starts_with('/') {
+        remove_dot_segments(path)
+    } else {
+        let directory = &base_path[..base_path.rfind('/').map_or(0, |pos| pos + 1)];
+        remove_dot_segments(&format!("/{}{}", directory.trim_start_matches('/'), path))
+    };
+
+    Some(format!("{}{}{}", origin, path, rest))
+}
+
+fn has_scheme(location: &str) -> bool {
+    match location.find(':') {
+        Some(pos) => {
+            let scheme = &location[..pos];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i + 1 == segments.len();
+        match *segment {
+            "." | ".." => {
+                if *segment == ".." {
+                    output.pop();
+                }
+                // a trailing dot segment still designates a directory
+                if last {
+                    output.push("");
+                }
+            }
+            segment => output.push(segment),
+        }
+    }
+    format!("/{}", output.join("/"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CanonicalPolicy, CanonicalRedirect, TrailingSlash};
+    use crate::{Method, Request, Response, StatusCode, TestRequest};
+
+    fn location(request: &Request, location: &str) -> Option<String> {
+        let response = Response::redirect_absolute(request, 302, location).ok()?;
+        Some(response.headers()[0].value.to_string())
+    }
+
+    #[test]
+    fn rejects_invalid_locations() {
+        assert!(Response::redirect(StatusCode::FOUND, "/a%20b?c=d#e").is_ok());
+        assert!(Response::redirect(StatusCode::OK, "/").is_err());
+        assert!(Response::redirect(301, "").is_err());
+        assert!(Response::redirect(301, "/a\nb").is_err());
+        assert!(Response::redirect(301, "/a\rb").is_err());
+        assert!(Response::redirect(301, "/a b").is_err());
+        assert!(Response::redirect(301, "/caf\u{e9}").is_err());
+        assert!(Response::redirect(301, "/50%").is_err());
+        assert!(Response::redirect(301, "/50%2").is_err());
+    }
+
+    #[test]
+    fn resolves_against_request() {
+        let request: Request = TestRequest::new()
+            .with_path("/a/b/c?q=1")
+            .with_header("Host: example.com:8080".parse().unwrap())
+            .into();
+
+        let cases = [
+            ("https://other.org/x", "https://other.org/x"),
+            ("//cdn.example.com/x", "http://cdn.example.com/x"),
+            ("/x/./y", "http://example.com:8080/x/y"),
+            ("d", "http://example.com:8080/a/b/d"),
+            ("../d?r=2", "http://example.com:8080/a/d?r=2"),
+            ("../../../d", "http://example.com:8080/d"),
+            ("..", "http://example.com:8080/a/"),
+            ("?r=2", "http://example.com:8080/a/b/c?r=2"),
+            ("#top", "http://example.com:8080/a/b/c?q=1#top"),
+        ];
+        for &(reference, expected) in &cases {
+            assert_eq!(location(&request, reference).as_deref(), Some(expected));
+        }
+
+        let request: Request = TestRequest::new()
+            .with_header("Host: evil.com/x".parse().unwrap())
+            .into();
+        assert_eq!(location(&request, "/x"), None);
+        let request: Request = TestRequest::new().into();
+        assert_eq!(location(&request, "/x"), None);
+    }
+
+    #[test]
+    fn canonical_redirects() {
+        let canonical = CanonicalRedirect::new()
+            .mount(
+                "/",
+                CanonicalPolicy::new()
+                    .with_trailing_slash(TrailingSlash::Remove)
+                    .with_lowercase_host(),
+            )
+            .mount(
+                "/docs/",
+                CanonicalPolicy::new()
+                    .with_trailing_slash(TrailingSlash::Add)
+                    .with_https(),
+            );
+        let request = |path: &str, host: &str, https: bool| -> Request {
+            let request = TestRequest::new()
+                .with_path(path)
+                .with_header(format!("Host: {}", host).parse().unwrap());
+            if https {
+                request.with_https().into()
+            } else {
+                request.into()
+            }
+        };
+
+        let cases = [
+            ("/", "example.com", false, None),
+            ("/a/?q=1", "example.com", false, Some("/a?q=1")),
+            ("/a//", "example.com", true, Some("/a")),
+            ("/a", "Example.COM", false, Some("http://example.com/a")),
+            ("/docs/intro", "example.com", true, Some("/docs/intro/")),
+            ("/docs/app.js", "example.com", true, None),
+            ("//evil.com/", "example.com", false, Some("/evil.com")),
+            ("//evil.com", "example.com", false, Some("/evil.com")),
+            (
+                "///evil.com?q=1",
+                "example.com",
+                false,
+                Some("/evil.com?q=1"),
+            ),
+            (
+                "/docs/",
+                "example.com:8080",
+                false,
+                Some("https://example.com:8080/docs/"),
+            ),
+        ];
+        for &(path, host, https, expected) in &cases {
+            let url = canonical.canonical_url(&request(path, host, https));
+            assert_eq!(url.as_deref(), expected, "{}", path);
+        }
+
+        let add = CanonicalRedirect::new().mount(
+            "/",
+            CanonicalPolicy::new().with_trailing_slash(TrailingSlash::Add),
+        );
+        let url = add.canonical_url(&request("//evil.com", "example.com", false));
+        assert_eq!(url.as_deref(), Some("/evil.com"));
+
+        let response = canonical
+            .redirect(&request("/a/", "example.com", false))
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCode::MOVED_PERMANENTLY);
+        let post: Request = TestRequest::new()
+            .with_method(Method::Post)
+            .with_path("/a/")
+            .into();
+        let response = canonical.redirect(&post).unwrap();
+        assert_eq!(response.status_code(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[0].to_string(), "Location: /a");
+    }
+}
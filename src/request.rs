@@ -1,15 +1,44 @@
 use std::io::Error as IoError;
-use std::io::{self, Cursor, ErrorKind, Read, Write};
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
 
+use std::error::Error;
 use std::fmt;
-use std::net::SocketAddr;
+use std::mem;
+use std::net::{Shutdown, SocketAddr};
 use std::str::FromStr;
 
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::util::{EqualReader, FusedReader};
-use crate::{HTTPVersion, Header, Method, Response, StatusCode};
-use chunked_transfer::Decoder;
+use crate::client::PipelineGuard;
+use crate::clock::{Clock, SystemClock};
+use crate::error_renderer::{error_response, ErrorRenderer};
+use crate::extensions::Extensions;
+use crate::log;
+#[cfg(feature = "otel")]
+use crate::otel::{RequestTelemetry, Telemetry};
+use crate::range::{ContentRange, RangeError};
+use crate::response::{CompletionHook, COPY_BUFFER_SIZE, DEFAULT_RESPONSE_BUFFER_MAX};
+use crate::ssl::TlsInfo;
+#[cfg(feature = "status-page")]
+use crate::status_page::StatusPage;
+use crate::trace::TraceContext;
+use crate::url::{ParsedUrl, UrlError};
+use crate::util::refined_tcp_stream::Stream;
+#[cfg(feature = "http3")]
+use crate::util::LimitedReader;
+use crate::util::{
+    EqualReader, FrameWriter, FusedReader, LimitedChunkedReader, MemoryBudget, ReservedReader,
+};
+use crate::{
+    BodyTransform, HTTPVersion, Header, LimitsConfig, Method, Response, ResponseDecorator,
+    ResponseStats, StatusCode, TransferEncoding, UnansweredPolicy,
+};
+
+/// Default of `LimitsConfig::discard_body_max`.
+const DEFAULT_DISCARD_BODY_MAX: usize = 64 * 1024;
 
 /// Represents an HTTP request made by a client.
 ///
@@ -44,6 +73,8 @@ use chunked_transfer::Decoder;
 /// This means that if your code fails during the handling of a request, this "internal server
 /// error" response will automatically be sent during the stack unwinding.
 ///
+/// This can be changed with [`ServerConfig::unanswered`](crate::ServerConfig::unanswered).
+///
 /// # Testing
 ///
 /// If you want to build fake requests to test your server, use [`TestRequest`](crate::test::TestRequest).
@@ -74,6 +105,113 @@ pub struct Request {
 
     // If Some, a message must be sent after responding
     notify_when_responded: Option<Sender<()>>,
+
+    // handle to the client's socket, `None` for requests not coming from a connection
+    socket: Option<Arc<Stream>>,
+
+    // parameters of the TLS handshake of the connection
+    tls_info: Option<Arc<TlsInfo>>,
+
+    // `Some` if the protocol frames the response itself (HTTP/3), `response_writer` is then only
+    // a placeholder
+    frame_writer: Option<Box<dyn FrameWriter>>,
+
+    // set by the body reader when the body exceeds the `LimitsConfig`
+    body_limit_exceeded: Arc<AtomicBool>,
+
+    // number of error responses sent on the connection, incremented when responding with a 4xx
+    error_count: Option<Arc<AtomicUsize>>,
+
+    // when the request line was received
+    received_at: Instant,
+
+    // when the last header was received
+    headers_received_at: Instant,
+
+    // identifier of the connection of the request, 0 if it wasn't read from a connection
+    connection_id: u64,
+
+    // position of the request on its connection, starting at 1
+    connection_sequence: usize,
+
+    // when the connection of the request was accepted
+    connection_opened_at: Instant,
+
+    // `LimitsConfig::request_timeout`
+    timeout: Option<Duration>,
+
+    // `ServerConfig::clock`
+    clock: Arc<dyn Clock>,
+
+    // capacity of the buffer in front of `response_writer`, if any
+    write_buffer_size: usize,
+
+    // size of the request line and headers as received
+    header_bytes: usize,
+
+    // copy of the request line and headers, see `LimitsConfig::retain_raw_headers`
+    raw_head: Option<Vec<u8>>,
+
+    // result of `url_parsed`, computed the first time it is called
+    parsed_url: Option<Result<ParsedUrl, UrlError>>,
+
+    // pattern of the route that matched the request, set by the application
+    route: Option<String>,
+
+    // values attached by the application, see `Request::extensions`
+    extensions: Extensions,
+
+    // received in the `traceparent` header, or generated
+    trace_context: TraceContext,
+
+    // span and metrics, `Some` if `ServerConfig::otel` is set
+    #[cfg(feature = "otel")]
+    telemetry: Option<RequestTelemetry>,
+
+    // counts the response by status code, `Some` if `ServerConfig::status_page` is set
+    #[cfg(feature = "status-page")]
+    status_page: Option<Arc<StatusPage>>,
+
+    // counts the request as pending on its connection until it is dropped
+    pipeline: Option<PipelineGuard>,
+
+    // `ServerConfig::unanswered`, applied if the request is dropped without being answered
+    unanswered: Option<UnansweredPolicy>,
+
+    // `ServerConfig::error_renderer`, for the responses sent when the request is dropped
+    error_renderer: Option<ErrorRenderer>,
+
+    // `ServerConfig::response_decorator`
+    response_decorator: Option<ResponseDecorator>,
+
+    // `ServerConfig::body_transform`
+    body_transform: Option<BodyTransform>,
+
+    // `ServerConfig::auto_etag`
+    auto_etag: bool,
+
+    // `ServerConfig::emit_default_headers`
+    emit_default_headers: bool,
+
+    // `ServerConfig::chunked_threshold`, for the responses that don't set theirs
+    chunked_threshold: Option<usize>,
+
+    // target as received, if `ServerConfig::normalize_absolute_form` rewrote it
+    raw_url: Option<String>,
+
+    // set when the rest of the body can't be discarded, the connection is closed after the
+    // response
+    close_connection: bool,
+
+    // `LimitsConfig::response_buffer_max`
+    response_buffer_max: usize,
+
+    // `LimitsConfig::discard_body_max` and `LimitsConfig::discard_body_before_response`
+    discard_body_max: usize,
+    discard_body_before_response: bool,
+
+    // `LimitsConfig::response_headers_max`
+    response_headers_max: Option<usize>,
 }
 
 struct NotifyOnDrop<R> {
@@ -100,12 +238,48 @@ impl<R> Drop for NotifyOnDrop<R> {
     }
 }
 
+/// Flushes the writer when dropped, after the chunked encoder wrapping it wrote the last chunk.
+struct FlushOnDrop(Box<dyn Write + Send>);
+
+impl Write for FlushOnDrop {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+impl Drop for FlushOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.flush();
+    }
+}
+
+/// Writer standing in for the writer to the client when it's gone, failing every write.
+struct FailingWriter(RespondError);
+
+impl Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(self.0.into())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Err(self.0.into())
+    }
+}
+
 /// Error that can happen when building a `Request` object.
 #[derive(Debug)]
 pub enum RequestCreationError {
     /// The client sent an `Expect` header that was not recognized by tiny-http.
     ExpectationFailed,
 
+    /// The client sent an invalid `Content-Length` header, or several that don't agree.
+    InvalidContentLength,
+
+    /// The client sent a `Transfer-Encoding` header whose last coding isn't `chunked`, or
+    /// together with a `Content-Length` header.
+    InvalidTransferEncoding,
+
     /// Error while reading data from the socket during the creation of the `Request`.
     CreationIoError(IoError),
 }
@@ -116,6 +290,44 @@ impl From<IoError> for RequestCreationError {
     }
 }
 
+/// Error of a request that can't be answered, returned by [`Request::try_into_writer`] and
+/// wrapped in the `io::Error` returned by [`Request::respond`].
+///
+/// Answering a request consumes it, so this only happens if the response writer was lost
+/// through a bug. It's reported instead of panicking the handler.
+///
+/// ```
+/// use tiny_http::RespondError;
+///
+/// fn already_responded(err: &std::io::Error) -> bool {
+///     err.get_ref().and_then(|err| err.downcast_ref::<RespondError>())
+///         == Some(&RespondError::AlreadyResponded)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RespondError {
+    /// A response was already sent, or the writer to the client was already taken.
+    AlreadyResponded,
+}
+
+impl fmt::Display for RespondError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RespondError::AlreadyResponded => {
+                formatter.write_str("the request was already answered")
+            }
+        }
+    }
+}
+
+impl Error for RespondError {}
+
+impl From<RespondError> for IoError {
+    fn from(err: RespondError) -> IoError {
+        IoError::other(err)
+    }
+}
+
 /// Builds a new request.
 ///
 /// After the request line and headers have been read from the socket, a new `Request` object
@@ -135,16 +347,37 @@ pub fn new_request<R, W>(
     remote_addr: Option<SocketAddr>,
     mut source_data: R,
     writer: W,
+    limits: &LimitsConfig,
+    body_budget: &Arc<MemoryBudget>,
 ) -> Result<Request, RequestCreationError>
 where
     R: Read + Send + 'static,
     W: Write + Send + 'static,
 {
-    // finding the transfer-encoding header
+    // combining the transfer-encoding headers, whose codings form a single list (RFC9110 #5.3)
     let transfer_encoding = headers
         .iter()
-        .find(|h: &&Header| h.field.equiv("Transfer-Encoding"))
-        .map(|h| h.value.clone());
+        .filter(|h| h.field.equiv("Transfer-Encoding"))
+        .map(|h| h.value.as_str().trim())
+        .fold(None, |combined: Option<String>, value| match combined {
+            Some(combined) if value.is_empty() => Some(combined),
+            Some(combined) if combined.is_empty() => Some(value.to_owned()),
+            Some(combined) => Some(combined + ", " + value),
+            None => Some(value.to_owned()),
+        });
+
+    // with both headers, or a last coding that isn't `chunked`, the length of the body can
+    // only be guessed, which allows smuggling requests (RFC9112 #6.3)
+    if let Some(ref transfer_encoding) = transfer_encoding {
+        let chunked_last = transfer_encoding
+            .rsplit(',')
+            .next()
+            .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+        let content_length = headers.iter().any(|h| h.field.equiv("Content-Length"));
+        if !chunked_last || content_length {
+            return Err(RequestCreationError::InvalidTransferEncoding);
+        }
+    }
 
     // finding the content-length header
     let content_length = if transfer_encoding.is_some() {
@@ -152,10 +385,7 @@ where
         // header must be ignored (RFC2616 #4.4)
         None
     } else {
-        headers
-            .iter()
-            .find(|h: &&Header| h.field.equiv("Content-Length"))
-            .and_then(|h| FromStr::from_str(h.value.as_str()).ok())
+        parse_content_length(&headers)?
     };
 
     // true if the client sent a `Expect: 100-continue` header
@@ -183,6 +413,21 @@ where
         }
     };
 
+    let trace_context = TraceContext::from_headers(&headers).unwrap_or_else(TraceContext::generate);
+
+    let body_limit_exceeded = Arc::new(AtomicBool::new(false));
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let received_at = clock.now();
+
+    // small bodies are read in advance, as long as they fit in the memory budget shared by the
+    // connections
+    let buffer_reservation = match content_length {
+        Some(content_length) if content_length <= 1024 && !expects_continue => {
+            body_budget.reserve(content_length)
+        }
+        _ => None,
+    };
+
     // we wrap `source_data` around a reading whose nature depends on the transfer-encoding and
     // content-length headers
     let reader = if connection_upgrade {
@@ -191,7 +436,7 @@ where
     } else if let Some(content_length) = content_length {
         if content_length == 0 {
             Box::new(io::empty()) as Box<dyn Read + Send + 'static>
-        } else if content_length <= 1024 && !expects_continue {
+        } else if let Some(reservation) = buffer_reservation {
             // if the content-length is small enough, we just read everything into a buffer
 
             let mut buffer = vec![0; content_length];
@@ -210,7 +455,8 @@ where
                 offset += read;
             }
 
-            Box::new(Cursor::new(buffer)) as Box<dyn Read + Send + 'static>
+            Box::new(ReservedReader::new(Cursor::new(buffer), reservation))
+                as Box<dyn Read + Send + 'static>
         } else {
             let (data_reader, _) = EqualReader::new(source_data, content_length); // TODO:
             Box::new(FusedReader::new(data_reader)) as Box<dyn Read + Send + 'static>
@@ -218,7 +464,12 @@ where
     } else if transfer_encoding.is_some() {
         // if a transfer-encoding was specified, then "chunked" is ALWAYS applied
         // over the message (RFC2616 #3.6)
-        Box::new(FusedReader::new(Decoder::new(source_data))) as Box<dyn Read + Send + 'static>
+        Box::new(FusedReader::new(LimitedChunkedReader::new(
+            source_data,
+            limits.chunk_max_size,
+            limits.chunked_body_max_size,
+            body_limit_exceeded.clone(),
+        ))) as Box<dyn Read + Send + 'static>
     } else {
         // if we have neither a Content-Length nor a Transfer-Encoding,
         // assuming that we have no data
@@ -226,6 +477,12 @@ where
         Box::new(io::empty()) as Box<dyn Read + Send + 'static>
     };
 
+    // there's nothing to continue with when the request has no body
+    let has_body = match content_length {
+        Some(content_length) => content_length > 0,
+        None => transfer_encoding.is_some() || connection_upgrade,
+    };
+
     Ok(Request {
         data_reader: Some(reader),
         response_writer: Some(Box::new(writer) as Box<dyn Write + Send + 'static>),
@@ -236,18 +493,110 @@ where
         http_version: version,
         headers,
         body_length: content_length,
-        must_send_continue: expects_continue,
+        must_send_continue: expects_continue && has_body,
         notify_when_responded: None,
+        socket: None,
+        tls_info: None,
+        frame_writer: None,
+        body_limit_exceeded,
+        error_count: None,
+        received_at,
+        headers_received_at: received_at,
+        connection_id: 0,
+        connection_sequence: 1,
+        connection_opened_at: received_at,
+        timeout: limits.request_timeout.map(Duration::from),
+        clock,
+        write_buffer_size: 0,
+        header_bytes: 0,
+        raw_head: None,
+        parsed_url: None,
+        route: None,
+        extensions: Extensions::new(),
+        trace_context,
+        #[cfg(feature = "otel")]
+        telemetry: None,
+        #[cfg(feature = "status-page")]
+        status_page: None,
+        pipeline: None,
+        unanswered: None,
+        error_renderer: None,
+        response_decorator: None,
+        body_transform: None,
+        auto_etag: false,
+        emit_default_headers: true,
+        chunked_threshold: None,
+        raw_url: None,
+        close_connection: false,
+        response_buffer_max: limits
+            .response_buffer_max
+            .unwrap_or(DEFAULT_RESPONSE_BUFFER_MAX),
+        discard_body_max: limits.discard_body_max.unwrap_or(DEFAULT_DISCARD_BODY_MAX),
+        discard_body_before_response: limits.discard_body_before_response,
+        response_headers_max: limits.response_headers_max,
     })
 }
 
+/// Returns the value of the `Content-Length` headers.
+///
+/// A message with several `Content-Length` headers, or a list as value, is only accepted if all
+/// the values are identical (RFC7230 #3.3.2). Anything else could be interpreted differently by
+/// another server in the chain, allowing to smuggle requests. The values must be only digits,
+/// which `usize::from_str` alone doesn't check as it accepts a leading `+`.
+fn parse_content_length(headers: &[Header]) -> Result<Option<usize>, RequestCreationError> {
+    let mut content_length = None;
+
+    for value in headers
+        .iter()
+        .filter(|h| h.field.equiv("Content-Length"))
+        .flat_map(|h| h.value.as_str().split(','))
+    {
+        let value = value.trim();
+        if !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(RequestCreationError::InvalidContentLength);
+        }
+
+        let value =
+            usize::from_str(value).map_err(|_| RequestCreationError::InvalidContentLength)?;
+
+        match content_length {
+            Some(previous) if previous != value => {
+                return Err(RequestCreationError::InvalidContentLength)
+            }
+            _ => content_length = Some(value),
+        }
+    }
+
+    Ok(content_length)
+}
+
 impl Request {
     /// Returns true if the request was made through HTTPS.
+    ///
+    /// Behind a TLS-terminating proxy, this is only true if the proxy is one of the
+    /// [`ServerConfig::trusted_proxies`](crate::ServerConfig::trusted_proxies) and tells so.
     #[inline]
     pub fn secure(&self) -> bool {
         self.secure
     }
 
+    /// Returns the TLS version and cipher suite negotiated with the client, `None` if the
+    /// request wasn't made through HTTPS.
+    ///
+    /// ```no_run
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// # let request = server.recv().unwrap();
+    /// if let Some(info) = request.tls_info() {
+    ///     if info.protocol_version.as_deref() == Some("TLSv1.0") {
+    ///         eprintln!("TLS 1.0 client: {:?}", request.remote_addr());
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls_info.as_deref()
+    }
+
     /// Returns the method requested by the client (eg. `GET`, `POST`, etc.).
     #[inline]
     pub fn method(&self) -> &Method {
@@ -260,12 +609,135 @@ impl Request {
         &self.path
     }
 
+    /// Returns the resource requested by the client as received, which differs from
+    /// [`url`](Request::url) only if
+    /// [`ServerConfig::normalize_absolute_form`](crate::ServerConfig::normalize_absolute_form)
+    /// rewrote an absolute-form target.
+    #[inline]
+    pub fn raw_url(&self) -> &str {
+        self.raw_url.as_deref().unwrap_or(&self.path)
+    }
+
+    /// Returns the resource requested by the client, split into path segments and query
+    /// parameters.
+    ///
+    /// The URL is parsed the first time this is called.
+    pub fn url_parsed(&mut self) -> Result<&ParsedUrl, UrlError> {
+        if self.parsed_url.is_none() {
+            self.parsed_url = Some(ParsedUrl::parse(&self.path));
+        }
+
+        match self.parsed_url {
+            Some(Ok(ref url)) => Ok(url),
+            Some(Err(ref err)) => Err(err.clone()),
+            None => unreachable!(),
+        }
+    }
+
+    /// Records the pattern of the route that matched this request, e.g. `/users/:id`.
+    ///
+    /// The route is returned in the [`ResponseStats`] of
+    /// [`respond_reporting`](Request::respond_reporting), so that access logs and metrics can be
+    /// grouped by route instead of by URL, which would create one series per user id.
+    pub fn set_route<S: Into<String>>(&mut self, pattern: S) {
+        self.route = Some(pattern.into());
+    }
+
+    /// Returns the route recorded by [`set_route`](Request::set_route).
+    #[inline]
+    pub fn route(&self) -> Option<&str> {
+        self.route.as_deref()
+    }
+
+    /// Returns the values attached to the request while it is handled, e.g. by a middleware for
+    /// the handler.
+    #[inline]
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns the values attached to the request, to add or remove some.
+    #[inline]
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Returns the W3C Trace Context of the request, parsed from its `traceparent` and
+    /// `tracestate` headers, or generated if it has none.
+    ///
+    /// The requests sent while handling this one should carry the
+    /// [`headers`](TraceContext::headers) of its [`child`](TraceContext::child).
+    #[inline]
+    pub fn trace_context(&self) -> &TraceContext {
+        &self.trace_context
+    }
+
+    /// Returns the number of requests read from the same connection and not answered yet,
+    /// including this one. More than one means that the client pipelines its requests.
+    ///
+    /// See [`LimitsConfig::pipeline_max`](crate::LimitsConfig::pipeline_max).
+    pub fn pending_requests(&self) -> usize {
+        match self.pipeline {
+            Some(ref pipeline) => pipeline.pending(),
+            None => 1,
+        }
+    }
+
     /// Returns a list of all headers sent by the client.
     #[inline]
     pub fn headers(&self) -> &[Header] {
         &self.headers
     }
 
+    /// Returns the values of all the headers named `field`, joined with `, ` in the order they
+    /// were received, or `None` if there is none.
+    ///
+    /// Splitting a list header such as `Accept` or `Cache-Control` over several lines doesn't
+    /// change its meaning (RFC 9110 #5.3), so this recovers the value a client or proxy may have
+    /// split. `Cookie` values are joined with `; ` instead, as HTTP/2 and HTTP/3 clients may
+    /// send a header per cookie (RFC 9113 #8.2.3, RFC 9114 #4.2.1). `Set-Cookie`, whose values
+    /// can't be combined at all, always returns `None`: its values must be read separately from
+    /// [`headers`](Request::headers).
+    ///
+    /// ```
+    /// # let request: tiny_http::Request = tiny_http::TestRequest::new()
+    /// #     .with_header("Accept: text/html".parse().unwrap())
+    /// #     .with_header("accept: application/json;q=0.9".parse().unwrap())
+    /// #     .into();
+    /// assert_eq!(
+    ///     request.header_combined("Accept").as_deref(),
+    ///     Some("text/html, application/json;q=0.9")
+    /// );
+    /// ```
+    pub fn header_combined(&self, field: &str) -> Option<String> {
+        if field.eq_ignore_ascii_case("Set-Cookie") {
+            return None;
+        }
+
+        let separator = if field.eq_ignore_ascii_case("Cookie") {
+            "; "
+        } else {
+            ", "
+        };
+
+        let mut values = self
+            .headers
+            .iter()
+            .filter(|h| h.field.as_str().as_str().eq_ignore_ascii_case(field))
+            .map(|h| h.value.as_str().trim());
+        let mut combined = values.next()?.to_owned();
+        for value in values {
+            if value.is_empty() {
+                continue;
+            }
+            if !combined.is_empty() {
+                combined.push_str(separator);
+            }
+            combined.push_str(value);
+        }
+        Some(combined)
+    }
+
     /// Returns the HTTP version of the request.
     #[inline]
     pub fn http_version(&self) -> &HTTPVersion {
@@ -280,6 +752,40 @@ impl Request {
         self.body_length
     }
 
+    /// Parses the `Content-Range` header of the request, `Ok(None)` if there is none.
+    ///
+    /// Fails with [`RangeError::LengthMismatch`] if the `Content-Length` of the body differs
+    /// from the length of the range.
+    pub fn content_range(&self) -> Result<Option<ContentRange>, RangeError> {
+        let range = ContentRange::from_headers(&self.headers)?;
+        match (range, self.body_length) {
+            (Some(range), Some(len)) if len as u64 != range.len() => {
+                Err(RangeError::LengthMismatch)
+            }
+            _ => Ok(range),
+        }
+    }
+
+    /// Resolves the `Range` header of a `GET` or `HEAD` request against a resource
+    /// `complete_length` bytes long, as [`ContentRange::from_range`] does.
+    ///
+    /// Returns `Ok(None)` for the other methods, or if there is no single valid `Range` header,
+    /// in which case the whole resource must be sent. The result is the same for `GET` and
+    /// `HEAD`, so that the headers of [`Response::partial`] and [`Response::partial_head`] match.
+    pub fn range(&self, complete_length: u64) -> Result<Option<ContentRange>, RangeError> {
+        if !matches!(self.method, Method::Get | Method::Head) {
+            return Ok(None);
+        }
+
+        let mut values = self.headers.iter().filter(|h| h.field.equiv("Range"));
+        match (values.next(), values.next()) {
+            (Some(header), None) => {
+                ContentRange::from_range(header.value.as_str(), complete_length)
+            }
+            _ => Ok(None),
+        }
+    }
+
     /// Returns the address of the client that sent this request.
     ///
     /// The address is always `Some` for TCP listeners, but always `None` for UNIX listeners
@@ -287,12 +793,133 @@ impl Request {
     ///
     /// Note that this is gathered from the socket. If you receive the request from a proxy,
     /// this function will return the address of the proxy and not the address of the actual
-    /// user.
+    /// user, unless the proxy sends a PROXY protocol header, see
+    /// [`TrustedProxies::with_proxy_protocol`](crate::TrustedProxies::with_proxy_protocol).
     #[inline]
     pub fn remote_addr(&self) -> Option<&SocketAddr> {
         self.remote_addr.as_ref()
     }
 
+    /// Returns the size in bytes of the request line and the headers as received, including the
+    /// line terminators and the empty line ending the headers.
+    ///
+    /// This is 0 for requests that didn't come from a connection.
+    #[inline]
+    pub fn header_bytes(&self) -> usize {
+        self.header_bytes
+    }
+
+    /// Returns the request line and the headers exactly as received, including the empty line
+    /// ending the headers.
+    ///
+    /// Only available if
+    /// [`LimitsConfig::retain_raw_headers`](crate::LimitsConfig::retain_raw_headers) is set and
+    /// the head isn't larger than its value.
+    #[inline]
+    pub fn raw_head(&self) -> Option<&[u8]> {
+        self.raw_head.as_deref()
+    }
+
+    /// Returns the header lines exactly as received and in their original order, without the
+    /// line terminators.
+    ///
+    /// Unlike [`headers`](Request::headers), the values aren't trimmed, which is what signature
+    /// schemes covering the headers need. Available under the same conditions as
+    /// [`raw_head`](Request::raw_head).
+    pub fn raw_headers(&self) -> Option<impl Iterator<Item = &[u8]>> {
+        let head = self.raw_head.as_deref()?;
+
+        // the head ends with an empty line, and every line ends with CRLF
+        let head = head.strip_suffix(b"\r\n\r\n").unwrap_or(head);
+        Some(
+            head.split(|&b| b == b'\n')
+                .skip(1) // request line
+                .map(|line| line.strip_suffix(b"\r").unwrap_or(line)),
+        )
+    }
+
+    /// Returns the instant at which the request line was received.
+    #[inline]
+    pub fn received_at(&self) -> Instant {
+        self.received_at
+    }
+
+    /// Returns the time it took the client to send the request line and the headers.
+    #[inline]
+    pub fn parse_duration(&self) -> Duration {
+        self.headers_received_at - self.received_at
+    }
+
+    /// Returns an identifier of the connection of this request, shared by all the requests of the
+    /// connection and unique among the connections accepted by the process.
+    ///
+    /// Requests which weren't read from a connection, like those built from a [`TestRequest`],
+    /// return `0`.
+    ///
+    /// [`TestRequest`]: crate::TestRequest
+    #[inline]
+    pub fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    /// Returns the position of this request among the requests of its keep-alive connection,
+    /// `1` for the first one.
+    ///
+    /// ```no_run
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// # let request = server.recv().unwrap();
+    /// use tiny_http::Response;
+    ///
+    /// // spreading the clients over the servers again every 100 requests
+    /// let mut response = Response::from_string("hello");
+    /// if request.connection_sequence() >= 100 {
+    ///     response = response.close_connection();
+    /// }
+    /// request.respond(response).unwrap();
+    /// ```
+    #[inline]
+    pub fn connection_sequence(&self) -> usize {
+        self.connection_sequence
+    }
+
+    /// Returns how long the connection of this request has been open.
+    pub fn connection_age(&self) -> Duration {
+        self.clock
+            .now()
+            .saturating_duration_since(self.connection_opened_at)
+    }
+
+    /// Returns the instant at which the request should be answered, if
+    /// [`LimitsConfig::request_timeout`](crate::LimitsConfig::request_timeout) is set.
+    #[inline]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| self.received_at + timeout)
+    }
+
+    /// Returns the time left until the [`deadline`](Request::deadline), zero if it has already
+    /// passed.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        let now = self.clock.now();
+        self.deadline()
+            .map(|deadline| deadline.checked_duration_since(now).unwrap_or_default())
+    }
+
+    /// Returns false if the client closed or reset the connection, so that long-running
+    /// handlers can give up on requests nobody waits for anymore.
+    ///
+    /// The socket is polled without reading it nor waiting. A client closing only its sending
+    /// side counts as gone, like for most servers, but one whose further data (e.g. the rest of
+    /// the body or a pipelined request) wasn't read yet is reported connected until that data
+    /// is read. Requests that didn't come from a TCP or Unix socket (e.g. custom listeners,
+    /// HTTP/3 or a [`TestRequest`](crate::test::TestRequest)), or that came from a socket on
+    /// another platform than Unix, are always reported connected.
+    pub fn is_client_connected(&self) -> bool {
+        match self.socket {
+            Some(ref socket) => socket.is_peer_connected(),
+            None => true,
+        }
+    }
+
     /// Sends a response with a `Connection: upgrade` header, then turns the `Request` into a `Stream`.
     ///
     /// The main purpose of this function is to support websockets.
@@ -302,27 +929,35 @@ impl Request {
     /// If you call this on a non-websocket request, tiny-http will wait until this `Stream` object
     ///  is destroyed before continuing to read or write on the socket. Therefore you should always
     ///  destroy it as soon as possible.
-    pub fn upgrade<R: Read>(
-        mut self,
-        protocol: &str,
-        response: Response<R>,
-    ) -> Box<dyn ReadWrite + Send> {
+    ///
+    /// See [`UpgradedStream`] for half-closing the stream.
+    pub fn upgrade<R: Read>(mut self, protocol: &str, response: Response<R>) -> UpgradedStream {
         use crate::util::CustomStream;
 
+        let mut writer = self.extract_writer_impl().unwrap_or_else(|err| {
+            log::error!("Can't upgrade the request: {}", err);
+            Box::new(FailingWriter(err))
+        });
+
+        let response = match self.response_decorator {
+            Some(ref decorator) => response.decorated(decorator, Some(&self)),
+            None => response,
+        };
         response
-            .raw_print(
-                self.response_writer.as_mut().unwrap().by_ref(),
+            .raw_print_with_clock(
+                writer.by_ref(),
                 self.http_version.clone(),
                 &self.headers,
                 false,
                 Some(protocol),
+                &*self.clock,
             )
             .ok(); // TODO: unused result
 
-        self.response_writer.as_mut().unwrap().flush().ok(); // TODO: unused result
+        writer.flush().ok(); // TODO: unused result
 
-        let stream = CustomStream::new(self.extract_reader_impl(), self.extract_writer_impl());
-        if let Some(sender) = self.notify_when_responded.take() {
+        let stream = CustomStream::new(self.extract_reader_impl(), writer);
+        let inner = if let Some(sender) = self.notify_when_responded.take() {
             let stream = NotifyOnDrop {
                 sender,
                 inner: stream,
@@ -330,7 +965,107 @@ impl Request {
             Box::new(stream) as Box<dyn ReadWrite + Send>
         } else {
             Box::new(stream) as Box<dyn ReadWrite + Send>
+        };
+
+        UpgradedStream {
+            inner,
+            socket: self.socket.take(),
+            remote_addr: self.remote_addr,
+            read_closed: false,
+        }
+    }
+
+    /// Sends the status line and the headers of `head`, then returns independent halves to read
+    /// the body of the request while writing the body of the response, for protocols streaming
+    /// both directions at once.
+    ///
+    /// Unlike [`upgrade`](Request::upgrade), the exchange stays HTTP: the request body is
+    /// decoded as usual, and the response body is sent in chunks (or until the connection is
+    /// closed for HTTP 1.0 clients), ending when the writer is dropped. The body and the length
+    /// of `head` are ignored. The connection is closed once both halves are dropped, as the
+    /// request body may not have been read entirely.
+    ///
+    /// If the client waits for a `100 Continue`, it is sent before `head`. Not supported over
+    /// HTTP/3.
+    ///
+    /// ```no_run
+    /// # use std::io::{Read, Write};
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// let request = server.recv().unwrap();
+    /// let (mut reader, mut writer) = request
+    ///     .into_duplex(tiny_http::Response::empty(200))
+    ///     .unwrap();
+    ///
+    /// // echoes each piece of the body as soon as it's received
+    /// let mut buf = [0; 1024];
+    /// loop {
+    ///     let len = reader.read(&mut buf).unwrap();
+    ///     if len == 0 {
+    ///         break;
+    ///     }
+    ///     writer.write_all(&buf[..len]).unwrap();
+    ///     writer.flush().unwrap();
+    /// }
+    /// ```
+    pub fn into_duplex<R: Read>(
+        mut self,
+        head: Response<R>,
+    ) -> Result<(DuplexReader, DuplexWriter), IoError> {
+        if self.frame_writer.is_some() {
+            return Err(IoError::other(
+                "Full-duplex bodies aren't supported over HTTP/3",
+            ));
+        }
+
+        let mut writer = self.extract_writer_impl()?;
+        if self.must_send_continue {
+            Response::new_empty(StatusCode::CONTINUE).raw_print_with_clock(
+                writer.by_ref(),
+                self.http_version.clone(),
+                &self.headers,
+                true,
+                None,
+                &*self.clock,
+            )?;
+            self.must_send_continue = false;
+        }
+        let (transfer_encoding, on_complete) =
+            head.print_streaming_head(writer.by_ref(), self.http_version.clone(), &*self.clock)?;
+        writer.flush()?;
+
+        if let Some(sender) = self.notify_when_responded.take() {
+            writer = Box::new(NotifyOnDrop {
+                sender,
+                inner: writer,
+            });
         }
+        let body = match transfer_encoding {
+            _ if self.method == Method::Head => DuplexBody::Discard(writer),
+            None => DuplexBody::Discard(writer),
+            Some(TransferEncoding::Identity) => DuplexBody::Identity(writer),
+            Some(TransferEncoding::Chunked) => {
+                let chunks_size = self.write_buffer_size.max(COPY_BUFFER_SIZE);
+                DuplexBody::Chunked(chunked_transfer::Encoder::with_chunks_size(
+                    FlushOnDrop(writer),
+                    chunks_size,
+                ))
+            }
+        };
+
+        let socket = self.socket.take();
+        let reader = DuplexReader {
+            inner: self.extract_reader_impl(),
+            socket: socket.clone(),
+        };
+        let writer = DuplexWriter {
+            body,
+            on_complete,
+            written: 0,
+            error: None,
+            // keeps the connection busy for `SocketConfig::keep_alive_idle_timeout`
+            _pipeline: self.pipeline.take(),
+        };
+        Ok((reader, writer))
     }
 
     /// Allows to read the body of the request.
@@ -360,16 +1095,19 @@ impl Request {
     #[inline]
     pub fn as_reader(&mut self) -> &mut dyn Read {
         if self.must_send_continue {
-            let msg = Response::new_empty(StatusCode(100));
-            msg.raw_print(
-                self.response_writer.as_mut().unwrap().by_ref(),
-                self.http_version.clone(),
-                &self.headers,
-                true,
-                None,
-            )
-            .ok();
-            self.response_writer.as_mut().unwrap().flush().ok();
+            if let Some(ref mut writer) = self.response_writer {
+                let msg = Response::new_empty(StatusCode::CONTINUE);
+                msg.raw_print_with_clock(
+                    writer.by_ref(),
+                    self.http_version.clone(),
+                    &self.headers,
+                    true,
+                    None,
+                    &*self.clock,
+                )
+                .ok();
+                writer.flush().ok();
+            }
             self.must_send_continue = false;
         }
 
@@ -387,16 +1125,25 @@ impl Request {
     /// the writing of the next response.
     /// Therefore you should always destroy the `Writer` as soon as possible.
     #[inline]
-    pub fn into_writer(mut self) -> Box<dyn Write + Send + 'static> {
-        let writer = self.extract_writer_impl();
+    pub fn into_writer(self) -> Box<dyn Write + Send + 'static> {
+        self.try_into_writer().unwrap_or_else(|err| {
+            log::error!("Can't turn the request into a writer: {}", err);
+            Box::new(FailingWriter(err)) as Box<dyn Write + Send + 'static>
+        })
+    }
+
+    /// Same as [`into_writer`](Request::into_writer), but fails if the writer to the client is
+    /// gone instead of returning a writer failing every write.
+    pub fn try_into_writer(mut self) -> Result<Box<dyn Write + Send + 'static>, RespondError> {
+        let writer = self.extract_writer_impl()?;
         if let Some(sender) = self.notify_when_responded.take() {
             let writer = NotifyOnDrop {
                 sender,
                 inner: writer,
             };
-            Box::new(writer) as Box<dyn Write + Send + 'static>
+            Ok(Box::new(writer) as Box<dyn Write + Send + 'static>)
         } else {
-            writer
+            Ok(writer)
         }
     }
 
@@ -404,14 +1151,10 @@ impl Request {
     /// as the object returned by `into_writer` above.
     ///
     /// This may only be called once on a single request.
-    fn extract_writer_impl(&mut self) -> Box<dyn Write + Send + 'static> {
-        use std::mem;
-
-        assert!(self.response_writer.is_some());
-
-        let mut writer = None;
-        mem::swap(&mut self.response_writer, &mut writer);
-        writer.unwrap()
+    fn extract_writer_impl(&mut self) -> Result<Box<dyn Write + Send + 'static>, RespondError> {
+        self.response_writer
+            .take()
+            .ok_or(RespondError::AlreadyResponded)
     }
 
     /// Extract the body `Reader` object from the Request.
@@ -427,12 +1170,127 @@ impl Request {
         reader.unwrap()
     }
 
+    /// Writes the body at the offset of `range` in `target`, e.g. a file opened for writing
+    /// without truncating it, and returns the number of bytes written.
+    ///
+    /// Fails with an error of kind `InvalidData` wrapping [`RangeError::LengthMismatch`] if the
+    /// body isn't exactly as long as the range, in which case part of it may have been written.
+    ///
+    /// ```no_run
+    /// # use std::fs::OpenOptions;
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// let mut request = server.recv().unwrap();
+    /// let range = match request.content_range() {
+    ///     Ok(Some(range)) => range,
+    ///     Ok(None) => return, // the whole resource is sent
+    ///     Err(err) => return request.respond(tiny_http::Response::empty(err.status_code())).unwrap(),
+    /// };
+    ///
+    /// let mut file = OpenOptions::new().write(true).create(true).open("upload").unwrap();
+    /// if let Err(err) = range.check(file.metadata().unwrap().len()) {
+    ///     return request.respond(tiny_http::Response::empty(err.status_code())).unwrap();
+    /// }
+    /// request.write_range_to(&range, &mut file).unwrap();
+    /// request.respond(tiny_http::Response::empty(204)).unwrap();
+    /// ```
+    pub fn write_range_to<W>(&mut self, range: &ContentRange, target: &mut W) -> io::Result<u64>
+    where
+        W: Write + Seek,
+    {
+        target.seek(SeekFrom::Start(range.first))?;
+        let reader = self.as_reader();
+        let written = io::copy(&mut reader.take(range.len()), target)?;
+        if written != range.len() || reader.read(&mut [0])? != 0 {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                RangeError::LengthMismatch,
+            ));
+        }
+        Ok(written)
+    }
+
+    /// Reads and throws away the rest of the body, so that the next request of the connection
+    /// can be read once the response is sent. Returns true if the whole body was discarded.
+    ///
+    /// At most `limit` bytes are read. If the body is larger, if the client waits for a
+    /// `100 Continue` before sending it, or if the request asks for an upgrade, the body is left
+    /// unread and the connection is closed after the response instead.
+    ///
+    /// [`respond`](Request::respond) calls this with the limit of
+    /// [`LimitsConfig::discard_body_max`], by default once the response is sent, so handlers
+    /// rejecting a request don't need to read its body. Calling it before responding allows
+    /// choosing another limit, and adds `Connection: close` to the response if the body couldn't
+    /// be discarded.
+    pub fn discard_body(&mut self, limit: usize) -> Result<bool, IoError> {
+        if self.close_connection {
+            return Ok(false);
+        }
+
+        let upgrade = self
+            .headers
+            .iter()
+            .filter(|h| h.field.equiv("Connection"))
+            .any(|h| h.value.as_str().to_ascii_lowercase().contains("upgrade"));
+        let waiting_client = self.must_send_continue && self.body_length != Some(0);
+
+        let reader = match self.data_reader {
+            Some(ref mut reader) if !upgrade && !waiting_client => reader,
+            Some(_) => {
+                self.close_connection = true;
+                return Ok(false);
+            }
+            None => return Ok(true),
+        };
+
+        match io::copy(&mut reader.take(limit as u64 + 1), &mut io::sink()) {
+            Ok(discarded) if discarded <= limit as u64 => Ok(true),
+            Ok(_) => {
+                self.close_connection = true;
+                Ok(false)
+            }
+            Err(err) => {
+                self.close_connection = true;
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns true if the client doesn't keep the connection open after this request, as with
+    /// `Connection: close`, or with HTTP/1.0 without `Connection: keep-alive`.
+    fn client_closes_connection(&self) -> bool {
+        let connection = self
+            .headers
+            .iter()
+            .find(|h| h.field.equiv("Connection"))
+            .map(|h| h.value.as_str().to_ascii_lowercase());
+        match connection {
+            Some(ref value) if value.contains("close") || value.contains("upgrade") => true,
+            Some(ref value) => self.http_version == (1, 0) && !value.contains("keep-alive"),
+            None => self.http_version == (1, 0),
+        }
+    }
+
     /// Sends a response to this request.
     #[inline]
-    pub fn respond<R>(mut self, response: Response<R>) -> Result<(), IoError>
+    pub fn respond<R>(self, response: Response<R>) -> Result<(), IoError>
+    where
+        R: Read,
+    {
+        self.respond_reporting(response).map(|_| ())
+    }
+
+    /// Same as [`respond`](Request::respond), but returns the number of bytes sent and the time
+    /// it took.
+    ///
+    /// If the client closes the connection while the response is sent, the statistics only
+    /// cover what has been written until then.
+    pub fn respond_reporting<R>(mut self, response: Response<R>) -> Result<ResponseStats, IoError>
     where
         R: Read,
     {
+        #[cfg(feature = "strict-responses")]
+        crate::strict_responses::check(response.status_code(), response.headers())?;
+
         let res = self.respond_impl(response);
         if let Some(sender) = self.notify_when_responded.take() {
             sender.send(()).unwrap();
@@ -440,23 +1298,143 @@ impl Request {
         res
     }
 
-    fn respond_impl<R>(&mut self, response: Response<R>) -> Result<(), IoError>
+    fn respond_impl<R>(&mut self, response: Response<R>) -> Result<ResponseStats, IoError>
     where
         R: Read,
     {
-        let mut writer = self.extract_writer_impl();
+        let response = response.with_default_chunked_threshold(self.chunked_threshold);
+        if self.auto_etag
+            && matches!(self.method, Method::Get | Method::Head)
+            && response.etag_applies()
+        {
+            let response = response.into_etagged(&self.headers)?;
+            return self.send_transformed(response);
+        }
+        self.send_transformed(response)
+    }
+
+    /// Applies `ServerConfig::body_transform` before sending the response.
+    fn send_transformed<R>(&mut self, response: Response<R>) -> Result<ResponseStats, IoError>
+    where
+        R: Read,
+    {
+        match self.body_transform.clone() {
+            Some(transform) => self.send_response(response.transformed(&transform)),
+            None => self.send_response(response),
+        }
+    }
+
+    fn send_response<R>(&mut self, response: Response<R>) -> Result<ResponseStats, IoError>
+    where
+        R: Read,
+    {
+        let start = self.clock.now();
+        let mut stats = ResponseStats::default();
+
+        // the rest of the body doesn't matter if the handler closes the connection
+        if response.closes_connection() {
+            self.close_connection = true;
+        }
+        if self.discard_body_before_response && self.data_reader.is_some() {
+            let _ = self.discard_body(self.discard_body_max);
+        }
+        let response = if self.emit_default_headers {
+            response
+        } else {
+            response.without_default_headers()
+        };
+        let client_closes = self.client_closes_connection();
+        let response = response.with_connection_headers(
+            self.close_connection,
+            client_closes,
+            &self.http_version,
+        );
+        let response = match self.response_decorator.clone() {
+            Some(decorator) => response.decorated(&decorator, Some(&*self)),
+            None => response,
+        };
+
+        if let Some(max) = self.response_headers_max {
+            let size: usize = response
+                .headers()
+                .iter()
+                .map(|h| h.field.as_str().as_str().len() + h.value.len() + 4)
+                .sum();
+            if size > max {
+                log::error!(
+                    "Response headers of {} bytes, over the limit of {}",
+                    size,
+                    max
+                );
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "Response headers too large",
+                ));
+            }
+        }
+
+        let mut writer = self.extract_writer_impl()?;
+
+        if let Some(ref error_count) = self.error_count {
+            if (400..500).contains(&response.status_code().0) {
+                error_count.fetch_add(1, Ordering::AcqRel);
+            }
+        }
+
+        #[cfg(feature = "status-page")]
+        if let Some(ref status_page) = self.status_page {
+            status_page.count_response(response.status_code());
+        }
 
         let do_not_send_body = self.method == Method::Head;
+        #[cfg(feature = "otel")]
+        let status_code = response.status_code();
 
-        Self::ignore_client_closing_errors(response.raw_print(
-            writer.by_ref(),
-            self.http_version.clone(),
-            &self.headers,
-            do_not_send_body,
-            None,
-        ))?;
+        let result = match self.frame_writer.take() {
+            Some(mut frame_writer) => response.print_frames(
+                &mut *frame_writer,
+                do_not_send_body,
+                &*self.clock,
+                self.write_buffer_size,
+                &mut stats,
+            ),
+            None => response.raw_print_with_stats(
+                writer.by_ref(),
+                self.http_version.clone(),
+                &self.headers,
+                do_not_send_body,
+                None,
+                &*self.clock,
+                self.write_buffer_size,
+                self.response_buffer_max,
+                &mut stats,
+            ),
+        };
+        let result = Self::ignore_client_closing_errors(result)
+            .and_then(|()| Self::ignore_client_closing_errors(writer.flush()));
+
+        // the next request can only be read once the body of this one is consumed, doing it
+        // after responding so that the client gets the response as soon as possible
+        drop(writer);
+        if result.is_err() {
+            // the response may have been cut anywhere, so nothing can be sent after it
+            if let Some(ref socket) = self.socket {
+                let _ = socket.shutdown(Shutdown::Both);
+            }
+        }
+        if self.data_reader.is_some() {
+            let _ = self.discard_body(self.discard_body_max);
+        }
 
-        Self::ignore_client_closing_errors(writer.flush())
+        stats.duration = self.clock.now().saturating_duration_since(start);
+        #[cfg(feature = "otel")]
+        if let Some(telemetry) = self.telemetry.take() {
+            let duration = self.clock.now().saturating_duration_since(self.received_at);
+            telemetry.finish(status_code, self.route.as_deref(), duration);
+        }
+        stats.route = self.route.take();
+        stats.trace_context = Some(self.trace_context.clone());
+        result.map(|()| stats)
     }
 
     fn ignore_client_closing_errors(result: io::Result<()>) -> io::Result<()> {
@@ -473,6 +1451,154 @@ impl Request {
         self.notify_when_responded = Some(sender);
         self
     }
+
+    pub(crate) fn with_socket(mut self, socket: Option<Arc<Stream>>) -> Self {
+        self.socket = socket;
+        self
+    }
+
+    pub(crate) fn with_tls_info(mut self, tls_info: Option<Arc<TlsInfo>>) -> Self {
+        self.tls_info = tls_info;
+        self
+    }
+
+    /// Replaces the reader of the body, for protocols delimiting the body themselves, failing
+    /// like a chunked body once more than `body_max_size` bytes are read.
+    #[cfg(feature = "http3")]
+    pub(crate) fn with_data_reader(
+        mut self,
+        reader: Box<dyn Read + Send + 'static>,
+        body_max_size: Option<usize>,
+    ) -> Self {
+        let reader = LimitedReader::new(reader, body_max_size, self.body_limit_exceeded.clone());
+        self.data_reader = Some(Box::new(FusedReader::new(reader)));
+        self
+    }
+
+    /// Puts back data read from the start of the body, to be read again.
+    pub(crate) fn prepend_body(&mut self, prefix: Vec<u8>) {
+        if let Some(reader) = self.data_reader.take() {
+            self.data_reader = Some(Box::new(Cursor::new(prefix).chain(reader)));
+        }
+    }
+
+    #[cfg_attr(not(feature = "http3"), allow(dead_code))]
+    pub(crate) fn with_frame_writer(mut self, frame_writer: Box<dyn FrameWriter>) -> Self {
+        self.frame_writer = Some(frame_writer);
+        self
+    }
+
+    pub(crate) fn with_head(mut self, header_bytes: usize, raw_head: Option<Vec<u8>>) -> Self {
+        self.header_bytes = header_bytes;
+        self.raw_head = raw_head;
+        self
+    }
+
+    pub(crate) fn with_connection_close(mut self) -> Self {
+        self.close_connection = true;
+        self
+    }
+
+    pub(crate) fn with_write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = write_buffer_size;
+        self
+    }
+
+    /// Starts the span of the request, and counts it in the metrics.
+    #[cfg(feature = "otel")]
+    pub(crate) fn with_telemetry(mut self, telemetry: Option<&Arc<Telemetry>>) -> Self {
+        self.telemetry = telemetry.map(|telemetry| telemetry.start(&self));
+        self
+    }
+
+    #[cfg(feature = "status-page")]
+    pub(crate) fn with_status_page(mut self, status_page: Option<Arc<StatusPage>>) -> Self {
+        self.status_page = status_page;
+        self
+    }
+
+    pub(crate) fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub(crate) fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    pub(crate) fn with_timing(
+        mut self,
+        received_at: Instant,
+        headers_received_at: Instant,
+    ) -> Self {
+        self.received_at = received_at;
+        self.headers_received_at = headers_received_at;
+        self
+    }
+
+    pub(crate) fn with_connection_id(mut self, id: u64) -> Self {
+        self.connection_id = id;
+        self
+    }
+
+    pub(crate) fn with_connection_stats(mut self, sequence: usize, opened_at: Instant) -> Self {
+        self.connection_sequence = sequence;
+        self.connection_opened_at = opened_at;
+        self
+    }
+
+    pub(crate) fn with_error_count(mut self, error_count: Arc<AtomicUsize>) -> Self {
+        self.error_count = Some(error_count);
+        self
+    }
+
+    pub(crate) fn with_pipeline(mut self, pipeline: PipelineGuard) -> Self {
+        self.pipeline = Some(pipeline);
+        self
+    }
+
+    pub(crate) fn with_unanswered_policy(mut self, unanswered: Option<UnansweredPolicy>) -> Self {
+        self.unanswered = unanswered;
+        self
+    }
+
+    pub(crate) fn with_error_renderer(mut self, error_renderer: Option<ErrorRenderer>) -> Self {
+        self.error_renderer = error_renderer;
+        self
+    }
+
+    pub(crate) fn with_response_decorator(
+        mut self,
+        response_decorator: Option<ResponseDecorator>,
+    ) -> Self {
+        self.response_decorator = response_decorator;
+        self
+    }
+
+    pub(crate) fn with_body_transform(mut self, body_transform: Option<BodyTransform>) -> Self {
+        self.body_transform = body_transform;
+        self
+    }
+
+    pub(crate) fn with_auto_etag(mut self, auto_etag: bool) -> Self {
+        self.auto_etag = auto_etag;
+        self
+    }
+
+    pub(crate) fn with_default_headers(mut self, emit_default_headers: bool) -> Self {
+        self.emit_default_headers = emit_default_headers;
+        self
+    }
+
+    pub(crate) fn with_chunked_threshold(mut self, chunked_threshold: Option<usize>) -> Self {
+        self.chunked_threshold = chunked_threshold;
+        self
+    }
+
+    pub(crate) fn with_raw_url(mut self, raw_url: Option<String>) -> Self {
+        self.raw_url = raw_url;
+        self
+    }
 }
 
 impl fmt::Debug for Request {
@@ -487,13 +1613,48 @@ impl fmt::Debug for Request {
 
 impl Drop for Request {
     fn drop(&mut self) {
+        let body_limit_exceeded = self.body_limit_exceeded.load(Ordering::Acquire);
+
         if self.response_writer.is_some() {
-            let response = Response::empty(500);
-            let _ = self.respond_impl(response); // ignoring any potential error
+            let renderer = self.error_renderer.as_ref();
+            let response = if body_limit_exceeded {
+                Some(error_response(
+                    renderer,
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    &self.headers,
+                ))
+            } else {
+                match self.unanswered.take().unwrap_or_default() {
+                    UnansweredPolicy::Respond(status_code) => {
+                        Some(error_response(renderer, status_code, &self.headers))
+                    }
+                    UnansweredPolicy::Close => None,
+                    UnansweredPolicy::Handler(handler) => handler.call(self),
+                }
+            };
+            match response {
+                Some(response) => {
+                    let _ = self.respond_impl(response); // ignoring any potential error
+                }
+                None => {
+                    // the next responses of the connection must not be taken for this one
+                    self.response_writer = None;
+                    if let Some(ref socket) = self.socket {
+                        let _ = socket.shutdown(Shutdown::Both);
+                    }
+                }
+            }
             if let Some(sender) = self.notify_when_responded.take() {
                 sender.send(()).unwrap();
             }
         }
+
+        // the rest of the body is still in the stream, so nothing after it can be read
+        if body_limit_exceeded || self.close_connection {
+            if let Some(ref socket) = self.socket {
+                let _ = socket.shutdown(Shutdown::Read);
+            }
+        }
     }
 }
 
@@ -503,9 +1664,196 @@ impl Drop for Request {
 pub trait ReadWrite: Read + Write {}
 impl<T> ReadWrite for T where T: Read + Write {}
 
+/// Stream to the client returned by [`Request::upgrade`].
+///
+/// Besides reading and writing, the stream can be half-closed, which is what tunnels (e.g. a
+/// `CONNECT` proxy) need to forward the end of one direction while the other one keeps flowing.
+///
+/// The socket options that matter for long-lived connections, such as timeouts, are also
+/// available. For requests that didn't come from a socket (e.g. a
+/// [`TestRequest`](crate::test::TestRequest)) these are no-ops.
+pub struct UpgradedStream {
+    inner: Box<dyn ReadWrite + Send>,
+    socket: Option<Arc<Stream>>,
+    remote_addr: Option<SocketAddr>,
+    read_closed: bool,
+}
+
+impl UpgradedStream {
+    /// Returns the address of the client, see [`Request::remote_addr`].
+    #[inline]
+    pub fn peer_addr(&self) -> Option<&SocketAddr> {
+        self.remote_addr.as_ref()
+    }
+
+    /// Sets the read timeout of the underlying socket. `None` means that reads block forever.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        match self.socket {
+            Some(ref socket) => socket.set_read_timeout(dur),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets the write timeout of the underlying socket. `None` means that writes block forever.
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        match self.socket {
+            Some(ref socket) => socket.set_write_timeout(dur),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets `TCP_NODELAY` on the underlying socket. Has no effect on Unix sockets.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        match self.socket {
+            Some(ref socket) => socket.set_nodelay(nodelay),
+            None => Ok(()),
+        }
+    }
+
+    /// Shuts down the read half, the write half or both halves of the connection.
+    ///
+    /// Buffered data is flushed before the write half is closed. This is a no-op if the request
+    /// didn't come from a socket (e.g. a [`TestRequest`](crate::test::TestRequest)).
+    pub fn shutdown(&mut self, how: Shutdown) -> io::Result<()> {
+        if how != Shutdown::Read {
+            self.inner.flush()?;
+        }
+
+        match self.socket {
+            Some(ref socket) => socket.shutdown(how),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true once the client has shut down its write half, i.e. once a read returned EOF.
+    ///
+    /// Writing to the stream is still possible afterwards.
+    #[inline]
+    pub fn is_read_closed(&self) -> bool {
+        self.read_closed
+    }
+}
+
+impl Read for UpgradedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if len == 0 && !buf.is_empty() {
+            self.read_closed = true;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for UpgradedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reading half returned by [`Request::into_duplex`], reading the body of the request.
+///
+/// Dropping it closes the reading side of the connection, which ends once the
+/// [`DuplexWriter`] is dropped too.
+pub struct DuplexReader {
+    inner: Box<dyn Read + Send>,
+    socket: Option<Arc<Stream>>,
+}
+
+impl Read for DuplexReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Drop for DuplexReader {
+    fn drop(&mut self) {
+        // whatever follows the body can't be read as the next request
+        if let Some(ref socket) = self.socket {
+            let _ = socket.shutdown(Shutdown::Read);
+        }
+    }
+}
+
+/// Writing half returned by [`Request::into_duplex`], writing the body of the response.
+///
+/// Written data may stay buffered until [`flush`](Write::flush) is called. The body ends when
+/// the writer is dropped, which calls the [completion hook](Response::on_complete) of the
+/// response.
+pub struct DuplexWriter {
+    body: DuplexBody,
+    on_complete: Option<CompletionHook>,
+    // bytes of the body written so far
+    written: u64,
+    // first error that interrupted the body
+    error: Option<IoError>,
+    _pipeline: Option<PipelineGuard>,
+}
+
+enum DuplexBody {
+    Chunked(chunked_transfer::Encoder<FlushOnDrop>),
+    Identity(Box<dyn Write + Send>),
+    // the response has no body, kept to send the next responses only after this one
+    Discard(Box<dyn Write + Send>),
+}
+
+impl Write for DuplexWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = match self.body {
+            DuplexBody::Chunked(ref mut encoder) => encoder.write(buf),
+            DuplexBody::Identity(ref mut writer) => writer.write(buf),
+            DuplexBody::Discard(_) => return Ok(buf.len()),
+        };
+        match result {
+            Ok(len) => self.written += len as u64,
+            Err(ref err) if self.error.is_none() => {
+                self.error = Some(IoError::new(err.kind(), err.to_string()));
+            }
+            Err(_) => (),
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.body {
+            DuplexBody::Chunked(ref mut encoder) => {
+                encoder.flush()?;
+                encoder.get_mut().flush()
+            }
+            DuplexBody::Identity(ref mut writer) | DuplexBody::Discard(ref mut writer) => {
+                writer.flush()
+            }
+        }
+    }
+}
+
+impl Drop for DuplexWriter {
+    fn drop(&mut self) {
+        // ends the body before reporting it
+        if let Err(err) = self.flush() {
+            self.error.get_or_insert(err);
+        }
+        drop(mem::replace(
+            &mut self.body,
+            DuplexBody::Discard(Box::new(io::sink())),
+        ));
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(match self.error.take() {
+                Some(err) => Err(err),
+                None => Ok(self.written),
+            });
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Request;
+    use super::{parse_content_length, Request, RequestCreationError, RespondError};
+    use crate::{ContentRange, Header, RangeError, Response, TestRequest};
+    use std::io::{Cursor, ErrorKind, Write};
 
     #[test]
     fn must_be_send() {
@@ -515,4 +1863,107 @@ mod tests {
             f(rq);
         }
     }
+
+    #[test]
+    fn content_length_duplicates() {
+        let parse = |values: &[&str]| {
+            let headers: Vec<Header> = values
+                .iter()
+                .map(|v| format!("Content-Length: {}", v).parse().unwrap())
+                .collect();
+            parse_content_length(&headers)
+        };
+
+        assert_eq!(parse(&[]).unwrap(), None);
+        assert_eq!(parse(&["5"]).unwrap(), Some(5));
+        assert_eq!(parse(&["5", "5"]).unwrap(), Some(5));
+        assert_eq!(parse(&["5, 5"]).unwrap(), Some(5));
+
+        for values in [
+            &["5", "6"][..],
+            &["5, 6"],
+            &["abc"],
+            &["5", ""],
+            &["-1"],
+            &["+5"],
+        ] {
+            assert!(matches!(
+                parse(values),
+                Err(RequestCreationError::InvalidContentLength)
+            ));
+        }
+    }
+
+    #[test]
+    fn already_responded() {
+        let request = || {
+            let mut request: Request = TestRequest::new().into();
+            request.response_writer = None;
+            request
+        };
+
+        let err = request().respond(Response::empty(200)).unwrap_err();
+        assert_eq!(
+            err.get_ref()
+                .and_then(|err| err.downcast_ref::<RespondError>()),
+            Some(&RespondError::AlreadyResponded)
+        );
+
+        assert_eq!(
+            request().try_into_writer().err(),
+            Some(RespondError::AlreadyResponded)
+        );
+        assert!(request().into_writer().write_all(b"data").is_err());
+    }
+
+    #[test]
+    fn write_range_to() {
+        let put = |range: &str| -> Request {
+            TestRequest::new()
+                .with_body("hello")
+                .with_header(Header::from_bytes(&b"Content-Range"[..], range).unwrap())
+                .into()
+        };
+
+        let mut request = put("bytes 5-9/10");
+        let range = request.content_range().unwrap().unwrap();
+        let mut target = Cursor::new(b"01234".to_vec());
+        range.check(5).unwrap();
+        assert_eq!(request.write_range_to(&range, &mut target).unwrap(), 5);
+        assert_eq!(target.into_inner(), b"01234hello");
+
+        let request = put("bytes 0-9/10");
+        assert_eq!(request.content_range(), Err(RangeError::LengthMismatch));
+
+        // a body longer than the range
+        let mut request = put("bytes 0-9/10");
+        let range = ContentRange::parse("bytes 0-3/*").unwrap();
+        let err = request
+            .write_range_to(&range, &mut Cursor::new(Vec::new()))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn header_combined() {
+        let request: Request = TestRequest::new()
+            .with_header("Cache-Control: no-cache".parse().unwrap())
+            .with_header("Set-Cookie: a=b".parse().unwrap())
+            .with_header("cache-control:".parse().unwrap())
+            .with_header("CACHE-CONTROL:  max-age=0 ".parse().unwrap())
+            .with_header("Cookie: a=1".parse().unwrap())
+            .with_header("cookie: b=2".parse().unwrap())
+            .into();
+
+        assert_eq!(
+            request.header_combined("Cache-Control").as_deref(),
+            Some("no-cache, max-age=0")
+        );
+        assert_eq!(
+            request.header_combined("cookie").as_deref(),
+            Some("a=1; b=2")
+        );
+        assert_eq!(request.header_combined("Accept"), None);
+        assert_eq!(request.header_combined("set-cookie"), None);
+    }
 }
@@ -1,6 +1,14 @@
-use crate::common::{HTTPVersion, Header, StatusCode};
+use crate::body_transform::{BodyInfo, BodyTransform, ResponseBody};
+use crate::clock::{Clock, SystemClock};
+use crate::common::{ContentType, HTTPVersion, Header, StatusCode};
+use crate::request::Request;
+use crate::response_decorator::{ResponseDecorator, ResponseHeaders};
+use crate::trace::TraceContext;
+use crate::util::{FrameWriter, RenderReader, SpillFile};
 use httpdate::HttpDate;
+use std::cell::Cell;
 use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::sync::mpsc::Receiver;
 
 use std::io::Result as IoResult;
@@ -9,14 +17,16 @@ use std::io::{self, Cursor, Read, Write};
 use std::fs::File;
 
 use std::str::FromStr;
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 /// Object representing an HTTP response whose purpose is to be given to a `Request`.
 ///
 /// Some headers cannot be changed. Trying to define the value
 /// of one of these will have no effect:
 ///
-///  - `Connection`
+///  - `Connection`, see [`close_connection`](Response::close_connection) and
+///    [`keep_alive`](Response::keep_alive) instead
 ///  - `Trailer`
 ///  - `Transfer-Encoding`
 ///  - `Upgrade`
@@ -24,18 +34,18 @@ use std::time::SystemTime;
 /// Some headers have special behaviors:
 ///
 ///  - `Content-Encoding`: If you define this header, the library
-///     will assume that the data from the `Read` object has the specified encoding
-///     and will just pass-through.
+///    will assume that the data from the `Read` object has the specified encoding
+///    and will just pass-through.
 ///
 ///  - `Content-Length`: The length of the data should be set manually
-///     using the `Reponse` object's API. Attempting to set the value of this
-///     header will be equivalent to modifying the size of the data but the header
-///     itself may not be present in the final result.
+///    using the `Reponse` object's API. Attempting to set the value of this
+///    header will be equivalent to modifying the size of the data but the header
+///    itself may not be present in the final result.
 ///
 ///  - `Content-Type`: You may only set this header to one value at a time. If you
-///     try to set it more than once, the existing value will be overwritten. This
-///     behavior differs from the default for most headers, which is to allow them to
-///     be set multiple times in the same response.
+///    try to set it more than once, the existing value will be overwritten. This
+///    behavior differs from the default for most headers, which is to allow them to
+///    be set multiple times in the same response.
 ///
 pub struct Response<R> {
     reader: R,
@@ -43,19 +53,121 @@ pub struct Response<R> {
     headers: Vec<Header>,
     data_length: Option<usize>,
     chunked_threshold: Option<usize>,
+    connection: Option<ConnectionChoice>,
+    default_headers: bool,
+    on_complete: Option<CompletionHook>,
 }
 
+/// What the handler asked for the connection, see [`Response::close_connection`] and
+/// [`Response::keep_alive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionChoice {
+    Close,
+    KeepAlive { timeout: Duration, max: u32 },
+}
+
+/// Function called once the body of a response has been written, see
+/// [`Response::on_complete`].
+pub(crate) type CompletionHook = Box<dyn FnOnce(IoResult<u64>) + Send>;
+
 /// A `Response` without a template parameter.
 pub type ResponseBox = Response<Box<dyn Read + Send>>;
 
 /// Transfer encoding to use when sending the message.
 /// Note that only *supported* encoding are listed here.
-#[derive(Copy, Clone)]
-enum TransferEncoding {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransferEncoding {
+    /// The body is sent as-is, its length is given by the `Content-Length` header.
     Identity,
+    /// The body is sent in chunks (`Transfer-Encoding: chunked`).
     Chunked,
 }
 
+/// Value of a `Retry-After` header, telling the client when to retry a request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RetryAfter {
+    /// Number of seconds to wait.
+    Seconds(u64),
+    /// Date after which the request can be retried, sent as an HTTP-date.
+    Date(SystemTime),
+}
+
+impl From<RetryAfter> for Header {
+    fn from(retry_after: RetryAfter) -> Header {
+        let value = match retry_after {
+            RetryAfter::Seconds(seconds) => seconds.to_string(),
+            RetryAfter::Date(date) => HttpDate::from(date).to_string(),
+        };
+        Header::from_bytes(&b"Retry-After"[..], value).unwrap()
+    }
+}
+
+/// Information about a response that has been sent, returned by
+/// [`Request::respond_reporting`](crate::Request::respond_reporting).
+#[derive(Debug, Clone, Default)]
+pub struct ResponseStats {
+    /// Number of bytes of the status line and the headers.
+    pub header_bytes: usize,
+
+    /// Number of bytes of the body as sent to the client, including the chunks framing.
+    pub body_bytes: usize,
+
+    /// Time spent writing the response.
+    pub duration: Duration,
+
+    /// Transfer encoding of the body, `None` if the response has no body framing (e.g. an
+    /// upgrade).
+    pub transfer_encoding: Option<TransferEncoding>,
+
+    /// Route of the request, see [`Request::set_route`](crate::Request::set_route).
+    pub route: Option<String>,
+
+    /// Trace context of the request, see
+    /// [`Request::trace_context`](crate::Request::trace_context).
+    pub trace_context: Option<TraceContext>,
+}
+
+/// Writer counting the bytes written to it.
+struct CountingWriter<W> {
+    inner: W,
+    count: usize,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writer failing once `aborted` is set, so that a chunked encoder dropped after the body failed
+/// doesn't end the body with the last chunk, which would pass the truncated body as complete.
+struct AbortableWriter<'a, W> {
+    inner: W,
+    aborted: &'a Cell<bool>,
+}
+
+impl<W: Write> Write for AbortableWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        if self.aborted.get() {
+            return Err(io::Error::other("The body failed"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        if self.aborted.get() {
+            return Err(io::Error::other("The body failed"));
+        }
+        self.inner.flush()
+    }
+}
+
 impl FromStr for TransferEncoding {
     type Err = ();
 
@@ -71,8 +183,8 @@ impl FromStr for TransferEncoding {
 }
 
 /// Builds a Date: header with the current date.
-fn build_date_header() -> Header {
-    let d = HttpDate::from(SystemTime::now());
+fn build_date_header(clock: &dyn Clock) -> Header {
+    let d = HttpDate::from(clock.system_time());
     Header::from_bytes(&b"Date"[..], &d.to_string().into_bytes()[..]).unwrap()
 }
 
@@ -85,15 +197,18 @@ fn write_message_header<W>(
 where
     W: Write,
 {
-    // writing status line
-    write!(
-        &mut writer,
-        "HTTP/{}.{} {} {}\r\n",
-        http_version.0,
-        http_version.1,
-        status_code.0,
-        status_code.default_reason_phrase()
-    )?;
+    // writing status line, which is precomputed for the usual versions and status codes
+    match status_code.status_line(http_version) {
+        Some(line) => writer.write_all(line)?,
+        None => write!(
+            &mut writer,
+            "HTTP/{}.{} {} {}\r\n",
+            http_version.0,
+            http_version.1,
+            status_code.0,
+            status_code.default_reason_phrase()
+        )?,
+    }
 
     // writing headers
     for header in headers.iter() {
@@ -109,6 +224,94 @@ where
     Ok(())
 }
 
+/// Headers of a `200` response kept in the `304 Not Modified` replacing it (RFC 9110 #15.4.5).
+const NOT_MODIFIED_HEADERS: [&str; 5] = [
+    "Cache-Control",
+    "Content-Location",
+    "Date",
+    "Expires",
+    "Vary",
+];
+
+/// Compares two entity tags with the weak comparison of RFC 9110 #8.8.3.2, ignoring `W/`.
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+/// 64-bit FNV-1a hash, which unlike the hasher of the standard library stays the same across
+/// builds, so that the tags survive restarts and upgrades.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Size of the buffer used by `io::copy`.
+pub(crate) const COPY_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Default of `LimitsConfig::response_buffer_max`.
+pub(crate) const DEFAULT_RESPONSE_BUFFER_MAX: usize = 1024 * 1024;
+
+/// Copies the body in blocks of at least `write_buffer_size` bytes, so that a `BufWriter` of this
+/// capacity writes them directly instead of copying them into its buffer first.
+fn copy_body<R, W>(reader: &mut R, writer: &mut W, write_buffer_size: usize) -> IoResult<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    if write_buffer_size <= COPY_BUFFER_SIZE {
+        return io::copy(reader, writer);
+    }
+
+    let mut buf = vec![0; write_buffer_size];
+    let mut copied = 0;
+    loop {
+        let len = match reader.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..len])?;
+        copied += len as u64;
+    }
+}
+
+/// Reads a body of unknown length to find out its length, keeping up to `memory_max` bytes in
+/// memory and writing a larger body to a temporary file.
+fn buffer_body<R>(reader: &mut R, memory_max: usize) -> IoResult<(Box<dyn Read>, usize)>
+where
+    R: Read,
+{
+    let mut buf = Vec::new();
+    reader
+        .by_ref()
+        .take((memory_max as u64).saturating_add(1))
+        .read_to_end(&mut buf)?;
+    if buf.len() <= memory_max {
+        let len = buf.len();
+        return Ok((Box::new(Cursor::new(buf)), len));
+    }
+
+    let mut file = SpillFile::create()?;
+    file.write_all(&buf)?;
+    let len = buf.len() as u64 + io::copy(reader, &mut file)?;
+    let len = usize::try_from(len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Response body too large"))?;
+    file.rewind()?;
+    Ok((Box::new(file), len))
+}
+
+/// Calls the completion hook of a response, if any, with the outcome of writing its body.
+fn complete(on_complete: Option<CompletionHook>, result: &IoResult<u64>) {
+    if let Some(on_complete) = on_complete {
+        on_complete(match result {
+            Ok(len) => Ok(*len),
+            Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+        });
+    }
+}
+
 fn choose_transfer_encoding(
     status_code: StatusCode,
     request_headers: &[Header],
@@ -206,6 +409,9 @@ where
             headers: Vec::with_capacity(16),
             data_length,
             chunked_threshold: None,
+            connection: None,
+            default_headers: true,
+            on_complete: None,
         };
 
         for h in headers {
@@ -231,6 +437,137 @@ where
         self
     }
 
+    /// Sets the chunked threshold to `threshold` unless the response has one already, see
+    /// [`ServerConfig::chunked_threshold`](crate::ServerConfig::chunked_threshold).
+    pub(crate) fn with_default_chunked_threshold(
+        mut self,
+        threshold: Option<usize>,
+    ) -> Response<R> {
+        if self.chunked_threshold.is_none() {
+            self.chunked_threshold = threshold;
+        }
+        self
+    }
+
+    /// Lets `decorator` edit the headers of the response before it is sent, see
+    /// [`ServerConfig::response_decorator`](crate::ServerConfig::response_decorator).
+    pub(crate) fn decorated(
+        mut self,
+        decorator: &ResponseDecorator,
+        request: Option<&Request>,
+    ) -> Response<R> {
+        decorator.call(
+            request,
+            &mut ResponseHeaders::new(self.status_code, &mut self.headers),
+        );
+        self
+    }
+
+    /// Lets `transform` wrap the body of the response before it is sent, see
+    /// [`ServerConfig::body_transform`](crate::ServerConfig::body_transform).
+    pub(crate) fn transformed<'a>(self, transform: &BodyTransform) -> Response<Box<dyn Read + 'a>>
+    where
+        R: 'a,
+    {
+        let info = BodyInfo {
+            status_code: self.status_code,
+            content_type: self
+                .headers
+                .iter()
+                .find(|h| h.field.equiv("Content-Type"))
+                .map(|h| h.value.as_str()),
+            length: self.data_length,
+        };
+        let body = ResponseBody::new(Box::new(self.reader), self.data_length);
+        let (reader, data_length) = transform.call(&info, body).into_parts();
+
+        Response {
+            reader,
+            status_code: self.status_code,
+            headers: self.headers,
+            data_length,
+            chunked_threshold: self.chunked_threshold,
+            connection: self.connection,
+            default_headers: self.default_headers,
+            on_complete: self.on_complete,
+        }
+    }
+
+    /// Adds a `Connection: close` header, which is forbidden in `with_header`.
+    pub(crate) fn with_connection_close(mut self) -> Response<R> {
+        self.headers
+            .push(Header::from_bytes(&b"Connection"[..], &b"close"[..]).unwrap());
+        self
+    }
+
+    /// Closes the connection once this response is sent, with a `Connection: close` header,
+    /// e.g. to spread the clients over the servers of a cluster again.
+    ///
+    /// The next requests the client may already have sent on the connection are not read.
+    pub fn close_connection(mut self) -> Response<R> {
+        self.connection = Some(ConnectionChoice::Close);
+        self
+    }
+
+    /// Tells the client how long the server keeps the connection open while idle, and how many
+    /// more requests it accepts on it, with a `Keep-Alive: timeout=..., max=...` header.
+    ///
+    /// The header is informative and is only sent if the connection stays open: it is left out
+    /// if the client or the server closes the connection after this response, and HTTP/1.0
+    /// clients also get the `Connection: keep-alive` header they need to keep it open. The
+    /// server doesn't enforce these values, see
+    /// [`SocketConfig::keep_alive_idle_timeout`](crate::SocketConfig::keep_alive_idle_timeout)
+    /// for that.
+    pub fn keep_alive(mut self, timeout: Duration, max: u32) -> Response<R> {
+        self.connection = Some(ConnectionChoice::KeepAlive { timeout, max });
+        self
+    }
+
+    /// Leaves out the `Date` and `Server` headers added to the responses that don't have them,
+    /// e.g. for devices with a tight budget of bytes. See also
+    /// [`ServerConfig::emit_default_headers`](crate::ServerConfig::emit_default_headers).
+    ///
+    /// Servers with a clock should still send `Date` (RFC 9110 #6.6.1).
+    pub fn without_default_headers(mut self) -> Response<R> {
+        self.default_headers = false;
+        self
+    }
+
+    /// Returns true if [`close_connection`](Response::close_connection) was called.
+    pub fn closes_connection(&self) -> bool {
+        self.connection == Some(ConnectionChoice::Close)
+    }
+
+    /// Adds the `Connection` and `Keep-Alive` headers matching the choice of the handler and
+    /// the fate of the connection: `close` if the connection is closed after this response,
+    /// whether the handler or the server decided it, and the `Keep-Alive` header only if it
+    /// stays open for a client which doesn't close it itself.
+    pub(crate) fn with_connection_headers(
+        self,
+        close: bool,
+        client_closes: bool,
+        http_version: &HTTPVersion,
+    ) -> Response<R> {
+        if close || self.closes_connection() {
+            return self.with_connection_close();
+        }
+
+        match self.connection {
+            Some(ConnectionChoice::KeepAlive { timeout, max }) if !client_closes => {
+                let value = format!("timeout={}, max={}", timeout.as_secs(), max);
+                let mut response =
+                    self.with_header(Header::from_bytes(&b"Keep-Alive"[..], value).unwrap());
+                if *http_version == (1, 0) {
+                    response
+                        .headers
+                        .push(Header::from_bytes(&b"Connection"[..], &b"keep-alive"[..]).unwrap());
+                }
+                response
+            }
+            _ => self,
+        }
+    }
+
     /// Convert the response into the underlying `Read` type.
     ///
     /// This is mainly useful for testing as it must consume the `Response`.
@@ -239,7 +576,9 @@ where
     }
 
     /// The current `Content-Length` threshold for switching over to
-    /// chunked transfer. The default is 32768 bytes. Notice that
+    /// chunked transfer. The default is 32768 bytes, or
+    /// [`ServerConfig::chunked_threshold`](crate::ServerConfig::chunked_threshold) once the
+    /// response is sent, if set. Notice that
     /// chunked transfer is mutually exclusive with sending a
     /// `Content-Length` header as per the HTTP spec.
     pub fn chunked_threshold(&self) -> usize {
@@ -308,6 +647,32 @@ where
         self
     }
 
+    /// Sets a function called once the response has been sent, with the number of bytes of the
+    /// body written, or with the error that interrupted it.
+    ///
+    /// This is where the resources backing the body, e.g. a temporary file or a database cursor,
+    /// can be released, and the size of the response logged. A response sent without its body,
+    /// e.g. to a `HEAD` request, reports 0 bytes. The function isn't called if the response is
+    /// dropped without being sent, and isn't copied by `clone`. With
+    /// [`Request::into_duplex`](crate::Request::into_duplex), it is called once the writer is
+    /// dropped.
+    ///
+    /// ```
+    /// use tiny_http::Response;
+    ///
+    /// let response = Response::from_string("hello").on_complete(|result| match result {
+    ///     Ok(len) => println!("sent {} bytes", len),
+    ///     Err(err) => println!("failed: {}", err),
+    /// });
+    /// ```
+    pub fn on_complete<F>(mut self, on_complete: F) -> Response<R>
+    where
+        F: FnOnce(IoResult<u64>) + Send + 'static,
+    {
+        self.on_complete = Some(Box::new(on_complete));
+        self
+    }
+
     /// Returns the same request, but with different data.
     pub fn with_data<S>(self, reader: S, data_length: Option<usize>) -> Response<S>
     where
@@ -319,9 +684,56 @@ where
             status_code: self.status_code,
             data_length,
             chunked_threshold: self.chunked_threshold,
+            connection: self.connection,
+            default_headers: self.default_headers,
+            on_complete: self.on_complete,
         }
     }
 
+    /// Sends this response to every request, e.g. to the waiters of a long poll getting the same
+    /// notification, and returns the result of each in order.
+    ///
+    /// The body is read once and shared by the responses, while the head is written for each
+    /// request as it depends on its HTTP version and headers. The requests are answered one after
+    /// the other: the write timeout of the [`SocketConfig`](crate::SocketConfig) bounds how long
+    /// a slow client holds up the next ones.
+    ///
+    /// Fails if the body can't be read, dropping the requests unanswered. The
+    /// [completion hook](Response::on_complete) is called once the body has been read.
+    ///
+    /// ```no_run
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// use tiny_http::Response;
+    ///
+    /// let waiters: Vec<_> = server.incoming_requests().take(100).collect();
+    /// let results = Response::from_string("new message")
+    ///     .respond_to_all(waiters)
+    ///     .unwrap();
+    /// let failed = results.iter().filter(|result| result.is_err()).count();
+    /// ```
+    pub fn respond_to_all<I>(mut self, requests: I) -> IoResult<Vec<IoResult<()>>>
+    where
+        I: IntoIterator<Item = Request>,
+    {
+        let mut body = Vec::with_capacity(self.data_length.unwrap_or(0));
+        let reader = &mut self.reader;
+        let read = match self.data_length {
+            Some(len) => reader.take(len as u64).read_to_end(&mut body),
+            None => reader.read_to_end(&mut body),
+        };
+        let read = read.map(|len| len as u64);
+        complete(self.on_complete.take(), &read);
+        read?;
+
+        let body_len = body.len();
+        let body: Arc<[u8]> = Arc::from(body);
+        let response = self.with_data(Cursor::new(body), Some(body_len));
+        Ok(requests
+            .into_iter()
+            .map(|request| request.respond(response.clone()))
+            .collect())
+    }
+
     /// Prints the HTTP response to a writer.
     ///
     /// This function is the one used to send the response to the client's socket.
@@ -332,13 +744,101 @@ where
     ///
     /// Note: does not flush the writer.
     pub fn raw_print<W: Write>(
+        self,
+        writer: W,
+        http_version: HTTPVersion,
+        request_headers: &[Header],
+        do_not_send_body: bool,
+        upgrade: Option<&str>,
+    ) -> IoResult<()> {
+        self.raw_print_with_clock(
+            writer,
+            http_version,
+            request_headers,
+            do_not_send_body,
+            upgrade,
+            &SystemClock,
+        )
+    }
+
+    /// Same as `raw_print`, but the `Date` header is taken from `clock`.
+    pub(crate) fn raw_print_with_clock<W: Write>(
+        self,
+        writer: W,
+        http_version: HTTPVersion,
+        request_headers: &[Header],
+        do_not_send_body: bool,
+        upgrade: Option<&str>,
+        clock: &dyn Clock,
+    ) -> IoResult<()> {
+        self.raw_print_with_stats(
+            writer,
+            http_version,
+            request_headers,
+            do_not_send_body,
+            upgrade,
+            clock,
+            0,
+            DEFAULT_RESPONSE_BUFFER_MAX,
+            &mut ResponseStats::default(),
+        )
+    }
+
+    /// Same as `raw_print_with_clock`, but fills `stats` with what has been written, even if an
+    /// error occurs. `stats.duration` is left untouched.
+    ///
+    /// `write_buffer_size` is the capacity of the buffer of `writer`, if any. The body is written
+    /// in blocks of at least this size so that it bypasses the buffer. `response_buffer_max` is
+    /// the size above which a body of unknown length sent to an HTTP/1.0 client is buffered in a
+    /// temporary file instead of in memory.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn raw_print_with_stats<W: Write>(
         mut self,
-        mut writer: W,
+        writer: W,
         http_version: HTTPVersion,
         request_headers: &[Header],
         do_not_send_body: bool,
         upgrade: Option<&str>,
+        clock: &dyn Clock,
+        write_buffer_size: usize,
+        response_buffer_max: usize,
+        stats: &mut ResponseStats,
     ) -> IoResult<()> {
+        let on_complete = self.on_complete.take();
+        let result = self.print_with_stats(
+            writer,
+            http_version,
+            request_headers,
+            do_not_send_body,
+            upgrade,
+            clock,
+            write_buffer_size,
+            response_buffer_max,
+            stats,
+        );
+        complete(on_complete, &result);
+        result.map(|_| ())
+    }
+
+    /// Does the work of `raw_print_with_stats`, returning the length of the body sent.
+    #[allow(clippy::too_many_arguments)]
+    fn print_with_stats<W: Write>(
+        mut self,
+        writer: W,
+        http_version: HTTPVersion,
+        request_headers: &[Header],
+        do_not_send_body: bool,
+        upgrade: Option<&str>,
+        clock: &dyn Clock,
+        write_buffer_size: usize,
+        response_buffer_max: usize,
+        stats: &mut ResponseStats,
+    ) -> IoResult<u64> {
+        let mut writer = CountingWriter {
+            inner: writer,
+            count: 0,
+        };
+
         let mut transfer_encoding = Some(choose_transfer_encoding(
             self.status_code,
             request_headers,
@@ -348,18 +848,7 @@ where
             self.chunked_threshold(),
         ));
 
-        // add `Date` if not in the headers
-        if !self.headers.iter().any(|h| h.field.equiv("Date")) {
-            self.headers.insert(0, build_date_header());
-        }
-
-        // add `Server` if not in the headers
-        if !self.headers.iter().any(|h| h.field.equiv("Server")) {
-            self.headers.insert(
-                0,
-                Header::from_bytes(&b"Server"[..], &b"tiny-http (Rust)"[..]).unwrap(),
-            );
-        }
+        self.add_default_headers(clock);
 
         // handling upgrade
         if let Some(upgrade) = upgrade {
@@ -374,6 +863,12 @@ where
             transfer_encoding = None;
         }
 
+        // the framing headers of a `304 Not Modified` would describe the body of the `200` it
+        // replaces, which isn't known here (RFC 9110 #8.6)
+        if self.status_code == 304 {
+            transfer_encoding = None;
+        }
+
         // if the transfer encoding is identity, the content length must be known ; therefore if
         // we don't know it, we buffer the entire response first here
         // while this is an expensive operation, it is only ever needed for clients using HTTP 1.0
@@ -381,10 +876,8 @@ where
             match (self.data_length, transfer_encoding) {
                 (Some(l), _) => (Box::new(self.reader), Some(l)),
                 (None, Some(TransferEncoding::Identity)) => {
-                    let mut buf = Vec::new();
-                    self.reader.read_to_end(&mut buf)?;
-                    let l = buf.len();
-                    (Box::new(Cursor::new(buf)), Some(l))
+                    let (buffer, l) = buffer_body(&mut self.reader, response_buffer_max)?;
+                    (buffer, Some(l))
                 }
                 _ => (Box::new(self.reader), None),
             };
@@ -419,22 +912,40 @@ where
             _ => (),
         };
 
+        stats.transfer_encoding = transfer_encoding;
+
         // sending headers
-        write_message_header(
+        let result = write_message_header(
             writer.by_ref(),
             &http_version,
             &self.status_code,
             &self.headers,
-        )?;
+        );
+        stats.header_bytes = writer.count;
+        result?;
 
         // sending the body
-        if !do_not_send_body {
+        let result = if do_not_send_body {
+            Ok(0)
+        } else {
             match transfer_encoding {
                 Some(TransferEncoding::Chunked) => {
                     use chunked_transfer::Encoder;
 
-                    let mut writer = Encoder::new(writer);
-                    io::copy(&mut reader, &mut writer)?;
+                    // the last chunk is written when the encoder is dropped, unless the body
+                    // failed
+                    let aborted = Cell::new(false);
+                    let mut encoder = Encoder::with_chunks_size(
+                        AbortableWriter {
+                            inner: writer.by_ref(),
+                            aborted: &aborted,
+                        },
+                        write_buffer_size.max(COPY_BUFFER_SIZE),
+                    );
+                    let result = io::copy(&mut reader, &mut encoder);
+                    aborted.set(result.is_err());
+                    drop(encoder);
+                    result
                 }
 
                 Some(TransferEncoding::Identity) => {
@@ -442,15 +953,220 @@ where
                     let data_length = data_length.unwrap();
 
                     if data_length >= 1 {
-                        io::copy(&mut reader, &mut writer)?;
+                        copy_body(&mut reader, &mut writer, write_buffer_size)
+                    } else {
+                        Ok(0)
                     }
                 }
 
-                _ => (),
+                _ => Ok(0),
             }
+        };
+        stats.body_bytes = writer.count - stats.header_bytes;
+
+        result
+    }
+
+    /// Returns true if [`into_etagged`](Response::into_etagged) applies: a `200` response
+    /// without `ETag`, whose body is smaller than the chunked threshold.
+    pub(crate) fn etag_applies(&self) -> bool {
+        self.status_code == 200
+            && !self.headers.iter().any(|h| h.field.equiv("ETag"))
+            && self
+                .data_length
+                .is_some_and(|len| len < self.chunked_threshold())
+    }
+
+    /// Buffers the body to add a weak `ETag` computed from it, or answers `304 Not Modified`
+    /// if the tag matches the `If-None-Match` header of the request.
+    pub(crate) fn into_etagged(
+        mut self,
+        request_headers: &[Header],
+    ) -> IoResult<Response<Cursor<Vec<u8>>>> {
+        let mut body = Vec::with_capacity(self.data_length.unwrap_or(0));
+        let read = self
+            .reader
+            .by_ref()
+            .take(self.data_length.unwrap_or(0) as u64)
+            .read_to_end(&mut body)
+            .map(|len| len as u64);
+        if read.is_err() {
+            complete(self.on_complete.take(), &read);
+            read?;
         }
 
-        Ok(())
+        let etag = format!("W/\"{:x}-{:016x}\"", body.len(), fnv1a(&body));
+        let etag = Header::from_bytes(&b"ETag"[..], etag.as_bytes()).unwrap();
+
+        let not_modified = request_headers
+            .iter()
+            .filter(|h| h.field.equiv("If-None-Match"))
+            .flat_map(|h| h.value.as_str().split(','))
+            .any(|tag| tag.trim() == "*" || weak_eq(tag.trim(), etag.value.as_str()));
+
+        if not_modified {
+            let mut headers: Vec<Header> = self
+                .headers
+                .into_iter()
+                .filter(|h| NOT_MODIFIED_HEADERS.iter().any(|name| h.field.equiv(name)))
+                .collect();
+            headers.push(etag);
+            let mut response = Response::new(
+                StatusCode::NOT_MODIFIED,
+                headers,
+                Cursor::new(Vec::new()),
+                None,
+                None,
+            );
+            response.connection = self.connection;
+            response.default_headers = self.default_headers;
+            response.on_complete = self.on_complete;
+            return Ok(response);
+        }
+
+        self.headers.push(etag);
+        Ok(Response {
+            data_length: Some(body.len()),
+            reader: Cursor::new(body),
+            status_code: self.status_code,
+            headers: self.headers,
+            chunked_threshold: self.chunked_threshold,
+            connection: self.connection,
+            default_headers: self.default_headers,
+            on_complete: self.on_complete,
+        })
+    }
+
+    /// Writes the status line and the headers only, for a body streamed afterwards by the caller
+    /// until the connection is closed. The body and the length of the response are ignored.
+    ///
+    /// Returns the transfer encoding of the body, `None` if the response can't have one, and
+    /// the completion hook, to be called by the caller once the body ends.
+    pub(crate) fn print_streaming_head<W: Write>(
+        mut self,
+        writer: W,
+        http_version: HTTPVersion,
+        clock: &dyn Clock,
+    ) -> IoResult<(Option<TransferEncoding>, Option<CompletionHook>)> {
+        self.add_default_headers(clock);
+
+        let transfer_encoding = match self.status_code.0 {
+            100..=199 | 204 | 304 => None,
+            // HTTP 1.0 has no chunks, the body ends with the connection
+            _ if http_version <= (1, 0) => Some(TransferEncoding::Identity),
+            _ => Some(TransferEncoding::Chunked),
+        };
+        if transfer_encoding == Some(TransferEncoding::Chunked) {
+            self.headers
+                .push(Header::from_bytes(&b"Transfer-Encoding"[..], &b"chunked"[..]).unwrap());
+        }
+        let mut response = self.with_connection_close();
+        let on_complete = response.on_complete.take();
+
+        if let Err(err) = write_message_header(
+            writer,
+            &http_version,
+            &response.status_code,
+            &response.headers,
+        ) {
+            let result: IoResult<u64> = Err(err);
+            complete(on_complete, &result);
+            return result.map(|_| (transfer_encoding, None));
+        }
+        Ok((transfer_encoding, on_complete))
+    }
+
+    /// Same as `raw_print_with_stats`, but for the protocols framing the head and the body of
+    /// the response themselves, such as HTTP/3.
+    ///
+    /// The headers specific to HTTP/1 connections aren't sent, and `stats.header_bytes` is the
+    /// size of the names and values of the headers, before any compression by the protocol.
+    pub(crate) fn print_frames(
+        mut self,
+        writer: &mut dyn FrameWriter,
+        do_not_send_body: bool,
+        clock: &dyn Clock,
+        write_buffer_size: usize,
+        stats: &mut ResponseStats,
+    ) -> IoResult<()> {
+        let on_complete = self.on_complete.take();
+        let result = self.write_frames(writer, do_not_send_body, clock, write_buffer_size, stats);
+        complete(on_complete, &result);
+        result.map(|_| ())
+    }
+
+    /// Does the work of `print_frames`, returning the length of the body sent.
+    fn write_frames(
+        mut self,
+        writer: &mut dyn FrameWriter,
+        do_not_send_body: bool,
+        clock: &dyn Clock,
+        write_buffer_size: usize,
+        stats: &mut ResponseStats,
+    ) -> IoResult<u64> {
+        self.add_default_headers(clock);
+
+        // RFC 9114 #4.2: connection-specific headers are malformed in HTTP/3
+        self.headers.retain(|h| {
+            !(h.field.equiv("Connection")
+                || h.field.equiv("Keep-Alive")
+                || h.field.equiv("Proxy-Connection")
+                || h.field.equiv("Transfer-Encoding")
+                || h.field.equiv("Upgrade"))
+        });
+
+        let no_body_allowed = matches!(self.status_code.0, 100..=199 | 204 | 304);
+        if let (Some(data_length), false) = (self.data_length, no_body_allowed) {
+            self.headers.push(
+                Header::from_bytes(&b"Content-Length"[..], data_length.to_string().as_bytes())
+                    .unwrap(),
+            );
+        }
+
+        stats.transfer_encoding = None;
+        stats.header_bytes = self
+            .headers
+            .iter()
+            .map(|h| h.field.as_str().len() + h.value.len())
+            .sum();
+        writer.write_head(self.status_code, &self.headers)?;
+
+        if !do_not_send_body && !no_body_allowed {
+            let mut buf = vec![0; write_buffer_size.max(COPY_BUFFER_SIZE)];
+            loop {
+                let len = match self.reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                writer.write_data(&buf[..len])?;
+                stats.body_bytes += len;
+            }
+        }
+
+        writer.finish()?;
+        Ok(stats.body_bytes as u64)
+    }
+
+    /// Adds the `Date` and `Server` headers, unless they are already set.
+    fn add_default_headers(&mut self, clock: &dyn Clock) {
+        if !self.default_headers {
+            return;
+        }
+
+        // add `Date` if not in the headers
+        if !self.headers.iter().any(|h| h.field.equiv("Date")) {
+            self.headers.insert(0, build_date_header(clock));
+        }
+
+        // add `Server` if not in the headers
+        if !self.headers.iter().any(|h| h.field.equiv("Server")) {
+            self.headers.insert(
+                0,
+                Header::from_bytes(&b"Server"[..], &b"tiny-http (Rust)"[..]).unwrap(),
+            );
+        }
     }
 
     /// Retrieves the current value of the `Response` status code
@@ -467,6 +1183,23 @@ where
     pub fn headers(&self) -> &[Header] {
         &self.headers
     }
+
+    /// Wraps the reader of the body, keeping its length.
+    pub(crate) fn map_reader<S, F>(self, f: F) -> Response<S>
+    where
+        F: FnOnce(R) -> S,
+    {
+        Response {
+            reader: f(self.reader),
+            status_code: self.status_code,
+            headers: self.headers,
+            data_length: self.data_length,
+            chunked_threshold: self.chunked_threshold,
+            connection: self.connection,
+            default_headers: self.default_headers,
+            on_complete: self.on_complete,
+        }
+    }
 }
 
 impl<R> Response<R>
@@ -475,13 +1208,7 @@ where
 {
     /// Turns this response into a `Response<Box<Read + Send>>`.
     pub fn boxed(self) -> ResponseBox {
-        Response {
-            reader: Box::new(self.reader) as Box<dyn Read + Send>,
-            status_code: self.status_code,
-            headers: self.headers,
-            data_length: self.data_length,
-            chunked_threshold: self.chunked_threshold,
-        }
+        self.map_reader(|reader| Box::new(reader) as Box<dyn Read + Send>)
     }
 }
 
@@ -489,20 +1216,103 @@ impl Response<File> {
     /// Builds a new `Response` from a `File`.
     ///
     /// The `Content-Type` will **not** be automatically detected,
-    ///  you must set it yourself.
+    ///  you must set it yourself. To have the file downloaded, add a
+    ///  [`Header::attachment`] with its name.
     pub fn from_file(file: File) -> Response<File> {
         let file_size = file.metadata().ok().map(|v| v.len() as usize);
 
+        Response::new(StatusCode::OK, Vec::with_capacity(0), file, file_size, None)
+    }
+}
+
+/// Memory map of a file, shared by the clones of a response built with
+/// [`Response::from_mmap`].
+#[cfg(feature = "memmap")]
+#[derive(Clone)]
+pub struct SharedMmap(Arc<memmap2::Mmap>);
+
+#[cfg(feature = "memmap")]
+impl AsRef<[u8]> for SharedMmap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "memmap")]
+impl Response<Cursor<SharedMmap>> {
+    /// Builds a new `Response` from a memory-mapped file, sent without being copied first.
+    ///
+    /// Cloning the response is cheap, the clones share the map. The `Content-Type` will
+    /// **not** be automatically detected, you must set it yourself.
+    ///
+    /// Mapping the file is left to the caller, as it's only sound if the file isn't modified
+    /// nor truncated while mapped.
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use tiny_http::{Mmap, Response};
+    ///
+    /// let file = File::open("static/app.js").unwrap();
+    /// // the deployed assets are never modified in place
+    /// let map = unsafe { Mmap::map(&file) }.unwrap();
+    /// let response = Response::from_mmap(map);
+    /// ```
+    pub fn from_mmap(map: memmap2::Mmap) -> Response<Cursor<SharedMmap>> {
+        let len = map.len();
+
         Response::new(
-            StatusCode(200),
+            StatusCode::OK,
             Vec::with_capacity(0),
-            file,
-            file_size,
+            Cursor::new(SharedMmap(Arc::new(map))),
+            Some(len),
             None,
         )
     }
 }
 
+#[cfg(feature = "memmap")]
+impl Clone for Response<Cursor<SharedMmap>> {
+    fn clone(&self) -> Response<Cursor<SharedMmap>> {
+        self.clone_with_reader(self.reader.clone())
+    }
+}
+
+impl Response<Cursor<Arc<[u8]>>> {
+    /// Builds a new `Response` from a body shared with other responses, e.g. a pre-rendered
+    /// error page.
+    ///
+    /// Cloning the response is cheap, the clones share the body instead of copying it like
+    /// [`from_data`](Response::from_data) does.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use tiny_http::Response;
+    ///
+    /// let body: Arc<[u8]> = Arc::from(&b"<h1>Not Found</h1>"[..]);
+    /// let not_found = Response::from_shared(body).with_status_code(404);
+    ///
+    /// // one clone per request
+    /// let response = not_found.clone();
+    /// ```
+    pub fn from_shared(data: Arc<[u8]>) -> Response<Cursor<Arc<[u8]>>> {
+        let data_len = data.len();
+
+        Response::new(
+            StatusCode::OK,
+            Vec::with_capacity(0),
+            Cursor::new(data),
+            Some(data_len),
+            None,
+        )
+    }
+}
+
+impl Clone for Response<Cursor<Arc<[u8]>>> {
+    fn clone(&self) -> Response<Cursor<Arc<[u8]>>> {
+        self.clone_with_reader(self.reader.clone())
+    }
+}
+
 impl Response<Cursor<Vec<u8>>> {
     pub fn from_data<D>(data: D) -> Response<Cursor<Vec<u8>>>
     where
@@ -512,7 +1322,7 @@ impl Response<Cursor<Vec<u8>>> {
         let data_len = data.len();
 
         Response::new(
-            StatusCode(200),
+            StatusCode::OK,
             Vec::with_capacity(0),
             Cursor::new(data),
             Some(data_len),
@@ -528,7 +1338,7 @@ impl Response<Cursor<Vec<u8>>> {
         let data_len = data.len();
 
         Response::new(
-            StatusCode(200),
+            StatusCode::OK,
             vec![
                 Header::from_bytes(&b"Content-Type"[..], &b"text/plain; charset=UTF-8"[..])
                     .unwrap(),
@@ -540,6 +1350,39 @@ impl Response<Cursor<Vec<u8>>> {
     }
 }
 
+impl Response<RenderReader> {
+    /// Builds a `200 OK` response whose body is written by `render`, e.g. a template engine,
+    /// with a `Content-Type` of `text/html; charset=UTF-8`.
+    ///
+    /// The body is sent with the chunked transfer encoding as it's written, without being
+    /// collected first. See [`RenderReader`] for how `render` is run.
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use tiny_http::Response;
+    ///
+    /// let response = Response::from_render(|writer| {
+    ///     writeln!(writer, "<ul>")?;
+    ///     for item in &["one", "two"] {
+    ///         writeln!(writer, "<li>{}</li>", item)?;
+    ///     }
+    ///     writeln!(writer, "</ul>")
+    /// });
+    /// ```
+    pub fn from_render<F>(render: F) -> Response<RenderReader>
+    where
+        F: FnOnce(&mut dyn Write) -> IoResult<()> + Send + 'static,
+    {
+        Response::new(
+            StatusCode::OK,
+            vec![ContentType::TextHtmlUtf8.into()],
+            RenderReader::new(render),
+            None,
+            None,
+        )
+    }
+}
+
 impl Response<io::Empty> {
     /// Builds an empty `Response` with the given status code.
     pub fn empty<S>(status_code: S) -> Response<io::Empty>
@@ -563,12 +1406,22 @@ impl Response<io::Empty> {
 
 impl Clone for Response<io::Empty> {
     fn clone(&self) -> Response<io::Empty> {
+        self.clone_with_reader(io::empty())
+    }
+}
+
+impl<R> Response<R> {
+    /// Copies everything but the reader, replaced with `reader`, and the completion hook.
+    fn clone_with_reader<S>(&self, reader: S) -> Response<S> {
         Response {
-            reader: io::empty(),
+            reader,
             status_code: self.status_code,
             headers: self.headers.clone(),
             data_length: self.data_length,
             chunked_threshold: self.chunked_threshold,
+            connection: self.connection,
+            default_headers: self.default_headers,
+            on_complete: None,
         }
     }
 }
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::common::{Header, StatusCode};
+use crate::Request;
+
+/// Function called with the headers of every response right before they are sent, to stamp
+/// headers on all of them, e.g. a request id, security headers or CORS headers.
+///
+/// Set in [`ServerConfig::response_decorator`](crate::ServerConfig::response_decorator), it
+/// applies to the responses of the handlers as well as those the server sends by itself, like
+/// errors, but not to the interim `100 Continue` responses. The request is `None` for the
+/// responses to requests that couldn't be read, e.g. malformed ones or those rejected by the
+/// pre-handler.
+///
+/// ```
+/// use tiny_http::{Header, ResponseDecorator};
+///
+/// let decorator = ResponseDecorator::new(|_, headers| {
+///     headers.set_header("X-Content-Type-Options: nosniff".parse::<Header>().unwrap());
+/// });
+/// ```
+#[derive(Clone)]
+pub struct ResponseDecorator(Arc<DecorateFn>);
+
+type DecorateFn = dyn Fn(Option<&Request>, &mut ResponseHeaders<'_>) + Send + Sync + 'static;
+
+impl ResponseDecorator {
+    /// Builds a decorator from a function.
+    pub fn new<F>(f: F) -> ResponseDecorator
+    where
+        F: Fn(Option<&Request>, &mut ResponseHeaders<'_>) + Send + Sync + 'static,
+    {
+        ResponseDecorator(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, request: Option<&Request>, headers: &mut ResponseHeaders<'_>) {
+        (self.0)(request, headers)
+    }
+}
+
+impl fmt::Debug for ResponseDecorator {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ResponseDecorator")
+    }
+}
+
+/// Headers of a response about to be sent, passed to a [`ResponseDecorator`].
+///
+/// The headers framing the response, `Connection`, `Content-Length`, `Trailer`,
+/// `Transfer-Encoding` and `Upgrade`, are managed by the server and can't be added.
+#[derive(Debug)]
+pub struct ResponseHeaders<'a> {
+    status_code: StatusCode,
+    headers: &'a mut Vec<Header>,
+}
+
+impl<'a> ResponseHeaders<'a> {
+    pub(crate) fn new(status_code: StatusCode, headers: &'a mut Vec<Header>) -> Self {
+        ResponseHeaders {
+            status_code,
+            headers,
+        }
+    }
+
+    /// Returns the status code of the response.
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    /// Returns the headers of the response.
+    pub fn headers(&self) -> &[Header] {
+        self.headers
+    }
+
+    /// Adds a header, keeping those with the same name.
+    pub fn add_header(&mut self, header: Header) {
+        if !is_framing(&header) {
+            self.headers.push(header);
+        }
+    }
+
+    /// Adds a header, replacing those with the same name.
+    pub fn set_header(&mut self, header: Header) {
+        if !is_framing(&header) {
+            self.headers.retain(|h| h.field != header.field);
+            self.headers.push(header);
+        }
+    }
+
+    /// Removes the headers with this name, returning true if there were any.
+    pub fn remove_header(&mut self, field: &'static str) -> bool {
+        let len = self.headers.len();
+        self.headers.retain(|h| !h.field.equiv(field));
+        self.headers.len() != len
+    }
+}
+
+fn is_framing(header: &Header) -> bool {
+    [
+        "Connection",
+        "Content-Length",
+        "Trailer",
+        "Transfer-Encoding",
+        "Upgrade",
+    ]
+    .iter()
+    .any(|field| header.field.equiv(field))
+}
+
+#[cfg(test)]
+mod test {
+    use super::ResponseHeaders;
+    use crate::{Header, StatusCode};
+
+    fn header(s: &str) -> Header {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn edit() {
+        let mut headers = vec![header("Vary: Accept"), header("Content-Type: text/plain")];
+        let mut decorated = ResponseHeaders::new(StatusCode(200), &mut headers);
+        decorated.add_header(header("Vary: Origin"));
+        decorated.set_header(header("content-type: text/html"));
+        decorated.add_header(header("Transfer-Encoding: chunked"));
+        decorated.set_header(header("Content-Length: 0"));
+        assert_eq!(decorated.status_code(), 200);
+        assert_eq!(decorated.headers().len(), 3);
+
+        assert!(decorated.remove_header("vary"));
+        assert!(!decorated.remove_header("Vary"));
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].value.as_str(), "text/html");
+    }
+}
@@ -5,6 +5,21 @@
 //! trait contract and specific implementations are re-exported as [`SslContextImpl`] and [`SslStream`].
 //! The concrete type of these aliases will depend on which module you enable in `Cargo.toml`.
 
+/// Parameters negotiated during the TLS handshake of a connection, returned by
+/// [`Request::tls_info`](crate::Request::tls_info).
+///
+/// The names are the ones used by the SSL implementation, e.g. `TLSv1.3` and
+/// `TLS13_AES_256_GCM_SHA384` with Rustls, and `TLSv1.3` and `TLS_AES_256_GCM_SHA384` with
+/// OpenSSL. `native-tls` doesn't report them, both are `None` with it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// Version of the protocol, e.g. `TLSv1.2`.
+    pub protocol_version: Option<String>,
+
+    /// Cipher suite.
+    pub cipher_suite: Option<String>,
+}
+
 #[cfg(feature = "ssl-openssl")]
 pub(crate) mod openssl;
 #[cfg(feature = "ssl-openssl")]
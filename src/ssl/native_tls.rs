@@ -1,9 +1,11 @@
 use crate::connection::Connection;
+use crate::ssl::TlsInfo;
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
 use std::error::Error;
 use std::io::{Read, Write};
 use std::net::{Shutdown, SocketAddr};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use zeroize::Zeroizing;
 
 /// A wrapper around a `native_tls` stream.
@@ -14,21 +16,57 @@ pub(crate) struct NativeTlsStream(Arc<Mutex<native_tls::TlsStream<Connection>>>)
 
 // These struct methods form the implict contract for swappable TLS implementations
 impl NativeTlsStream {
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
+    pub(crate) fn peer_addr(&self) -> std::io::Result<Option<SocketAddr>> {
         self.0
             .lock()
             .expect("Failed to lock SSL stream mutex")
-            .get_mut()
+            .get_ref()
             .peer_addr()
     }
 
-    pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
+    pub(crate) fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
         self.0
             .lock()
             .expect("Failed to lock SSL stream mutex")
-            .get_mut()
+            .get_ref()
             .shutdown(how)
     }
+    pub(crate) fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .get_ref()
+            .set_read_timeout(dur)
+    }
+
+    pub(crate) fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .get_ref()
+            .set_write_timeout(dur)
+    }
+
+    pub(crate) fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .get_ref()
+            .set_nodelay(nodelay)
+    }
+
+    pub(crate) fn is_peer_connected(&self) -> bool {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .get_ref()
+            .is_peer_connected()
+    }
+
+    pub(crate) fn tls_info(&self) -> TlsInfo {
+        // native-tls doesn't expose the negotiated parameters
+        TlsInfo::default()
+    }
 }
 
 impl Read for NativeTlsStream {
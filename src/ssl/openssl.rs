@@ -1,9 +1,11 @@
 use crate::connection::Connection;
+use crate::ssl::TlsInfo;
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
 use std::error::Error;
 use std::io::{Read, Write};
 use std::net::{Shutdown, SocketAddr};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use zeroize::Zeroizing;
 
 pub(crate) struct OpenSslStream {
@@ -15,12 +17,42 @@ pub(crate) struct SplitOpenSslStream(Arc<Mutex<OpenSslStream>>);
 
 // These struct methods form the implict contract for swappable TLS implementations
 impl SplitOpenSslStream {
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
-        self.0.lock().unwrap().inner.get_mut().peer_addr()
+    pub(crate) fn peer_addr(&self) -> std::io::Result<Option<SocketAddr>> {
+        self.0.lock().unwrap().inner.get_ref().peer_addr()
     }
 
-    pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
-        self.0.lock().unwrap().inner.get_mut().shutdown(how)
+    pub(crate) fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
+        self.0.lock().unwrap().inner.get_ref().shutdown(how)
+    }
+
+    pub(crate) fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        self.0.lock().unwrap().inner.get_ref().set_read_timeout(dur)
+    }
+
+    pub(crate) fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .inner
+            .get_ref()
+            .set_write_timeout(dur)
+    }
+
+    pub(crate) fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        self.0.lock().unwrap().inner.get_ref().set_nodelay(nodelay)
+    }
+
+    pub(crate) fn is_peer_connected(&self) -> bool {
+        self.0.lock().unwrap().inner.get_ref().is_peer_connected()
+    }
+
+    pub(crate) fn tls_info(&self) -> TlsInfo {
+        let stream = self.0.lock().unwrap();
+        let ssl = stream.inner.ssl();
+        TlsInfo {
+            protocol_version: Some(ssl.version_str().to_owned()),
+            cipher_suite: ssl.current_cipher().map(|cipher| cipher.name().to_owned()),
+        }
     }
 }
 
@@ -62,15 +94,49 @@ impl Write for OpenSslStream {
     }
 }
 
+/// Options of the OpenSSL context, set in [`SslConfig::openssl`](crate::SslConfig::openssl).
+///
+/// The defaults are the ones of OpenSSL.
+#[derive(Debug, Clone)]
+pub struct OpenSslOptions {
+    /// If `Some`, a DER-encoded OCSP response stapled to the handshake of the clients asking
+    /// for the status of the certificate. It must be renewed before it expires, by restarting
+    /// the server with a new one.
+    pub ocsp_response: Option<Vec<u8>>,
+
+    /// Allows the clients to resume their sessions with session tickets. Defaults to `true`.
+    pub session_tickets: bool,
+
+    /// Maximum number of sessions kept in the cache for the clients resuming their sessions by
+    /// ID. `Some(0)` disables the cache, `None` keeps the default of OpenSSL.
+    pub session_cache_size: Option<u32>,
+
+    /// If `Some`, the Diffie-Hellman parameters in PEM format used by the `DHE` cipher suites.
+    pub dh_params: Option<Vec<u8>>,
+}
+
+impl Default for OpenSslOptions {
+    fn default() -> OpenSslOptions {
+        OpenSslOptions {
+            ocsp_response: None,
+            session_tickets: true,
+            session_cache_size: None,
+            dh_params: None,
+        }
+    }
+}
+
 pub(crate) struct OpenSslContext(openssl::ssl::SslContext);
 
 impl OpenSslContext {
-    pub fn from_pem(
+    pub fn from_pem_with_options(
         certificates: Vec<u8>,
         private_key: Zeroizing<Vec<u8>>,
+        options: &OpenSslOptions,
     ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        use openssl::dh::Dh;
         use openssl::pkey::PKey;
-        use openssl::ssl::{self, SslVerifyMode};
+        use openssl::ssl::{self, SslOptions, SslSessionCacheMode, SslVerifyMode};
         use openssl::x509::X509;
 
         let mut ctx = openssl::ssl::SslContext::builder(ssl::SslMethod::tls())?;
@@ -89,6 +155,30 @@ impl OpenSslContext {
         ctx.set_verify(SslVerifyMode::NONE);
         ctx.check_private_key()?;
 
+        if let Some(ref ocsp_response) = options.ocsp_response {
+            let ocsp_response = ocsp_response.clone();
+            ctx.set_status_callback(move |ssl| {
+                ssl.set_ocsp_status(&ocsp_response)?;
+                Ok(true)
+            })?;
+        }
+        if !options.session_tickets {
+            ctx.set_options(SslOptions::NO_TICKET);
+        }
+        match options.session_cache_size {
+            Some(0) => {
+                ctx.set_session_cache_mode(SslSessionCacheMode::OFF);
+            }
+            Some(size) => {
+                ctx.set_session_cache_size(size.min(i32::MAX as u32) as i32);
+            }
+            None => {}
+        }
+        if let Some(ref dh_params) = options.dh_params {
+            let dh = Dh::params_from_pem(dh_params)?;
+            ctx.set_tmp_dh(&dh)?;
+        }
+
         Ok(Self(ctx.build()))
     }
 
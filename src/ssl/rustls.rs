@@ -1,9 +1,11 @@
 use crate::connection::Connection;
+use crate::ssl::TlsInfo;
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
 use std::error::Error;
 use std::io::{Read, Write};
 use std::net::{Shutdown, SocketAddr};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use zeroize::Zeroizing;
 
 /// A wrapper around an owned Rustls connection and corresponding stream.
@@ -14,7 +16,7 @@ pub(crate) struct RustlsStream(
 );
 
 impl RustlsStream {
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
+    pub(crate) fn peer_addr(&self) -> std::io::Result<Option<SocketAddr>> {
         self.0
             .lock()
             .expect("Failed to lock SSL stream mutex")
@@ -22,13 +24,59 @@ impl RustlsStream {
             .peer_addr()
     }
 
-    pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
+    pub(crate) fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
         self.0
             .lock()
             .expect("Failed to lock SSL stream mutex")
             .sock
             .shutdown(how)
     }
+    pub(crate) fn set_read_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .sock
+            .set_read_timeout(dur)
+    }
+
+    pub(crate) fn set_write_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .sock
+            .set_write_timeout(dur)
+    }
+
+    pub(crate) fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .sock
+            .set_nodelay(nodelay)
+    }
+
+    pub(crate) fn is_peer_connected(&self) -> bool {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .sock
+            .is_peer_connected()
+    }
+
+    pub(crate) fn tls_info(&self) -> TlsInfo {
+        let stream = self.0.lock().expect("Failed to lock SSL stream mutex");
+        TlsInfo {
+            protocol_version: stream.conn.protocol_version().map(|version| match version {
+                rustls::ProtocolVersion::TLSv1_2 => "TLSv1.2".to_owned(),
+                rustls::ProtocolVersion::TLSv1_3 => "TLSv1.3".to_owned(),
+                version => format!("{:?}", version),
+            }),
+            cipher_suite: stream
+                .conn
+                .negotiated_cipher_suite()
+                .map(|suite| format!("{:?}", suite.suite())),
+        }
+    }
 }
 
 impl Clone for RustlsStream {
@@ -65,34 +113,15 @@ impl Write for RustlsStream {
 pub(crate) struct RustlsContext(Arc<rustls::ServerConfig>);
 
 impl RustlsContext {
+    pub(crate) fn from_config(config: Arc<rustls::ServerConfig>) -> Self {
+        Self(config)
+    }
+
     pub(crate) fn from_pem(
         certificates: Vec<u8>,
         private_key: Zeroizing<Vec<u8>>,
     ) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let certificate_chain: Vec<rustls::Certificate> =
-            rustls_pemfile::certs(&mut certificates.as_slice())?
-                .into_iter()
-                .map(|bytes| rustls::Certificate(bytes))
-                .collect();
-
-        if certificate_chain.is_empty() {
-            return Err("Couldn't extract certificate chain from config.".into());
-        }
-
-        let private_key = rustls::PrivateKey({
-            let pkcs8_keys = rustls_pemfile::pkcs8_private_keys(
-                &mut private_key.clone().as_slice(),
-            )
-            .expect("file contains invalid pkcs8 private key (encrypted keys are not supported)");
-
-            if let Some(pkcs8_key) = pkcs8_keys.first() {
-                pkcs8_key.clone()
-            } else {
-                let rsa_keys = rustls_pemfile::rsa_private_keys(&mut private_key.as_slice())
-                    .expect("file contains invalid rsa private key");
-                rsa_keys[0].clone()
-            }
-        });
+        let (certificate_chain, private_key) = parse_pem(&certificates, &private_key)?;
 
         let tls_conf = rustls::ServerConfig::builder()
             .with_safe_defaults()
@@ -113,6 +142,35 @@ impl RustlsContext {
     }
 }
 
+/// Parses a certificate chain and its private key, in PKCS#8 or RSA format, from PEM files.
+pub(crate) fn parse_pem(
+    certificates: &[u8],
+    private_key: &[u8],
+) -> Result<(Vec<rustls::Certificate>, rustls::PrivateKey), Box<dyn Error + Send + Sync>> {
+    let certificate_chain: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut &*certificates)?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+
+    if certificate_chain.is_empty() {
+        return Err("Couldn't extract certificate chain from config.".into());
+    }
+
+    let pkcs8_keys = rustls_pemfile::pkcs8_private_keys(&mut &*private_key).map_err(|_| {
+        "file contains invalid pkcs8 private key (encrypted keys are not supported)"
+    })?;
+    let private_key = match pkcs8_keys.into_iter().next() {
+        Some(pkcs8_key) => pkcs8_key,
+        None => rustls_pemfile::rsa_private_keys(&mut &*private_key)
+            .map_err(|_| "file contains invalid rsa private key")?
+            .into_iter()
+            .next()
+            .ok_or("Couldn't extract private key from config.")?,
+    };
+
+    Ok((certificate_chain, rustls::PrivateKey(private_key)))
+}
+
 impl From<RustlsStream> for RefinedStream {
     fn from(stream: RustlsStream) -> Self {
         Self::Https(stream)
@@ -0,0 +1,105 @@
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Lifecycle state of a server, returned by [`Server::state`](crate::Server::state) and
+/// reported by the health check (see
+/// [`ServerConfig::health_check_path`](crate::ServerConfig::health_check_path)).
+///
+/// Only `Ready` is healthy. The process is alive in every state but `Stopped`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServerState {
+    /// The server accepts connections but isn't ready to handle requests yet, see
+    /// [`Server::set_ready`](crate::Server::set_ready).
+    Starting,
+
+    /// The server handles requests. This is the state of a new server.
+    Ready,
+
+    /// The server still handles requests but is about to be shut down, see
+    /// [`Server::set_draining`](crate::Server::set_draining).
+    Draining,
+
+    /// The server has been dropped. Connections still open finish their current requests.
+    Stopped,
+}
+
+impl ServerState {
+    /// Returns the name of the state in lower case, as sent by the health check.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServerState::Starting => "starting",
+            ServerState::Ready => "ready",
+            ServerState::Draining => "draining",
+            ServerState::Stopped => "stopped",
+        }
+    }
+
+    fn from_u8(value: u8) -> ServerState {
+        match value {
+            0 => ServerState::Starting,
+            1 => ServerState::Ready,
+            2 => ServerState::Draining,
+            _ => ServerState::Stopped,
+        }
+    }
+}
+
+impl Display for ServerState {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+/// State shared between the server and its connections.
+pub(crate) struct SharedState(AtomicU8);
+
+impl SharedState {
+    pub(crate) fn new(state: ServerState) -> SharedState {
+        SharedState(AtomicU8::new(state as u8))
+    }
+
+    pub(crate) fn get(&self) -> ServerState {
+        ServerState::from_u8(self.0.load(Ordering::Acquire))
+    }
+
+    pub(crate) fn set(&self, state: ServerState) {
+        self.0.store(state as u8, Ordering::Release);
+    }
+
+    /// Moves from one of the `from` states to `to`. Returns false if the state was another one.
+    pub(crate) fn transition(&self, from: &[ServerState], to: ServerState) -> bool {
+        let mut current = self.0.load(Ordering::Acquire);
+        loop {
+            if !from.contains(&ServerState::from_u8(current)) {
+                return false;
+            }
+
+            match self.0.compare_exchange_weak(
+                current,
+                to as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ServerState, SharedState};
+
+    #[test]
+    fn transitions() {
+        let state = SharedState::new(ServerState::Ready);
+        assert!(state.transition(&[ServerState::Ready], ServerState::Draining));
+        assert!(!state.transition(&[ServerState::Ready], ServerState::Starting));
+        assert_eq!(state.get(), ServerState::Draining);
+
+        state.set(ServerState::Stopped);
+        assert_eq!(state.get(), ServerState::Stopped);
+        assert_eq!(state.get().to_string(), "stopped");
+    }
+}
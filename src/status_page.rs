@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::common::{ContentType, Header, StatusCode};
+use crate::recorder::json_string;
+use crate::state::ServerState;
+use crate::util::{ConnectionCounter, MessagesQueue, PoolMonitor};
+use crate::{ErrorFormat, IpFilter, Message, Response, ServerConfig};
+
+/// Path of the status page, see [`ServerConfig::status_page`].
+pub const STATUS_PAGE_PATH: &str = "/__tiny_http/status";
+
+/// Live statistics of a server, built from [`ServerConfig::status_page`] and shared by the
+/// connections of all the listeners.
+pub(crate) struct StatusPage {
+    // addresses allowed to see the page
+    filter: IpFilter,
+
+    started_at: Instant,
+
+    // number of open connections, also reported by `Server::num_connections`
+    connections: Arc<ConnectionCounter>,
+
+    // number of responses sent, by status code
+    responses: Mutex<BTreeMap<u16, u64>>,
+
+    // requests waiting for `Server::recv`
+    messages: Arc<MessagesQueue<Message>>,
+
+    // threads handling the connections
+    pool: PoolMonitor,
+
+    // JSON object of the configuration, rendered once
+    config: String,
+}
+
+impl StatusPage {
+    pub(crate) fn new(
+        filter: IpFilter,
+        config: String,
+        connections: Arc<ConnectionCounter>,
+        messages: Arc<MessagesQueue<Message>>,
+        pool: PoolMonitor,
+        started_at: Instant,
+    ) -> StatusPage {
+        StatusPage {
+            filter,
+            started_at,
+            connections,
+            responses: Mutex::new(BTreeMap::new()),
+            messages,
+            pool,
+            config,
+        }
+    }
+
+    /// Returns true if a client at this address may see the page. Connections without IP
+    /// address, like Unix sockets, always may.
+    pub(crate) fn is_allowed(&self, remote_addr: Option<&SocketAddr>) -> bool {
+        remote_addr.map_or(true, |addr| self.filter.is_allowed(&addr.ip()))
+    }
+
+    /// Counts a response about to be sent.
+    pub(crate) fn count_response(&self, status: StatusCode) {
+        *self.responses.lock().unwrap().entry(status.0).or_insert(0) += 1;
+    }
+
+    /// Builds the page, in HTML if the `Accept` header among the `headers` of the request
+    /// prefers it, in JSON otherwise.
+    pub(crate) fn render(
+        &self,
+        state: ServerState,
+        now: Instant,
+        headers: &[Header],
+    ) -> Response<Cursor<Vec<u8>>> {
+        let stats = self.stats(state, now);
+        let (body, content_type): (String, Header) =
+            if ErrorFormat::negotiate(headers) == ErrorFormat::Html {
+                (stats.html(), ContentType::TextHtmlUtf8.into())
+            } else {
+                (stats.json(), ContentType::ApplicationJson.into())
+            };
+
+        Response::from_data(body)
+            .with_header(content_type)
+            .with_header(Header::from_bytes(&b"Cache-Control"[..], &b"no-store"[..]).unwrap())
+    }
+
+    fn stats(&self, state: ServerState, now: Instant) -> Stats<'_> {
+        let pool = self.pool.stats();
+        Stats {
+            state,
+            uptime_secs: now.saturating_duration_since(self.started_at).as_secs(),
+            connections: self.connections.count(),
+            queue_depth: self.messages.len(),
+            threads: pool.threads,
+            idle_threads: pool.idle,
+            queued_tasks: pool.queued,
+            responses: self.responses.lock().unwrap().clone(),
+            config: &self.config,
+        }
+    }
+}
+
+/// Snapshot of the statistics, rendered by the page.
+struct Stats<'a> {
+    state: ServerState,
+    uptime_secs: u64,
+    connections: usize,
+    queue_depth: usize,
+    threads: usize,
+    idle_threads: usize,
+    queued_tasks: usize,
+    responses: BTreeMap<u16, u64>,
+    config: &'a str,
+}
+
+impl Stats<'_> {
+    fn json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"state\":\"{}\",\"uptime_secs\":{},\"connections\":{},\"queue_depth\":{},\
+             \"workers\":{{\"threads\":{},\"busy\":{},\"idle\":{},\"queued_tasks\":{}}},\
+             \"responses\":{{",
+            self.state,
+            self.uptime_secs,
+            self.connections,
+            self.queue_depth,
+            self.threads,
+            self.threads.saturating_sub(self.idle_threads),
+            self.idle_threads,
+            self.queued_tasks,
+        );
+        for (i, (status, count)) in self.responses.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "\"{}\":{}", status, count);
+        }
+        let _ = write!(out, "}},\"config\":{}}}", self.config);
+        out
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html><head><title>tiny-http status</title>\
+             <meta http-equiv=\"refresh\" content=\"5\"></head><body>\n<h1>tiny-http status</h1>\n<table>\n",
+        );
+        let rows = [
+            ("State", self.state.to_string()),
+            ("Uptime", format!("{} s", self.uptime_secs)),
+            ("Connections", self.connections.to_string()),
+            ("Queued requests", self.queue_depth.to_string()),
+            ("Threads", self.threads.to_string()),
+            (
+                "Busy threads",
+                self.threads.saturating_sub(self.idle_threads).to_string(),
+            ),
+            ("Idle threads", self.idle_threads.to_string()),
+            ("Queued connections", self.queued_tasks.to_string()),
+        ];
+        for (name, value) in rows.iter() {
+            let _ = writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", name, value);
+        }
+        out.push_str("</table>\n<h2>Responses</h2>\n<table>\n");
+        for (status, count) in self.responses.iter() {
+            let _ = writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", status, count);
+        }
+        out.push_str("</table>\n<h2>Configuration</h2>\n<pre>");
+        html_escape(&mut out, self.config);
+        out.push_str("</pre>\n</body></html>\n");
+        out
+    }
+}
+
+/// Renders the parts of the configuration useful to debug the server, leaving out secrets
+/// like the private key.
+pub(crate) fn config_json(config: &ServerConfig) -> String {
+    let mut out = String::from("{\"addr\":");
+    json_string(&mut out, &format!("{:?}", config.addr));
+    out.push_str(",\"listeners\":[");
+    for (i, listener) in config.listeners.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        json_string(&mut out, &format!("{:?}", listener.addr));
+    }
+    let _ = write!(out, "],\"ssl\":{},\"pool\":", config.ssl.is_some());
+    json_string(&mut out, &format!("{:?}", config.pool));
+    out.push_str(",\"socket\":");
+    json_string(&mut out, &format!("{:?}", config.socket));
+    out.push_str(",\"limits\":");
+    json_string(&mut out, &format!("{:?}", config.limits));
+    out.push_str(",\"health_check_path\":");
+    match config.health_check_path {
+        Some(ref path) => json_string(&mut out, path),
+        None => out.push_str("null"),
+    }
+    out.push_str(",\"chunked_threshold\":");
+    match config.chunked_threshold {
+        Some(threshold) => {
+            let _ = write!(out, "{}", threshold);
+        }
+        None => out.push_str("null"),
+    }
+    let _ = write!(
+        out,
+        ",\"auto_etag\":{},\"emit_default_headers\":{},\"normalize_absolute_form\":{}}}",
+        config.auto_etag, config.emit_default_headers, config.normalize_absolute_form
+    );
+    out
+}
+
+fn html_escape(out: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{html_escape, Stats};
+    use crate::ServerState;
+    use std::collections::BTreeMap;
+
+    fn stats(responses: BTreeMap<u16, u64>) -> Stats<'static> {
+        Stats {
+            state: ServerState::Ready,
+            uptime_secs: 3,
+            connections: 2,
+            queue_depth: 1,
+            threads: 4,
+            idle_threads: 3,
+            queued_tasks: 0,
+            responses,
+            config: "{\"ssl\":false}",
+        }
+    }
+
+    #[test]
+    fn json() {
+        let mut responses = BTreeMap::new();
+        responses.insert(404, 1);
+        responses.insert(200, 10);
+        assert_eq!(
+            stats(responses).json(),
+            "{\"state\":\"ready\",\"uptime_secs\":3,\"connections\":2,\"queue_depth\":1,\
+             \"workers\":{\"threads\":4,\"busy\":1,\"idle\":3,\"queued_tasks\":0},\
+             \"responses\":{\"200\":10,\"404\":1},\"config\":{\"ssl\":false}}"
+        );
+        assert!(stats(BTreeMap::new()).json().contains("\"responses\":{},"));
+    }
+
+    #[test]
+    fn html() {
+        let html = stats(BTreeMap::new()).html();
+        assert!(html.contains("<tr><th>Busy threads</th><td>1</td></tr>"));
+        assert!(html.contains("<pre>{&quot;ssl&quot;:false}</pre>"));
+
+        let mut out = String::new();
+        html_escape(&mut out, "<a & b>");
+        assert_eq!(out, "&lt;a &amp; b&gt;");
+    }
+}
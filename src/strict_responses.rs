@@ -0,0 +1,129 @@
+use std::io::{Error as IoError, ErrorKind};
+
+use crate::common::{Header, StatusCode};
+use crate::log;
+
+/// Protocol requirement violated by a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Violation {
+    pub(crate) description: &'static str,
+    // false for the requirements the RFCs only recommend
+    pub(crate) mandatory: bool,
+}
+
+/// Returns the protocol requirement that a response with this status code and these headers
+/// violates, if any.
+///
+/// These are the `Location` header of the redirections, the `Allow` header of
+/// `405 Method Not Allowed`, and the `Content-Range` header of `206 Partial Content`, unless its
+/// body is `multipart/byteranges`. The `Location` header of `201 Created` is only recommended.
+pub(crate) fn violation(status: StatusCode, headers: &[Header]) -> Option<Violation> {
+    let has = |field: &'static str| headers.iter().any(|h| h.field.equiv(field));
+    let mandatory = |description| {
+        Some(Violation {
+            description,
+            mandatory: true,
+        })
+    };
+
+    match status.0 {
+        201 if !has("Location") => Some(Violation {
+            description: "201 Created without Location header",
+            mandatory: false,
+        }),
+        301 | 302 | 303 | 307 | 308 if !has("Location") => {
+            mandatory("redirection without Location header")
+        }
+        405 if !has("Allow") => mandatory("405 Method Not Allowed without Allow header"),
+        206 if !has("Content-Range") && !is_multipart_byteranges(headers) => {
+            mandatory("206 Partial Content without Content-Range header")
+        }
+        _ => None,
+    }
+}
+
+fn is_multipart_byteranges(headers: &[Header]) -> bool {
+    headers
+        .iter()
+        .filter(|h| h.field.equiv("Content-Type"))
+        .any(|h| {
+            h.value
+                .as_str()
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with("multipart/byteranges")
+        })
+}
+
+/// Checks a response of the handler before it is sent, with the `strict-responses` feature.
+///
+/// Violations are logged, and those of mandatory requirements make
+/// [`Request::respond`](crate::Request::respond) fail with `InvalidInput` in debug builds, so
+/// that the request is answered according to the [`UnansweredPolicy`](crate::UnansweredPolicy)
+/// and the bug is noticed in development. The responses sent by the server itself aren't
+/// checked.
+pub(crate) fn check(status: StatusCode, headers: &[Header]) -> Result<(), IoError> {
+    let violation = match violation(status, headers) {
+        Some(violation) => violation,
+        None => return Ok(()),
+    };
+
+    if !violation.mandatory {
+        log::warn!("Questionable response: {}", violation.description);
+        return Ok(());
+    }
+
+    log::error!("Invalid response: {}", violation.description);
+    if cfg!(debug_assertions) {
+        return Err(IoError::new(ErrorKind::InvalidInput, violation.description));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::violation;
+    use crate::{Header, Response, StatusCode, TestRequest};
+    use std::io::ErrorKind;
+
+    fn header(header: &str) -> Header {
+        header.parse().unwrap()
+    }
+
+    #[test]
+    fn requirements() {
+        let location = [header("Location: /items/1")];
+        assert!(!violation(StatusCode(201), &[]).unwrap().mandatory);
+        assert!(violation(StatusCode(201), &location).is_none());
+        assert!(violation(StatusCode(308), &[]).unwrap().mandatory);
+        assert!(violation(StatusCode(301), &location).is_none());
+        assert!(violation(StatusCode(304), &[]).is_none());
+
+        assert!(violation(StatusCode(405), &[]).is_some());
+        assert!(violation(StatusCode(405), &[header("Allow: GET, HEAD")]).is_none());
+
+        assert!(violation(StatusCode(206), &[]).is_some());
+        assert!(violation(StatusCode(206), &[header("Content-Range: bytes 0-9/100")]).is_none());
+        let multipart = header("Content-Type: multipart/byteranges; boundary=THIS");
+        assert!(violation(StatusCode(206), &[multipart]).is_none());
+
+        assert!(violation(StatusCode(200), &[]).is_none());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn respond() {
+        let request: crate::Request = TestRequest::new().into();
+        let err = request.respond(Response::empty(405)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        let request: crate::Request = TestRequest::new().into();
+        let response = Response::empty(405).with_header(header("Allow: GET"));
+        assert!(request.respond(response).is_ok());
+
+        // only recommended
+        let request: crate::Request = TestRequest::new().into();
+        assert!(request.respond(Response::empty(201)).is_ok());
+    }
+}
@@ -1,7 +1,13 @@
-use crate::{request::new_request, HTTPVersion, Header, HeaderField, Method, Request};
+use crate::util::MemoryBudget;
+use crate::{
+    request::new_request, HTTPVersion, Header, HeaderField, LimitsConfig, Method, Request,
+};
 use ascii::AsciiString;
-use std::net::SocketAddr;
+use std::io::{ErrorKind, Read, Result as IoResult, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
 use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
 /// A simpler version of [`Request`] that is useful for testing. No data actually goes anywhere.
 ///
@@ -37,7 +43,7 @@ use std::str::FromStr;
 /// #     }
 /// # }
 /// let response = server.handle_request(request.into());
-/// assert_eq!(response.status_code(), StatusCode(200));
+/// assert_eq!(response.status_code(), StatusCode::OK);
 /// ```
 pub struct TestRequest {
     body: &'static str,
@@ -73,6 +79,8 @@ impl From<TestRequest> for Request {
             Some(mock.remote_addr),
             mock.body.as_bytes(),
             std::io::sink(),
+            &LimitsConfig::default(),
+            &MemoryBudget::new(usize::MAX),
         )
         .unwrap()
     }
@@ -125,3 +133,141 @@ impl TestRequest {
         self
     }
 }
+
+/// Wraps the client side of a connection to a server under test, injecting network faults into
+/// what the client writes: partial writes, delays, byte corruption and an early end of stream.
+///
+/// The faults are deterministic, which lets the timeout and error paths of the server be tested
+/// without hand-made sleeps between writes.
+///
+/// ```no_run
+/// # use tiny_http::FaultInjector;
+/// # use std::io::Write;
+/// # use std::net::TcpStream;
+/// # use std::time::Duration;
+/// let stream = TcpStream::connect("127.0.0.1:8000").unwrap();
+/// let mut client = FaultInjector::new(stream)
+///     .with_max_write(4)
+///     .with_write_delay(Duration::from_millis(50))
+///     .with_fin_after(16);
+///
+/// // sent 4 bytes at a time, and cut before the end of the headers
+/// write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap_err();
+/// ```
+pub struct FaultInjector<S> {
+    inner: S,
+    max_write: Option<usize>,
+    write_delay: Option<Duration>,
+    corruptions: Vec<(u64, u8)>,
+    fin_after: Option<u64>,
+    // closes the writing half of `inner`, a no-op for other than TCP streams
+    shutdown: fn(&S) -> IoResult<()>,
+    // number of bytes written to `inner` so far
+    written: u64,
+}
+
+impl<S> FaultInjector<S> {
+    /// Wraps `inner`, without any fault until one is added.
+    pub fn new(inner: S) -> Self {
+        FaultInjector {
+            inner,
+            max_write: None,
+            write_delay: None,
+            corruptions: Vec::new(),
+            fin_after: None,
+            shutdown: |_| Ok(()),
+            written: 0,
+        }
+    }
+
+    /// Writes at most `max` bytes at a time, leaving the rest to the next write.
+    pub fn with_max_write(mut self, max: usize) -> Self {
+        self.max_write = Some(max.max(1));
+        self
+    }
+
+    /// Waits `delay` before each write.
+    pub fn with_write_delay(mut self, delay: Duration) -> Self {
+        self.write_delay = Some(delay);
+        self
+    }
+
+    /// XORs the byte at `offset` in the written stream with `mask`.
+    pub fn with_corruption(mut self, offset: u64, mask: u8) -> Self {
+        self.corruptions.push((offset, mask));
+        self
+    }
+
+    /// Returns a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwraps the stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl FaultInjector<TcpStream> {
+    /// Shuts down the writing half of the connection once `len` bytes are written, failing the
+    /// writes that follow with `BrokenPipe`. The server reads an end of stream there.
+    pub fn with_fin_after(mut self, len: u64) -> Self {
+        if len == 0 {
+            // no write will reach the limit
+            let _ = self.inner.shutdown(Shutdown::Write);
+        }
+        self.fin_after = Some(len);
+        self.shutdown = |stream| stream.shutdown(Shutdown::Write);
+        self
+    }
+}
+
+impl<S: Read> Read for FaultInjector<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<S: Write> Write for FaultInjector<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let mut len = buf.len();
+        if let Some(max) = self.max_write {
+            len = len.min(max);
+        }
+        if let Some(fin) = self.fin_after {
+            if self.written >= fin {
+                return Err(ErrorKind::BrokenPipe.into());
+            }
+            len = len.min((fin - self.written) as usize);
+        }
+        if len == 0 {
+            return Ok(0);
+        }
+
+        if let Some(delay) = self.write_delay {
+            thread::sleep(delay);
+        }
+
+        let mut data = buf[..len].to_vec();
+        for &(offset, mask) in &self.corruptions {
+            if offset >= self.written && offset < self.written + len as u64 {
+                data[(offset - self.written) as usize] ^= mask;
+            }
+        }
+        let len = self.inner.write(&data)?;
+        self.written += len as u64;
+
+        if let Some(fin) = self.fin_after {
+            if self.written >= fin {
+                self.inner.flush()?;
+                (self.shutdown)(&self.inner)?;
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Display, Formatter, Write};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::common::Header;
+
+/// Flag of the requests recorded by the caller.
+const FLAG_SAMPLED: u8 = 0x01;
+
+/// The W3C Trace Context of a request, correlating it with the other requests of the same
+/// distributed trace.
+///
+/// Parsed from the `traceparent` and `tracestate` headers of the request, or generated when the
+/// request has none (or an invalid one), starting a new trace. Returned by
+/// [`Request::trace_context`](crate::Request::trace_context), and in the
+/// [`ResponseStats`](crate::ResponseStats) of the response for the access logs.
+///
+/// The requests sent while handling the request carry the headers of a [`child`] context, so
+/// that the services they reach join the same trace.
+///
+/// ```
+/// let context = tiny_http::TraceContext::parse(
+///     "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+///     Some("congo=t61rcWkgMzE"),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(context.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+/// assert!(context.sampled());
+///
+/// let child = context.child();
+/// assert_eq!(child.trace_id(), context.trace_id());
+/// assert_ne!(child.parent_id(), context.parent_id());
+/// ```
+///
+/// [`child`]: TraceContext::child
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    parent_id: [u8; 8],
+    flags: u8,
+    tracestate: Option<String>,
+    remote: bool,
+}
+
+impl TraceContext {
+    /// Parses the value of a `traceparent` header, and the value of the `tracestate` header if
+    /// any. Returns `None` if `traceparent` is invalid, in which case `tracestate` must be
+    /// ignored too.
+    ///
+    /// Versions above `00` are parsed as `00`, ignoring the fields they add.
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<TraceContext> {
+        let traceparent = traceparent.trim();
+        let version = traceparent.get(..2)?;
+        let fields = match version {
+            "ff" => return None,
+            "00" if traceparent.len() != 55 => return None,
+            _ if traceparent.len() == 55 => traceparent,
+            _ if traceparent.as_bytes().get(55) == Some(&b'-') => &traceparent[..55],
+            _ => return None,
+        };
+
+        let mut parts = fields.split('-');
+        let mut version_byte = [0];
+        decode_hex(parts.next()?, &mut version_byte)?;
+        let mut trace_id = [0; 16];
+        decode_hex(parts.next()?, &mut trace_id)?;
+        let mut parent_id = [0; 8];
+        decode_hex(parts.next()?, &mut parent_id)?;
+        let mut flags = [0];
+        decode_hex(parts.next()?, &mut flags)?;
+
+        if trace_id == [0; 16] || parent_id == [0; 8] {
+            return None;
+        }
+
+        let tracestate = tracestate
+            .map(str::trim)
+            .filter(|tracestate| !tracestate.is_empty())
+            .map(str::to_owned);
+
+        Some(TraceContext {
+            trace_id,
+            parent_id,
+            flags: flags[0],
+            tracestate,
+            remote: true,
+        })
+    }
+
+    /// Parses the `traceparent` and `tracestate` headers of a request.
+    ///
+    /// Returns `None` if there is no `traceparent`, or several. The `tracestate` headers are
+    /// combined, as if they were a single one.
+    pub fn from_headers(headers: &[Header]) -> Option<TraceContext> {
+        let mut traceparents = headers.iter().filter(|h| h.field.equiv("traceparent"));
+        let traceparent = traceparents.next()?;
+        if traceparents.next().is_some() {
+            return None;
+        }
+
+        let tracestate = headers
+            .iter()
+            .filter(|h| h.field.equiv("tracestate"))
+            .map(|h| h.value.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let tracestate = Some(tracestate.as_str()).filter(|s| !s.is_empty());
+
+        TraceContext::parse(traceparent.value.as_str(), tracestate)
+    }
+
+    /// Starts a new trace, with random identifiers and the sampled flag set.
+    pub fn generate() -> TraceContext {
+        let mut trace_id = [0; 16];
+        trace_id[..8].copy_from_slice(&random_id().to_be_bytes());
+        trace_id[8..].copy_from_slice(&random_id().to_be_bytes());
+
+        TraceContext {
+            trace_id,
+            parent_id: random_id().to_be_bytes(),
+            flags: FLAG_SAMPLED,
+            tracestate: None,
+            remote: false,
+        }
+    }
+
+    /// Returns the context to send with the requests made while handling this one: same trace,
+    /// flags and `tracestate`, with a new parent id identifying this server's part of the trace.
+    pub fn child(&self) -> TraceContext {
+        TraceContext {
+            parent_id: random_id().to_be_bytes(),
+            remote: false,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the id of the whole trace.
+    #[inline]
+    pub fn trace_id(&self) -> [u8; 16] {
+        self.trace_id
+    }
+
+    /// Returns the trace id in hexadecimal, as usually logged.
+    pub fn trace_id_hex(&self) -> String {
+        encode_hex(&self.trace_id)
+    }
+
+    /// Returns the id of the caller's part of the trace.
+    #[inline]
+    pub fn parent_id(&self) -> [u8; 8] {
+        self.parent_id
+    }
+
+    /// Returns the trace flags.
+    #[inline]
+    pub fn trace_flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns true if the caller may record the trace.
+    #[inline]
+    pub fn sampled(&self) -> bool {
+        self.flags & FLAG_SAMPLED != 0
+    }
+
+    /// Returns the vendor-specific data of the `tracestate` header.
+    #[inline]
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+
+    /// Returns true if the context was received from the client, false if it was generated.
+    #[inline]
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    /// Returns the `traceparent` header, and the `tracestate` one if any, carrying this context.
+    pub fn headers(&self) -> Vec<Header> {
+        let mut headers =
+            vec![Header::from_bytes(&b"traceparent"[..], self.to_string().as_bytes()).unwrap()];
+        if let Some(ref tracestate) = self.tracestate {
+            if let Ok(header) = Header::from_bytes(&b"tracestate"[..], tracestate.as_bytes()) {
+                headers.push(header);
+            }
+        }
+        headers
+    }
+}
+
+/// Formats the context as the value of a `traceparent` header, always in version `00`.
+impl Display for TraceContext {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "00-{}-{}-{:02x}",
+            encode_hex(&self.trace_id),
+            encode_hex(&self.parent_id),
+            self.flags
+        )
+    }
+}
+
+/// Decodes lowercase hexadecimal digits, filling exactly `out`.
+fn decode_hex(hex: &str, out: &mut [u8]) -> Option<()> {
+    if hex.len() != out.len() * 2 {
+        return None;
+    }
+
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        _ => None,
+    };
+    for (byte, pair) in out.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+    Some(())
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+/// Returns a random non-zero id. Trace ids only need to be unique, not unpredictable, so the
+/// randomly keyed hasher of the standard library is enough.
+fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            hasher.write_u128(now.as_nanos());
+        }
+
+        let id = hasher.finish();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TraceContext;
+    use crate::{Header, Request, TestRequest};
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parse() {
+        let context = TraceContext::parse(TRACEPARENT, Some(" rojo=00f067aa0ba902b7 ")).unwrap();
+        assert_eq!(context.to_string(), TRACEPARENT);
+        assert_eq!(
+            context.parent_id(),
+            [0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7]
+        );
+        assert_eq!(context.tracestate(), Some("rojo=00f067aa0ba902b7"));
+        assert!(context.sampled());
+        assert!(context.is_remote());
+
+        // future versions may append fields
+        let future = "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra";
+        let context = TraceContext::parse(future, None).unwrap();
+        assert!(!context.sampled());
+        assert_eq!(
+            context.to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+        );
+
+        for invalid in &[
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-0g",
+            "00_4bf92f3577b34da6a3ce929d0e0e4736_00f067aa0ba902b7_01",
+            "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01.",
+        ] {
+            assert!(TraceContext::parse(invalid, None).is_none(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn from_headers() {
+        let traceparent = Header::from_bytes(&b"Traceparent"[..], TRACEPARENT).unwrap();
+        let headers = [
+            traceparent.clone(),
+            Header::from_bytes(&b"tracestate"[..], "a=1").unwrap(),
+            Header::from_bytes(&b"tracestate"[..], "b=2").unwrap(),
+        ];
+        let context = TraceContext::from_headers(&headers).unwrap();
+        assert_eq!(context.tracestate(), Some("a=1,b=2"));
+
+        assert!(TraceContext::from_headers(&[]).is_none());
+        assert!(TraceContext::from_headers(&[traceparent.clone(), traceparent]).is_none());
+    }
+
+    #[test]
+    fn generate_and_child() {
+        let context = TraceContext::generate();
+        assert!(!context.is_remote());
+        assert!(context.sampled());
+        assert_ne!(context.trace_id(), TraceContext::generate().trace_id());
+        assert_eq!(
+            TraceContext::parse(&context.to_string(), None)
+                .unwrap()
+                .trace_id(),
+            context.trace_id()
+        );
+
+        let context = TraceContext::parse(TRACEPARENT, Some("a=1")).unwrap();
+        let child = context.child();
+        assert_eq!(child.trace_id(), context.trace_id());
+        assert_ne!(child.parent_id(), context.parent_id());
+        assert!(!child.is_remote());
+
+        let headers = child.headers();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].value.as_str(), child.to_string());
+        assert_eq!(headers[1].value.as_str(), "a=1");
+    }
+
+    #[test]
+    fn request() {
+        let header = Header::from_bytes(&b"traceparent"[..], TRACEPARENT).unwrap();
+        let request: Request = TestRequest::new().with_header(header).into();
+        assert_eq!(request.trace_context().to_string(), TRACEPARENT);
+
+        let request: Request = TestRequest::new().into();
+        assert!(!request.trace_context().is_remote());
+    }
+}
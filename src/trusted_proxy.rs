@@ -0,0 +1,330 @@
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::common::Header;
+use crate::IpCidr;
+
+/// Addresses of the TLS-terminating proxies in front of the server, trusted to tell whether the
+/// client used HTTPS, so that [`Request::secure`](crate::Request::secure) is right behind them.
+///
+/// By default, the requests of connections from these addresses are secure if the last element
+/// of their `X-Forwarded-Proto` header, or the `proto` parameter of the last element of their
+/// `Forwarded` header, is `https`. Requests from other addresses are secure only if their
+/// connection is, whatever their headers.
+///
+/// ```
+/// use tiny_http::TrustedProxies;
+///
+/// let proxies = TrustedProxies::new()
+///     .trust("10.0.0.0/8".parse().unwrap())
+///     .trust("::1".parse().unwrap());
+///
+/// assert!(proxies.is_trusted(&"10.1.2.3".parse().unwrap()));
+/// assert!(!proxies.is_trusted(&"192.168.1.1".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<IpCidr>,
+    proxy_protocol: bool,
+}
+
+impl TrustedProxies {
+    /// Builds a configuration trusting no address.
+    pub fn new() -> TrustedProxies {
+        TrustedProxies::default()
+    }
+
+    /// Trusts the proxies of this range of addresses.
+    pub fn trust(mut self, range: IpCidr) -> TrustedProxies {
+        self.ranges.push(range);
+        self
+    }
+
+    /// Reads a PROXY protocol header, version 1 or 2, at the start of the connections from the
+    /// trusted addresses, instead of looking at the forwarded headers, which a TCP proxy passes
+    /// unchanged from the client.
+    ///
+    /// The requests of a connection are secure if its header is of version 2 and carries the
+    /// `PP2_CLIENT_SSL` flag, and [`Request::remote_addr`](crate::Request::remote_addr) returns
+    /// the source address of the header. Connections from the trusted addresses without a valid
+    /// header are closed.
+    ///
+    /// On HTTPS listeners, the header is read before the TLS handshake, as sent by the proxies
+    /// passing TLS through to the server, e.g. HAProxy in TCP mode with `send-proxy`. The
+    /// requests are then always secure. The header must arrive within a second, or the
+    /// connection is closed.
+    pub fn with_proxy_protocol(mut self) -> TrustedProxies {
+        self.proxy_protocol = true;
+        self
+    }
+
+    /// Returns true if the proxies at this address are trusted.
+    pub fn is_trusted(&self, addr: &IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(addr))
+    }
+
+    /// Returns true if the connections from this address start with a PROXY protocol header.
+    pub(crate) fn expects_proxy_protocol(&self, addr: &IpAddr) -> bool {
+        self.proxy_protocol && self.is_trusted(addr)
+    }
+
+    /// Returns whether the client of a request from a trusted proxy used HTTPS, `None` if the
+    /// headers don't tell or if the proxy sends a PROXY protocol header instead.
+    pub(crate) fn forwarded_secure(&self, headers: &[Header]) -> Option<bool> {
+        if self.proxy_protocol {
+            return None;
+        }
+
+        let proto = match headers
+            .iter()
+            .rev()
+            .find(|h| h.field.equiv("X-Forwarded-Proto"))
+        {
+            Some(header) => header.value.as_str().rsplit(',').next().map(str::to_owned),
+            None => headers
+                .iter()
+                .rev()
+                .find(|h| h.field.equiv("Forwarded"))
+                .and_then(|header| forwarded_proto(header.value.as_str())),
+        };
+
+        proto.map(|proto| proto.trim().eq_ignore_ascii_case("https"))
+    }
+}
+
+// `proto` parameter of the last element of a `Forwarded` header (RFC 7239)
+fn forwarded_proto(value: &str) -> Option<String> {
+    value.rsplit(',').next()?.split(';').find_map(|pair| {
+        let mut elems = pair.splitn(2, '=');
+        let name = elems.next()?.trim();
+        let value = elems.next()?.trim().trim_matches('"');
+        if name.eq_ignore_ascii_case("proto") {
+            Some(value.to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+/// Content of a PROXY protocol header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProxyHeader {
+    /// Address of the client, `None` for the connections of the proxy itself, e.g. health
+    /// checks, or for unknown protocols.
+    pub(crate) source: Option<SocketAddr>,
+    /// True if the client used TLS.
+    pub(crate) ssl: bool,
+}
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+// type of the TLV of version 2 describing the TLS connection of the client, and its flag
+const PP2_TYPE_SSL: u8 = 0x20;
+const PP2_CLIENT_SSL: u8 = 0x01;
+
+/// Reads a PROXY protocol header from the start of a connection.
+pub(crate) fn read_proxy_header<R: Read>(reader: &mut R) -> IoResult<ProxyHeader> {
+    let mut start = [0u8; 6];
+    reader.read_exact(&mut start)?;
+
+    if &start == b"PROXY " {
+        read_v1(reader)
+    } else if start[..] == V2_SIGNATURE[..6] {
+        let mut rest = [0u8; 6];
+        reader.read_exact(&mut rest)?;
+        if rest[..] != V2_SIGNATURE[6..] {
+            return Err(invalid("Invalid PROXY protocol signature"));
+        }
+        read_v2(reader)
+    } else {
+        Err(invalid("Missing PROXY protocol header"))
+    }
+}
+
+// `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n`, after `PROXY `
+fn read_v1<R: Read>(reader: &mut R) -> IoResult<ProxyHeader> {
+    // the whole line is at most 107 bytes
+    let mut line = Vec::new();
+    loop {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        if line.len() > 100 {
+            return Err(invalid("PROXY protocol header too long"));
+        }
+        line.push(byte[0]);
+    }
+    if line.pop() != Some(b'\r') {
+        return Err(invalid("Invalid PROXY protocol header"));
+    }
+
+    let line = std::str::from_utf8(&line).map_err(|_| invalid("Invalid PROXY protocol header"))?;
+    let elems: Vec<&str> = line.split(' ').collect();
+    let source = match elems.as_slice() {
+        ["UNKNOWN", ..] => None,
+        [protocol @ "TCP4", source, _, port, _] | [protocol @ "TCP6", source, _, port, _] => {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| invalid("Invalid PROXY protocol address"))?;
+            if ip.is_ipv4() != (*protocol == "TCP4") {
+                return Err(invalid("Invalid PROXY protocol address"));
+            }
+            let port = port
+                .parse()
+                .map_err(|_| invalid("Invalid PROXY protocol port"))?;
+            Some(SocketAddr::new(ip, port))
+        }
+        _ => return Err(invalid("Invalid PROXY protocol header")),
+    };
+
+    Ok(ProxyHeader { source, ssl: false })
+}
+
+// binary header, after the signature
+fn read_v2<R: Read>(reader: &mut R) -> IoResult<ProxyHeader> {
+    let mut fixed = [0u8; 4];
+    reader.read_exact(&mut fixed)?;
+    let (version_command, family) = (fixed[0], fixed[1]);
+    let len = usize::from(u16::from_be_bytes([fixed[2], fixed[3]]));
+    if version_command >> 4 != 2 || version_command & 0x0f > 1 {
+        return Err(invalid("Unsupported PROXY protocol version or command"));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+
+    // the `LOCAL` command is used by the proxy for its own connections
+    let local = version_command & 0x0f == 0;
+    let (source, addresses_len) = match family >> 4 {
+        0x1 if payload.len() >= 12 => {
+            let ip = Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]);
+            let port = u16::from_be_bytes([payload[8], payload[9]]);
+            (Some(SocketAddr::new(IpAddr::V4(ip), port)), 12)
+        }
+        0x2 if payload.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&payload[..16]);
+            let port = u16::from_be_bytes([payload[32], payload[33]]);
+            (
+                Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port)),
+                36,
+            )
+        }
+        0x1 | 0x2 => return Err(invalid("Truncated PROXY protocol addresses")),
+        // UNIX sockets, or unspecified
+        0x3 if payload.len() >= 216 => (None, 216),
+        _ => (None, 0),
+    };
+
+    let mut ssl = false;
+    let mut tlvs = &payload[addresses_len.min(payload.len())..];
+    while tlvs.len() >= 3 {
+        let kind = tlvs[0];
+        let value_len = usize::from(u16::from_be_bytes([tlvs[1], tlvs[2]]));
+        let value = tlvs
+            .get(3..3 + value_len)
+            .ok_or_else(|| invalid("Truncated PROXY protocol TLV"))?;
+        if kind == PP2_TYPE_SSL {
+            ssl = value
+                .first()
+                .is_some_and(|client| client & PP2_CLIENT_SSL != 0);
+        }
+        tlvs = &tlvs[3 + value_len..];
+    }
+
+    if local {
+        return Ok(ProxyHeader {
+            source: None,
+            ssl: false,
+        });
+    }
+
+    Ok(ProxyHeader { source, ssl })
+}
+
+fn invalid(message: &str) -> IoError {
+    IoError::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_proxy_header, ProxyHeader, TrustedProxies};
+    use crate::Header;
+
+    fn secure(headers: &[&str]) -> Option<bool> {
+        let headers: Vec<Header> = headers.iter().map(|h| h.parse().unwrap()).collect();
+        TrustedProxies::new().forwarded_secure(&headers)
+    }
+
+    #[test]
+    fn forwarded_headers() {
+        assert_eq!(secure(&[]), None);
+        assert_eq!(secure(&["X-Forwarded-Proto: https"]), Some(true));
+        assert_eq!(secure(&["X-Forwarded-Proto: HTTPS"]), Some(true));
+        assert_eq!(secure(&["X-Forwarded-Proto: https, http"]), Some(false));
+        assert_eq!(secure(&["X-Forwarded-Proto: http, https"]), Some(true));
+        assert_eq!(
+            secure(&["Forwarded: for=192.0.2.60;proto=http, for=198.51.100.17;proto=\"https\""]),
+            Some(true)
+        );
+        assert_eq!(secure(&["Forwarded: for=192.0.2.60"]), None);
+
+        let proxies = TrustedProxies::new().with_proxy_protocol();
+        let header: Header = "X-Forwarded-Proto: https".parse().unwrap();
+        assert_eq!(proxies.forwarded_secure(&[header]), None);
+    }
+
+    #[test]
+    fn proxy_protocol_v1() {
+        let mut data = &b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET /"[..];
+        assert_eq!(
+            read_proxy_header(&mut data).unwrap(),
+            ProxyHeader {
+                source: Some("192.0.2.1:56324".parse().unwrap()),
+                ssl: false,
+            }
+        );
+        assert_eq!(data, b"GET /");
+
+        let mut data = &b"PROXY UNKNOWN\r\n"[..];
+        assert_eq!(read_proxy_header(&mut data).unwrap().source, None);
+
+        let mut data = &b"PROXY TCP4 ::1 ::1 56324 443\r\n"[..];
+        assert!(read_proxy_header(&mut data).is_err());
+        let mut data = &b"GET / HTTP/1.1\r\n"[..];
+        assert!(read_proxy_header(&mut data).is_err());
+    }
+
+    #[test]
+    fn proxy_protocol_v2() {
+        let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x11".to_vec();
+        let mut payload = vec![192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb];
+        // PP2_TYPE_SSL with PP2_CLIENT_SSL, verified
+        payload.extend_from_slice(&[0x20, 0x00, 0x05, 0x01, 0, 0, 0, 0]);
+        header.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        header.extend_from_slice(&payload);
+        header.extend_from_slice(b"GET /");
+
+        let mut data = &header[..];
+        assert_eq!(
+            read_proxy_header(&mut data).unwrap(),
+            ProxyHeader {
+                source: Some("192.0.2.1:56324".parse().unwrap()),
+                ssl: true,
+            }
+        );
+        assert_eq!(data, b"GET /");
+
+        // LOCAL command
+        let mut data = &b"\r\n\r\n\0\r\nQUIT\n\x20\x00\x00\x00"[..];
+        assert_eq!(
+            read_proxy_header(&mut data).unwrap(),
+            ProxyHeader {
+                source: None,
+                ssl: false,
+            }
+        );
+    }
+}
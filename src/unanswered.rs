@@ -0,0 +1,72 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Request, ResponseBox, StatusCode};
+
+/// What happens to a request dropped without being answered, e.g. because its handler returned
+/// early or panicked.
+///
+/// Set in [`ServerConfig::unanswered`](crate::ServerConfig::unanswered). A request whose chunked
+/// body exceeded the [`LimitsConfig`](crate::LimitsConfig) is always answered with
+/// `413 Payload Too Large`.
+#[derive(Debug, Clone)]
+pub enum UnansweredPolicy {
+    /// Answers with an empty response with this status code. The default is
+    /// `500 Internal Server Error`.
+    Respond(StatusCode),
+
+    /// Sends nothing and closes the connection, as the responses to the next requests of the
+    /// connection would otherwise be taken as the response to this one.
+    Close,
+
+    /// Calls the handler, which returns the response to send, or `None` to close the connection
+    /// like [`Close`](UnansweredPolicy::Close).
+    Handler(UnansweredHandler),
+}
+
+impl Default for UnansweredPolicy {
+    fn default() -> Self {
+        UnansweredPolicy::Respond(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// Function called with a request dropped without being answered, returning the response to
+/// send instead, or `None` to close the connection without answering.
+///
+/// The function is called by the thread dropping the request, which may be unwinding from a
+/// panic of the handler: [`std::thread::panicking`] tells it apart, and a panic of the
+/// function itself then aborts the process.
+///
+/// ```
+/// use tiny_http::{Response, UnansweredHandler};
+///
+/// let handler = UnansweredHandler::new(|request| {
+///     let status = if std::thread::panicking() { 500 } else { 503 };
+///     eprintln!("{} {} left unanswered", request.method(), request.url());
+///     Some(Response::empty(status).boxed())
+/// });
+/// ```
+#[derive(Clone)]
+pub struct UnansweredHandler(Arc<HandlerFn>);
+
+type HandlerFn = dyn Fn(&Request) -> Option<ResponseBox> + Send + Sync + 'static;
+
+impl UnansweredHandler {
+    /// Builds a handler from a function.
+    pub fn new<F>(f: F) -> UnansweredHandler
+    where
+        F: Fn(&Request) -> Option<ResponseBox> + Send + Sync + 'static,
+    {
+        UnansweredHandler(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, request: &Request) -> Option<ResponseBox> {
+        (self.0)(request)
+    }
+}
+
+impl fmt::Debug for UnansweredHandler {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("UnansweredHandler")
+    }
+}
@@ -0,0 +1,271 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The target of a request, split into its path segments and query parameters.
+///
+/// Returned by [`Request::url_parsed`](crate::Request::url_parsed).
+///
+/// ```
+/// let url = tiny_http::ParsedUrl::parse("/api/hello%20world/?lang=en&q=a+b").unwrap();
+///
+/// assert_eq!(url.path(), "/api/hello%20world/");
+/// assert_eq!(url.segments(), ["api", "hello world", ""]);
+/// assert_eq!(url.query("q"), Some("a b"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedUrl {
+    path: String,
+    segments: Vec<String>,
+    query_string: Option<String>,
+    query: Vec<(String, String)>,
+}
+
+/// Error returned when the target of a request can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// The target contains a fragment (`#...`), which clients must not send.
+    Fragment,
+
+    /// A `%` isn't followed by two hexadecimal digits, or the decoded bytes aren't UTF-8.
+    InvalidEncoding,
+
+    /// The target is neither a path nor an absolute URL, e.g. the `host:port` of a `CONNECT`.
+    InvalidTarget,
+}
+
+impl ParsedUrl {
+    /// Parses the target of a request.
+    ///
+    /// Absolute URLs (`http://host/path`) are reduced to their path and query. The target `*`
+    /// (e.g. `OPTIONS *`) has no segments.
+    pub fn parse(url: &str) -> Result<ParsedUrl, UrlError> {
+        if url.contains('#') {
+            return Err(UrlError::Fragment);
+        }
+
+        // removing the scheme and authority of an absolute URL
+        let origin;
+        let url = match split_absolute_form(url) {
+            Some((_, origin_form)) => {
+                origin = origin_form;
+                &origin
+            }
+            None => url,
+        };
+
+        let (path, query_string) = match url.find('?') {
+            Some(pos) => (&url[..pos], Some(&url[pos + 1..])),
+            None => (url, None),
+        };
+
+        if path != "*" && !path.starts_with('/') {
+            return Err(UrlError::InvalidTarget);
+        }
+
+        ParsedUrl::from_parts(path, query_string)
+    }
+
+    fn from_parts(path: &str, query_string: Option<&str>) -> Result<ParsedUrl, UrlError> {
+        let segments = match path.strip_prefix('/') {
+            Some("") | None => Vec::new(),
+            Some(segments) => segments
+                .split('/')
+                .map(|segment| percent_decode(segment, false))
+                .collect::<Result<_, _>>()?,
+        };
+
+        let query = match query_string {
+            Some(query_string) => query_string
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let mut elems = pair.splitn(2, '=');
+                    let name = percent_decode(elems.next().unwrap_or(""), true)?;
+                    let value = percent_decode(elems.next().unwrap_or(""), true)?;
+                    Ok((name, value))
+                })
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(ParsedUrl {
+            path: path.to_owned(),
+            segments,
+            query_string: query_string.map(str::to_owned),
+            query,
+        })
+    }
+
+    /// Returns the path as sent by the client, without decoding.
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the decoded segments of the path.
+    ///
+    /// A trailing slash results in a last empty segment. The path `/` has no segments.
+    #[inline]
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// Returns the query string as sent by the client, without the `?`.
+    #[inline]
+    pub fn query_string(&self) -> Option<&str> {
+        self.query_string.as_deref()
+    }
+
+    /// Returns the decoded query parameters, in their order in the query string.
+    #[inline]
+    pub fn query_pairs(&self) -> &[(String, String)] {
+        &self.query
+    }
+
+    /// Returns the value of the first query parameter with this name.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl Display for UrlError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            UrlError::Fragment => "the URL contains a fragment",
+            UrlError::InvalidEncoding => "the URL contains an invalid percent-encoding",
+            UrlError::InvalidTarget => "the request target isn't a path or an absolute URL",
+        };
+        formatter.write_str(msg)
+    }
+}
+
+impl Error for UrlError {}
+
+/// Splits an absolute-form target (`http://host/path?query`) into its authority and its origin
+/// form (`/path?query`). Returns `None` for the other forms of target.
+pub(crate) fn split_absolute_form(target: &str) -> Option<(&str, String)> {
+    if target.starts_with('/') {
+        return None;
+    }
+
+    let rest = &target[target.find("://")? + 3..];
+    let (authority, origin) = match rest.find(['/', '?']) {
+        Some(start) => rest.split_at(start),
+        None => (rest, ""),
+    };
+
+    if origin.starts_with('/') {
+        Some((authority, origin.to_owned()))
+    } else {
+        Some((authority, format!("/{}", origin)))
+    }
+}
+
+/// Decodes `%XX` sequences, and `+` as a space if `plus_as_space`.
+fn percent_decode(input: &str, plus_as_space: bool) -> Result<String, UrlError> {
+    if !input.contains(|c| c == '%' || (plus_as_space && c == '+')) {
+        return Ok(input.to_owned());
+    }
+
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut iter = input.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'%' => {
+                let hi = iter.next().and_then(hex_value);
+                let lo = iter.next().and_then(hex_value);
+                match (hi, lo) {
+                    (Some(hi), Some(lo)) => bytes.push(hi << 4 | lo),
+                    _ => return Err(UrlError::InvalidEncoding),
+                }
+            }
+            b'+' if plus_as_space => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| UrlError::InvalidEncoding)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_absolute_form, ParsedUrl, UrlError};
+
+    #[test]
+    fn parse_path() {
+        let url = ParsedUrl::parse("/").unwrap();
+        assert!(url.segments().is_empty());
+        assert_eq!(url.query_string(), None);
+
+        let url = ParsedUrl::parse("/a//b%2Fc").unwrap();
+        assert_eq!(url.segments(), ["a", "", "b/c"]);
+
+        let url = ParsedUrl::parse("/a+b").unwrap();
+        assert_eq!(url.segments(), ["a+b"]);
+
+        let url = ParsedUrl::parse("*").unwrap();
+        assert!(url.segments().is_empty());
+    }
+
+    #[test]
+    fn parse_query() {
+        let url = ParsedUrl::parse("/?a=1&b&a=2&c=x%26y&&").unwrap();
+        assert_eq!(url.query("a"), Some("1"));
+        assert_eq!(url.query("b"), Some(""));
+        assert_eq!(url.query("c"), Some("x&y"));
+        assert_eq!(url.query("d"), None);
+        assert_eq!(url.query_pairs().len(), 4);
+    }
+
+    #[test]
+    fn parse_absolute() {
+        let url = ParsedUrl::parse("http://example.com/a/b?x=1").unwrap();
+        assert_eq!(url.path(), "/a/b");
+        assert_eq!(url.segments(), ["a", "b"]);
+        assert_eq!(url.query("x"), Some("1"));
+
+        let url = ParsedUrl::parse("http://example.com").unwrap();
+        assert_eq!(url.path(), "/");
+
+        let url = ParsedUrl::parse("http://example.com?x=1").unwrap();
+        assert_eq!(url.path(), "/");
+        assert_eq!(url.query("x"), Some("1"));
+    }
+
+    #[test]
+    fn split_absolute() {
+        let split = |target| split_absolute_form(target).map(|(a, o)| (a.to_owned(), o));
+        let owned = |authority: &str, origin: &str| Some((authority.to_owned(), origin.to_owned()));
+
+        assert_eq!(split("http://a:8080/b?c"), owned("a:8080", "/b?c"));
+        assert_eq!(split("https://a"), owned("a", "/"));
+        assert_eq!(split("http://a?c"), owned("a", "/?c"));
+        assert_eq!(split("/b?c=http://a"), None);
+        assert_eq!(split("*"), None);
+        assert_eq!(split("a:443"), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(ParsedUrl::parse("/a#b"), Err(UrlError::Fragment));
+        assert_eq!(ParsedUrl::parse("/a%2"), Err(UrlError::InvalidEncoding));
+        assert_eq!(ParsedUrl::parse("/a%zz"), Err(UrlError::InvalidEncoding));
+        assert_eq!(ParsedUrl::parse("/%ff"), Err(UrlError::InvalidEncoding));
+        assert_eq!(
+            ParsedUrl::parse("example.com:443"),
+            Err(UrlError::InvalidTarget)
+        );
+    }
+}
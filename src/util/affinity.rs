@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+#[cfg(feature = "cpu-affinity")]
+use crate::log;
+
+/// A thread of the server pinned to a CPU core, see [`Server::pinned_threads`](crate::Server::pinned_threads).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedThread {
+    /// Name of the thread, e.g. `tiny-http-worker-0`.
+    pub thread_name: String,
+
+    /// Identifier of the core as given by the operating system.
+    pub core: usize,
+}
+
+/// Pins threads to the available CPU cores in turn, and keeps track of the assignments.
+pub struct CorePinning {
+    #[cfg(feature = "cpu-affinity")]
+    cores: Vec<core_affinity::CoreId>,
+
+    // index of the core to use for the next thread
+    next: AtomicUsize,
+
+    pinned: Mutex<Vec<PinnedThread>>,
+}
+
+impl CorePinning {
+    /// Returns `None` if the cores of the machine can't be listed.
+    #[cfg(feature = "cpu-affinity")]
+    pub fn new() -> Option<CorePinning> {
+        let cores = core_affinity::get_core_ids().filter(|cores| !cores.is_empty())?;
+
+        Some(CorePinning {
+            cores,
+            next: AtomicUsize::new(0),
+            pinned: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Returns `None` as pinning requires the `cpu-affinity` feature.
+    #[cfg(not(feature = "cpu-affinity"))]
+    pub fn new() -> Option<CorePinning> {
+        None
+    }
+
+    /// Pins the current thread to the next core, until the returned guard is dropped.
+    pub fn pin_current(&self) -> PinGuard<'_> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "cpu-affinity")]
+        {
+            let core = self.cores[index % self.cores.len()];
+            if core_affinity::set_for_current(core) {
+                self.pinned.lock().unwrap().push(PinnedThread {
+                    thread_name: thread::current().name().unwrap_or_default().to_owned(),
+                    core: core.id,
+                });
+            } else {
+                log::error!("Failed to pin thread to core {}", core.id);
+            }
+        }
+
+        #[cfg(not(feature = "cpu-affinity"))]
+        let _ = index;
+
+        PinGuard { pinning: self }
+    }
+
+    /// Returns the threads currently pinned.
+    pub fn pinned_threads(&self) -> Vec<PinnedThread> {
+        self.pinned.lock().unwrap().clone()
+    }
+}
+
+/// Forgets the assignment of the current thread when dropped, must not leave the thread.
+pub struct PinGuard<'a> {
+    pinning: &'a CorePinning,
+}
+
+impl<'a> Drop for PinGuard<'a> {
+    fn drop(&mut self) {
+        let current = thread::current();
+        let name = current.name().unwrap_or_default();
+        self.pinning
+            .pinned
+            .lock()
+            .unwrap()
+            .retain(|pinned| pinned.thread_name != name);
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Number of connections currently open, shared by the connections of all the listeners.
+#[derive(Default)]
+pub struct ConnectionCounter {
+    open: AtomicUsize,
+}
+
+/// Counts a connection as open until dropped, see [`ConnectionCounter::open`].
+pub struct ConnectionGuard(Arc<ConnectionCounter>);
+
+impl ConnectionCounter {
+    pub fn new() -> Arc<ConnectionCounter> {
+        Arc::new(ConnectionCounter::default())
+    }
+
+    /// Counts a connection as open until the guard is dropped.
+    pub fn open(self: &Arc<Self>) -> ConnectionGuard {
+        self.open.fetch_add(1, Ordering::AcqRel);
+        ConnectionGuard(self.clone())
+    }
+
+    /// Returns the number of connections currently open.
+    pub fn count(&self) -> usize {
+        self.open.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.open.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConnectionCounter;
+
+    #[test]
+    fn guards_are_counted_until_dropped() {
+        let counter = ConnectionCounter::new();
+        assert_eq!(counter.count(), 0);
+
+        let first = counter.open();
+        let second = counter.open();
+        assert_eq!(counter.count(), 2);
+
+        drop(first);
+        assert_eq!(counter.count(), 1);
+        drop(second);
+        assert_eq!(counter.count(), 0);
+    }
+}
@@ -0,0 +1,19 @@
+use std::io::Result as IoResult;
+
+use crate::common::{Header, StatusCode};
+
+/// Destination of the responses of protocols that frame the head and the body themselves, such
+/// as HTTP/3, instead of receiving them as HTTP/1 text.
+///
+/// Responses are written with `Response::print_frames`, which calls `write_head` once, then
+/// `write_data` for every piece of the body and finally `finish`.
+pub trait FrameWriter: Send {
+    /// Sends the status code and the headers of the response.
+    fn write_head(&mut self, status_code: StatusCode, headers: &[Header]) -> IoResult<()>;
+
+    /// Sends a piece of the body.
+    fn write_data(&mut self, data: &[u8]) -> IoResult<()>;
+
+    /// Ends the response.
+    fn finish(&mut self) -> IoResult<()>;
+}
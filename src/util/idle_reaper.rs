@@ -0,0 +1,117 @@
+use std::io::Result as IoResult;
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::client::PipelineCounter;
+use crate::log;
+use crate::util::refined_tcp_stream::Stream;
+use crate::Clock;
+
+/// Closes the connections staying idle longer than `SocketConfig::keep_alive_idle_timeout`.
+///
+/// A connection is idle while it waits for the first line of its next request and all its
+/// requests are answered. A background thread checks the connections a few times per timeout
+/// and shuts down the idle ones, which ends their thread and frees their socket. It stops once
+/// the server and all its connections are gone.
+pub(crate) struct IdleReaper {
+    timeout: Duration,
+    clock: Arc<dyn Clock>,
+    connections: Mutex<Vec<Watched>>,
+}
+
+struct Watched {
+    watch: Weak<IdleWatch>,
+    // when the connection was first seen idle, `None` while it's busy
+    idle_since: Option<Instant>,
+}
+
+/// State of a connection, updated by its thread and checked by the reaper.
+pub(crate) struct IdleWatch {
+    // true while waiting for the first line of the next request
+    waiting: AtomicBool,
+    pipeline: Arc<PipelineCounter>,
+    socket: Arc<Stream>,
+}
+
+impl IdleReaper {
+    /// Starts the thread of the reaper.
+    pub(crate) fn start(timeout: Duration, clock: Arc<dyn Clock>) -> IoResult<Arc<IdleReaper>> {
+        let reaper = Arc::new(IdleReaper {
+            timeout,
+            clock,
+            connections: Mutex::new(Vec::new()),
+        });
+
+        let interval = (timeout / 4).clamp(Duration::from_millis(1), Duration::from_secs(1));
+        let inside_reaper = Arc::downgrade(&reaper);
+        thread::Builder::new()
+            .name("tiny-http-idle-reaper".to_owned())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                match inside_reaper.upgrade() {
+                    Some(reaper) => reaper.cull(),
+                    None => break,
+                }
+            })?;
+
+        Ok(reaper)
+    }
+
+    /// Starts watching a connection, until the returned watch is dropped.
+    pub(crate) fn watch(
+        &self,
+        pipeline: Arc<PipelineCounter>,
+        socket: Arc<Stream>,
+    ) -> Arc<IdleWatch> {
+        let watch = Arc::new(IdleWatch {
+            waiting: AtomicBool::new(false),
+            pipeline,
+            socket,
+        });
+        self.connections.lock().unwrap().push(Watched {
+            watch: Arc::downgrade(&watch),
+            idle_since: None,
+        });
+        watch
+    }
+
+    /// Shuts down the connections idle for longer than the timeout.
+    fn cull(&self) {
+        let now = self.clock.now();
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|watched| watched.watch.strong_count() > 0);
+
+        for watched in connections.iter_mut() {
+            let watch = match watched.watch.upgrade() {
+                Some(watch) => watch,
+                None => continue,
+            };
+            if !watch.is_idle() {
+                watched.idle_since = None;
+                continue;
+            }
+
+            let idle_since = *watched.idle_since.get_or_insert(now);
+            if now.saturating_duration_since(idle_since) >= self.timeout {
+                log::debug!("Closing connection idle for {:?}", self.timeout);
+                watch.waiting.store(false, Ordering::Release);
+                watch.socket.shutdown(Shutdown::Both).ok();
+                watched.idle_since = None;
+            }
+        }
+    }
+}
+
+impl IdleWatch {
+    /// Marks the connection as waiting for its next request, or not anymore.
+    pub(crate) fn set_waiting(&self, waiting: bool) {
+        self.waiting.store(waiting, Ordering::Release);
+    }
+
+    fn is_idle(&self) -> bool {
+        self.waiting.load(Ordering::Acquire) && self.pipeline.pending() == 0
+    }
+}
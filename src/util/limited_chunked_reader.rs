@@ -0,0 +1,228 @@
+use std::io::Error as IoError;
+use std::io::{ErrorKind, Read, Result as IoResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Decodes a `Transfer-Encoding: chunked` body and enforces limits on the size of each chunk
+/// and of the whole decoded body.
+///
+/// A chunk exceeding the limit is detected as soon as its size line has been read, so that the
+/// declared data doesn't need to be consumed, nor waited for. Once a limit is exceeded, every
+/// read fails and `exceeded` is set.
+pub struct LimitedChunkedReader<R>
+where
+    R: Read,
+{
+    source: R,
+    // size of the rest of the current chunk, `None` before the size line of the next one
+    remaining: Option<usize>,
+    // true once the last chunk has been read
+    finished: bool,
+    chunk_max_size: Option<usize>,
+    body_max_size: Option<usize>,
+    body_size: usize,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl<R> LimitedChunkedReader<R>
+where
+    R: Read,
+{
+    pub fn new(
+        reader: R,
+        chunk_max_size: Option<usize>,
+        body_max_size: Option<usize>,
+        exceeded: Arc<AtomicBool>,
+    ) -> LimitedChunkedReader<R> {
+        LimitedChunkedReader {
+            source: reader,
+            remaining: None,
+            finished: false,
+            chunk_max_size,
+            body_max_size,
+            body_size: 0,
+            exceeded,
+        }
+    }
+
+    fn fail(&self, info: &'static str) -> IoError {
+        self.exceeded.store(true, Ordering::Release);
+        IoError::new(ErrorKind::InvalidData, info)
+    }
+
+    fn read_byte(&mut self) -> IoResult<u8> {
+        let mut byte = [0u8];
+        self.source.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn read_line_end(&mut self) -> IoResult<()> {
+        match (self.read_byte()?, self.read_byte()?) {
+            (b'\r', b'\n') => Ok(()),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Reads the size line of a chunk, ignoring its extensions. `None` if the size doesn't fit
+    /// in an `usize`.
+    fn read_chunk_size(&mut self) -> IoResult<Option<usize>> {
+        let mut size = Some(0usize);
+        let mut digits = 0;
+        let mut byte = self.read_byte()?;
+        while byte == b' ' || byte == b'\t' {
+            byte = self.read_byte()?;
+        }
+        while let Some(digit) = (byte as char).to_digit(16) {
+            size = size
+                .and_then(|size| size.checked_mul(16))
+                .and_then(|size| size.checked_add(digit as usize));
+            digits += 1;
+            byte = self.read_byte()?;
+        }
+        while byte == b' ' || byte == b'\t' {
+            byte = self.read_byte()?;
+        }
+        if byte == b';' {
+            while byte != b'\r' {
+                byte = self.read_byte()?;
+            }
+        }
+        if digits == 0 || byte != b'\r' || self.read_byte()? != b'\n' {
+            return Err(invalid());
+        }
+        Ok(size)
+    }
+}
+
+fn invalid() -> IoError {
+    IoError::new(ErrorKind::InvalidInput, "Error while decoding chunks")
+}
+
+impl<R> Read for LimitedChunkedReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.exceeded.load(Ordering::Acquire) {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "Chunked body exceeded the limits",
+            ));
+        }
+        if self.finished || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let remaining = match self.remaining {
+            Some(remaining) => remaining,
+            None => {
+                // the sizes come from the client, an overflow is over any limit
+                let size = self.read_chunk_size()?;
+                if let Some(max) = self.chunk_max_size {
+                    if size.map_or(true, |size| size > max) {
+                        return Err(self.fail("Chunk size exceeds the limit"));
+                    }
+                }
+                if let Some(max) = self.body_max_size {
+                    if size
+                        .and_then(|size| size.checked_add(self.body_size))
+                        .map_or(true, |size| size > max)
+                    {
+                        return Err(self.fail("Chunked body size exceeds the limit"));
+                    }
+                }
+
+                match size {
+                    Some(0) => {
+                        self.read_line_end()?;
+                        self.finished = true;
+                        return Ok(0);
+                    }
+                    Some(size) => size,
+                    None => return Err(invalid()),
+                }
+            }
+        };
+
+        let len = buf.len().min(remaining);
+        let read = self.source.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.body_size += read;
+        self.remaining = if read == remaining {
+            self.read_line_end()?;
+            None
+        } else {
+            Some(remaining - read)
+        };
+
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LimitedChunkedReader;
+    use std::io::Read;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn read(body: &str, chunk_max: Option<usize>, body_max: Option<usize>) -> (bool, String) {
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let mut reader =
+            LimitedChunkedReader::new(body.as_bytes(), chunk_max, body_max, exceeded.clone());
+
+        let mut output = String::new();
+        let result = reader.read_to_string(&mut output);
+        assert_eq!(result.is_err(), exceeded.load(Ordering::Acquire));
+        (result.is_ok(), output)
+    }
+
+    #[test]
+    fn test_within_limits() {
+        let body = "3\r\nhel\r\nb\r\nlo world!!!\r\n0\r\n\r\n";
+        assert_eq!(read(body, None, None), (true, "hello world!!!".to_owned()));
+        assert_eq!(
+            read(body, Some(11), Some(14)),
+            (true, "hello world!!!".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_chunk_too_large() {
+        let body = "3\r\nhel\r\nb\r\nlo world!!!\r\n0\r\n\r\n";
+        assert!(!read(body, Some(10), None).0);
+
+        // the huge chunk is rejected without waiting for its data
+        let (ok, output) = read("3\r\nhel\r\nffffffff\r\nlo", Some(1024), None);
+        assert!(!ok);
+        assert_eq!(output, "hel");
+
+        // and so is a huge chunk whose data doesn't come at all
+        assert_eq!(
+            read("ffffffff\r\n", Some(1024), None),
+            (false, String::new())
+        );
+    }
+
+    #[test]
+    fn test_size_overflow() {
+        let huge = format!("3\r\nhel\r\n{:x}\r\nlo", usize::MAX);
+        let (ok, output) = read(&huge, None, Some(usize::MAX));
+        assert!(!ok);
+        assert_eq!(output, "hel");
+    }
+
+    #[test]
+    fn test_extensions() {
+        let body = "3;a=b\r\nhel\r\n2 ; c\r\nlo\r\n0\r\n\r\n";
+        assert_eq!(read(body, Some(3), None), (true, "hello".to_owned()));
+    }
+
+    #[test]
+    fn test_body_too_large() {
+        let body = "3\r\nhel\r\nb\r\nlo world!!!\r\n0\r\n\r\n";
+        assert!(!read(body, None, Some(13)).0);
+    }
+}
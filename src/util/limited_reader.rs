@@ -0,0 +1,85 @@
+use std::io::Error as IoError;
+use std::io::{ErrorKind, Read, Result as IoResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Enforces a limit on the size of a body delimited by the end of its stream, whose length
+/// isn't known in advance.
+///
+/// Once the limit is exceeded, every read fails and `exceeded` is set.
+pub struct LimitedReader<R>
+where
+    R: Read,
+{
+    inner: R,
+    max_size: Option<usize>,
+    size: usize,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl<R> LimitedReader<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R, max_size: Option<usize>, exceeded: Arc<AtomicBool>) -> LimitedReader<R> {
+        LimitedReader {
+            inner: reader,
+            max_size,
+            size: 0,
+            exceeded,
+        }
+    }
+}
+
+impl<R> Read for LimitedReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.exceeded.load(Ordering::Acquire) {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "Body exceeded the limit",
+            ));
+        }
+
+        let len = self.inner.read(buf)?;
+        self.size = self.size.saturating_add(len);
+        if let Some(max) = self.max_size {
+            if self.size > max {
+                self.exceeded.store(true, Ordering::Release);
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "Body size exceeds the limit",
+                ));
+            }
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LimitedReader;
+    use std::io::Read;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn read(body: &str, max: Option<usize>) -> bool {
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let mut reader = LimitedReader::new(body.as_bytes(), max, exceeded.clone());
+
+        let mut output = String::new();
+        let result = reader.read_to_string(&mut output);
+        assert_eq!(result.is_err(), exceeded.load(Ordering::Acquire));
+        result.is_ok()
+    }
+
+    #[test]
+    fn test_limit() {
+        assert!(read("hello world", None));
+        assert!(read("hello world", Some(11)));
+        assert!(!read("hello world", Some(10)));
+    }
+}
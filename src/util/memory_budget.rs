@@ -0,0 +1,97 @@
+use std::io::{Read, Result as IoResult};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Number of bytes that all the connections together can keep in memory.
+pub struct MemoryBudget {
+    used: AtomicUsize,
+    max: usize,
+}
+
+/// Bytes reserved from a `MemoryBudget`, given back when dropped.
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    len: usize,
+}
+
+/// Reader of data kept in memory, holding the reservation of its size.
+pub struct ReservedReader<R> {
+    inner: R,
+    _reservation: Reservation,
+}
+
+impl MemoryBudget {
+    pub fn new(max: usize) -> Arc<MemoryBudget> {
+        Arc::new(MemoryBudget {
+            used: AtomicUsize::new(0),
+            max,
+        })
+    }
+
+    /// Reserves `len` bytes, or returns `None` if they would exceed the budget.
+    pub fn reserve(self: &Arc<Self>, len: usize) -> Option<Reservation> {
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            let new_used = used
+                .checked_add(len)
+                .filter(|&new_used| new_used <= self.max)?;
+            match self.used.compare_exchange_weak(
+                used,
+                new_used,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    return Some(Reservation {
+                        budget: self.clone(),
+                        len,
+                    })
+                }
+                Err(actual) => used = actual,
+            }
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.used.fetch_sub(self.len, Ordering::AcqRel);
+    }
+}
+
+impl<R> ReservedReader<R> {
+    pub fn new(inner: R, reservation: Reservation) -> ReservedReader<R> {
+        ReservedReader {
+            inner,
+            _reservation: reservation,
+        }
+    }
+}
+
+impl<R: Read> Read for ReservedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryBudget;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn reservations_are_given_back() {
+        let budget = MemoryBudget::new(10);
+
+        let first = budget.reserve(6).unwrap();
+        assert!(budget.reserve(5).is_none());
+        let second = budget.reserve(4).unwrap();
+        assert_eq!(budget.used.load(Ordering::Acquire), 10);
+
+        drop(first);
+        assert_eq!(budget.used.load(Ordering::Acquire), 4);
+        assert!(budget.reserve(6).is_some());
+        drop(second);
+        assert_eq!(budget.used.load(Ordering::Acquire), 0);
+    }
+}
@@ -1,7 +1,9 @@
 use std::collections::VecDeque;
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::util::sync::{Condvar, Mutex};
+
 enum Control<T> {
     Elem(T),
     Unblock,
@@ -40,6 +42,16 @@ where
         self.condvar.notify_one();
     }
 
+    /// Returns the number of elements waiting to be popped.
+    #[cfg(feature = "status-page")]
+    pub fn len(&self) -> usize {
+        let queue = self.queue.lock().unwrap();
+        queue
+            .iter()
+            .filter(|control| matches!(control, Control::Elem(_)))
+            .count()
+    }
+
     /// Pops an element. Blocks until one is available.
     /// Returns None in case unblock() was issued.
     pub fn pop(&self) -> Option<T> {
@@ -94,3 +106,42 @@ where
         }
     }
 }
+
+#[cfg(all(test, loom))]
+mod loom_test {
+    use super::MessagesQueue;
+    use loom::thread;
+
+    #[test]
+    fn push_wakes_pop() {
+        loom::model(|| {
+            let queue = MessagesQueue::with_capacity(1);
+            let popper = {
+                let queue = queue.clone();
+                thread::spawn(move || queue.pop())
+            };
+
+            queue.push(1);
+            assert_eq!(popper.join().unwrap(), Some(1));
+        });
+    }
+
+    #[test]
+    fn unblock_wakes_pop() {
+        loom::model(|| {
+            let queue = MessagesQueue::<u8>::with_capacity(1);
+            let poppers: Vec<_> = (0..2)
+                .map(|_| {
+                    let queue = queue.clone();
+                    thread::spawn(move || queue.pop())
+                })
+                .collect();
+
+            queue.unblock();
+            queue.unblock();
+            for popper in poppers {
+                assert_eq!(popper.join().unwrap(), None);
+            }
+        });
+    }
+}
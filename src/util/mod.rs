@@ -1,20 +1,43 @@
+pub use self::affinity::{CorePinning, PinnedThread};
+pub use self::connection_counter::{ConnectionCounter, ConnectionGuard};
 pub use self::custom_stream::CustomStream;
 pub use self::equal_reader::EqualReader;
+pub use self::frame_writer::FrameWriter;
 pub use self::fused_reader::FusedReader;
+pub(crate) use self::idle_reaper::{IdleReaper, IdleWatch};
+pub use self::limited_chunked_reader::LimitedChunkedReader;
+#[cfg(feature = "http3")]
+pub use self::limited_reader::LimitedReader;
+pub use self::memory_budget::{MemoryBudget, ReservedReader};
 pub use self::messages_queue::MessagesQueue;
 pub use self::refined_tcp_stream::RefinedTcpStream;
+pub use self::render_reader::RenderReader;
+pub use self::sequential::SequentialWriterBuilder;
 pub use self::sequential::{SequentialReader, SequentialReaderBuilder};
-pub use self::sequential::{SequentialWriter, SequentialWriterBuilder};
+pub use self::spill_file::SpillFile;
+#[cfg(feature = "status-page")]
+pub use self::task_pool::PoolMonitor;
 pub use self::task_pool::TaskPool;
 
 use std::str::FromStr;
 
+mod affinity;
+mod connection_counter;
 mod custom_stream;
 mod equal_reader;
+mod frame_writer;
 mod fused_reader;
+mod idle_reaper;
+mod limited_chunked_reader;
+#[cfg(feature = "http3")]
+mod limited_reader;
+mod memory_budget;
 mod messages_queue;
 pub(crate) mod refined_tcp_stream;
+mod render_reader;
 mod sequential;
+mod spill_file;
+mod sync;
 mod task_pool;
 
 /// Parses a the value of a header.
@@ -23,10 +46,10 @@ mod task_pool;
 /// For example with `text/plain, image/png; q=1.5` this function would
 /// return `[ ("text/plain", 1.0), ("image/png", 1.5) ]`
 pub fn parse_header_value(input: &str) -> Vec<(&str, f32)> {
-    input
-        .split(',')
+    split_header_list(input)
+        .into_iter()
         .filter_map(|elem| {
-            let mut params = elem.split(';');
+            let mut params = split_unquoted(elem, ';').into_iter();
 
             let t = params.next()?;
 
@@ -46,6 +69,42 @@ pub fn parse_header_value(input: &str) -> Vec<(&str, f32)> {
         .collect()
 }
 
+/// Splits the value of a header made of a comma-separated list (e.g. `Accept`,
+/// `Cache-Control` or `Forwarded`) into its trimmed elements.
+///
+/// Commas inside quoted strings don't separate elements, and empty elements are skipped.
+pub fn split_header_list(input: &str) -> Vec<&str> {
+    split_unquoted(input, ',')
+        .into_iter()
+        .map(str::trim)
+        .filter(|elem| !elem.is_empty())
+        .collect()
+}
+
+/// Splits `input` at every `separator` that isn't inside a quoted string.
+fn split_unquoted(input: &str, separator: char) -> Vec<&str> {
+    let mut elems = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (pos, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            elems.push(&input[start..pos]);
+            start = pos + c.len_utf8();
+        }
+    }
+
+    elems.push(&input[start..]);
+    elems
+}
+
 #[cfg(test)]
 mod test {
     #[test]
@@ -61,4 +120,19 @@ mod test {
         assert_eq!(result[2].0, "image/png");
         assert_eq!(result[2].1, 2.0);
     }
+
+    #[test]
+    fn test_split_header_list() {
+        let result = super::split_header_list(
+            r#"for=192.0.2.60;proto=http, , for="[2001:db8::1]:80,x";by="a\"b,c""#,
+        );
+
+        assert_eq!(
+            result,
+            [
+                "for=192.0.2.60;proto=http",
+                r#"for="[2001:db8::1]:80,x";by="a\"b,c""#,
+            ]
+        );
+    }
 }
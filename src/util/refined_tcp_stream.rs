@@ -1,6 +1,7 @@
 use std::io::Result as IoResult;
 use std::io::{Read, Write};
 use std::net::{Shutdown, SocketAddr};
+use std::time::Duration;
 
 use crate::connection::Connection;
 #[cfg(any(
@@ -9,6 +10,7 @@ use crate::connection::Connection;
     feature = "ssl-native-tls"
 ))]
 use crate::ssl::SslStream;
+use crate::ssl::TlsInfo;
 
 pub(crate) enum Stream {
     Http(Connection),
@@ -22,15 +24,7 @@ pub(crate) enum Stream {
 
 impl Clone for Stream {
     fn clone(&self) -> Self {
-        match self {
-            Stream::Http(tcp_stream) => Stream::Http(tcp_stream.try_clone().unwrap()),
-            #[cfg(any(
-                feature = "ssl-openssl",
-                feature = "ssl-rustls",
-                feature = "ssl-native-tls"
-            ))]
-            Stream::Https(ssl_stream) => Stream::Https(ssl_stream.clone()),
-        }
+        self.try_clone().unwrap()
     }
 }
 
@@ -41,6 +35,18 @@ impl From<Connection> for Stream {
 }
 
 impl Stream {
+    pub(crate) fn try_clone(&self) -> IoResult<Stream> {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.try_clone().map(Stream::Http),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => Ok(Stream::Https(ssl_stream.clone())),
+        }
+    }
+
     fn secure(&self) -> bool {
         match self {
             Stream::Http(_) => false,
@@ -53,7 +59,20 @@ impl Stream {
         }
     }
 
-    fn peer_addr(&mut self) -> IoResult<Option<SocketAddr>> {
+    /// Returns the parameters of the TLS handshake, `None` for plain connections.
+    pub(crate) fn tls_info(&self) -> Option<TlsInfo> {
+        match self {
+            Stream::Http(_) => None,
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => Some(ssl_stream.tls_info()),
+        }
+    }
+
+    pub(crate) fn peer_addr(&self) -> IoResult<Option<SocketAddr>> {
         match self {
             Stream::Http(tcp_stream) => tcp_stream.peer_addr(),
             #[cfg(any(
@@ -65,7 +84,7 @@ impl Stream {
         }
     }
 
-    fn shutdown(&mut self, how: Shutdown) -> IoResult<()> {
+    pub(crate) fn shutdown(&self, how: Shutdown) -> IoResult<()> {
         match self {
             Stream::Http(tcp_stream) => tcp_stream.shutdown(how),
             #[cfg(any(
@@ -76,6 +95,54 @@ impl Stream {
             Stream::Https(ssl_stream) => ssl_stream.shutdown(how),
         }
     }
+
+    pub(crate) fn set_read_timeout(&self, dur: Option<Duration>) -> IoResult<()> {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.set_read_timeout(dur),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => ssl_stream.set_read_timeout(dur),
+        }
+    }
+
+    pub(crate) fn set_write_timeout(&self, dur: Option<Duration>) -> IoResult<()> {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.set_write_timeout(dur),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => ssl_stream.set_write_timeout(dur),
+        }
+    }
+
+    pub(crate) fn set_nodelay(&self, nodelay: bool) -> IoResult<()> {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.set_nodelay(nodelay),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => ssl_stream.set_nodelay(nodelay),
+        }
+    }
+
+    pub(crate) fn is_peer_connected(&self) -> bool {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.is_peer_connected(),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => ssl_stream.is_peer_connected(),
+        }
+    }
 }
 
 impl Read for Stream {
@@ -157,6 +224,12 @@ impl RefinedTcpStream {
     pub(crate) fn peer_addr(&mut self) -> IoResult<Option<SocketAddr>> {
         self.stream.peer_addr()
     }
+
+    /// Returns a handle sharing the underlying socket which, unlike `self`, doesn't shut down the
+    /// connection when dropped.
+    pub(crate) fn try_clone_stream(&self) -> IoResult<Stream> {
+        self.stream.try_clone()
+    }
 }
 
 impl Drop for RefinedTcpStream {
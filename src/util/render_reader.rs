@@ -0,0 +1,164 @@
+use std::io::{Cursor, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+// size of the pieces of output sent by the rendering thread
+const CHUNK_SIZE: usize = 8 * 1024;
+
+// number of pieces the rendering thread can produce ahead of the reader
+const CHANNEL_BOUND: usize = 4;
+
+type RenderFn = Box<dyn FnOnce(&mut dyn Write) -> IoResult<()> + Send>;
+
+/// Body of a response built with [`Response::from_render`](crate::Response::from_render).
+///
+/// The render function runs in its own thread the first time the body is read, and its output
+/// is read as it's written. If the response isn't sent entirely, the writes of the render
+/// function fail with a `BrokenPipe` error. If the render function fails, the connection is
+/// closed without ending the body, so that the client can tell it's incomplete.
+pub struct RenderReader {
+    render: Option<RenderFn>,
+    receiver: Option<Receiver<IoResult<Vec<u8>>>>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl RenderReader {
+    pub fn new<F>(render: F) -> RenderReader
+    where
+        F: FnOnce(&mut dyn Write) -> IoResult<()> + Send + 'static,
+    {
+        RenderReader {
+            render: Some(Box::new(render)),
+            receiver: None,
+            current: Cursor::new(Vec::new()),
+        }
+    }
+
+    fn start(&mut self, render: RenderFn) -> IoResult<()> {
+        let (sender, receiver) = sync_channel(CHANNEL_BOUND);
+        self.receiver = Some(receiver);
+
+        thread::Builder::new()
+            .name("tiny-http-render".to_owned())
+            .spawn(move || {
+                let mut writer = ChannelWriter {
+                    sender,
+                    buffer: Vec::with_capacity(CHUNK_SIZE),
+                };
+                let result = render(&mut writer).and_then(|_| writer.flush());
+                if let Err(err) = result {
+                    let _ = writer.sender.send(Err(err));
+                }
+            })?;
+        Ok(())
+    }
+}
+
+impl Read for RenderReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if let Some(render) = self.render.take() {
+            self.start(render)?;
+        }
+
+        loop {
+            let len = self.current.read(buf)?;
+            if len != 0 || buf.is_empty() {
+                return Ok(len);
+            }
+
+            let received = match &self.receiver {
+                Some(receiver) => receiver.recv(),
+                None => return Ok(0),
+            };
+            match received {
+                Ok(Ok(chunk)) => self.current = Cursor::new(chunk),
+                Ok(Err(err)) => {
+                    self.receiver = None;
+                    return Err(err);
+                }
+                // the render function returned
+                Err(_) => {
+                    self.receiver = None;
+                    return Ok(0);
+                }
+            }
+        }
+    }
+}
+
+struct ChannelWriter {
+    sender: SyncSender<IoResult<Vec<u8>>>,
+    buffer: Vec<u8>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.sender
+            .send(Ok(chunk))
+            .map_err(|_| IoError::new(ErrorKind::BrokenPipe, "the response was dropped"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RenderReader;
+    use std::io::{Error, ErrorKind, Read};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn render() {
+        let mut reader = RenderReader::new(|writer| {
+            for i in 0..10_000 {
+                write!(writer, "{},", i)?;
+            }
+            Ok(())
+        });
+
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert!(output.starts_with("0,1,2,"));
+        assert!(output.ends_with("9998,9999,"));
+    }
+
+    #[test]
+    fn render_error() {
+        let mut reader = RenderReader::new(|writer| {
+            writer.write_all(b"partial")?;
+            Err(Error::other("template error"))
+        });
+
+        let mut output = Vec::new();
+        let err = reader.read_to_end(&mut output).unwrap_err();
+        assert_eq!(err.to_string(), "template error");
+    }
+
+    #[test]
+    fn dropped_reader() {
+        let (tx, rx) = mpsc::channel();
+        let mut reader = RenderReader::new(move |writer| {
+            let result = (0..).try_for_each(|_| writer.write_all(&[0; 1024]));
+            tx.send(result.unwrap_err().kind()).unwrap();
+            Ok(())
+        });
+
+        reader.read_exact(&mut [0; 16]).unwrap();
+        drop(reader);
+
+        let kind = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(kind, ErrorKind::BrokenPipe);
+    }
+}
@@ -4,9 +4,15 @@ use std::io::{Read, Write};
 use std::sync::mpsc::channel;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use std::mem;
 
+use crate::Clock;
+
+/// Called with the time a writer waited for the previous writers to finish.
+pub type WaitObserver = Arc<dyn Fn(Duration) + Send + Sync>;
+
 pub struct SequentialReaderBuilder<R>
 where
     R: Read + Send,
@@ -45,6 +51,7 @@ where
 {
     writer: Arc<Mutex<W>>,
     next_trigger: Option<Receiver<()>>,
+    on_wait: Option<(Arc<dyn Clock>, WaitObserver)>,
 }
 
 pub struct SequentialWriter<W>
@@ -54,6 +61,7 @@ where
     trigger: Option<Receiver<()>>,
     writer: Arc<Mutex<W>>,
     on_finish: Sender<()>,
+    on_wait: Option<(Arc<dyn Clock>, WaitObserver)>,
 }
 
 impl<R: Read + Send> SequentialReaderBuilder<R> {
@@ -69,8 +77,20 @@ impl<W: Write + Send> SequentialWriterBuilder<W> {
         SequentialWriterBuilder {
             writer: Arc::new(Mutex::new(writer)),
             next_trigger: None,
+            on_wait: None,
         }
     }
+
+    /// Calls `observer` whenever a writer has to wait for the previous ones, with the time
+    /// measured by `clock`.
+    pub fn with_wait_observer(
+        mut self,
+        clock: Arc<dyn Clock>,
+        observer: WaitObserver,
+    ) -> SequentialWriterBuilder<W> {
+        self.on_wait = Some((clock, observer));
+        self
+    }
 }
 
 impl<R: Read + Send> Iterator for SequentialReaderBuilder<R> {
@@ -106,6 +126,7 @@ impl<W: Write + Send> Iterator for SequentialWriterBuilder<W> {
             trigger: next_next_trigger,
             writer: self.writer.clone(),
             on_finish: tx,
+            on_wait: self.on_wait.clone(),
         })
     }
 }
@@ -124,22 +145,37 @@ impl<R: Read + Send> Read for SequentialReader<R> {
     }
 }
 
-impl<W: Write + Send> Write for SequentialWriter<W> {
-    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
-        if let Some(v) = self.trigger.as_mut() {
-            v.recv().unwrap()
+impl<W: Write + Send> SequentialWriter<W> {
+    /// Blocks until the previous writers are finished.
+    fn wait_turn(&mut self) {
+        let trigger = match self.trigger.take() {
+            Some(trigger) => trigger,
+            None => return,
+        };
+
+        if trigger.try_recv().is_ok() {
+            return;
         }
-        self.trigger = None;
 
+        match self.on_wait {
+            Some((ref clock, ref observer)) => {
+                let start = clock.now();
+                trigger.recv().unwrap();
+                observer(clock.now().saturating_duration_since(start));
+            }
+            None => trigger.recv().unwrap(),
+        }
+    }
+}
+
+impl<W: Write + Send> Write for SequentialWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.wait_turn();
         self.writer.lock().unwrap().write(buf)
     }
 
     fn flush(&mut self) -> IoResult<()> {
-        if let Some(v) = self.trigger.as_mut() {
-            v.recv().unwrap()
-        }
-        self.trigger = None;
-
+        self.wait_turn();
         self.writer.lock().unwrap().flush()
     }
 }
@@ -0,0 +1,204 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Maximum number of bytes held by all the temporary files of the process together.
+const SPILL_MAX: u64 = 1024 * 1024 * 1024;
+
+/// Number of bytes held by all the temporary files of the process.
+static SPILLED: SpillBudget = SpillBudget {
+    used: AtomicU64::new(0),
+    max: SPILL_MAX,
+};
+
+/// Bytes on disk shared by temporary files.
+struct SpillBudget {
+    used: AtomicU64,
+    max: u64,
+}
+
+impl SpillBudget {
+    /// Takes `len` bytes from the budget, or returns false if there aren't enough left.
+    fn reserve(&self, len: u64) -> bool {
+        let mut used = self.used.load(Ordering::Acquire);
+        loop {
+            let new = match used.checked_add(len) {
+                Some(new) if new <= self.max => new,
+                _ => return false,
+            };
+            match self
+                .used
+                .compare_exchange_weak(used, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return true,
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    fn release(&self, len: u64) {
+        self.used.fetch_sub(len, Ordering::AcqRel);
+    }
+}
+
+/// Temporary file holding data too large to be kept in memory, removed when dropped.
+///
+/// The file is only readable by the user of the process. On Unix, it is removed as soon as it
+/// is created, so that it can't be opened by name at all.
+pub struct SpillFile {
+    // `None` once dropped, so that the file is closed before being removed
+    file: Option<File>,
+
+    // `None` if the file has already been removed
+    path: Option<PathBuf>,
+
+    // bytes written, taken from `budget`
+    size: u64,
+
+    budget: &'static SpillBudget,
+}
+
+impl SpillFile {
+    /// Creates an empty file in the temporary directory of the system.
+    pub fn create() -> IoResult<SpillFile> {
+        SpillFile::create_with_budget(&SPILLED)
+    }
+
+    fn create_with_budget(budget: &'static SpillBudget) -> IoResult<SpillFile> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        loop {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let path =
+                std::env::temp_dir().join(format!("tiny-http-{}-{}", std::process::id(), id));
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+            match options.open(&path) {
+                Ok(file) => {
+                    // an open file can't be removed on Windows
+                    let path = if cfg!(unix) {
+                        fs::remove_file(&path)?;
+                        None
+                    } else {
+                        Some(path)
+                    };
+                    return Ok(SpillFile {
+                        file: Some(file),
+                        path,
+                        size: 0,
+                        budget,
+                    });
+                }
+                // left by a previous process with the same id
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Moves back to the start of the file, to read what has been written.
+    pub fn rewind(&mut self) -> IoResult<()> {
+        self.file_mut().seek(SeekFrom::Start(0)).map(|_| ())
+    }
+
+    fn file_mut(&mut self) -> &mut File {
+        self.file.as_mut().unwrap()
+    }
+}
+
+impl Read for SpillFile {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.file_mut().read(buf)
+    }
+}
+
+impl Write for SpillFile {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let len = buf.len() as u64;
+        if !self.budget.reserve(len) {
+            return Err(IoError::other(
+                "Temporary files exceed their disk space limit",
+            ));
+        }
+
+        match self.file_mut().write(buf) {
+            Ok(written) => {
+                self.budget.release(len - written as u64);
+                self.size += written as u64;
+                Ok(written)
+            }
+            Err(err) => {
+                self.budget.release(len);
+                Err(err)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.file_mut().flush()
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        drop(self.file.take());
+        if let Some(ref path) = self.path {
+            let _ = fs::remove_file(path);
+        }
+        self.budget.release(self.size);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SpillBudget, SpillFile};
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn read_back() {
+        let mut file = SpillFile::create().unwrap();
+        file.write_all(b"spilled").unwrap();
+        file.rewind().unwrap();
+
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "spilled");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = file.file.as_ref().unwrap().metadata().unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+            assert!(file.path.is_none());
+        }
+
+        let path = file.path.clone();
+        drop(file);
+        assert!(path.map_or(true, |path| !path.exists()));
+    }
+
+    #[test]
+    fn budget() {
+        let budget: &'static SpillBudget = Box::leak(Box::new(SpillBudget {
+            used: AtomicU64::new(0),
+            max: 10,
+        }));
+
+        let mut first = SpillFile::create_with_budget(budget).unwrap();
+        first.write_all(b"0123456").unwrap();
+        let mut second = SpillFile::create_with_budget(budget).unwrap();
+        assert!(second.write_all(b"0123").is_err());
+        second.write_all(b"012").unwrap();
+        assert_eq!(budget.used.load(Ordering::Acquire), 10);
+
+        drop(first);
+        assert_eq!(budget.used.load(Ordering::Acquire), 3);
+        drop(second);
+        assert_eq!(budget.used.load(Ordering::Acquire), 0);
+    }
+}
@@ -0,0 +1,23 @@
+//! Synchronization primitives of the [`MessagesQueue`](super::MessagesQueue) and the
+//! [`TaskPool`](super::TaskPool).
+//!
+//! They come from `loom` when building with `--cfg loom`, so that the tests of the `loom_test`
+//! modules can check every interleaving of their threads:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib loom_test
+//! ```
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicUsize};
+#[cfg(loom)]
+pub(crate) use loom::sync::{Condvar, Mutex};
+#[cfg(loom)]
+pub(crate) use loom::thread;
+
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{AtomicBool, AtomicUsize};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Condvar, Mutex};
+#[cfg(not(loom))]
+pub(crate) use std::thread;
@@ -1,13 +1,17 @@
 use std::collections::VecDeque;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Condvar, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::util::sync::{thread, AtomicBool, AtomicUsize, Condvar, Mutex};
+use crate::util::CorePinning;
+use crate::PoolConfig;
 
 /// Manages a collection of threads.
 ///
-/// A new thread is created every time all the existing threads are full.
-/// Any idle thread will automatically die after a few seconds.
+/// A new thread is created every time all the existing threads are full, unless the maximum
+/// number of threads is reached.
+/// Idle threads above the minimum die after the idle timeout of the `PoolConfig`.
 pub struct TaskPool {
     sharing: Arc<Sharing>,
 }
@@ -24,10 +28,21 @@ struct Sharing {
 
     // number of idle worker threads
     waiting_tasks: AtomicUsize,
-}
 
-/// Minimum number of active threads.
-static MIN_THREADS: usize = 4;
+    // start of the current shrinking period and number of threads that died during it
+    shrinking: Mutex<(Instant, usize)>,
+
+    // set when the pool is destroyed, idle threads die without waiting for their turn
+    closed: AtomicBool,
+
+    // number of threads spawned so far, used to name them
+    spawned_threads: AtomicUsize,
+
+    config: PoolConfig,
+
+    // if `Some`, every thread is pinned to a core
+    pinning: Option<Arc<CorePinning>>,
+}
 
 struct Registration<'a> {
     nb: &'a AtomicUsize,
@@ -38,6 +53,11 @@ impl<'a> Registration<'a> {
         nb.fetch_add(1, Ordering::Release);
         Registration { nb }
     }
+
+    /// Unregisters now instead of when dropped.
+    fn release(self) {
+        drop(self)
+    }
 }
 
 impl<'a> Drop for Registration<'a> {
@@ -47,17 +67,24 @@ impl<'a> Drop for Registration<'a> {
 }
 
 impl TaskPool {
-    pub fn new() -> TaskPool {
+    pub fn new(config: PoolConfig, pinning: Option<Arc<CorePinning>>) -> TaskPool {
+        let min_threads = config.min_threads;
+
         let pool = TaskPool {
             sharing: Arc::new(Sharing {
                 todo: Mutex::new(VecDeque::new()),
                 condvar: Condvar::new(),
                 active_tasks: AtomicUsize::new(0),
                 waiting_tasks: AtomicUsize::new(0),
+                shrinking: Mutex::new((Instant::now(), 0)),
+                closed: AtomicBool::new(false),
+                spawned_threads: AtomicUsize::new(0),
+                config,
+                pinning,
             }),
         };
 
-        for _ in 0..MIN_THREADS {
+        for _ in 0..min_threads {
             pool.add_thread(None)
         }
 
@@ -65,11 +92,17 @@ impl TaskPool {
     }
 
     /// Executes a function in a thread.
-    /// If no thread is available, spawns a new one.
+    /// If no thread is available, spawns a new one. If the maximum number of threads is reached,
+    /// the function is queued until a thread becomes available.
     pub fn spawn(&self, code: Box<dyn FnMut() + Send>) {
         let mut queue = self.sharing.todo.lock().unwrap();
 
-        if self.sharing.waiting_tasks.load(Ordering::Acquire) == 0 {
+        let can_grow = match self.sharing.config.max_threads {
+            Some(max) => self.sharing.active_tasks.load(Ordering::Acquire) < max,
+            None => true,
+        };
+
+        if self.sharing.waiting_tasks.load(Ordering::Acquire) == 0 && can_grow {
             self.add_thread(Some(code));
         } else {
             queue.push_back(code);
@@ -80,9 +113,30 @@ impl TaskPool {
     fn add_thread(&self, initial_fn: Option<Box<dyn FnMut() + Send>>) {
         let sharing = self.sharing.clone();
 
-        thread::spawn(move || {
+        // threads started with the pool are workers, the ones started on demand are tasks
+        let number = sharing.spawned_threads.fetch_add(1, Ordering::Relaxed);
+        let name = if initial_fn.is_none() {
+            format!("tiny-http-worker-{}", number)
+        } else {
+            format!("tiny-http-task-{}", number)
+        };
+        let mut builder = thread::Builder::new().name(name);
+        if let Some(stack_size) = sharing.config.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+
+        // registering before spawning so that `spawn` never exceeds the maximum
+        sharing.active_tasks.fetch_add(1, Ordering::Release);
+
+        let spawned = builder.spawn(move || {
             let sharing = sharing;
-            let _active_guard = Registration::new(&sharing.active_tasks);
+            let active_guard = Registration {
+                nb: &sharing.active_tasks,
+            };
+            let _pin_guard = sharing
+                .pinning
+                .as_ref()
+                .map(|pinning| pinning.pin_current());
 
             if let Some(mut f) = initial_fn {
                 f();
@@ -98,22 +152,36 @@ impl TaskPool {
                             task = poped_task;
                             break;
                         }
+                        if sharing.closed.load(Ordering::Acquire) {
+                            // nobody will notify the threads of a destroyed pool
+                            active_guard.release();
+                            return;
+                        }
                         let _waiting_guard = Registration::new(&sharing.waiting_tasks);
 
-                        let received =
-                            if sharing.active_tasks.load(Ordering::Acquire) <= MIN_THREADS {
-                                todo = sharing.condvar.wait(todo).unwrap();
-                                true
-                            } else {
-                                let (new_lock, waitres) = sharing
-                                    .condvar
-                                    .wait_timeout(todo, Duration::from_millis(5000))
-                                    .unwrap();
-                                todo = new_lock;
-                                !waitres.timed_out()
-                            };
-
-                        if !received && todo.is_empty() {
+                        let received = if sharing.active_tasks.load(Ordering::Acquire)
+                            <= sharing.config.min_threads
+                        {
+                            todo = sharing.condvar.wait(todo).unwrap();
+                            true
+                        } else {
+                            let (new_lock, waitres) = sharing
+                                .condvar
+                                .wait_timeout(todo, sharing.config.idle_timeout.as_duration())
+                                .unwrap();
+                            todo = new_lock;
+                            !waitres.timed_out()
+                        };
+
+                        if !received
+                            && todo.is_empty()
+                            && sharing.active_tasks.load(Ordering::Acquire)
+                                > sharing.config.min_threads
+                            && sharing.may_shrink()
+                        {
+                            // unregistering while holding the lock, so that other idle threads
+                            // never see an outdated number of threads and die below the minimum
+                            active_guard.release();
                             return;
                         }
                     }
@@ -124,14 +192,239 @@ impl TaskPool {
                 task();
             }
         });
+        spawned.expect("failed to spawn thread");
+    }
+}
+
+/// Read-only view of the threads of a pool, which doesn't keep the pool alive.
+#[cfg(feature = "status-page")]
+#[derive(Clone)]
+pub struct PoolMonitor {
+    sharing: Arc<Sharing>,
+}
+
+/// Threads of a pool at some point, see [`PoolMonitor::stats`].
+#[cfg(feature = "status-page")]
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+    /// Number of threads, idle or not.
+    pub threads: usize,
+
+    /// Number of threads waiting for a task.
+    pub idle: usize,
+
+    /// Number of tasks waiting for a thread.
+    pub queued: usize,
+}
+
+#[cfg(feature = "status-page")]
+impl TaskPool {
+    pub fn monitor(&self) -> PoolMonitor {
+        PoolMonitor {
+            sharing: self.sharing.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "status-page")]
+impl PoolMonitor {
+    pub fn stats(&self) -> PoolStats {
+        let queued = self.sharing.todo.lock().unwrap().len();
+        let threads = if self.sharing.closed.load(Ordering::Acquire) {
+            0
+        } else {
+            self.sharing.active_tasks.load(Ordering::Acquire)
+        };
+
+        PoolStats {
+            threads,
+            idle: self.sharing.waiting_tasks.load(Ordering::Acquire),
+            queued,
+        }
+    }
+}
+
+impl Sharing {
+    /// Returns true if an idle thread is allowed to die now, according to `shrink_step`.
+    fn may_shrink(&self) -> bool {
+        if self.closed.load(Ordering::Acquire) {
+            return true;
+        }
+
+        let step = match self.config.shrink_step {
+            Some(step) => step,
+            None => return true,
+        };
+
+        let mut shrinking = self.shrinking.lock().unwrap();
+        let now = Instant::now();
+        if now.duration_since(shrinking.0) >= self.config.idle_timeout.as_duration() {
+            *shrinking = (now, 0);
+        }
+
+        if shrinking.1 < step {
+            shrinking.1 += 1;
+            true
+        } else {
+            false
+        }
     }
 }
 
 impl Drop for TaskPool {
     fn drop(&mut self) {
+        // holding the lock, so that no idle thread is between checking and waiting and misses
+        // the notification
+        let _todo = self.sharing.todo.lock().unwrap();
+        self.sharing.closed.store(true, Ordering::Release);
         self.sharing
             .active_tasks
             .store(999_999_999, Ordering::Release);
         self.sharing.condvar.notify_all();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::TaskPool;
+    use crate::{ConfigDuration, PoolConfig};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn max_threads() {
+        let pool = TaskPool::new(
+            PoolConfig {
+                min_threads: 0,
+                max_threads: Some(2),
+                ..PoolConfig::default()
+            },
+            None,
+        );
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = mpsc::channel();
+
+        for _ in 0..6 {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let tx = tx.clone();
+            pool.spawn(Box::new(move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                tx.send(()).unwrap();
+            }));
+        }
+
+        for _ in 0..6 {
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn idle_threads_die() {
+        let pool = TaskPool::new(
+            PoolConfig {
+                min_threads: 1,
+                idle_timeout: ConfigDuration::from_millis(50),
+                ..PoolConfig::default()
+            },
+            None,
+        );
+
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..4 {
+            let tx = tx.clone();
+            pool.spawn(Box::new(move || {
+                thread::sleep(Duration::from_millis(20));
+                tx.send(()).unwrap();
+            }));
+        }
+        for _ in 0..4 {
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(pool.sharing.active_tasks.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn thread_names() {
+        let pool = TaskPool::new(
+            PoolConfig {
+                min_threads: 0,
+                stack_size: Some(256 * 1024),
+                ..PoolConfig::default()
+            },
+            None,
+        );
+
+        let (tx, rx) = mpsc::channel();
+        pool.spawn(Box::new(move || {
+            tx.send(thread::current().name().map(str::to_owned))
+                .unwrap();
+        }));
+
+        let name = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(name, "tiny-http-task-0");
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_test {
+    use super::TaskPool;
+    use crate::PoolConfig;
+    use loom::sync::{Arc, Condvar, Mutex};
+
+    #[test]
+    fn spawned_tasks_run() {
+        loom::model(|| {
+            let pool = TaskPool::new(
+                PoolConfig {
+                    min_threads: 0,
+                    max_threads: Some(1),
+                    ..PoolConfig::default()
+                },
+                None,
+            );
+
+            let done = Arc::new((Mutex::new(0), Condvar::new()));
+            for _ in 0..2 {
+                let done = done.clone();
+                pool.spawn(Box::new(move || {
+                    *done.0.lock().unwrap() += 1;
+                    done.1.notify_one();
+                }));
+            }
+
+            let mut count = done.0.lock().unwrap();
+            while *count < 2 {
+                count = done.1.wait(count).unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn drop_stops_idle_threads() {
+        loom::model(|| {
+            let pool = TaskPool::new(
+                PoolConfig {
+                    min_threads: 1,
+                    ..PoolConfig::default()
+                },
+                None,
+            );
+
+            pool.spawn(Box::new(|| ()));
+
+            // the model fails if a thread of the pool is left waiting forever
+            drop(pool);
+        });
+    }
+}
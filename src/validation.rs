@@ -0,0 +1,420 @@
+use crate::{ssl_context, ConfigDuration, LimitsConfig, ServerConfig, SocketConfig, SslConfig};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// Shortest duration that makes sense for most fields, shorter ones are probably milliseconds
+/// given for seconds.
+const MIN_DURATION: ConfigDuration = ConfigDuration::from_millis(100);
+
+const DAY: ConfigDuration = ConfigDuration::from_secs(24 * 60 * 60);
+
+/// A problem of a [`ServerConfig`], found by [`ServerConfig::validate`].
+///
+/// The fields are named by their path from the `ServerConfig`, e.g. `socket.read_timeout` or
+/// `listeners[0].limits.pipeline_max` for the overrides of a listener.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigProblem {
+    /// A timeout is zero, which the sockets reject or which closes every connection right away.
+    /// `None` waits forever instead.
+    ZeroTimeout(String),
+
+    /// A limit is zero, which rejects or blocks every request or connection. `None` sets no
+    /// limit instead.
+    ZeroLimit(String),
+
+    /// `pool.max_threads` is below `pool.min_threads`.
+    MaxThreadsBelowMinThreads {
+        /// Value of `pool.min_threads`.
+        min_threads: usize,
+        /// Value of `pool.max_threads`.
+        max_threads: usize,
+    },
+
+    /// `chunk_max_size` is larger than `chunked_body_max_size` in these limits, so it is never
+    /// reached.
+    ChunkLargerThanBody(String),
+
+    /// A duration is outside of the bounds that make sense for its field, probably because of
+    /// a wrong unit.
+    DurationOutOfBounds {
+        /// Path of the field.
+        field: String,
+        /// Value of the field.
+        value: ConfigDuration,
+        /// Shortest sensible value.
+        min: ConfigDuration,
+        /// Longest sensible value.
+        max: ConfigDuration,
+    },
+
+    /// A path answered by the server itself doesn't start with `/`, so no request ever matches
+    /// it.
+    RelativePath(String),
+
+    /// The SSL configuration can't be used, e.g. because the private key doesn't match the
+    /// certificate, with the error of the TLS library.
+    InvalidSsl {
+        /// Path of the SSL configuration.
+        field: String,
+        /// Error building the TLS context.
+        error: String,
+    },
+}
+
+impl Display for ConfigProblem {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigProblem::ZeroTimeout(field) => {
+                write!(formatter, "{} is zero, use None to wait forever", field)
+            }
+            ConfigProblem::ZeroLimit(field) => {
+                write!(formatter, "{} is zero, use None not to limit", field)
+            }
+            ConfigProblem::MaxThreadsBelowMinThreads {
+                min_threads,
+                max_threads,
+            } => write!(
+                formatter,
+                "pool.max_threads ({}) is below pool.min_threads ({})",
+                max_threads, min_threads
+            ),
+            ConfigProblem::ChunkLargerThanBody(field) => write!(
+                formatter,
+                "{0}.chunk_max_size is larger than {0}.chunked_body_max_size",
+                field
+            ),
+            ConfigProblem::DurationOutOfBounds {
+                field,
+                value,
+                min,
+                max,
+            } => write!(
+                formatter,
+                "{} ({}) isn't between {} and {}, check its unit",
+                field, value, min, max
+            ),
+            ConfigProblem::RelativePath(field) => {
+                write!(formatter, "{} doesn't start with '/'", field)
+            }
+            ConfigProblem::InvalidSsl { field, error } => {
+                write!(formatter, "{} can't be used: {}", field, error)
+            }
+        }
+    }
+}
+
+/// Error of [`Server::new`](crate::Server::new) for a configuration with problems, see
+/// [`ServerConfig::validate`].
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+    problems: Vec<ConfigProblem>,
+}
+
+impl ConfigError {
+    /// Returns the problems of the configuration, never empty.
+    pub fn problems(&self) -> &[ConfigProblem] {
+        &self.problems
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        formatter.write_str("invalid server configuration")?;
+        for (i, problem) in self.problems.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(formatter, "{}{}", separator, problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ConfigError {}
+
+impl ServerConfig {
+    /// Returns the problems of the configuration, which are probably mistakes: zero timeouts,
+    /// durations outside of the bounds that make sense for their field (see
+    /// [`ConfigDuration`]), limits blocking every request, a thread pool whose maximum is below its minimum, a limit
+    /// that is never reached because of another one, a relative health check path, or an SSL
+    /// configuration that can't be used, e.g. because the private key doesn't match the
+    /// certificate. The overrides of the [`listeners`](ServerConfig::listeners) are checked
+    /// too.
+    ///
+    /// Called by [`Server::new`](crate::Server::new), which fails with a [`ConfigError`] if
+    /// there is any problem, unless [`skip_validation`](ServerConfig::skip_validation) is set.
+    pub fn validate(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+
+        check_ssl(&mut problems, "ssl", &self.ssl);
+        check_socket(&mut problems, "socket", &self.socket);
+        check_limits(&mut problems, "limits", &self.limits);
+
+        let pool = &self.pool;
+        check_duration(
+            &mut problems,
+            "pool.idle_timeout".to_owned(),
+            Some(pool.idle_timeout),
+            ConfigDuration::from_millis(10),
+            DAY,
+        );
+        match pool.max_threads {
+            Some(0) => problems.push(ConfigProblem::ZeroLimit("pool.max_threads".to_owned())),
+            Some(max_threads) if max_threads < pool.min_threads => {
+                problems.push(ConfigProblem::MaxThreadsBelowMinThreads {
+                    min_threads: pool.min_threads,
+                    max_threads,
+                })
+            }
+            _ => (),
+        }
+
+        if let Some(ref path) = self.health_check_path {
+            if !path.starts_with('/') {
+                problems.push(ConfigProblem::RelativePath("health_check_path".to_owned()));
+            }
+        }
+
+        for (i, listener) in self.listeners.iter().enumerate() {
+            let overrides = &listener.overrides;
+            if let Some(ref ssl) = overrides.ssl {
+                check_ssl(&mut problems, &format!("listeners[{}].ssl", i), ssl);
+            }
+            if let Some(ref socket) = overrides.socket {
+                check_socket(&mut problems, &format!("listeners[{}].socket", i), socket);
+            }
+            if let Some(ref limits) = overrides.limits {
+                check_limits(&mut problems, &format!("listeners[{}].limits", i), limits);
+            }
+        }
+
+        problems
+    }
+
+    /// Returns an error with the problems of the configuration, if any and if the validation
+    /// isn't skipped.
+    pub(crate) fn check(&self) -> Result<(), ConfigError> {
+        if self.skip_validation {
+            return Ok(());
+        }
+
+        let problems = self.validate();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+}
+
+fn check_ssl(problems: &mut Vec<ConfigProblem>, field: &str, ssl: &Option<SslConfig>) {
+    if let Some(ref ssl) = ssl {
+        if let Err(err) = ssl_context(ssl.clone()) {
+            problems.push(ConfigProblem::InvalidSsl {
+                field: field.to_owned(),
+                error: err.to_string(),
+            });
+        }
+    }
+}
+
+fn check_socket(problems: &mut Vec<ConfigProblem>, field: &str, socket: &SocketConfig) {
+    let timeouts = [
+        ("read_timeout", socket.read_timeout),
+        ("write_timeout", socket.write_timeout),
+        ("keep_alive_idle_timeout", socket.keep_alive_idle_timeout),
+    ];
+    for (name, timeout) in timeouts.iter() {
+        let field = format!("{}.{}", field, name);
+        if *timeout == Some(ConfigDuration::from_secs(0)) {
+            problems.push(ConfigProblem::ZeroTimeout(field));
+        } else {
+            check_duration(problems, field, *timeout, MIN_DURATION, DAY);
+        }
+    }
+}
+
+fn check_limits(problems: &mut Vec<ConfigProblem>, field: &str, limits: &LimitsConfig) {
+    let counts = [
+        ("connection_errors_max", limits.connection_errors_max),
+        ("pipeline_max", limits.pipeline_max),
+        ("header_line_len", limits.header_line_len),
+        (
+            "tls_handshake_failures.failures",
+            limits.tls_handshake_failures.map(|ban| ban.failures),
+        ),
+    ];
+    for (name, count) in counts.iter() {
+        if *count == Some(0) {
+            problems.push(ConfigProblem::ZeroLimit(format!("{}.{}", field, name)));
+        }
+    }
+
+    let durations = [
+        ("request_timeout", limits.request_timeout, MIN_DURATION, DAY),
+        (
+            "connection_max_lifetime",
+            limits.connection_max_lifetime,
+            MIN_DURATION,
+            ConfigDuration::from_secs(30 * 24 * 60 * 60),
+        ),
+        (
+            "tls_handshake_failures.window",
+            limits.tls_handshake_failures.map(|ban| ban.window),
+            ConfigDuration::from_secs(1),
+            DAY,
+        ),
+        (
+            "tls_handshake_failures.duration",
+            limits.tls_handshake_failures.map(|ban| ban.duration),
+            ConfigDuration::from_secs(1),
+            ConfigDuration::from_secs(30 * 24 * 60 * 60),
+        ),
+    ];
+    for (name, duration, min, max) in durations.iter() {
+        let field = format!("{}.{}", field, name);
+        check_duration(problems, field, *duration, *min, *max);
+    }
+
+    if let (Some(chunk), Some(body)) = (limits.chunk_max_size, limits.chunked_body_max_size) {
+        if chunk > body {
+            problems.push(ConfigProblem::ChunkLargerThanBody(field.to_owned()));
+        }
+    }
+}
+
+fn check_duration(
+    problems: &mut Vec<ConfigProblem>,
+    field: String,
+    value: Option<ConfigDuration>,
+    min: ConfigDuration,
+    max: ConfigDuration,
+) {
+    match value {
+        Some(value) if value < min || value > max => {
+            problems.push(ConfigProblem::DurationOutOfBounds {
+                field,
+                value,
+                min,
+                max,
+            })
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConfigProblem;
+    use crate::{
+        ConfigDuration, HandshakeBan, LimitsConfig, ListenerOverrides, ListenerSpec, PoolConfig,
+        Server, ServerConfig, SocketConfig, SslConfig,
+    };
+
+    fn config() -> ServerConfig {
+        ServerConfig::new(crate::ConfigListenAddr::from_socket_addrs("0.0.0.0:0").unwrap())
+    }
+
+    #[test]
+    fn valid() {
+        assert!(config().validate().is_empty());
+    }
+
+    #[test]
+    fn problems() {
+        let config = ServerConfig {
+            socket: SocketConfig {
+                read_timeout: Some(ConfigDuration::from_secs(0)),
+                write_timeout: Some(ConfigDuration::from_millis(30)),
+                ..SocketConfig::default()
+            },
+            limits: LimitsConfig {
+                pipeline_max: Some(0),
+                chunk_max_size: Some(1024),
+                chunked_body_max_size: Some(512),
+                tls_handshake_failures: Some(HandshakeBan {
+                    failures: 0,
+                    window: ConfigDuration::from_secs(60),
+                    duration: ConfigDuration::from_secs(60),
+                }),
+                ..LimitsConfig::default()
+            },
+            pool: PoolConfig {
+                min_threads: 8,
+                max_threads: Some(4),
+                ..PoolConfig::default()
+            },
+            health_check_path: Some("healthz".to_owned()),
+            listeners: vec![ListenerSpec {
+                addr: crate::ConfigListenAddr::from_socket_addrs("0.0.0.0:0").unwrap(),
+                overrides: ListenerOverrides {
+                    ssl: Some(Some(SslConfig::new(b"no".to_vec(), b"pem".to_vec()))),
+                    socket: Some(SocketConfig {
+                        keep_alive_idle_timeout: Some(ConfigDuration::from_secs(0)),
+                        ..SocketConfig::default()
+                    }),
+                    limits: None,
+                },
+            }],
+            ..config()
+        };
+
+        let problems = config.validate();
+        assert_eq!(
+            problems[..7],
+            [
+                ConfigProblem::ZeroTimeout("socket.read_timeout".to_owned()),
+                ConfigProblem::DurationOutOfBounds {
+                    field: "socket.write_timeout".to_owned(),
+                    value: ConfigDuration::from_millis(30),
+                    min: ConfigDuration::from_millis(100),
+                    max: ConfigDuration::from_secs(24 * 60 * 60),
+                },
+                ConfigProblem::ZeroLimit("limits.pipeline_max".to_owned()),
+                ConfigProblem::ZeroLimit("limits.tls_handshake_failures.failures".to_owned()),
+                ConfigProblem::ChunkLargerThanBody("limits".to_owned()),
+                ConfigProblem::MaxThreadsBelowMinThreads {
+                    min_threads: 8,
+                    max_threads: 4
+                },
+                ConfigProblem::RelativePath("health_check_path".to_owned()),
+            ]
+        );
+        assert!(matches!(
+            problems[7],
+            ConfigProblem::InvalidSsl { ref field, .. } if field == "listeners[0].ssl"
+        ));
+        assert_eq!(
+            problems[8],
+            ConfigProblem::ZeroTimeout("listeners[0].socket.keep_alive_idle_timeout".to_owned())
+        );
+        assert_eq!(problems.len(), 9);
+        assert_eq!(
+            problems[1].to_string(),
+            "socket.write_timeout (30ms) isn't between 100ms and 1d, check its unit"
+        );
+    }
+
+    #[test]
+    fn server_new() {
+        let invalid = ServerConfig {
+            pool: PoolConfig {
+                max_threads: Some(0),
+                ..PoolConfig::default()
+            },
+            ..config()
+        };
+        let err = match Server::new(invalid.clone()) {
+            Ok(_) => panic!("the configuration should be rejected"),
+            Err(err) => err,
+        };
+        assert_eq!(
+            err.to_string(),
+            "invalid server configuration: pool.max_threads is zero, use None not to limit"
+        );
+
+        let skipped = ServerConfig {
+            skip_validation: true,
+            ..invalid
+        };
+        assert!(Server::new(skipped).is_ok());
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::Result as IoResult;
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+
+use crate::{Request, Server};
+
+/// Handler of the requests of a server run by worker threads that each own a state, like a
+/// database connection or a cache, built once per thread. See [`Server::spawn_workers`].
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use tiny_http::{Request, Response, Server, StatefulRequestHandler};
+///
+/// # struct Connection;
+/// # impl Connection { fn open() -> Connection { Connection } fn query(&mut self, _: &str) -> String { String::new() } }
+/// struct App;
+///
+/// impl StatefulRequestHandler for App {
+///     type State = Connection;
+///
+///     fn init(&self, _worker: usize) -> Connection {
+///         Connection::open()
+///     }
+///
+///     fn handle_request(&self, connection: &mut Connection, request: Request) {
+///         let body = connection.query(request.url());
+///         let _ = request.respond(Response::from_string(body));
+///     }
+/// }
+///
+/// let server = Arc::new(Server::http("0.0.0.0:0").unwrap());
+/// let workers = Server::spawn_workers(&server, 4, App).unwrap();
+/// # for worker in workers { worker.join().unwrap(); }
+/// ```
+pub trait StatefulRequestHandler: Send + Sync + 'static {
+    /// State owned by a worker thread.
+    type State;
+
+    /// Builds the state of a worker thread, once, before the thread handles requests. `worker`
+    /// is the index of the thread, from 0.
+    fn init(&self, worker: usize) -> Self::State;
+
+    /// Handles a request with the state of the worker thread receiving it.
+    fn handle_request(&self, state: &mut Self::State, request: Request);
+}
+
+impl Server {
+    /// Starts `threads` worker threads, named `tiny-http-handler-N`, receiving the requests of
+    /// the server and handing them to `handler` with the state of the thread.
+    ///
+    /// A thread stops when [`Server::recv`] fails, e.g. after a call to [`Server::unblock`],
+    /// which stops one thread per call.
+    pub fn spawn_workers<H>(
+        server: &Arc<Server>,
+        threads: usize,
+        handler: H,
+    ) -> IoResult<Vec<JoinHandle<()>>>
+    where
+        H: StatefulRequestHandler,
+    {
+        let handler = Arc::new(handler);
+        (0..threads)
+            .map(|worker| {
+                let server = server.clone();
+                let handler = handler.clone();
+                thread::Builder::new()
+                    .name(format!("tiny-http-handler-{}", worker))
+                    .spawn(move || {
+                        let mut state = handler.init(worker);
+                        for request in server.incoming_requests() {
+                            handler.handle_request(&mut state, request);
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    /// Same as [`spawn_workers`](Server::spawn_workers), but the requests of a connection are
+    /// always handled by the same worker thread, chosen from [`Request::connection_id`], in the
+    /// order they were received. Handlers can then keep per-connection data in their state.
+    ///
+    /// A dispatch thread, named `tiny-http-dispatch`, receives the requests of the server and
+    /// hands them to the workers. All the threads stop when [`Server::recv`] fails, e.g. after a
+    /// single call to [`Server::unblock`]. A slow request delays the next requests of the other
+    /// connections handled by the same thread.
+    pub fn spawn_workers_by_connection<H>(
+        server: &Arc<Server>,
+        threads: usize,
+        handler: H,
+    ) -> IoResult<Vec<JoinHandle<()>>>
+    where
+        H: StatefulRequestHandler,
+    {
+        let handler = Arc::new(handler);
+        let mut senders = Vec::with_capacity(threads);
+        let mut workers = Vec::with_capacity(threads + 1);
+        for worker in 0..threads {
+            let (sender, receiver) = mpsc::channel::<Request>();
+            senders.push(sender);
+            let handler = handler.clone();
+            workers.push(
+                thread::Builder::new()
+                    .name(format!("tiny-http-handler-{}", worker))
+                    .spawn(move || {
+                        let mut state = handler.init(worker);
+                        for request in receiver {
+                            handler.handle_request(&mut state, request);
+                        }
+                    })?,
+            );
+        }
+
+        let server = server.clone();
+        workers.push(
+            thread::Builder::new()
+                .name("tiny-http-dispatch".to_owned())
+                .spawn(move || {
+                    if senders.is_empty() {
+                        return;
+                    }
+                    for request in server.incoming_requests() {
+                        let worker = (request.connection_id() % senders.len() as u64) as usize;
+                        // a worker only stops once its sender is dropped
+                        let _ = senders[worker].send(request);
+                    }
+                })?,
+        );
+
+        Ok(workers)
+    }
+}
@@ -0,0 +1,422 @@
+//! HTTP/1.1 conformance suite.
+//!
+//! Each case sends raw bytes to a server whose handler echoes the request body, and compares
+//! the responses with the ones expected with the default lenient parsing and with
+//! `LimitsConfig::strict_parsing`. Responses are written as their status code, followed by the
+//! echoed body for the ones sent by the handler.
+
+extern crate tiny_http;
+
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[allow(dead_code)]
+mod support;
+
+struct Case {
+    name: &'static str,
+    request: &'static str,
+    lenient: &'static [&'static str],
+    strict: &'static [&'static str],
+}
+
+const fn case(
+    name: &'static str,
+    request: &'static str,
+    lenient: &'static [&'static str],
+    strict: &'static [&'static str],
+) -> Case {
+    Case {
+        name,
+        request,
+        lenient,
+        strict,
+    }
+}
+
+const CASES: &[Case] = &[
+    // request line
+    case(
+        "simple GET",
+        "GET / HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "asterisk-form",
+        "OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "absolute-form",
+        "GET http://a/b HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "HTTP/1.0 without Host",
+        "GET / HTTP/1.0\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "double space in request line",
+        "GET  / HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "tab in request line",
+        "GET\t/ HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "missing version",
+        "GET /\r\nHost: a\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "unknown minor version",
+        "GET / HTTP/1.2\r\nHost: a\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "HTTP/2.0",
+        "GET / HTTP/2.0\r\nHost: a\r\n\r\n",
+        &["505"],
+        &["505"],
+    ),
+    case(
+        "empty line before the request line",
+        "\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "too many empty lines before the request line",
+        "\r\n\r\n\r\n\r\n\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "empty line between pipelined requests",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\n\r\na\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["200 [a]", "200 []"],
+        &["200 [a]", "200 []"],
+    ),
+    // line endings
+    // leniently, a bare LF doesn't end a line
+    case(
+        "bare LF",
+        "GET / HTTP/1.1\nHost: a\n\n",
+        &[],
+        &["400"],
+    ),
+    case(
+        "bare LF in header",
+        "GET / HTTP/1.1\r\nHost: a\nX-A: b\r\n\r\n",
+        &["200 []"],
+        &["400"],
+    ),
+    case(
+        "bare CR in header",
+        "GET / HTTP/1.1\r\nHost: a\rX-A: b\r\n\r\n",
+        &["200 []"],
+        &["400"],
+    ),
+    // headers
+    case(
+        "missing Host",
+        "GET / HTTP/1.1\r\n\r\n",
+        &["200 []"],
+        &["400"],
+    ),
+    case(
+        "duplicate Host",
+        "GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n",
+        &["200 []"],
+        &["400"],
+    ),
+    case(
+        "empty header value",
+        "GET / HTTP/1.1\r\nHost: a\r\nX-Empty:\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "space before colon",
+        "GET / HTTP/1.1\r\nHost : a\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "header without colon",
+        "GET / HTTP/1.1\r\nHost: a\r\nX-A\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "obsolete line folding",
+        "GET / HTTP/1.1\r\nHost: a\r\nX-A: b\r\n c\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    // Content-Length
+    case(
+        "Content-Length",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\r\nabc",
+        &["200 [abc]"],
+        &["200 [abc]"],
+    ),
+    case(
+        "lowercase Content-Length",
+        "POST / HTTP/1.1\r\nHost: a\r\ncontent-length: 3\r\n\r\nabc",
+        &["200 [abc]"],
+        &["200 [abc]"],
+    ),
+    case(
+        "Content-Length with trailing whitespace",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3 \t\r\n\r\nabc",
+        &["200 [abc]"],
+        &["200 [abc]"],
+    ),
+    case(
+        "Content-Length zero",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 0\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "identical Content-Length headers",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\nContent-Length: 1\r\n\r\na",
+        &["200 [a]"],
+        &["200 [a]"],
+    ),
+    case(
+        "identical Content-Length list",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1, 1\r\n\r\na",
+        &["200 [a]"],
+        &["200 [a]"],
+    ),
+    case(
+        "different Content-Length headers",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "Content-Length not a number",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: abc\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "negative Content-Length",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: -1\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "Content-Length with a sign",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: +1\r\n\r\na",
+        &["400"],
+        &["400"],
+    ),
+    // Transfer-Encoding
+    case(
+        "chunked",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+        &["200 [abc]"],
+        &["200 [abc]"],
+    ),
+    case(
+        "chunked with uppercase coding",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: Chunked\r\n\r\n1\r\na\r\n0\r\n\r\n",
+        &["200 [a]"],
+        &["200 [a]"],
+    ),
+    case(
+        "empty chunked body",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "chunk extension",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n1;a=b\r\na\r\n0\r\n\r\n",
+        &["200 [a]"],
+        &["200 [a]"],
+    ),
+    case(
+        "chunked with Content-Length",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n1\r\na\r\n0\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "chunked not last",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, identity\r\n\r\n1\r\na\r\n0\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "chunked not last over two headers",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: identity\r\n\r\n1\r\na\r\n0\r\n\r\n",
+        &["400"],
+        &["400"],
+    ),
+    case(
+        "chunked last over two headers",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: identity\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\n0\r\n\r\n",
+        &["200 [a]"],
+        &["200 [a]"],
+    ),
+    case(
+        "chunked applied twice",
+        "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, chunked\r\n\r\n1\r\na\r\n0\r\n\r\n",
+        &["200 [a]"],
+        &["200 [a]"],
+    ),
+    // Expect
+    case(
+        "Expect 100-continue",
+        "POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 1\r\n\r\na",
+        &["100", "200 [a]"],
+        &["100", "200 [a]"],
+    ),
+    case(
+        "Expect 100-continue with an empty body",
+        "POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 0\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "Expect 100-continue without a body",
+        "GET / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "unknown expectation",
+        "POST / HTTP/1.1\r\nHost: a\r\nExpect: something\r\nContent-Length: 1\r\n\r\na",
+        &["417"],
+        &["417"],
+    ),
+    // connection management
+    case(
+        "pipelined requests",
+        "GET / HTTP/1.1\r\nHost: a\r\n\r\nPOST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\n\r\na",
+        &["200 []", "200 [a]"],
+        &["200 []", "200 [a]"],
+    ),
+    case(
+        "request after Connection: close",
+        "GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "request after HTTP/1.0",
+        "GET / HTTP/1.0\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        &["200 []"],
+        &["200 []"],
+    ),
+    case(
+        "request after HTTP/1.0 keep-alive",
+        "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        &["200 []", "200 []"],
+        &["200 []", "200 []"],
+    ),
+];
+
+/// Sends `request` to a new server and returns the responses received until the connection
+/// is closed.
+fn exchange(request: &str, strict_parsing: bool) -> Vec<String> {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                strict_parsing,
+                ..Default::default()
+            },
+            ..support::default_config()
+        });
+
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client.write_all(request.as_bytes()).unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).unwrap();
+        sender.send(output).unwrap();
+    });
+
+    // answering until the server closes the connection
+    let output = loop {
+        if let Ok(output) = receiver.try_recv() {
+            break output;
+        }
+
+        if let Ok(Some(mut rq)) = server.recv_timeout(Duration::from_millis(10)) {
+            let mut body = String::new();
+            let response = match rq.as_reader().read_to_string(&mut body) {
+                Ok(_) => tiny_http::Response::from_string(format!("[{}]", body)),
+                Err(_) => tiny_http::Response::from_string("[error]").with_status_code(500),
+            };
+            rq.respond(response).ok();
+        }
+    };
+
+    parse_responses(&String::from_utf8(output).unwrap())
+}
+
+/// Splits the output of the server into its responses.
+fn parse_responses(mut output: &str) -> Vec<String> {
+    let mut responses = Vec::new();
+
+    while !output.is_empty() {
+        let head_end = output.find("\r\n\r\n").expect("incomplete head") + 4;
+        let (head, rest) = output.split_at(head_end);
+
+        let status = head[9..12].to_owned();
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(field, _)| field.eq_ignore_ascii_case("Content-Length"))
+            .map_or(0, |(_, value)| value.trim().parse().unwrap());
+        let (body, rest) = rest.split_at(length);
+
+        if status.starts_with('2') {
+            responses.push(format!("{} {}", status, body));
+        } else {
+            responses.push(status);
+        }
+        output = rest;
+    }
+
+    responses
+}
+
+#[test]
+fn lenient_parsing() {
+    for case in CASES {
+        assert_eq!(exchange(case.request, false), case.lenient, "{}", case.name);
+    }
+}
+
+#[test]
+fn strict_parsing() {
+    for case in CASES {
+        assert_eq!(exchange(case.request, true), case.strict, "{}", case.name);
+    }
+}
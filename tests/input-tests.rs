@@ -94,6 +94,407 @@ fn invalid_header_name() {
     assert!(&content[9..].starts_with("400 Bad Request")); // 400 status code
 }
 
+#[test]
+fn conflicting_content_length() {
+    let mut client = support::new_client_to_hello_world_server();
+
+    // a proxy picking the other value would see "hello" as a new request
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nContent-Length: 5\r\n\r\nhello"
+    ))
+    .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(&content[9..].starts_with("400 Bad Request"));
+    assert_eq!(content.matches("HTTP/1.1").count(), 1);
+}
+
+#[test]
+fn identical_content_length() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\nhello"
+    ))
+    .unwrap();
+
+    let mut request = server.recv().unwrap();
+    let mut output = String::new();
+    request.as_reader().read_to_string(&mut output).unwrap();
+    assert_eq!(output, "hello");
+}
+
+#[test]
+fn chunk_size_limit() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                chunk_max_size: Some(16),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    // the data of the huge chunk never arrives
+    (write!(client, "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\nffffffff\r\nworld")).unwrap();
+
+    thread::spawn(move || {
+        let mut request = server.recv().unwrap();
+        let mut output = String::new();
+        assert!(request.as_reader().read_to_string(&mut output).is_err());
+    });
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(&content[9..].starts_with("413"));
+}
+
+#[test]
+fn chunk_size_limit_size_line_only() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                chunk_max_size: Some(16),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+    client
+        .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+        .unwrap();
+
+    // only the size line of the huge chunk is sent
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\nffffffff\r\n"
+    ))
+    .unwrap();
+
+    thread::spawn(move || {
+        let mut request = server.recv().unwrap();
+        let mut output = String::new();
+        assert!(request.as_reader().read_to_string(&mut output).is_err());
+    });
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(&content[9..].starts_with("413"));
+}
+
+#[test]
+fn chunked_body_size_limit() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                chunked_body_max_size: Some(8),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    (write!(client, "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n")).unwrap();
+
+    let mut request = server.recv().unwrap();
+    let mut output = String::new();
+    let err = request.as_reader().read_to_string(&mut output).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn header_line_too_long() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                header_line_len: Some(32),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    // exactly at the limit
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nX-Value: {}\r\n\r\n",
+        "a".repeat(23)
+    ))
+    .unwrap();
+    let request = server.recv().unwrap();
+    request.respond(tiny_http::Response::empty(200)).unwrap();
+
+    // the rest of the head is read before closing, the client gets the response
+    let value = "a".repeat(20000);
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nX-Value: {}\r\nX-Other: b\r\n\r\n",
+        value
+    ))
+    .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    let rejection = &content[content.rfind("HTTP/1.1").unwrap()..];
+    assert!(rejection.starts_with("HTTP/1.1 431"));
+    assert!(rejection.contains("Connection: close"));
+
+    assert!(server
+        .recv_timeout(std::time::Duration::from_millis(100))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn pre_handler_reject() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            pre_handler: Some(tiny_http::PreHandler::new(|head| {
+                match head
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("Content-Length"))
+                {
+                    Some(h) if h.value.as_str() != "5" => {
+                        tiny_http::PreDecision::Reject(tiny_http::StatusCode(413))
+                    }
+                    _ => tiny_http::PreDecision::Accept,
+                }
+            })),
+            ..support::default_config()
+        });
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello"
+    ))
+    .unwrap();
+    let request = server.recv().unwrap();
+    request.respond(tiny_http::Response::empty(200)).unwrap();
+
+    // the body is never sent, the server must answer without waiting for it
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 100000\r\n\r\n"
+    ))
+    .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    let rejection = &content[content.rfind("HTTP/1.1").unwrap()..];
+    assert!(rejection.starts_with("HTTP/1.1 413"));
+    assert!(rejection.contains("Connection: close"));
+
+    assert!(server
+        .recv_timeout(std::time::Duration::from_millis(100))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn pre_handler_retry_after() {
+    let (_server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            pre_handler: Some(tiny_http::PreHandler::new(|_| {
+                tiny_http::PreDecision::Reject(tiny_http::StatusCode(503))
+            })),
+            limits: tiny_http::LimitsConfig {
+                retry_after: Some(tiny_http::RetryAfter::Seconds(120)),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+    assert!(content.contains("Retry-After: 120\r\n"));
+}
+
+#[test]
+fn negotiated_error_bodies() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            pre_handler: Some(tiny_http::PreHandler::new(|head| {
+                if head.url() == "/forbidden" {
+                    tiny_http::PreDecision::Reject(tiny_http::StatusCode(403))
+                } else {
+                    tiny_http::PreDecision::Accept
+                }
+            })),
+            error_renderer: Some(tiny_http::ErrorRenderer::negotiated()),
+            ..support::default_config()
+        });
+
+    (write!(
+        client,
+        "GET /dropped HTTP/1.1\r\nHost: localhost\r\nAccept: text/html\r\n\r\n"
+    ))
+    .unwrap();
+    drop(server.recv().unwrap());
+    (write!(
+        client,
+        "GET /forbidden HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\n\r\n"
+    ))
+    .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 500"));
+    assert!(content.contains("Content-Type: text/html; charset=UTF-8\r\n"));
+    assert!(content.contains("<h1>500 Internal Server Error</h1>"));
+    let rejection = &content[content.rfind("HTTP/1.1").unwrap()..];
+    assert!(rejection.starts_with("HTTP/1.1 403"));
+    assert!(rejection.contains("Content-Type: application/problem+json\r\n"));
+    assert!(rejection.ends_with(r#"{"type":"about:blank","title":"Forbidden","status":403}"#));
+}
+
+#[test]
+fn unread_chunked_body() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(client, "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n")).unwrap();
+    (write!(
+        client,
+        "GET /next HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ))
+    .unwrap();
+
+    // the first body is discarded by `respond`
+    let request = server.recv().unwrap();
+    request.respond(tiny_http::Response::empty(401)).unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.url(), "/next");
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 401"));
+    assert!(content.contains("HTTP/1.1 204"));
+}
+
+#[test]
+fn discard_body_too_large() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5000\r\n\r\n"
+    ))
+    .unwrap();
+    (write!(client, "{}", "x".repeat(5000))).unwrap();
+
+    let mut request = server.recv().unwrap();
+    assert!(!request.discard_body(100).unwrap());
+    request.respond(tiny_http::Response::empty(413)).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 413"));
+    assert!(content.contains("Connection: close"));
+}
+
+#[test]
+fn discard_body_before_response() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                discard_body_max: Some(100),
+                discard_body_before_response: true,
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello"
+    ))
+    .unwrap();
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 500\r\n\r\n"
+    ))
+    .unwrap();
+    (write!(client, "{}", "x".repeat(500))).unwrap();
+
+    // neither handler reads its body
+    let request = server.recv().unwrap();
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.body_length(), Some(500));
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    let second = content.rfind("HTTP/1.1 204").unwrap();
+    assert!(second > 0);
+    assert!(!content[..second].contains("Connection: close"));
+    assert!(content[second..].contains("Connection: close"));
+}
+
+#[test]
+fn request_timing() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                request_timeout: Some(tiny_http::ConfigDuration::from_secs(10)),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    (write!(client, "GET / HTTP/1.1\r\n")).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    (write!(client, "Host: localhost\r\n\r\n")).unwrap();
+
+    let request = server.recv().unwrap();
+    assert!(request.parse_duration() >= std::time::Duration::from_millis(50));
+    assert_eq!(
+        request.deadline(),
+        Some(request.received_at() + std::time::Duration::from_secs(10))
+    );
+    assert!(request.remaining_time().unwrap() <= std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn raw_head() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                retain_raw_headers: Some(64),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    let head = "GET / HTTP/1.1\r\nHost: localhost\r\nX-Test:  spaced \r\n\r\n";
+    (write!(client, "{}", head)).unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.header_bytes(), head.len());
+    assert_eq!(request.raw_head(), Some(head.as_bytes()));
+    let raw_headers: Vec<&[u8]> = request.raw_headers().unwrap().collect();
+    assert_eq!(
+        raw_headers,
+        [&b"Host: localhost"[..], &b"X-Test:  spaced "[..]]
+    );
+    request.respond(tiny_http::Response::empty(200)).unwrap();
+
+    // too large to be kept, but still measured
+    let head = format!(
+        "GET / HTTP/1.1\r\nHost: localhost\r\nX-Test: {}\r\n\r\n",
+        "a".repeat(64)
+    );
+    (write!(client, "{}", head)).unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.header_bytes(), head.len());
+    assert_eq!(request.raw_head(), None);
+}
+
 #[test]
 fn custom_content_type_response_header() {
     let (server, mut stream) = support::new_one_server_one_client();
@@ -2,6 +2,7 @@ extern crate tiny_http;
 
 use std::io::{Read, Write};
 use std::net::{Shutdown, TcpStream};
+use std::sync::{mpsc, Mutex};
 use std::thread;
 use std::time::Duration;
 
@@ -35,6 +36,263 @@ fn http_1_0_connection_close() {
     client.read_to_end(&mut out).unwrap();
 }
 
+#[test]
+fn response_connection_choice() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(client, "GET /a HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")).unwrap();
+    let request = server.recv().unwrap();
+    let response = tiny_http::Response::empty(204).keep_alive(Duration::from_secs(5), 10);
+    request.respond(response).unwrap();
+
+    (write!(client, "GET /b HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let request = server.recv().unwrap();
+    let response = tiny_http::Response::empty(204)
+        .keep_alive(Duration::from_secs(5), 9)
+        .close_connection();
+    request.respond(response).unwrap();
+
+    // the server closes the connection after the second response
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    let second = content.rfind("HTTP/1.1").unwrap();
+    assert!(content[..second].contains("Keep-Alive: timeout=5, max=10\r\n"));
+    assert!(content[..second].contains("Connection: keep-alive\r\n"));
+    assert!(content[second..].contains("Connection: close\r\n"));
+    assert!(!content[second..].contains("Keep-Alive"));
+}
+
+#[test]
+fn connection_sequence() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    let mut ids = Vec::new();
+    for expected in 1..=3 {
+        (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+        let request = server.recv().unwrap();
+        assert_eq!(request.connection_sequence(), expected);
+        assert!(request.connection_age() < Duration::from_secs(10));
+        ids.push(request.connection_id());
+        request.respond(tiny_http::Response::empty(204)).unwrap();
+    }
+    assert!(ids.iter().all(|&id| id == ids[0] && id != 0));
+
+    // a new connection starts over
+    let port = server.server_addr().to_ip().unwrap().port();
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.connection_sequence(), 1);
+    assert_ne!(request.connection_id(), ids[0]);
+}
+
+#[test]
+fn trusted_proxy_forwarded_proto() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            trusted_proxies: Some(
+                tiny_http::TrustedProxies::new().trust("127.0.0.0/8".parse().unwrap()),
+            ),
+            ..support::default_config()
+        });
+
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-Proto: https\r\n\r\n"
+    ))
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert!(request.secure());
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    assert!(!server.recv().unwrap().secure());
+
+    // the headers of other clients are ignored
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            trusted_proxies: Some(
+                tiny_http::TrustedProxies::new().trust("10.0.0.0/8".parse().unwrap()),
+            ),
+            ..support::default_config()
+        });
+
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-Proto: https\r\n\r\n"
+    ))
+    .unwrap();
+    assert!(!server.recv().unwrap().secure());
+}
+
+#[test]
+fn trusted_proxy_protocol() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            trusted_proxies: Some(
+                tiny_http::TrustedProxies::new()
+                    .trust("127.0.0.0/8".parse().unwrap())
+                    .with_proxy_protocol(),
+            ),
+            ..support::default_config()
+        });
+
+    (write!(
+        client,
+        "PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n\
+         GET / HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-Proto: https\r\n\r\n"
+    ))
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert!(!request.secure());
+    assert_eq!(
+        request.remote_addr(),
+        Some(&"192.0.2.1:56324".parse().unwrap())
+    );
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    // a connection without header is closed
+    let port = server.server_addr().to_ip().unwrap().port();
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.is_empty());
+    assert!(server
+        .recv_timeout(Duration::from_millis(100))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+#[cfg(feature = "ssl-openssl")]
+fn trusted_proxy_protocol_https() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            ssl: Some(tiny_http::SslConfig::new(
+                include_bytes!("../examples/ssl-cert.pem").to_vec(),
+                include_bytes!("../examples/ssl-key.pem").to_vec(),
+            )),
+            trusted_proxies: Some(
+                tiny_http::TrustedProxies::new()
+                    .trust("127.0.0.0/8".parse().unwrap())
+                    .with_proxy_protocol(),
+            ),
+            ..support::default_config()
+        });
+
+    // the header comes before the handshake of the client
+    (write!(client, "PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n")).unwrap();
+    let mut client = support::tls_client(client);
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ))
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    assert!(request.secure());
+    assert_eq!(
+        request.remote_addr(),
+        Some(&"192.0.2.1:56324".parse().unwrap())
+    );
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+    let mut content = String::new();
+    let _ = client.read_to_string(&mut content);
+    assert!(content.starts_with("HTTP/1.1 204"));
+}
+
+#[test]
+#[cfg(feature = "ssl-openssl")]
+fn trusted_proxy_protocol_https_silent_proxy() {
+    let (server, silent) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            ssl: Some(tiny_http::SslConfig::new(
+                include_bytes!("../examples/ssl-cert.pem").to_vec(),
+                include_bytes!("../examples/ssl-key.pem").to_vec(),
+            )),
+            trusted_proxies: Some(
+                tiny_http::TrustedProxies::new()
+                    .trust("127.0.0.0/8".parse().unwrap())
+                    .with_proxy_protocol(),
+            ),
+            ..support::default_config()
+        });
+
+    // a connection without header doesn't keep the next ones from being accepted
+    let port = server.server_addr().to_ip().unwrap().port();
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    (write!(client, "PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n")).unwrap();
+    let mut client = support::tls_client(client);
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ))
+    .unwrap();
+
+    let request = server
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap()
+        .unwrap();
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+    let mut content = String::new();
+    let _ = client.read_to_string(&mut content);
+    assert!(content.starts_with("HTTP/1.1 204"));
+    drop(silent);
+}
+
+#[test]
+fn listener_overrides() {
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        limits: tiny_http::LimitsConfig {
+            header_line_len: Some(16),
+            ..tiny_http::LimitsConfig::default()
+        },
+        listeners: vec![tiny_http::ListenerSpec {
+            addr: tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+            overrides: tiny_http::ListenerOverrides {
+                limits: Some(tiny_http::LimitsConfig::default()),
+                ..tiny_http::ListenerOverrides::default()
+            },
+        }],
+        ..support::default_config()
+    })
+    .unwrap();
+
+    let ports: Vec<u16> = server
+        .server_addrs()
+        .into_iter()
+        .map(|addr| addr.to_ip().unwrap().port())
+        .collect();
+    assert_eq!(ports.len(), 2);
+    assert_eq!(ports[0], server.server_addr().to_ip().unwrap().port());
+
+    // the requests of both listeners are received by the same server
+    let mut client = TcpStream::connect(("127.0.0.1", ports[1])).unwrap();
+    (write!(
+        client,
+        "GET /internal HTTP/1.1\r\nHost: localhost\r\nX-Long-Header: {}\r\n\r\n",
+        "a".repeat(100)
+    ))
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.url(), "/internal");
+
+    let mut client = TcpStream::connect(("127.0.0.1", ports[0])).unwrap();
+    (write!(
+        client,
+        "GET /public HTTP/1.1\r\nHost: localhost\r\nX-Long-Header: {}\r\n\r\n",
+        "a".repeat(100)
+    ))
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 431"));
+}
+
 #[test]
 fn detect_connection_closed() {
     let mut client = support::new_client_to_hello_world_server();
@@ -50,6 +308,25 @@ fn detect_connection_closed() {
     client.read_to_end(&mut out).unwrap();
 }
 
+#[test]
+fn num_connections() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(server.num_connections(), 1);
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    drop(client);
+    for _ in 0..100 {
+        if server.num_connections() == 0 {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("the closed connection is still counted");
+}
+
 #[test]
 fn poor_network_test() {
     let mut client = support::new_client_to_hello_world_server();
@@ -181,38 +458,1112 @@ fn no_transfer_encoding_on_204() {
     assert!(!content.contains("Transfer-Encoding: chunked"));
 }
 
-/* FIXME: uncomment and fix
 #[test]
-fn connection_timeout() {
-    let (server, mut client) = {
-        let server = tiny_http::ServerBuilder::new()
-            .with_client_connections_timeout(3000)
-            .with_random_port().build().unwrap();
-        let port = server.server_addr().port();
-        let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
-        (server, client)
-    };
+fn upgraded_stream_half_close() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\nUpgrade: tunnel\r\n\r\n"
+    ))
+    .unwrap();
 
-    let (tx_stop, rx_stop) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let rq = server.recv().unwrap();
+        let response = tiny_http::Response::empty(tiny_http::StatusCode(101));
+        let mut stream = rq.upgrade("tunnel", response);
 
-    // executing server in parallel
-    thread::spawn(move || {
-        loop {
-            server.try_recv();
-            thread::sleep(Duration::from_millis(100));
-            if rx_stop.try_recv().is_ok() { break }
+        // closing our direction must not prevent reading the client's data
+        stream.write_all(b"from server").unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+
+        let mut content = String::new();
+        stream.read_to_string(&mut content).unwrap();
+        assert!(stream.is_read_closed());
+        content
+    });
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 101"));
+    assert!(content.ends_with("from server"));
+
+    (write!(client, "from client")).unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+
+    assert_eq!(handle.join().unwrap(), "from client");
+}
+
+#[test]
+fn upgraded_stream_read_timeout() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\nUpgrade: tunnel\r\n\r\n"
+    ))
+    .unwrap();
+
+    let rq = server.recv().unwrap();
+    let response = tiny_http::Response::empty(tiny_http::StatusCode(101));
+    let mut stream = rq.upgrade("tunnel", response);
+
+    assert!(stream.peer_addr().is_some());
+    stream.set_nodelay(true).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+
+    let err = stream.read(&mut [0u8; 16]).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    ));
+}
+
+#[test]
+fn duplex_body() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n"
+    )
+    .unwrap();
+
+    let handle = thread::spawn(move || {
+        let rq = server.recv().unwrap();
+        let (mut reader, mut writer) = rq.into_duplex(tiny_http::Response::empty(200)).unwrap();
+
+        // answers each piece of the body before the next one is sent
+        let mut buf = [0; 5];
+        while reader.read_exact(&mut buf).is_ok() {
+            writer.write_all(&buf.to_ascii_uppercase()).unwrap();
+            writer.flush().unwrap();
         }
     });
 
+    // the head and the first piece arrive while the request body is still being sent
+    let mut received = Vec::new();
+    let mut buf = [0; 1024];
+    while !String::from_utf8_lossy(&received).ends_with("5\r\nHELLO\r\n") {
+        let len = client.read(&mut buf).unwrap();
+        assert_ne!(len, 0);
+        received.extend_from_slice(&buf[..len]);
+    }
+    let head = String::from_utf8(received).unwrap().to_ascii_lowercase();
+    assert!(head.starts_with("http/1.1 200"), "{}", head);
+    assert!(head.contains("transfer-encoding: chunked"));
+    assert!(head.contains("connection: close"));
+
+    write!(client, "5\r\nworld\r\n0\r\n\r\n").unwrap();
+    handle.join().unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "5\r\nWORLD\r\n0\r\n\r\n");
+}
+
+#[test]
+fn duplex_on_complete() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let head = tiny_http::Response::empty(200)
+        .on_complete(move |result| sender.send(result.unwrap()).unwrap());
+    let (_reader, mut writer) = server.recv().unwrap().into_duplex(head).unwrap();
+
+    // the hook waits for the end of the body
+    writer.write_all(b"hello").unwrap();
+    writer.flush().unwrap();
+    assert!(receiver.try_recv().is_err());
+    drop(writer);
+    assert_eq!(receiver.try_recv(), Ok(5));
+
+    // the end of the body is sent while the connection stays open for the reader
+    let mut received = Vec::new();
+    let mut buf = [0; 1024];
+    while !String::from_utf8_lossy(&received).ends_with("5\r\nhello\r\n0\r\n\r\n") {
+        let len = client.read(&mut buf).unwrap();
+        assert_ne!(len, 0);
+        received.extend_from_slice(&buf[..len]);
+    }
+}
+
+#[test]
+fn connection_timeout() {
+    let (_server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            socket: tiny_http::SocketConfig {
+                read_timeout: Some(tiny_http::ConfigDuration::from_millis(300)),
+                ..tiny_http::SocketConfig::default()
+            },
+            ..support::default_config()
+        });
+
     // waiting for the 408 response
     let mut content = String::new();
     client.read_to_string(&mut content).unwrap();
     assert!(&content[9..].starts_with("408"));
+}
+
+#[test]
+fn keep_alive_idle_timeout() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            socket: tiny_http::SocketConfig {
+                keep_alive_idle_timeout: Some(tiny_http::ConfigDuration::from_millis(200)),
+                ..tiny_http::SocketConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    // a request handled for longer than the timeout isn't culled
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    request
+        .respond(tiny_http::Response::from_string("hello"))
+        .unwrap();
+
+    // the connection is closed once idle, without a response
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    assert!(content.ends_with("hello"));
+}
+
+#[test]
+fn connection_max_lifetime() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                connection_max_lifetime: Some(tiny_http::ConfigDuration::from_millis(200)),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("first"))
+        .unwrap();
+
+    thread::sleep(Duration::from_millis(300));
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("second"))
+        .unwrap();
+
+    // the connection is closed after the second response
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    let (first, second) = content.split_at(content.find("first").unwrap() + 5);
+    assert!(!first.to_ascii_lowercase().contains("connection: close"));
+    assert!(second.to_ascii_lowercase().contains("connection: close"));
+    assert!(second.ends_with("second"));
+}
+
+#[test]
+fn connection_errors_max() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                connection_errors_max: Some(2),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    for _ in 0..2 {
+        write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let request = server.recv().unwrap();
+        request
+            .respond(tiny_http::Response::empty(tiny_http::StatusCode(404)))
+            .unwrap();
+    }
 
-    // stopping server
-    tx_stop.send(());
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    assert!(server
+        .recv_timeout(Duration::from_millis(100))
+        .unwrap()
+        .is_none());
+
+    // the third request is answered by the server itself before closing the connection
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("HTTP/1.1 404").count(), 2);
+    let (_, last) = content.split_once("HTTP/1.1 400").unwrap();
+    assert!(last.to_ascii_lowercase().contains("connection: close\r\n"));
+}
+
+#[test]
+fn ip_filter() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            ip_filter: Some(tiny_http::IpFilter::new().deny("127.0.0.0/8".parse().unwrap())),
+            ..support::default_config()
+        });
+
+    // the connection is closed without reading anything
+    let mut content = String::new();
+    let _ = client.read_to_string(&mut content);
+    assert!(content.is_empty());
+
+    server.set_ip_filter(Some(
+        tiny_http::IpFilter::new().allow("127.0.0.1".parse().unwrap()),
+    ));
+
+    let port = server.server_addr().to_ip().unwrap().port();
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    assert!(server
+        .recv_timeout(Duration::from_millis(1000))
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+#[cfg(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+))]
+fn set_ssl_config_without_ssl() {
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let config = tiny_http::SslConfig::new(
+        include_bytes!("../examples/ssl-cert.pem").to_vec(),
+        include_bytes!("../examples/ssl-key.pem").to_vec(),
+    );
+    assert!(server.set_ssl_config(config).is_err());
+}
+
+#[test]
+#[cfg(feature = "ssl-openssl")]
+fn set_ssl_config() {
+    use openssl::nid::Nid;
+
+    let server = tiny_http::Server::https(
+        "127.0.0.1:0",
+        tiny_http::SslConfig::new(
+            include_bytes!("../examples/ssl-cert.pem").to_vec(),
+            include_bytes!("../examples/ssl-key.pem").to_vec(),
+        ),
+    )
+    .unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    // name of the certificate presented to a new connection
+    let common_name = || {
+        let client = support::tls_client(TcpStream::connect(("127.0.0.1", port)).unwrap());
+        let certificate = client.ssl().peer_certificate().unwrap();
+        let entry = certificate
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .unwrap();
+        String::from_utf8(entry.data().as_slice().to_vec()).unwrap()
+    };
+    assert_eq!(common_name(), "localhost");
+
+    let (certificate, private_key) = support::self_signed_certificate("renewed.localhost");
+    server
+        .set_ssl_config(tiny_http::SslConfig::new(certificate, private_key))
+        .unwrap();
+    assert_eq!(common_name(), "renewed.localhost");
+
+    // an invalid configuration keeps the previous one
+    let invalid = tiny_http::SslConfig::new(Vec::new(), Vec::new());
+    assert!(server.set_ssl_config(invalid).is_err());
+    assert_eq!(common_name(), "renewed.localhost");
+}
+
+#[test]
+fn health_check() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            health_check_path: Some("/healthz".to_owned()),
+            limits: tiny_http::LimitsConfig {
+                retry_after: Some(tiny_http::RetryAfter::Seconds(30)),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    // the probe is answered while the next request waits in the queue
+    write!(
+        client,
+        "GET /healthz?probe=1 HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.url(), "/");
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    assert!(content.contains("\r\n\r\nokHTTP/1.1 204"));
+    assert!(!content.contains("Retry-After"));
+
+    server.set_ready(false);
+    assert_eq!(server.state(), tiny_http::ServerState::Starting);
+    server.set_draining(true);
+    assert!(server.is_draining());
+    server.set_ready(true);
+    assert_eq!(server.state(), tiny_http::ServerState::Draining);
+
+    let port = server.server_addr().to_ip().unwrap().port();
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        client,
+        "GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+    assert!(content.contains("\r\nRetry-After: 30\r\n"));
+    assert!(content.ends_with("draining"));
+}
+
+#[test]
+#[cfg(feature = "status-page")]
+fn status_page() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            status_page: Some(tiny_http::IpFilter::new()),
+            ..support::default_config()
+        });
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    server
+        .recv()
+        .unwrap()
+        .respond(tiny_http::Response::empty(204))
+        .unwrap();
+    client.read_to_string(&mut String::new()).unwrap();
+
+    let port = server.server_addr().to_ip().unwrap().port();
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        client,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        tiny_http::STATUS_PAGE_PATH
+    )
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    assert!(content.contains("Content-Type: application/json\r\n"));
+    assert!(content.contains("\"state\":\"ready\""));
+    assert!(content.contains("\"queue_depth\":0"));
+    assert!(content.contains("\"responses\":{\"204\":1}"));
+    assert!(content.contains("\"ssl\":false"));
+
+    // HTML for browsers
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        client,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+        tiny_http::STATUS_PAGE_PATH
+    )
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Content-Type: text/html; charset=UTF-8\r\n"));
+    assert!(content.contains("<tr><th>200</th><td>1</td></tr>"));
+
+    // handled normally for the addresses denied by the filter
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            status_page: Some(tiny_http::IpFilter::new().deny("127.0.0.0/8".parse().unwrap())),
+            ..support::default_config()
+        });
+    write!(
+        client,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        tiny_http::STATUS_PAGE_PATH
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.url(), tiny_http::STATUS_PAGE_PATH);
+}
+
+#[test]
+fn pipeline_max() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                pipeline_max: Some(1),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET /1 HTTP/1.1\r\nHost: localhost\r\n\r\nGET /2 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    // the second request isn't read before the first one is answered
+    let first = server.recv().unwrap();
+    assert_eq!(first.pending_requests(), 1);
+    assert!(server
+        .recv_timeout(Duration::from_millis(200))
+        .unwrap()
+        .is_none());
+    first.respond(tiny_http::Response::empty(204)).unwrap();
+
+    let second = server.recv().unwrap();
+    assert_eq!(second.url(), "/2");
+    assert_eq!(second.pending_requests(), 1);
+}
+
+#[test]
+fn pipeline_wait_handler() {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            pipeline_wait_handler: Some(tiny_http::PipelineWaitHandler::new(move |wait| {
+                tx.lock().unwrap().send(wait.duration).unwrap();
+            })),
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET /1 HTTP/1.1\r\nHost: localhost\r\n\r\nGET /2 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let first = server.recv().unwrap();
+    let second = server.recv().unwrap();
+
+    // the second response waits until the first one is sent
+    let thread = thread::spawn(move || second.respond(tiny_http::Response::empty(204)));
+    thread::sleep(Duration::from_millis(100));
+    first.respond(tiny_http::Response::empty(204)).unwrap();
+    thread.join().unwrap().unwrap();
+
+    let waited = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(waited >= Duration::from_millis(50));
+}
+
+#[test]
+fn unanswered_respond() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            unanswered: Some(tiny_http::UnansweredPolicy::Respond(503.into())),
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    drop(server.recv().unwrap());
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"), "{}", content);
+}
+
+// the response of the unanswered policy isn't checked, even when the handler's one failed
+#[test]
+#[cfg(all(feature = "strict-responses", debug_assertions))]
+fn unanswered_strict_response() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            unanswered: Some(tiny_http::UnansweredPolicy::Respond(405.into())),
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert!(request.respond(tiny_http::Response::empty(308)).is_err());
+
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 405"), "{}", content);
+}
+
+#[test]
+fn unanswered_close() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            unanswered: Some(tiny_http::UnansweredPolicy::Close),
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET /1 HTTP/1.1\r\nHost: localhost\r\n\r\nGET /2 HTTP/1.1\r\nHost: localhost\r\n\r\n"
+    )
+    .unwrap();
+    let first = server.recv().unwrap();
+    let second = server.recv().unwrap();
+    drop(first);
+    second.respond(tiny_http::Response::empty(204)).ok();
+
+    // nothing is sent, not even the response to the second request
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "");
+}
+
+#[test]
+fn unanswered_handler() {
+    let handler = tiny_http::UnansweredHandler::new(|request| {
+        if request.url() == "/close" {
+            None
+        } else {
+            Some(tiny_http::Response::from_string(request.url().to_owned()).boxed())
+        }
+    });
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            unanswered: Some(tiny_http::UnansweredPolicy::Handler(handler)),
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET /dropped HTTP/1.1\r\nHost: localhost\r\n\r\nGET /close HTTP/1.1\r\nHost: localhost\r\n\r\n"
+    )
+    .unwrap();
+    drop(server.recv().unwrap());
+    drop(server.recv().unwrap());
+
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"), "{}", content);
+    assert!(content.ends_with("/dropped"), "{}", content);
+}
+
+#[test]
+fn is_client_connected() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    assert!(request.is_client_connected());
+
+    drop(client);
+    thread::sleep(Duration::from_millis(100));
+    assert!(!request.is_client_connected());
+}
+
+#[test]
+fn auto_etag() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            auto_etag: true,
+            ..support::default_config()
+        });
+
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        for request in server.incoming_requests().take(2) {
+            let sender = sender.clone();
+            let response = tiny_http::Response::from_string("hello")
+                .with_header(
+                    "Cache-Control: max-age=60"
+                        .parse::<tiny_http::Header>()
+                        .unwrap(),
+                )
+                .on_complete(move |result| sender.send(result.unwrap()).unwrap());
+            request.respond(response).unwrap();
+        }
+    });
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut buf = [0; 1024];
+    let len = client.read(&mut buf).unwrap();
+    let first = String::from_utf8_lossy(&buf[..len]).into_owned();
+    assert!(first.starts_with("HTTP/1.1 200"), "{}", first);
+    assert!(first.ends_with("hello"));
+    let etag = first
+        .lines()
+        .find_map(|line| line.strip_prefix("ETag: "))
+        .unwrap();
+    assert!(etag.starts_with("W/\""));
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nIf-None-Match: \"other\", {}\r\nConnection: close\r\n\r\n",
+        etag
+    )
+    .unwrap();
+    let mut second = String::new();
+    client.read_to_string(&mut second).unwrap();
+    assert!(second.starts_with("HTTP/1.1 304"), "{}", second);
+    assert!(second.contains(&format!("ETag: {}", etag)));
+    assert!(second.contains("Cache-Control: max-age=60"));
+    assert!(!second.contains("Content-Length"));
+    assert!(!second.contains("Transfer-Encoding"));
+    assert!(second.ends_with("\r\n\r\n"));
+
+    handle.join().unwrap();
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [5, 0]);
+}
+
+#[test]
+fn fault_partial_writes() {
+    let client = support::new_client_to_hello_world_server();
+    let mut client = tiny_http::FaultInjector::new(client)
+        .with_max_write(3)
+        .with_write_delay(Duration::from_millis(10));
+
+    write!(
+        client,
+        "GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut data = String::new();
+    client.read_to_string(&mut data).unwrap();
+    assert!(data.ends_with("hello world"));
+}
+
+#[test]
+fn fault_early_fin() {
+    let (server, client) = support::new_one_server_one_client();
+    let mut client = tiny_http::FaultInjector::new(client).with_fin_after(20);
+
+    let err = write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+
+    // the truncated request is never handed out, and the connection is closed
+    assert!(server
+        .recv_timeout(Duration::from_millis(100))
+        .unwrap()
+        .is_none());
+    let mut data = String::new();
+    client.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "");
+}
+
+#[test]
+fn fault_corruption() {
+    let client = support::new_client_to_hello_world_server();
+    // `HTTP/1.1` becomes `HTTP.1.1`
+    let mut client = tiny_http::FaultInjector::new(client).with_corruption(10, b'/' ^ b'.');
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut data = String::new();
+    client.read_to_string(&mut data).unwrap();
+    assert!(data.starts_with("HTTP/1.1 400"), "{}", data);
+}
+
+#[test]
+fn custom_acceptor() {
+    use std::net::{SocketAddr, TcpListener};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tiny_http::{AcceptedStream, Acceptor, ListenAddr, Listener};
+
+    struct Counting(TcpListener, Arc<AtomicUsize>);
+
+    impl Acceptor for Counting {
+        fn accept(&self) -> std::io::Result<(Box<dyn AcceptedStream>, Option<SocketAddr>)> {
+            let accepted = Acceptor::accept(&self.0)?;
+            self.1.fetch_add(1, Ordering::SeqCst);
+            Ok(accepted)
+        }
+
+        fn local_addr(&self) -> std::io::Result<ListenAddr> {
+            Acceptor::local_addr(&self.0)
+        }
+    }
+
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let server = tiny_http::Server::from_listener(
+        Listener::custom(Counting(listener, accepted.clone())),
+        None,
+    )
+    .unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert!(request.remote_addr().is_some());
+    request
+        .respond(tiny_http::Response::from_string("hello"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello"));
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    // the accept thread is woken up, and stops, closing the listener
+    drop(server);
+    let mut tries = 0;
+    while TcpStream::connect(("127.0.0.1", port)).is_ok() {
+        tries += 1;
+        assert!(tries < 50, "the listener is still open");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+#[cfg(all(feature = "vsock", target_os = "linux"))]
+fn vsock_listener() {
+    let server = match tiny_http::Server::new(tiny_http::ServerConfig {
+        // any port
+        addr: tiny_http::ConfigListenAddr::vsock(
+            tiny_http::ConfigListenAddr::VMADDR_CID_ANY,
+            u32::MAX,
+        ),
+        ..support::default_config()
+    }) {
+        Ok(server) => server,
+        // the kernel has no support for VM sockets
+        Err(err) if err.to_string().contains("Address family not supported") => return,
+        Err(err) => panic!("{}", err),
+    };
+
+    match server.server_addr() {
+        tiny_http::ListenAddr::Vsock { cid, port } => {
+            assert_eq!(cid, tiny_http::ConfigListenAddr::VMADDR_CID_ANY);
+            assert_ne!(port, u32::MAX);
+        }
+        addr => panic!("{}", addr),
+    }
+    assert!(server.server_addr().to_ip().is_none());
+    assert!(server.server_addr().to_string().starts_with("vsock:"));
+
+    // the accept thread stops without a connection
+    drop(server);
+}
+
+#[test]
+fn shared_response() {
+    let body: std::sync::Arc<[u8]> = std::sync::Arc::from(&b"shared body"[..]);
+    let response = tiny_http::Response::from_shared(body.clone()).with_status_code(404);
+
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    for _ in 0..2 {
+        server.recv().unwrap().respond(response.clone()).unwrap();
+    }
+    // the body isn't copied
+    assert_eq!(std::sync::Arc::strong_count(&body), 2);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("HTTP/1.1 404").count(), 2);
+    assert_eq!(content.matches("shared body").count(), 2);
+}
+
+#[test]
+fn response_on_complete() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    write!(
+        client,
+        "HEAD / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..2 {
+        let sender = sender.clone();
+        let response = tiny_http::Response::from_string("hello")
+            .on_complete(move |result| sender.send(result.unwrap()).unwrap());
+        server.recv().unwrap().respond(response).unwrap();
+    }
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [5, 0]);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("HTTP/1.1 200").count(), 2);
+}
+
+#[test]
+fn http10_response_spilled_to_file() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                response_buffer_max: Some(4),
+                ..Default::default()
+            },
+            ..support::default_config()
+        });
+
+    write!(client, "GET / HTTP/1.0\r\n\r\n").unwrap();
+    let body = std::io::Cursor::new(b"larger than the buffer".to_vec());
+    let response = tiny_http::Response::new(200.into(), Vec::new(), body, None, None);
+    server.recv().unwrap().respond(response).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Content-Length: 22\r\n"), "{}", content);
+    assert!(content.ends_with("\r\n\r\nlarger than the buffer"));
+}
+
+#[test]
+fn buffered_bodies_budget() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                buffered_bodies_max: Some(0),
+                ..Default::default()
+            },
+            ..support::default_config()
+        });
+
+    for body in &["first", "second"] {
+        write!(
+            client,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+    }
+
+    // out of budget, the body isn't read in advance, and the next request waits for it
+    let mut first = server.recv().unwrap();
+    let early = server.recv_timeout(Duration::from_millis(100)).unwrap();
+
+    let mut body = String::new();
+    first.as_reader().read_to_string(&mut body).unwrap();
+    assert_eq!(body, "first");
+    first.respond(tiny_http::Response::empty(204)).unwrap();
+    assert!(early.is_none());
+
+    let mut second = server.recv().unwrap();
+    let mut body = String::new();
+    second.as_reader().read_to_string(&mut body).unwrap();
+    assert_eq!(body, "second");
+}
+
+#[test]
+fn respond_to_all() {
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    let mut clients: Vec<_> = (0..3)
+        .map(|_| {
+            let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            write!(
+                client,
+                "GET /poll HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            client
+        })
+        .collect();
+    let waiters: Vec<_> = server.incoming_requests().take(3).collect();
+
+    // a body of unknown length is collected once
+    let body = std::io::Cursor::new(b"notification".to_vec());
+    let response = tiny_http::Response::empty(200).with_data(body, None);
+    let results = response.respond_to_all(waiters).unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(Result::is_ok));
+
+    for client in &mut clients {
+        let mut content = String::new();
+        client.read_to_string(&mut content).unwrap();
+        assert!(content.contains("Content-Length: 12\r\n"), "{}", content);
+        assert!(content.ends_with("notification"));
+    }
+}
+
+#[test]
+fn parking() {
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+    let parking = tiny_http::Parking::new().unwrap();
+
+    let mut clients: Vec<_> = ["/woken", "/timeout"]
+        .iter()
+        .map(|path| {
+            let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            write!(
+                client,
+                "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                path
+            )
+            .unwrap();
+            client
+        })
+        .collect();
+    for _ in 0..2 {
+        let request = server.recv().unwrap();
+        let (key, timeout) = match request.url() {
+            "/woken" => ("woken", Duration::from_secs(30)),
+            _ => ("timeout", Duration::from_millis(50)),
+        };
+        parking.park(request, key, timeout);
+    }
+
+    let woken = parking
+        .wake(&"woken", tiny_http::Response::from_string("event"))
+        .unwrap();
+    assert_eq!(woken, 1);
+    assert_eq!(
+        parking
+            .wake(&"woken", tiny_http::Response::empty(200))
+            .unwrap(),
+        0
+    );
+
+    let mut content = String::new();
+    clients[0].read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    assert!(content.ends_with("event"));
+
+    let mut content = String::new();
+    clients[1].read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 204"), "{}", content);
+    assert!(parking.is_empty());
+}
+
+#[test]
+fn normalize_absolute_form() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            normalize_absolute_form: true,
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET http://localhost/a?b HTTP/1.1\r\nHost: localhost\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.url(), "/a?b");
+    assert_eq!(request.raw_url(), "http://localhost/a?b");
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    write!(
+        client,
+        "GET http://example.com/ HTTP/1.1\r\nHost: localhost\r\n\r\n"
+    )
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 204"), "{}", content);
+    assert!(content.contains("HTTP/1.1 400"), "{}", content);
+}
+
+#[test]
+#[cfg(feature = "memmap")]
+fn mmap_response() {
+    let path = std::env::temp_dir().join(format!("tiny-http-mmap-{}", std::process::id()));
+    std::fs::write(&path, "mapped body").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let map = unsafe { tiny_http::Mmap::map(&file) }.unwrap();
+    let response = tiny_http::Response::from_mmap(map);
+
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    for _ in 0..2 {
+        server.recv().unwrap().respond(response.clone()).unwrap();
+    }
+    drop(response);
+    std::fs::remove_file(&path).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("Content-Length: 11\r\n").count(), 2);
+    assert_eq!(content.matches("mapped body").count(), 2);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn tcp_keepalive_options() {
+    let (server, mut client) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            socket: tiny_http::SocketConfig {
+                tcp_keepalive_time: Some(tiny_http::ConfigDuration::from_secs(60)),
+                tcp_keepalive_interval: Some(tiny_http::ConfigDuration::from_secs(5)),
+                tcp_keepalive_retries: Some(3),
+                ..tiny_http::SocketConfig::default()
+            },
+            ..support::default_config()
+        });
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 204"), "{}", content);
+}
+
+#[test]
+#[cfg(target_os = "windows")]
+fn unsupported_socket_option() {
+    let result = tiny_http::Server::new(tiny_http::ServerConfig {
+        socket: tiny_http::SocketConfig {
+            tcp_keepalive_retries: Some(3),
+            ..tiny_http::SocketConfig::default()
+        },
+        ..support::default_config()
+    });
+    assert!(result.is_err());
 }
-*/
 
 #[test]
 fn chunked_threshold() {
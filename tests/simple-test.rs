@@ -1,6 +1,9 @@
 extern crate tiny_http;
 
 use std::io::{Read, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 #[allow(dead_code)]
 mod support;
@@ -27,3 +30,508 @@ fn basic_handling() {
     stream.read_to_string(&mut content).unwrap();
     assert!(content.ends_with("hello world"));
 }
+
+#[test]
+fn respond_reporting() {
+    let (server, mut stream) = support::new_one_server_one_client();
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut request = server.recv().unwrap();
+    request.set_route("/");
+    assert_eq!(request.route(), Some("/"));
+    let stats = request
+        .respond_reporting(tiny_http::Response::from_string("hello world".to_owned()))
+        .unwrap();
+    assert_eq!(stats.body_bytes, 11);
+    assert_eq!(stats.route.as_deref(), Some("/"));
+    assert_eq!(
+        stats.transfer_encoding,
+        Some(tiny_http::TransferEncoding::Identity)
+    );
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert_eq!(stats.header_bytes + stats.body_bytes, content.len());
+}
+
+#[test]
+fn response_cache() {
+    let (server, mut stream) = support::new_one_server_one_client();
+    write!(
+        stream,
+        "GET /a HTTP/1.1\r\nHost: localhost\r\n\r\nGET /a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let cache = tiny_http::ResponseCache::new(1024);
+    let mut calls = 0;
+    for _ in 0..2 {
+        let request = server.recv().unwrap();
+        cache
+            .respond(request, |_| {
+                calls += 1;
+                tiny_http::Response::from_string("cached").with_header(
+                    "Cache-Control: max-age=60"
+                        .parse::<tiny_http::Header>()
+                        .unwrap(),
+                )
+            })
+            .unwrap();
+    }
+    assert_eq!(calls, 1);
+    assert_eq!(cache.len(), 1);
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("cached").count(), 2);
+    assert_eq!(content.matches("Age: 0").count(), 1);
+
+    assert!(cache.invalidate("/a"));
+    assert!(cache.is_empty());
+    assert_eq!(cache.size(), 0);
+}
+
+#[test]
+fn response_cache_per_host() {
+    let (server, mut stream) = support::new_one_server_one_client();
+    write!(
+        stream,
+        "GET /a HTTP/1.1\r\nHost: a.example\r\n\r\nGET /a HTTP/1.1\r\nHost: b.example\r\n\r\n\
+         GET /a HTTP/1.1\r\nHost: A.example\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let cache = tiny_http::ResponseCache::new(1024);
+    let mut calls = 0;
+    for _ in 0..3 {
+        let request = server.recv().unwrap();
+        cache
+            .respond(request, |request| {
+                calls += 1;
+                let host = request.headers().iter().find(|h| h.field.equiv("Host"));
+                tiny_http::Response::from_string(host.unwrap().value.as_str()).with_header(
+                    "Cache-Control: max-age=60"
+                        .parse::<tiny_http::Header>()
+                        .unwrap(),
+                )
+            })
+            .unwrap();
+    }
+    assert_eq!(calls, 2);
+    assert_eq!(cache.len(), 2);
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("a.example").count(), 2);
+    assert_eq!(content.matches("b.example").count(), 1);
+
+    assert!(cache.invalidate("/a"));
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn render_response() {
+    let (server, mut stream) = support::new_one_server_one_client();
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_render(|writer| {
+            write!(writer, "<p>hello</p>")
+        }))
+        .unwrap();
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Content-Type: text/html; charset=UTF-8\r\n"));
+    assert!(content.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(content.ends_with("\r\nc\r\n<p>hello</p>\r\n0\r\n\r\n"));
+}
+
+#[test]
+fn render_response_error() {
+    let (server, mut stream) = support::new_one_server_one_client();
+    write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let request = server.recv().unwrap();
+    let result = request.respond(tiny_http::Response::from_render(|writer| {
+        writer.write_all(&[b'a'; 10_000])?;
+        Err(std::io::Error::other("template error"))
+    }));
+    assert!(result.is_err());
+
+    // the connection is closed without ending the body
+    let mut content = Vec::new();
+    stream.read_to_end(&mut content).unwrap();
+    let content = String::from_utf8(content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    assert!(!content.ends_with("0\r\n\r\n"));
+}
+
+#[derive(Debug)]
+struct FixedClock(Instant);
+
+impl tiny_http::Clock for FixedClock {
+    fn now(&self) -> Instant {
+        self.0
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(420895020)
+    }
+}
+
+#[test]
+fn custom_clock() {
+    let (server, mut stream) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            clock: Some(Arc::new(FixedClock(Instant::now()))),
+            ..support::default_config()
+        });
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    assert_eq!(request.parse_duration(), Duration::from_secs(0));
+    let stats = request
+        .respond_reporting(tiny_http::Response::from_string("hello world".to_owned()))
+        .unwrap();
+    assert_eq!(stats.duration, Duration::from_secs(0));
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Date: Wed, 04 May 1983 11:17:00 GMT\r\n"));
+}
+
+#[test]
+fn no_default_headers() {
+    let (server, mut stream) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            emit_default_headers: false,
+            ..support::default_config()
+        });
+    write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+
+    // responses of the server itself too
+    write!(stream, "NOT A REQUEST\r\n\r\n").unwrap();
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 204"));
+    assert!(content.contains("HTTP/1.1 400"));
+    assert!(!content.contains("Date:"));
+    assert!(!content.contains("Server:"));
+}
+
+#[test]
+fn response_decorator() {
+    let (server, mut stream) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            response_decorator: Some(tiny_http::ResponseDecorator::new(|request, headers| {
+                let origin = match request {
+                    Some(request) => format!("X-Decorated: {}", request.url()),
+                    None => "X-Decorated: internal".to_owned(),
+                };
+                headers.set_header(origin.parse::<tiny_http::Header>().unwrap());
+                headers.add_header("Connection: keep-alive".parse().unwrap());
+            })),
+            ..support::default_config()
+        });
+    write!(stream, "GET /a HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    request
+        .respond(
+            tiny_http::Response::from_string("hello")
+                .with_header("X-Decorated: handler".parse::<tiny_http::Header>().unwrap()),
+        )
+        .unwrap();
+
+    // unanswered requests and malformed ones too
+    write!(stream, "GET /b HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    drop(server.recv().unwrap());
+    write!(stream, "NOT A REQUEST\r\n\r\n").unwrap();
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("X-Decorated").count(), 3);
+    assert!(content.contains("X-Decorated: /a\r\n"));
+    assert!(content.contains("X-Decorated: /b\r\n"));
+    assert!(content.contains("X-Decorated: internal\r\n"));
+    assert!(!content.contains("keep-alive"));
+}
+
+struct Uppercase<R>(R);
+
+impl<R: Read> Read for Uppercase<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.0.read(buf)?;
+        buf[..len].make_ascii_uppercase();
+        Ok(len)
+    }
+}
+
+#[test]
+fn body_transform() {
+    let (server, mut stream) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            body_transform: Some(tiny_http::BodyTransform::new(|info, body| {
+                if info.status_code == 404 {
+                    body.wrap(|reader| reader.chain(&b" (transformed)"[..]))
+                } else if info.content_type == Some("text/plain; charset=UTF-8") {
+                    body.wrap_with_length(info.length, Uppercase)
+                } else {
+                    body
+                }
+            })),
+            ..support::default_config()
+        });
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("hello"))
+        .unwrap();
+    let request = server.recv().unwrap();
+    let stats = request
+        .respond_reporting(tiny_http::Response::from_string("missing").with_status_code(404))
+        .unwrap();
+    assert_eq!(
+        stats.transfer_encoding,
+        Some(tiny_http::TransferEncoding::Chunked)
+    );
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    let (first, second) = content.split_at(content.find("HTTP/1.1 404").unwrap());
+    assert!(first.contains("Content-Length: 5\r\n"));
+    assert!(first.ends_with("\r\n\r\nHELLO"));
+    assert!(second.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(second.contains("missing"));
+    assert!(second.contains(" (transformed)"));
+}
+
+#[test]
+fn default_chunked_threshold() {
+    let (server, mut stream) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            chunked_threshold: Some(1024 * 1024),
+            ..support::default_config()
+        });
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let body = vec![b'a'; 100_000];
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_data(body.clone()))
+        .unwrap();
+    // the threshold of the response itself wins
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_data(body).with_chunked_threshold(1024))
+        .unwrap();
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    let (first, second) = content.split_at(content.rfind("HTTP/1.1 200").unwrap());
+    assert!(first.contains("Content-Length: 100000\r\n"));
+    assert!(!first.contains("Transfer-Encoding"));
+    assert!(second.contains("Transfer-Encoding: chunked\r\n"));
+}
+
+#[test]
+fn response_headers_limit() {
+    let (server, mut stream) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            limits: tiny_http::LimitsConfig {
+                response_headers_max: Some(1024),
+                ..tiny_http::LimitsConfig::default()
+            },
+            ..support::default_config()
+        });
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    let mut response = tiny_http::Response::from_string("hello");
+    for i in 0..100 {
+        let cookie = format!("Set-Cookie: session{}=abcdef", i);
+        response.add_header(cookie.parse::<tiny_http::Header>().unwrap());
+    }
+    let err = request.respond(response).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // the request is answered like an unanswered one instead
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 500"));
+    assert!(!content.contains("Set-Cookie"));
+}
+
+struct CountingHandler;
+
+impl tiny_http::StatefulRequestHandler for CountingHandler {
+    type State = (usize, usize);
+
+    fn init(&self, worker: usize) -> (usize, usize) {
+        (worker, 0)
+    }
+
+    fn handle_request(&self, state: &mut (usize, usize), request: tiny_http::Request) {
+        state.1 += 1;
+        let body = format!("worker {} request {}", state.0, state.1);
+        request
+            .respond(tiny_http::Response::from_string(body))
+            .unwrap();
+    }
+}
+
+#[test]
+fn stateful_workers() {
+    let (server, mut stream) = support::new_one_server_one_client();
+    let server = Arc::new(server);
+    let workers = tiny_http::Server::spawn_workers(&server, 1, CountingHandler).unwrap();
+
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let mut content = String::new();
+    stream.read_to_string(&mut content).unwrap();
+    assert!(content.contains("worker 0 request 1"));
+    assert!(content.ends_with("worker 0 request 2"));
+
+    server.unblock();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+struct ConnectionTracker;
+
+impl tiny_http::StatefulRequestHandler for ConnectionTracker {
+    // requests handled per connection
+    type State = (usize, std::collections::HashMap<u64, usize>);
+
+    fn init(&self, worker: usize) -> Self::State {
+        (worker, std::collections::HashMap::new())
+    }
+
+    fn handle_request(&self, state: &mut Self::State, request: tiny_http::Request) {
+        let count = state.1.entry(request.connection_id()).or_insert(0);
+        *count += 1;
+        let body = format!("[worker {} count {}]", state.0, count);
+        request
+            .respond(tiny_http::Response::from_string(body))
+            .unwrap();
+    }
+}
+
+#[test]
+fn workers_by_connection() {
+    let server = Arc::new(tiny_http::Server::new(support::default_config()).unwrap());
+    let workers =
+        tiny_http::Server::spawn_workers_by_connection(&server, 3, ConnectionTracker).unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    for _ in 0..4 {
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        for _ in 0..3 {
+            write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        }
+        write!(
+            stream,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+
+        let mut content = String::new();
+        stream.read_to_string(&mut content).unwrap();
+        let bodies: Vec<&str> = content
+            .split('[')
+            .skip(1)
+            .map(|body| body.split(']').next().unwrap())
+            .collect();
+        let worker = bodies[0].split(" count").next().unwrap();
+        let expected: Vec<String> = (1..=4)
+            .map(|count| format!("{} count {}", worker, count))
+            .collect();
+        assert_eq!(bodies, expected);
+    }
+
+    server.unblock();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+#[test]
+fn pin_worker_threads() {
+    let result = tiny_http::Server::new(tiny_http::ServerConfig {
+        pool: tiny_http::PoolConfig {
+            pin_worker_threads: true,
+            ..tiny_http::PoolConfig::default()
+        },
+        ..support::default_config()
+    });
+
+    if cfg!(feature = "cpu-affinity") {
+        let server = result.unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let pinned = server.pinned_threads();
+        assert!(pinned.iter().any(|t| t.thread_name == "tiny-http-accept"));
+        assert!(pinned.iter().any(|t| t.thread_name == "tiny-http-worker-0"));
+    } else {
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn large_write_buffer() {
+    let (server, mut stream) =
+        support::new_one_server_one_client_with_config(tiny_http::ServerConfig {
+            socket: tiny_http::SocketConfig {
+                write_buffer_size: Some(64 * 1024),
+                ..tiny_http::SocketConfig::default()
+            },
+            ..support::default_config()
+        });
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nTE: identity\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let body: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_data(body.clone()))
+        .unwrap();
+
+    let mut content = Vec::new();
+    stream.read_to_end(&mut content).unwrap();
+    assert!(content.ends_with(&body));
+}
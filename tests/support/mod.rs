@@ -2,6 +2,13 @@ use std::net::TcpStream;
 use std::thread;
 use std::time::Duration;
 
+/// Returns the default configuration for a server on a random port.
+pub fn default_config() -> tiny_http::ServerConfig {
+    tiny_http::ServerConfig::new(
+        tiny_http::ConfigListenAddr::from_socket_addrs("0.0.0.0:0").unwrap(),
+    )
+}
+
 /// Creates a server and a client connected to the server.
 pub fn new_one_server_one_client() -> (tiny_http::Server, TcpStream) {
     let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
@@ -10,6 +17,16 @@ pub fn new_one_server_one_client() -> (tiny_http::Server, TcpStream) {
     (server, client)
 }
 
+/// Creates a server with the given configuration and a client connected to the server.
+pub fn new_one_server_one_client_with_config(
+    config: tiny_http::ServerConfig,
+) -> (tiny_http::Server, TcpStream) {
+    let server = tiny_http::Server::new(config).unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+    let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (server, client)
+}
+
 /// Creates a "hello world" server with a client connected to the server.
 ///
 /// The server will automatically close after 3 seconds.
@@ -38,3 +55,49 @@ pub fn new_client_to_hello_world_server() -> TcpStream {
 
     client
 }
+
+/// Runs the TLS handshake of a client over `stream`, accepting any certificate.
+#[cfg(feature = "ssl-openssl")]
+pub fn tls_client(stream: TcpStream) -> openssl::ssl::SslStream<TcpStream> {
+    use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+
+    let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+    connector.set_verify(SslVerifyMode::NONE);
+    connector.build().connect("localhost", stream).unwrap()
+}
+
+/// Returns a self-signed certificate for `name` and its private key, in PEM format.
+#[cfg(feature = "ssl-openssl")]
+pub fn self_signed_certificate(name: &str) -> (Vec<u8>, Vec<u8>) {
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509NameBuilder, X509};
+
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut subject = X509NameBuilder::new().unwrap();
+    subject.append_entry_by_text("CN", name).unwrap();
+    let subject = subject.build();
+
+    let mut certificate = X509::builder().unwrap();
+    certificate.set_version(2).unwrap();
+    let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+    certificate.set_serial_number(&serial).unwrap();
+    certificate.set_subject_name(&subject).unwrap();
+    certificate.set_issuer_name(&subject).unwrap();
+    certificate.set_pubkey(&key).unwrap();
+    certificate
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    certificate
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    certificate.sign(&key, MessageDigest::sha256()).unwrap();
+
+    (
+        certificate.build().to_pem().unwrap(),
+        key.private_key_to_pem_pkcs8().unwrap(),
+    )
+}
@@ -42,3 +42,84 @@ fn unix_basic_handling() {
     client.read_to_string(&mut content).unwrap();
     assert!(content.ends_with("hello world"));
 }
+
+#[test]
+fn unix_socket_removed_on_drop() {
+    let path = Path::new("/tmp/tiny-http-test-removed.sock");
+    let server = tiny_http::Server::http_unix(path).unwrap();
+    assert_eq!(server.server_addr().unix_path(), Some(path));
+    assert!(path.exists());
+
+    drop(server);
+    assert!(!path.exists());
+}
+
+#[test]
+fn unix_socket_bound_again_is_kept() {
+    let path = Path::new("/tmp/tiny-http-test-restart.sock");
+    let old_server = tiny_http::Server::http_unix(path).unwrap();
+
+    // a restarted server replaces the socket file of the old one, still running
+    std::fs::remove_file(path).unwrap();
+    let new_server = tiny_http::Server::http_unix(path).unwrap();
+
+    drop(old_server);
+    assert!(path.exists());
+
+    // the accept thread of the old server stops, closing its listener
+    #[cfg(target_os = "linux")]
+    {
+        let mut tries = 0;
+        while listeners(path) > 1 {
+            tries += 1;
+            assert!(tries < 50, "the old listener is still open");
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+
+    let mut client = UnixStream::connect(path).unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = new_server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("restarted"))
+        .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("restarted"));
+
+    drop(new_server);
+    assert!(!path.exists());
+}
+
+/// Counts the listening Unix sockets bound to `path`.
+#[cfg(target_os = "linux")]
+fn listeners(path: &Path) -> usize {
+    let path = path.to_str().unwrap();
+    std::fs::read_to_string("/proc/net/unix")
+        .unwrap()
+        .lines()
+        .filter(|line| {
+            // `__SO_ACCEPTCON` in the flags of the socket
+            line.ends_with(path) && line.split_whitespace().nth(3) == Some("00010000")
+        })
+        .count()
+}
+
+#[test]
+fn unix_is_client_connected() {
+    let path = Path::new("/tmp/tiny-http-test-connected.sock");
+    let server = tiny_http::Server::http_unix(path).unwrap();
+    let mut client = UnixStream::connect(path).unwrap();
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    assert!(request.is_client_connected());
+
+    drop(client);
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert!(!request.is_client_connected());
+}